@@ -0,0 +1,105 @@
+use anyhow::{anyhow, Result};
+use norm::{image::grammar::ImageKind, recipe::Recipe};
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+const USAGE: &str =
+    "Usage: norm_batch <recipe> <input directory> <output directory> [--jobs <count>]";
+
+fn main() -> Result<ExitCode> {
+    let mut args = std::env::args().skip(1);
+    let mut paths = Vec::new();
+    let mut jobs = thread::available_parallelism().map_or(1, usize::from);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--jobs" => {
+                jobs = args
+                    .next()
+                    .ok_or_else(|| anyhow!(USAGE))?
+                    .parse()
+                    .ok()
+                    .filter(|&jobs| jobs > 0)
+                    .ok_or_else(|| anyhow!("The job count must be a positive number."))?;
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let [recipe_path, input_directory, output_directory] = paths.as_slice() else {
+        return Err(anyhow!(USAGE));
+    };
+
+    let recipe = Recipe::load(recipe_path)?;
+
+    let mut inputs = fs::read_dir(input_directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    inputs.retain(|path| path.is_file() && ImageKind::from_extension(path).is_some());
+    inputs.sort();
+
+    // Inputs differing only by extension, like `a.png` and `a.jpg`, would overwrite each other.
+    let mut outputs = HashMap::new();
+    for input in &inputs {
+        let output = recipe.output_path(input, output_directory)?;
+
+        if let Some(other) = outputs.insert(output.clone(), input) {
+            return Err(anyhow!(
+                "{} and {} would both be written to {}.",
+                other.display(),
+                input.display(),
+                output.display()
+            ));
+        }
+    }
+
+    fs::create_dir_all(output_directory)?;
+
+    // Workers take the next image until none are left, so slow images don't hold others up.
+    let next = AtomicUsize::new(0);
+    let failures = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(inputs.len()) {
+            scope.spawn(|| {
+                while let Some(input) = inputs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    match recipe.process(input, output_directory) {
+                        Ok(output) => println!("{} -> {}", input.display(), output.display()),
+                        Err(error) => {
+                            eprintln!("{}: {error:#}", input.display());
+                            failures
+                                .lock()
+                                .expect("no worker panics while holding the lock")
+                                .push(input);
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    let failures = failures
+        .into_inner()
+        .expect("no worker panics while holding the lock");
+
+    println!(
+        "Processed {} of {} images.",
+        inputs.len() - failures.len(),
+        inputs.len()
+    );
+
+    Ok(if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
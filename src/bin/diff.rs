@@ -0,0 +1,61 @@
+use anyhow::{anyhow, Result};
+use norm::{
+    image::{ImageDiff, ImageReader},
+    png::{grammar::Png, PngEncoder},
+};
+use std::{fs::File, io::BufWriter, process::ExitCode};
+
+const USAGE: &str =
+    "Usage: norm_diff <reference image> <test image> [--heatmap <path>] [--tolerance <levels>]";
+
+fn main() -> Result<ExitCode> {
+    let mut args = std::env::args().skip(1);
+    let mut paths = Vec::new();
+    let mut heatmap_path = None;
+    let mut tolerance = 0.0;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--heatmap" => heatmap_path = Some(args.next().ok_or_else(|| anyhow!(USAGE))?),
+            "--tolerance" => {
+                tolerance = args
+                    .next()
+                    .ok_or_else(|| anyhow!(USAGE))?
+                    .parse()
+                    .map_err(|_| anyhow!("The tolerance must be a number of 8-bit levels."))?;
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    let [reference_path, test_path] = paths.as_slice() else {
+        return Err(anyhow!(USAGE));
+    };
+
+    let reference = ImageReader::read_from_path(reference_path, None)?;
+    let test = ImageReader::read_from_path(test_path, None)?;
+
+    let diff = ImageDiff::new(reference.as_ref(), test.as_ref(), tolerance)?;
+    let pixel_count = reference.width() as usize * reference.height() as usize;
+
+    println!("max error:  {:.3}", diff.max_error());
+    println!("mean error: {:.3}", diff.mean_error());
+    println!("psnr:       {:.3} dB", diff.psnr());
+    println!(
+        "differing:  {} of {} pixels",
+        diff.differing_pixels(),
+        pixel_count
+    );
+
+    if let Some(heatmap_path) = heatmap_path {
+        let writer = BufWriter::new(File::create(heatmap_path)?);
+        PngEncoder::new(writer).encode(&Png::from(&diff.heatmap()))?;
+    }
+
+    // Differing images fail, so the tool can gate visual regression tests.
+    Ok(if diff.is_identical() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -1,31 +1,96 @@
 use anyhow::{anyhow, Result};
-use norm::png::{grammar::Png, PngDecoder};
-use std::{fs, time::Instant};
+use norm::{
+    image::ImageReader,
+    metrics::{self, DeltaEMap, Ssim, SsimChannels},
+    png::{grammar::Png, PngEncoder},
+};
+use std::{fs::File, io::BufWriter, time::Instant};
 
-fn read_png(image_path: &str) -> Result<Png> {
-    let image_data = fs::read(image_path)?;
-    let image = PngDecoder::new(&image_data).decode()?;
-
-    Ok(image)
-}
+const USAGE: &str = "Usage: norm_ssim <reference image> <test image> [--color] [--ms-ssim] \
+    [--map <path>] [--psnr] [--dssim] [--delta-e] [--delta-e-map <path>]";
 
 fn main() -> Result<()> {
     let mut args = std::env::args().skip(1);
-    let (reference_image, test_image) = match (args.next(), args.next()) {
-        (Some(reference_image_path), Some(test_image_path)) => (
-            read_png(&reference_image_path)?,
-            read_png(&test_image_path)?,
-        ),
-        _ => {
-            return Err(anyhow!(
-                "Provide paths to a reference image AND a test image."
-            ))
+    let mut paths = Vec::new();
+    let mut ssim = Ssim::new();
+    let mut multi_scale = false;
+    let mut map_path = None;
+    let mut psnr = false;
+    let mut dssim = false;
+    let mut delta_e = false;
+    let mut delta_e_map_path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--color" => ssim = ssim.with_channels(SsimChannels::Rgb),
+            "--ms-ssim" => multi_scale = true,
+            "--map" => map_path = Some(args.next().ok_or_else(|| anyhow!(USAGE))?),
+            "--psnr" => psnr = true,
+            "--dssim" => dssim = true,
+            "--delta-e" => delta_e = true,
+            "--delta-e-map" => {
+                delta_e = true;
+                delta_e_map_path = Some(args.next().ok_or_else(|| anyhow!(USAGE))?);
+            }
+            _ => paths.push(arg),
         }
+    }
+
+    let [reference_path, test_path] = paths.as_slice() else {
+        return Err(anyhow!(
+            "Provide paths to a reference image AND a test image.\n{USAGE}"
+        ));
     };
 
+    let reference_image = ImageReader::read_from_path(reference_path, None)?;
+    let test_image = ImageReader::read_from_path(test_path, None)?;
+
     let now = Instant::now();
-    let ssim = reference_image.compute_sim(&test_image)?;
-    println!("ssim score: {}\telapsed: {:?}", ssim, now.elapsed());
+    let map = ssim.map(reference_image.as_ref(), test_image.as_ref())?;
+    println!("ssim score: {}\telapsed: {:?}", map.mean(), now.elapsed());
+
+    if map.channel_means().len() > 1 {
+        let [r, g, b] = map.channel_means() else {
+            unreachable!("Color SSIM compares three channels");
+        };
+        println!("r: {r}\tg: {g}\tb: {b}");
+    }
+
+    if multi_scale {
+        let now = Instant::now();
+        let score = ssim.multi_scale(reference_image.as_ref(), test_image.as_ref())?;
+        println!("ms-ssim score: {}\telapsed: {:?}", score, now.elapsed());
+    }
+
+    if psnr {
+        let score = metrics::psnr(reference_image.as_ref(), test_image.as_ref())?;
+        println!("psnr (L*): {score:.3} dB");
+    }
+
+    if dssim {
+        let score = metrics::dssim(reference_image.as_ref(), test_image.as_ref())?;
+        println!("dssim: {score}");
+    }
+
+    if delta_e {
+        let delta_e_map = DeltaEMap::new(reference_image.as_ref(), test_image.as_ref())?;
+        println!(
+            "ciede2000 mean: {:.3}\tmax: {:.3}",
+            delta_e_map.mean(),
+            delta_e_map.max()
+        );
+
+        // A difference of 10 is obvious at a glance, so it saturates the map.
+        if let Some(path) = delta_e_map_path {
+            let writer = BufWriter::new(File::create(path)?);
+            PngEncoder::new(writer).encode(&Png::from(&delta_e_map.to_image(10.0)))?;
+        }
+    }
+
+    if let Some(map_path) = map_path {
+        let writer = BufWriter::new(File::create(map_path)?);
+        PngEncoder::new(writer).encode(&Png::from(&map.to_image()))?;
+    }
 
     Ok(())
 }
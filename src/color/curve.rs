@@ -0,0 +1,151 @@
+/// A transfer function from encoded values to linear light, both normalized to [0.0, 1.0].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToneCurve {
+    /// `x^g`.
+    Gamma(f32),
+    /// The ICC parametric curve, `(a·x + b)^g + e` from `d` on and `c·x + f` below it. Every
+    /// ICC parametric function type is a special case.
+    Parametric {
+        g: f32,
+        a: f32,
+        b: f32,
+        c: f32,
+        d: f32,
+        e: f32,
+        f: f32,
+    },
+    /// Evenly spaced samples over [0.0, 1.0], linearly interpolated.
+    Table(Vec<f32>),
+}
+
+impl ToneCurve {
+    pub const LINEAR: Self = Self::Gamma(1.0);
+
+    /// The sRGB transfer function, also used by Display P3.
+    pub const SRGB: Self = Self::Parametric {
+        g: 2.4,
+        a: 1.0 / 1.055,
+        b: 0.055 / 1.055,
+        c: 1.0 / 12.92,
+        d: 0.04045,
+        e: 0.0,
+        f: 0.0,
+    };
+
+    pub fn eval(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+
+        match self {
+            &Self::Gamma(g) => x.powf(g),
+            &Self::Parametric {
+                g,
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+            } => {
+                if x >= d {
+                    a.mul_add(x, b).max(0.0).powf(g) + e
+                } else {
+                    c.mul_add(x, f)
+                }
+            }
+            Self::Table(table) => match table.len() {
+                0 => x,
+                1 => table[0],
+                len => {
+                    let position = x * (len - 1) as f32;
+                    let i = (position as usize).min(len - 2);
+                    let t = position - i as f32;
+
+                    (table[i + 1] - table[i]).mul_add(t, table[i])
+                }
+            },
+        }
+    }
+
+    /// Maps linear light back to the encoded value. Tables are inverted numerically and must
+    /// be monotonic.
+    pub fn eval_inverse(&self, y: f32) -> f32 {
+        let y = y.clamp(0.0, 1.0);
+
+        let x = match self {
+            &Self::Gamma(g) => y.powf(1.0 / g),
+            &Self::Parametric {
+                g,
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+            } => {
+                if y >= self.eval(d) {
+                    ((y - e).max(0.0).powf(1.0 / g) - b) / a
+                } else if c != 0.0 {
+                    (y - f) / c
+                } else {
+                    0.0
+                }
+            }
+            Self::Table(table) => {
+                let increasing = table.first() <= table.last();
+                let (mut low, mut high) = (0.0, 1.0);
+
+                for _ in 0..24 {
+                    let mid = (low + high) / 2.0;
+
+                    if (self.eval(mid) < y) == increasing {
+                        low = mid;
+                    } else {
+                        high = mid;
+                    }
+                }
+
+                (low + high) / 2.0
+            }
+        };
+
+        x.clamp(0.0, 1.0)
+    }
+
+    pub fn is_linear(&self) -> bool {
+        match self {
+            &Self::Gamma(g) => g == 1.0,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inverse() {
+        let curves = [
+            ToneCurve::LINEAR,
+            ToneCurve::Gamma(2.2),
+            ToneCurve::SRGB,
+            ToneCurve::Table((0..1024).map(|i| (i as f32 / 1023.0).powf(1.8)).collect()),
+        ];
+
+        for curve in curves {
+            for i in 0..=100 {
+                let x = i as f32 / 100.0;
+                let roundtrip = curve.eval_inverse(curve.eval(x));
+
+                assert!(
+                    (roundtrip - x).abs() < 1e-3,
+                    "{curve:?} at {x}: {roundtrip}"
+                );
+            }
+        }
+
+        // The linear segment of sRGB.
+        assert!((ToneCurve::SRGB.eval(0.02) - 0.02 / 12.92).abs() < 1e-7);
+        assert!((ToneCurve::SRGB.eval(0.5) - 0.214_041).abs() < 1e-5);
+    }
+}
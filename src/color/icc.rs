@@ -0,0 +1,657 @@
+use crate::{
+    color::{
+        curve::ToneCurve,
+        matrix::{Matrix3, IDENTITY},
+        profile::{Clut, ColorProfile, Lut, LutStage, PcsEncoding, Shaper},
+    },
+    impl_read_for_datatype, impl_read_slice,
+};
+use anyhow::{anyhow, bail, ensure, Result};
+
+/// Reads big-endian ICC data, from a whole profile or from a single tag.
+struct IccReader<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> IccReader<'a> {
+    const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    const fn at(data: &'a [u8], cursor: usize) -> Self {
+        Self { cursor, data }
+    }
+
+    fn read_s15_fixed16(&mut self) -> Result<f32> {
+        Ok(self.read_i32()? as f32 / 65536.0)
+    }
+
+    fn read_signature(&mut self) -> Result<&'a [u8]> {
+        self.read_slice(4)
+    }
+
+    fn read_matrix(&mut self) -> Result<Matrix3> {
+        let mut matrix = IDENTITY;
+
+        for row in &mut matrix {
+            for element in row {
+                *element = self.read_s15_fixed16()?;
+            }
+        }
+
+        Ok(matrix)
+    }
+
+    /// Reads a `curv` or `para` element, leaving the cursor after its 4-byte aligned end.
+    fn read_curve(&mut self) -> Result<ToneCurve> {
+        let start = self.cursor;
+        let signature = self.read_signature()?;
+        self.read_u32()?;
+
+        let curve = match signature {
+            b"curv" => match self.read_u32()? {
+                0 => ToneCurve::LINEAR,
+                1 => ToneCurve::Gamma(self.read_u16()? as f32 / 256.0),
+                count => ToneCurve::Table(
+                    self.read_vec(count as usize, |r| Ok(r.read_u16()? as f32 / 65535.0))?,
+                ),
+            },
+            b"para" => {
+                let function_type = self.read_u16()?;
+                self.read_u16()?;
+
+                let count = match function_type {
+                    0 => 1,
+                    1 => 3,
+                    2 => 4,
+                    3 => 5,
+                    4 => 7,
+                    foreign => bail!("Unsupported parametric curve type: {foreign}"),
+                };
+
+                let p = self.read_vec(count, Self::read_s15_fixed16)?;
+
+                match function_type {
+                    0 => ToneCurve::Gamma(p[0]),
+                    1 => ToneCurve::Parametric {
+                        g: p[0],
+                        a: p[1],
+                        b: p[2],
+                        c: 0.0,
+                        d: -p[2] / p[1],
+                        e: 0.0,
+                        f: 0.0,
+                    },
+                    2 => ToneCurve::Parametric {
+                        g: p[0],
+                        a: p[1],
+                        b: p[2],
+                        c: 0.0,
+                        d: -p[2] / p[1],
+                        e: p[3],
+                        f: p[3],
+                    },
+                    3 => ToneCurve::Parametric {
+                        g: p[0],
+                        a: p[1],
+                        b: p[2],
+                        c: p[3],
+                        d: p[4],
+                        e: 0.0,
+                        f: 0.0,
+                    },
+                    _ => ToneCurve::Parametric {
+                        g: p[0],
+                        a: p[1],
+                        b: p[2],
+                        c: p[3],
+                        d: p[4],
+                        e: p[5],
+                        f: p[6],
+                    },
+                }
+            }
+            foreign => bail!(
+                "Expected a curve, found {:?}",
+                String::from_utf8_lossy(foreign)
+            ),
+        };
+
+        self.cursor = start + (self.cursor - start).next_multiple_of(4);
+
+        Ok(curve)
+    }
+
+    fn read_curves(&mut self, count: usize) -> Result<Vec<ToneCurve>> {
+        self.read_vec(count, Self::read_curve)
+    }
+
+    /// Reads `count` tables of `entries` samples each, as in `lut8` and `lut16`.
+    fn read_tables(&mut self, count: usize, entries: usize, wide: bool) -> Result<Vec<ToneCurve>> {
+        self.read_vec(count, |r| {
+            Ok(ToneCurve::Table(
+                r.read_vec(entries, |r| r.read_normalized(wide))?,
+            ))
+        })
+    }
+
+    fn read_normalized(&mut self, wide: bool) -> Result<f32> {
+        Ok(if wide {
+            self.read_u16()? as f32 / 65535.0
+        } else {
+            self.read_u8()? as f32 / 255.0
+        })
+    }
+
+    fn read_clut(&mut self, grid_points: Vec<usize>, wide: bool) -> Result<Clut> {
+        ensure!(
+            grid_points.iter().all(|&points| points >= 2),
+            "Color lookup tables need at least two grid points per dimension."
+        );
+
+        let len = grid_points.iter().product::<usize>() * 3;
+        ensure!(
+            len * (1 + wide as usize) <= self.data.len(),
+            "Color lookup table exceeds its tag."
+        );
+
+        Ok(Clut {
+            grid_points,
+            values: self.read_vec(len, |r| r.read_normalized(wide))?,
+        })
+    }
+
+    impl_read_for_datatype!(read_u8, u8);
+    impl_read_for_datatype!(read_u16, u16);
+    impl_read_for_datatype!(read_u32, u32);
+    impl_read_for_datatype!(read_i32, i32);
+    impl_read_slice!();
+}
+
+impl ColorProfile {
+    /// Parses an ICC v2 or v4 profile of an RGB or grayscale device, either matrix/TRC or
+    /// table based.
+    pub fn from_icc(data: &[u8]) -> Result<Self> {
+        let mut reader = IccReader::new(data);
+
+        ensure!(
+            data.len() >= 132 && &data[36..40] == b"acsp",
+            "Invalid ICC profile: missing the 'acsp' signature."
+        );
+
+        let version = data[8];
+        ensure!(
+            matches!(version, 2 | 4),
+            "Unsupported ICC profile version: {version}"
+        );
+
+        let input_channels = match &data[16..20] {
+            b"RGB " => 3,
+            b"GRAY" => 1,
+            foreign => bail!(
+                "Unsupported ICC color space: {:?}",
+                String::from_utf8_lossy(foreign)
+            ),
+        };
+
+        let lab = match &data[20..24] {
+            b"XYZ " => false,
+            b"Lab " => true,
+            foreign => bail!(
+                "Unsupported ICC connection space: {:?}",
+                String::from_utf8_lossy(foreign)
+            ),
+        };
+
+        reader.cursor = 128;
+        let tag_count = reader.read_u32()? as usize;
+
+        let tags = reader.read_vec(tag_count, |r| {
+            let signature = r.read_signature()?;
+            let offset = r.read_u32()? as usize;
+            let size = r.read_u32()? as usize;
+
+            let tag = data
+                .get(offset..offset + size)
+                .ok_or_else(|| anyhow!("ICC tag {signature:?} exceeds the profile."))?;
+
+            Ok((signature, tag))
+        })?;
+
+        let tag = |signature: &[u8]| {
+            tags.iter()
+                .find(|(s, _)| *s == signature)
+                .map(|&(_, tag)| tag)
+        };
+
+        let shaper = match input_channels {
+            3 => match (
+                tag(b"rXYZ"),
+                tag(b"gXYZ"),
+                tag(b"bXYZ"),
+                tag(b"rTRC"),
+                tag(b"gTRC"),
+                tag(b"bTRC"),
+            ) {
+                (Some(r), Some(g), Some(b), Some(r_trc), Some(g_trc), Some(b_trc)) => {
+                    let columns = [parse_xyz(r)?, parse_xyz(g)?, parse_xyz(b)?];
+                    let mut matrix = IDENTITY;
+
+                    for (c, column) in columns.iter().enumerate() {
+                        for (r, &value) in column.iter().enumerate() {
+                            matrix[r][c] = value;
+                        }
+                    }
+
+                    Some(Shaper::Rgb {
+                        curves: [
+                            IccReader::new(r_trc).read_curve()?,
+                            IccReader::new(g_trc).read_curve()?,
+                            IccReader::new(b_trc).read_curve()?,
+                        ],
+                        matrix,
+                    })
+                }
+                _ => None,
+            },
+            _ => tag(b"kTRC")
+                .map(|k_trc| IccReader::new(k_trc).read_curve())
+                .transpose()?
+                .map(Shaper::Gray),
+        };
+
+        let lut = tag(b"A2B0")
+            .map(|a2b0| parse_lut(a2b0, input_channels, lab))
+            .transpose()?;
+
+        ensure!(
+            shaper.is_some() || lut.is_some(),
+            "The ICC profile has neither tone curves nor a lookup table."
+        );
+
+        let description = tag(b"desc").and_then(parse_description);
+
+        Ok(Self {
+            description,
+            shaper,
+            lut,
+        })
+    }
+}
+
+fn parse_xyz(tag: &[u8]) -> Result<[f32; 3]> {
+    let mut reader = IccReader::new(tag);
+    ensure!(reader.read_signature()? == b"XYZ ", "Expected an XYZ tag.");
+    reader.read_u32()?;
+
+    Ok([
+        reader.read_s15_fixed16()?,
+        reader.read_s15_fixed16()?,
+        reader.read_s15_fixed16()?,
+    ])
+}
+
+fn parse_lut(tag: &[u8], input_channels: usize, lab: bool) -> Result<Lut> {
+    let mut reader = IccReader::new(tag);
+    let signature = reader.read_signature()?;
+    reader.read_u32()?;
+
+    ensure!(
+        reader.read_u8()? as usize == input_channels && reader.read_u8()? == 3,
+        "The lookup table's channels don't match the profile."
+    );
+
+    let mut stages = Vec::new();
+
+    let encoding = match signature {
+        b"mft1" | b"mft2" => {
+            let wide = signature == b"mft2";
+            let grid_points = reader.read_u8()? as usize;
+            reader.read_u8()?;
+
+            // The matrix only applies to XYZ input, never to device values.
+            let _ = reader.read_matrix()?;
+
+            let (input_entries, output_entries) = if wide {
+                (reader.read_u16()? as usize, reader.read_u16()? as usize)
+            } else {
+                (256, 256)
+            };
+
+            stages.push(LutStage::Curves(reader.read_tables(
+                input_channels,
+                input_entries,
+                wide,
+            )?));
+            stages.push(LutStage::Clut(
+                reader.read_clut(vec![grid_points; input_channels], wide)?,
+            ));
+            stages.push(LutStage::Curves(reader.read_tables(
+                3,
+                output_entries,
+                wide,
+            )?));
+
+            match (lab, wide) {
+                (false, _) => PcsEncoding::Xyz,
+                (true, true) => PcsEncoding::LegacyLab,
+                (true, false) => PcsEncoding::Lab,
+            }
+        }
+        b"mAB " => {
+            reader.read_u16()?;
+            let offsets = reader.read_fixed_array::<5, _>(|r| Ok(r.read_u32()? as usize))?;
+            let [b_curves, matrix, m_curves, clut, a_curves] = offsets;
+
+            if a_curves != 0 {
+                let mut reader = IccReader::at(tag, a_curves);
+                stages.push(LutStage::Curves(reader.read_curves(input_channels)?));
+            }
+
+            if clut != 0 {
+                let mut reader = IccReader::at(tag, clut);
+                let grid_points = reader.read_slice(16)?[..input_channels]
+                    .iter()
+                    .map(|&points| points as usize)
+                    .collect();
+                let wide = reader.read_u8()? == 2;
+                reader.read_slice(3)?;
+
+                stages.push(LutStage::Clut(reader.read_clut(grid_points, wide)?));
+            } else {
+                ensure!(
+                    input_channels == 3,
+                    "A lookup table without a grid must have three inputs."
+                );
+            }
+
+            if m_curves != 0 {
+                let mut reader = IccReader::at(tag, m_curves);
+                stages.push(LutStage::Curves(reader.read_curves(3)?));
+            }
+
+            if matrix != 0 {
+                let mut reader = IccReader::at(tag, matrix);
+
+                stages.push(LutStage::Matrix {
+                    matrix: reader.read_matrix()?,
+                    offset: reader.read_fixed_array(IccReader::read_s15_fixed16)?,
+                });
+            }
+
+            ensure!(b_curves != 0, "The lookup table is missing its B curves.");
+            let mut reader = IccReader::at(tag, b_curves);
+            stages.push(LutStage::Curves(reader.read_curves(3)?));
+
+            if lab {
+                PcsEncoding::Lab
+            } else {
+                PcsEncoding::Xyz
+            }
+        }
+        foreign => bail!(
+            "Unsupported lookup table type: {:?}",
+            String::from_utf8_lossy(foreign)
+        ),
+    };
+
+    Ok(Lut {
+        input_channels,
+        stages,
+        encoding,
+    })
+}
+
+/// Reads a v2 `desc` text or the first record of a v4 `mluc` tag.
+fn parse_description(tag: &[u8]) -> Option<String> {
+    let mut reader = IccReader::new(tag);
+    let signature = reader.read_signature().ok()?;
+    reader.read_u32().ok()?;
+
+    let description = match signature {
+        b"desc" => {
+            let len = reader.read_u32().ok()? as usize;
+            let text = reader.read_slice(len).ok()?;
+
+            String::from_utf8_lossy(text)
+                .trim_end_matches('\0')
+                .to_string()
+        }
+        b"mluc" => {
+            let _records = reader.read_u32().ok()?;
+            let _record_size = reader.read_u32().ok()?;
+            let _language = reader.read_u32().ok()?;
+            let len = reader.read_u32().ok()? as usize;
+            let offset = reader.read_u32().ok()? as usize;
+
+            let units = tag
+                .get(offset..offset + len)?
+                .chunks_exact(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect::<Vec<_>>();
+
+            String::from_utf16_lossy(&units)
+        }
+        _ => return None,
+    };
+
+    Some(description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::{xyz_to_lab, ColorTransform},
+        png::PngDecoder,
+    };
+
+    fn s15_fixed16(value: f32) -> [u8; 4] {
+        ((value * 65536.0).round() as i32).to_be_bytes()
+    }
+
+    /// Assembles a display profile from its tags.
+    fn build_profile(
+        color_space: &[u8; 4],
+        pcs: &[u8; 4],
+        tags: &[(&[u8; 4], Vec<u8>)],
+    ) -> Vec<u8> {
+        let mut header = vec![0; 128];
+        header[8] = 4;
+        header[12..16].copy_from_slice(b"mntr");
+        header[16..20].copy_from_slice(color_space);
+        header[20..24].copy_from_slice(pcs);
+        header[36..40].copy_from_slice(b"acsp");
+
+        let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+        let mut data = Vec::new();
+        let data_start = 132 + 12 * tags.len();
+
+        for (signature, tag) in tags {
+            table.extend_from_slice(*signature);
+            table.extend_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+            table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+
+            data.extend_from_slice(tag);
+            data.resize(data.len().next_multiple_of(4), 0);
+        }
+
+        let mut profile = [header, table, data].concat();
+        let size = profile.len() as u32;
+        profile[..4].copy_from_slice(&size.to_be_bytes());
+
+        profile
+    }
+
+    /// Converts sRGB to D50 XYZ with the built-in profile.
+    fn srgb_to_xyz(rgb: [f32; 3]) -> [f32; 3] {
+        let Some(Shaper::Rgb { curves, matrix }) = ColorProfile::srgb().shaper else {
+            unreachable!()
+        };
+
+        crate::color::matrix::apply(&matrix, [0, 1, 2].map(|c| curves[c].eval(rgb[c])))
+    }
+
+    /// Asserts that `profile` describes sRGB, to within `tolerance`.
+    fn assert_srgb(profile: &ColorProfile, tolerance: f32) -> Result<()> {
+        let transform = ColorTransform::new(profile, &ColorProfile::srgb())?;
+
+        for rgb in [
+            [0.0; 3],
+            [1.0; 3],
+            [0.5; 3],
+            [0.9, 0.2, 0.1],
+            [0.1, 0.6, 0.3],
+            [0.25, 0.3, 0.95],
+        ] {
+            let converted = transform.transform(rgb);
+
+            for (value, expected) in converted.iter().zip(rgb) {
+                assert!(
+                    (value - expected).abs() < tolerance,
+                    "{rgb:?}: {converted:?}"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_matrix_profile() -> Result<()> {
+        let png = PngDecoder::new(&std::fs::read("./tests/obama.png")?).decode()?;
+        let icc_profile = png
+            .icc_profile
+            .as_deref()
+            .expect("obama.png has an iCCP chunk");
+
+        // An ICC v2 sRGB profile written by lcms, with 1024 entry curves.
+        let profile = ColorProfile::from_icc(icc_profile)?;
+        assert_eq!(profile.description(), Some("sRGB"));
+        assert!(matches!(
+            &profile.shaper,
+            Some(Shaper::Rgb { curves: [ToneCurve::Table(table), ..], .. }) if table.len() == 1024
+        ));
+        assert!(profile.lut.is_none());
+
+        assert_srgb(&profile, 1.0 / 255.0)
+    }
+
+    #[test]
+    fn test_lut16_profile() -> Result<()> {
+        let grid_points = 17;
+
+        let mut lut = b"mft2\0\0\0\0".to_vec();
+        lut.extend_from_slice(&[3, 3, grid_points as u8, 0]);
+
+        for i in 0..9 {
+            lut.extend_from_slice(&s15_fixed16(if i % 4 == 0 { 1.0 } else { 0.0 }));
+        }
+
+        // Two-entry identity tables around a grid of legacy encoded CIELAB.
+        lut.extend_from_slice(&[0, 2, 0, 2]);
+        let identity = [0u8, 0, 0xFF, 0xFF].repeat(3);
+        lut.extend_from_slice(&identity);
+
+        for i in 0..grid_points * grid_points * grid_points {
+            let rgb = [
+                i / (grid_points * grid_points),
+                i / grid_points % grid_points,
+                i % grid_points,
+            ]
+            .map(|v| v as f32 / (grid_points - 1) as f32);
+            let [l, a, b] = xyz_to_lab(srgb_to_xyz(rgb));
+
+            for value in [l * 652.8, (a + 128.0) * 256.0, (b + 128.0) * 256.0] {
+                lut.extend_from_slice(&(value.round().clamp(0.0, 65535.0) as u16).to_be_bytes());
+            }
+        }
+
+        lut.extend_from_slice(&identity);
+
+        let profile = ColorProfile::from_icc(&build_profile(b"RGB ", b"Lab ", &[(b"A2B0", lut)]))?;
+        assert!(matches!(
+            profile.lut,
+            Some(Lut {
+                encoding: PcsEncoding::LegacyLab,
+                ..
+            })
+        ));
+
+        assert_srgb(&profile, 0.01)
+    }
+
+    #[test]
+    fn test_lut_a_to_b_profile() -> Result<()> {
+        let Some(Shaper::Rgb { matrix, .. }) = ColorProfile::srgb().shaper else {
+            unreachable!()
+        };
+
+        // The sRGB curve as a type 3 parametric curve, padded to a multiple of 4 bytes.
+        let mut srgb_curve = b"para\0\0\0\0\0\x03\0\0".to_vec();
+
+        for parameter in [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045] {
+            srgb_curve.extend_from_slice(&s15_fixed16(parameter));
+        }
+
+        let identity_curve = b"curv\0\0\0\0\0\0\0\0".to_vec();
+
+        // M curves, then the matrix in the XYZ encoding, then B curves.
+        let m_curves = 32;
+        let matrix_offset = m_curves + 3 * srgb_curve.len();
+        let b_curves = matrix_offset + 48;
+
+        let mut lut = b"mAB \0\0\0\0".to_vec();
+        lut.extend_from_slice(&[3, 3, 0, 0]);
+
+        for offset in [b_curves, matrix_offset, m_curves, 0, 0] {
+            lut.extend_from_slice(&(offset as u32).to_be_bytes());
+        }
+
+        lut.extend_from_slice(&srgb_curve.repeat(3));
+
+        for element in matrix.iter().flatten() {
+            lut.extend_from_slice(&s15_fixed16(element * 32768.0 / 65535.0));
+        }
+
+        lut.extend_from_slice(&[0; 12]);
+        lut.extend_from_slice(&identity_curve.repeat(3));
+
+        let mut description = b"mluc\0\0\0\0".to_vec();
+
+        for value in [1, 12, u32::from_be_bytes(*b"enUS"), 8, 28] {
+            description.extend_from_slice(&u32::to_be_bytes(value));
+        }
+
+        description.extend("sRGB".encode_utf16().flat_map(u16::to_be_bytes));
+
+        let profile = ColorProfile::from_icc(&build_profile(
+            b"RGB ",
+            b"XYZ ",
+            &[(b"desc", description), (b"A2B0", lut)],
+        ))?;
+
+        assert_eq!(profile.description(), Some("sRGB"));
+        assert!(matches!(
+            profile.lut,
+            Some(Lut { encoding: PcsEncoding::Xyz, ref stages, .. }) if stages.len() == 3
+        ));
+
+        assert_srgb(&profile, 1e-3)
+    }
+
+    #[test]
+    fn test_invalid_profiles() {
+        assert!(ColorProfile::from_icc(&[0; 200]).is_err());
+
+        // A CMYK profile.
+        assert!(ColorProfile::from_icc(&build_profile(b"CMYK", b"Lab ", &[])).is_err());
+
+        // An RGB profile without any colorants or tables.
+        let curve = b"curv\0\0\0\0\0\0\0\0".to_vec();
+        assert!(
+            ColorProfile::from_icc(&build_profile(b"RGB ", b"XYZ ", &[(b"rTRC", curve)])).is_err()
+        );
+    }
+}
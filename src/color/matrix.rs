@@ -0,0 +1,69 @@
+/// A row-major 3x3 matrix.
+pub type Matrix3 = [[f32; 3]; 3];
+
+pub const IDENTITY: Matrix3 = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// The Bradford cone response matrix, used for chromatic adaptation.
+const BRADFORD: Matrix3 = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+pub fn apply(m: &Matrix3, [x, y, z]: [f32; 3]) -> [f32; 3] {
+    m.map(|row| row[2].mul_add(z, row[1].mul_add(y, row[0] * x)))
+}
+
+pub fn multiply(a: &Matrix3, b: &Matrix3) -> Matrix3 {
+    let mut product = [[0.0; 3]; 3];
+
+    for (r, row) in product.iter_mut().enumerate() {
+        for (c, element) in row.iter_mut().enumerate() {
+            *element = (0..3).map(|k| a[r][k] * b[k][c]).sum();
+        }
+    }
+
+    product
+}
+
+pub fn invert(m: &Matrix3) -> Option<Matrix3> {
+    let m = m.map(|row| row.map(f64::from));
+    let cofactor = |r: usize, c: usize| {
+        let (r0, r1) = ((r + 1) % 3, (r + 2) % 3);
+        let (c0, c1) = ((c + 1) % 3, (c + 2) % 3);
+
+        m[r0][c0].mul_add(m[r1][c1], -m[r0][c1] * m[r1][c0])
+    };
+
+    let determinant = (0..3).map(|c| m[0][c] * cofactor(0, c)).sum::<f64>();
+
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+
+    let mut inverse = [[0.0; 3]; 3];
+
+    for (r, row) in inverse.iter_mut().enumerate() {
+        for (c, element) in row.iter_mut().enumerate() {
+            // The adjugate is the transposed cofactor matrix.
+            *element = (cofactor(c, r) / determinant) as f32;
+        }
+    }
+
+    Some(inverse)
+}
+
+/// Builds the matrix mapping XYZ relative to `source_white` to XYZ relative to
+/// `destination_white` with the Bradford transform.
+pub fn adaptation(source_white: [f32; 3], destination_white: [f32; 3]) -> Option<Matrix3> {
+    let source = apply(&BRADFORD, source_white);
+    let destination = apply(&BRADFORD, destination_white);
+
+    let mut scale = IDENTITY;
+
+    for i in 0..3 {
+        scale[i][i] = destination[i] / source[i];
+    }
+
+    Some(multiply(&invert(&BRADFORD)?, &multiply(&scale, &BRADFORD)))
+}
@@ -0,0 +1,9 @@
+pub use curve::*;
+pub use profile::*;
+pub use transform::*;
+
+mod curve;
+mod icc;
+mod matrix;
+mod profile;
+mod transform;
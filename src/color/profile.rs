@@ -0,0 +1,319 @@
+use crate::color::{
+    curve::ToneCurve,
+    matrix::{self, Matrix3},
+};
+use anyhow::{anyhow, Result};
+
+/// The ICC profile connection space white point, D50.
+pub const D50: [f32; 3] = [0.9642, 1.0, 0.8249];
+
+/// The white point of sRGB, Display P3 and Adobe RGB, as a chromaticity.
+pub const D65_CHROMATICITY: [f32; 2] = [0.3127, 0.3290];
+
+pub const SRGB_PRIMARIES: [[f32; 2]; 3] = [[0.64, 0.33], [0.30, 0.60], [0.15, 0.06]];
+
+/// Describes how the samples of an image map to colors, as the ICC profile connection space
+/// (CIE XYZ relative to D50).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorProfile {
+    pub(crate) description: Option<String>,
+    pub(crate) shaper: Option<Shaper>,
+    /// The device to connection space table, preferred over the shaper when both are present.
+    pub(crate) lut: Option<Lut>,
+}
+
+/// The tone curve and matrix model used by most RGB and grayscale profiles.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shaper {
+    /// Per-channel curves to linear RGB, then a matrix whose columns are the primaries in XYZ.
+    Rgb {
+        curves: [ToneCurve; 3],
+        matrix: Matrix3,
+    },
+    /// A curve to linear gray, scaled by the D50 white.
+    Gray(ToneCurve),
+}
+
+/// A pipeline of lookup stages from device values to the profile connection space, as in the
+/// ICC `lut8`, `lut16` and `lutAToB` types. Values between stages are normalized to
+/// [0.0, 1.0].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut {
+    pub(crate) input_channels: usize,
+    pub(crate) stages: Vec<LutStage>,
+    pub(crate) encoding: PcsEncoding,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LutStage {
+    Curves(Vec<ToneCurve>),
+    Matrix { matrix: Matrix3, offset: [f32; 3] },
+    Clut(Clut),
+}
+
+/// A multidimensional table with three outputs, interpolated multilinearly. The first input
+/// varies slowest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clut {
+    pub(crate) grid_points: Vec<usize>,
+    pub(crate) values: Vec<f32>,
+}
+
+/// How normalized values encode the connection space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcsEncoding {
+    /// XYZ, where 1.0 is stored as 0x8000 of 0xFFFF.
+    Xyz,
+    /// CIELAB, where L spans [0, 100] and a and b span [-128, 127].
+    Lab,
+    /// The 16-bit CIELAB encoding of ICC v2, used by every `lut16` table, where L = 100 is
+    /// stored as 0xFF00.
+    LegacyLab,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileColorSpace {
+    Rgb,
+    Gray,
+}
+
+impl ColorProfile {
+    /// Builds an RGB profile from the chromaticities of its white point and primaries, like the
+    /// PNG `cHRM` chunk describes.
+    pub fn from_chromaticities(
+        white: [f32; 2],
+        primaries: [[f32; 2]; 3],
+        curve: ToneCurve,
+    ) -> Result<Self> {
+        let degenerate = || anyhow!("Degenerate chromaticities: {white:?}, {primaries:?}");
+
+        let xyz = |[x, y]: [f32; 2]| -> Result<[f32; 3]> {
+            if y <= 0.0 {
+                return Err(degenerate());
+            }
+
+            Ok([x / y, 1.0, (1.0 - x - y) / y])
+        };
+
+        let white = xyz(white)?;
+        let columns = [xyz(primaries[0])?, xyz(primaries[1])?, xyz(primaries[2])?];
+
+        let mut primaries_matrix = [[0.0; 3]; 3];
+
+        for (c, column) in columns.iter().enumerate() {
+            for (r, &value) in column.iter().enumerate() {
+                primaries_matrix[r][c] = value;
+            }
+        }
+
+        // Scale each primary so that full intensity of all three is the white point.
+        let scale = matrix::apply(
+            &matrix::invert(&primaries_matrix).ok_or_else(degenerate)?,
+            white,
+        );
+        let rgb_to_xyz = primaries_matrix.map(|row| [0, 1, 2].map(|c| row[c] * scale[c]));
+
+        let adaptation = matrix::adaptation(white, D50).ok_or_else(degenerate)?;
+
+        Ok(Self {
+            description: None,
+            shaper: Some(Shaper::Rgb {
+                curves: [curve.clone(), curve.clone(), curve],
+                matrix: matrix::multiply(&adaptation, &rgb_to_xyz),
+            }),
+            lut: None,
+        })
+    }
+
+    pub const fn gray(curve: ToneCurve) -> Self {
+        Self {
+            description: None,
+            shaper: Some(Shaper::Gray(curve)),
+            lut: None,
+        }
+    }
+
+    pub fn srgb() -> Self {
+        Self::builtin("sRGB", SRGB_PRIMARIES, ToneCurve::SRGB)
+    }
+
+    /// sRGB without its transfer function, for blending and filtering in linear light.
+    pub fn linear_srgb() -> Self {
+        Self::builtin("Linear sRGB", SRGB_PRIMARIES, ToneCurve::LINEAR)
+    }
+
+    pub fn display_p3() -> Self {
+        Self::builtin(
+            "Display P3",
+            [[0.680, 0.320], [0.265, 0.690], [0.150, 0.060]],
+            ToneCurve::SRGB,
+        )
+    }
+
+    pub fn adobe_rgb() -> Self {
+        Self::builtin(
+            "Adobe RGB (1998)",
+            [[0.64, 0.33], [0.21, 0.71], [0.15, 0.06]],
+            ToneCurve::Gamma(563.0 / 256.0),
+        )
+    }
+
+    fn builtin(description: &str, primaries: [[f32; 2]; 3], curve: ToneCurve) -> Self {
+        let profile = Self::from_chromaticities(D65_CHROMATICITY, primaries, curve)
+            .expect("Built-in chromaticities are valid");
+
+        profile.with_description(description)
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// The matrix from linear RGB to XYZ relative to D50, for RGB matrix profiles.
+    pub const fn rgb_to_xyz(&self) -> Option<&Matrix3> {
+        match &self.shaper {
+            Some(Shaper::Rgb { matrix, .. }) => Some(matrix),
+            _ => None,
+        }
+    }
+
+    pub const fn color_space(&self) -> ProfileColorSpace {
+        match (&self.shaper, &self.lut) {
+            (Some(Shaper::Gray(_)), _) => ProfileColorSpace::Gray,
+            (None, Some(lut)) if lut.input_channels == 1 => ProfileColorSpace::Gray,
+            _ => ProfileColorSpace::Rgb,
+        }
+    }
+}
+
+impl Lut {
+    /// Maps device values to XYZ relative to D50.
+    pub fn eval(&self, input: [f32; 3]) -> [f32; 3] {
+        let mut values = input;
+
+        for stage in &self.stages {
+            values = match stage {
+                LutStage::Curves(curves) => {
+                    let mut output = values;
+
+                    for (value, curve) in output.iter_mut().zip(curves) {
+                        *value = curve.eval(*value);
+                    }
+
+                    output
+                }
+                LutStage::Matrix { matrix, offset } => {
+                    let [x, y, z] = matrix::apply(matrix, values);
+                    [x + offset[0], y + offset[1], z + offset[2]].map(|v| v.clamp(0.0, 1.0))
+                }
+                LutStage::Clut(clut) => clut.eval(&values[..clut.grid_points.len()]),
+            };
+        }
+
+        match self.encoding {
+            PcsEncoding::Xyz => values.map(|v| v * 65535.0 / 32768.0),
+            PcsEncoding::Lab => lab_to_xyz([
+                values[0] * 100.0,
+                values[1].mul_add(255.0, -128.0),
+                values[2].mul_add(255.0, -128.0),
+            ]),
+            PcsEncoding::LegacyLab => lab_to_xyz([
+                values[0] * 65535.0 / 65280.0 * 100.0,
+                values[1].mul_add(65535.0 / 256.0, -128.0),
+                values[2].mul_add(65535.0 / 256.0, -128.0),
+            ]),
+        }
+    }
+}
+
+impl Clut {
+    fn eval(&self, input: &[f32]) -> [f32; 3] {
+        let dimensions = self.grid_points.len();
+        let mut base = [0; 3];
+        let mut fraction = [0.0; 3];
+
+        for (d, &points) in self.grid_points.iter().enumerate() {
+            let position = input[d].clamp(0.0, 1.0) * (points - 1) as f32;
+            base[d] = (position as usize).min(points - 2);
+            fraction[d] = position - base[d] as f32;
+        }
+
+        let mut output = [0.0; 3];
+
+        // Blends the 2^n corners of the cell around the input.
+        for corner in 0..1usize << dimensions {
+            let mut index = 0;
+            let mut weight = 1.0;
+
+            for d in 0..dimensions {
+                let high = (corner >> (dimensions - 1 - d)) & 1;
+
+                index = index * self.grid_points[d] + base[d] + high;
+                weight *= if high == 1 {
+                    fraction[d]
+                } else {
+                    1.0 - fraction[d]
+                };
+            }
+
+            if weight == 0.0 {
+                continue;
+            }
+
+            for (o, value) in output.iter_mut().enumerate() {
+                *value = weight.mul_add(self.values[index * 3 + o], *value);
+            }
+        }
+
+        output
+    }
+}
+
+const EPSILON: f32 = 216.0 / 24389.0;
+const KAPPA: f32 = 24389.0 / 27.0;
+
+fn lab_to_xyz([l, a, b]: [f32; 3]) -> [f32; 3] {
+    let fy = (l + 16.0) / 116.0;
+    let fx = a / 500.0 + fy;
+    let fz = fy - b / 200.0;
+
+    let inverse = |f: f32| {
+        let cubed = f * f * f;
+
+        if cubed > EPSILON {
+            cubed
+        } else {
+            116.0f32.mul_add(f, -16.0) / KAPPA
+        }
+    };
+
+    [
+        inverse(fx) * D50[0],
+        inverse(fy) * D50[1],
+        inverse(fz) * D50[2],
+    ]
+}
+
+/// Converts XYZ relative to D50 to CIELAB, where L spans [0, 100].
+pub fn xyz_to_lab(xyz: [f32; 3]) -> [f32; 3] {
+    let [fx, fy, fz] = [0, 1, 2].map(|i| {
+        let t = xyz[i] / D50[i];
+
+        if t > EPSILON {
+            t.cbrt()
+        } else {
+            KAPPA.mul_add(t, 16.0) / 116.0
+        }
+    });
+
+    [
+        116.0f32.mul_add(fy, -16.0),
+        500.0 * (fx - fy),
+        200.0 * (fy - fz),
+    ]
+}
@@ -0,0 +1,273 @@
+use crate::{
+    color::{
+        curve::ToneCurve,
+        matrix::{self, Matrix3},
+        profile::{ColorProfile, Lut, Shaper, D50},
+    },
+    image::{ImageBuffer, Pixel},
+};
+use anyhow::{anyhow, Result};
+
+/// Converts colors from one profile to another through the connection space, with relative
+/// colorimetric intent.
+#[derive(Debug, Clone)]
+pub struct ColorTransform {
+    source: Source,
+    /// Maps the linear source (or the connection space for tables) to linear destination RGB.
+    matrix: Matrix3,
+    output_curves: [OutputCurve; 3],
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    /// Linearizes each channel. Grayscale uses the same curve for every channel.
+    Curves([ToneCurve; 3]),
+    Lut(Lut),
+}
+
+#[derive(Debug, Clone)]
+enum OutputCurve {
+    Exact(ToneCurve),
+    /// The inverse of a table, sampled evenly over linear light.
+    Sampled(ToneCurve),
+}
+
+impl OutputCurve {
+    const SAMPLES: usize = 4096;
+
+    fn new(curve: &ToneCurve) -> Self {
+        match curve {
+            ToneCurve::Table(_) => Self::Sampled(ToneCurve::Table(
+                (0..Self::SAMPLES)
+                    .map(|i| curve.eval_inverse(i as f32 / (Self::SAMPLES - 1) as f32))
+                    .collect(),
+            )),
+            curve => Self::Exact(curve.clone()),
+        }
+    }
+
+    fn eval(&self, y: f32) -> f32 {
+        match self {
+            Self::Exact(curve) => curve.eval_inverse(y),
+            Self::Sampled(inverse) => inverse.eval(y),
+        }
+    }
+}
+
+impl ColorTransform {
+    /// The destination must be an RGB profile with tone curves and a matrix, such as
+    /// [`ColorProfile::srgb`] or any of the other built-in working spaces.
+    pub fn new(source: &ColorProfile, destination: &ColorProfile) -> Result<Self> {
+        let Some(Shaper::Rgb {
+            curves: destination_curves,
+            matrix: destination_matrix,
+        }) = &destination.shaper
+        else {
+            return Err(anyhow!(
+                "Only RGB matrix profiles can be converted to, not {:?}",
+                destination.description().unwrap_or("an unnamed profile")
+            ));
+        };
+
+        let xyz_to_destination = matrix::invert(destination_matrix)
+            .ok_or_else(|| anyhow!("The destination profile's matrix isn't invertible."))?;
+
+        let (source, matrix) = match (&source.lut, &source.shaper) {
+            (Some(lut), _) => (Source::Lut(lut.clone()), xyz_to_destination),
+            (None, Some(Shaper::Rgb { curves, matrix })) => (
+                Source::Curves(curves.clone()),
+                matrix::multiply(&xyz_to_destination, matrix),
+            ),
+            (None, Some(Shaper::Gray(curve))) => {
+                // Gray is the D50 white scaled by the linear value.
+                let mut white = [[0.0; 3]; 3];
+
+                for (row, value) in white.iter_mut().zip(D50) {
+                    row[0] = value;
+                }
+
+                (
+                    Source::Curves([curve.clone(), curve.clone(), curve.clone()]),
+                    matrix::multiply(&xyz_to_destination, &white),
+                )
+            }
+            (None, None) => unreachable!("Profiles have tone curves or a lookup table"),
+        };
+
+        Ok(Self {
+            source,
+            matrix,
+            output_curves: [
+                OutputCurve::new(&destination_curves[0]),
+                OutputCurve::new(&destination_curves[1]),
+                OutputCurve::new(&destination_curves[2]),
+            ],
+        })
+    }
+
+    /// Converts a normalized color. Grayscale sources only read the first channel.
+    pub fn transform(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let linear = match &self.source {
+            Source::Curves(curves) => [0, 1, 2].map(|c| curves[c].eval(rgb[c])),
+            Source::Lut(lut) => lut.eval(rgb),
+        };
+
+        self.encode(linear)
+    }
+
+    fn encode(&self, linear: [f32; 3]) -> [f32; 3] {
+        let [r, g, b] = matrix::apply(&self.matrix, linear);
+
+        [
+            self.output_curves[0].eval(r),
+            self.output_curves[1].eval(g),
+            self.output_curves[2].eval(b),
+        ]
+    }
+
+    pub fn apply_rgb8(&self, rgb: &mut [u8]) {
+        self.apply8(rgb, 3);
+    }
+
+    /// Converts RGBA samples with straight alpha, leaving alpha untouched.
+    pub fn apply_rgba8(&self, rgba: &mut [u8]) {
+        self.apply8(rgba, 4);
+    }
+
+    fn apply8(&self, samples: &mut [u8], channel_count: usize) {
+        // Linearizing 8-bit samples only takes 256 evaluations per curve.
+        let input_tables = match &self.source {
+            Source::Curves(curves) => Some(curves.clone().map(|curve| {
+                (0..=255)
+                    .map(|v| curve.eval(v as f32 / 255.0))
+                    .collect::<Vec<_>>()
+            })),
+            Source::Lut(_) => None,
+        };
+
+        for pixel in samples.chunks_exact_mut(channel_count) {
+            let rgb = input_tables.as_ref().map_or_else(
+                || self.transform([pixel[0], pixel[1], pixel[2]].map(|v| v as f32 / 255.0)),
+                |tables| self.encode([0, 1, 2].map(|c| tables[c][pixel[c] as usize])),
+            );
+
+            for (sample, value) in pixel.iter_mut().zip(rgb) {
+                *sample = (value * 255.0).round() as u8;
+            }
+        }
+    }
+
+    /// Converts normalized RGBA samples with straight alpha, leaving alpha untouched.
+    pub fn apply_rgba32f(&self, rgba: &mut [f32]) {
+        for pixel in rgba.chunks_exact_mut(4) {
+            let rgb = self.transform([pixel[0], pixel[1], pixel[2]]);
+            pixel[..3].copy_from_slice(&rgb);
+        }
+    }
+
+    /// Converts every pixel of `image` in place. Alpha is left untouched, and grayscale
+    /// images keep the luma of the converted color.
+    pub fn apply<P: Pixel>(&self, image: &mut ImageBuffer<P>) {
+        for pixel in image.pixels_mut() {
+            let [r, g, b, a] = pixel.to_rgba();
+            let [r, g, b] = self.transform([r, g, b]);
+
+            *pixel = P::from_rgba([r, g, b, a]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Rgba16;
+
+    /// Converts `rgb` from one profile to another and back.
+    fn round_trip(from: &ColorProfile, to: &ColorProfile, rgb: [f32; 3]) -> Result<[f32; 3]> {
+        let there = ColorTransform::new(from, to)?.transform(rgb);
+
+        Ok(ColorTransform::new(to, from)?.transform(there))
+    }
+
+    #[test]
+    fn test_builtin_matrices() {
+        let Some(Shaper::Rgb { matrix, .. }) = ColorProfile::srgb().shaper else {
+            panic!("sRGB is a matrix profile");
+        };
+
+        // The D50 adapted sRGB matrix, as published by the ICC.
+        let expected = [
+            [0.4361, 0.3851, 0.1431],
+            [0.2225, 0.7169, 0.0606],
+            [0.0139, 0.0971, 0.7142],
+        ];
+
+        for (row, expected_row) in matrix.iter().zip(expected) {
+            for (value, expected_value) in row.iter().zip(expected_row) {
+                assert!((value - expected_value).abs() < 5e-4, "{matrix:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_working_spaces() -> Result<()> {
+        let srgb = ColorProfile::srgb();
+        let p3 = ColorProfile::display_p3();
+
+        for rgb in [[0.0; 3], [1.0; 3], [0.2, 0.5, 0.8], [1.0, 0.0, 0.0]] {
+            for profile in [
+                &p3,
+                &ColorProfile::adobe_rgb(),
+                &ColorProfile::linear_srgb(),
+            ] {
+                let back = round_trip(&srgb, profile, rgb)?;
+
+                for (value, expected) in back.iter().zip(rgb) {
+                    assert!((value - expected).abs() < 1e-3, "{rgb:?}: {back:?}");
+                }
+            }
+        }
+
+        // White stays white, and sRGB red sits inside P3.
+        let transform = ColorTransform::new(&srgb, &p3)?;
+        let white = transform.transform([1.0; 3]);
+        assert!(white.iter().all(|v| (v - 1.0).abs() < 1e-3), "{white:?}");
+
+        let [r, g, b] = transform.transform([1.0, 0.0, 0.0]);
+        assert!(
+            (r - 0.9175).abs() < 2e-3 && (g - 0.2003).abs() < 2e-3 && (b - 0.1386).abs() < 2e-3
+        );
+
+        // P3 red doesn't fit in sRGB and clips.
+        let clipped = ColorTransform::new(&p3, &srgb)?.transform([1.0, 0.0, 0.0]);
+        assert_eq!(clipped.map(|v| (v * 255.0).round()), [255.0, 0.0, 0.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply() -> Result<()> {
+        let transform = ColorTransform::new(&ColorProfile::display_p3(), &ColorProfile::srgb())?;
+
+        let mut rgba = vec![255, 0, 0, 128, 255, 255, 255, 0, 0, 0, 0, 255];
+        transform.apply_rgba8(&mut rgba);
+        assert_eq!(rgba, [255, 0, 0, 128, 255, 255, 255, 0, 0, 0, 0, 255]);
+
+        let mut rgb = vec![128, 64, 32];
+        transform.apply_rgb8(&mut rgb);
+        let expected = transform.transform([128.0, 64.0, 32.0].map(|v| v / 255.0));
+        assert_eq!(rgb, expected.map(|v| (v * 255.0).round() as u8));
+
+        let mut image = ImageBuffer::from_pixel(2, 2, Rgba16([65535, 0, 0, 1000]));
+        transform.apply(&mut image);
+        assert_eq!(image.pixel(1, 1).0[3], 1000);
+        assert_eq!(image.pixel(1, 1).0[1], 0);
+
+        // Gray sources are neutral in any RGB space.
+        let gray = ColorProfile::gray(ToneCurve::Gamma(2.2));
+        let [r, g, b] = ColorTransform::new(&gray, &ColorProfile::adobe_rgb())?.transform([0.5; 3]);
+        assert!((r - g).abs() < 1e-3 && (g - b).abs() < 1e-3);
+
+        Ok(())
+    }
+}
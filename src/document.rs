@@ -0,0 +1,442 @@
+//! Edit documents: a source image and the edits over it, saved as a `.norm` file so an editing
+//! session can be picked up later.
+//!
+//! Documents are UTF-8 text, one `key value` pair per line after a versioned header:
+//!
+//! ```text
+//! norm-document 1
+//! source obama.png
+//! effect invert
+//! effect blur 10.5
+//! circle 0.25 0.5 0.1
+//! ```
+//!
+//! Missing keys keep their defaults. Relative sources and LUTs are relative to the document.
+//! Effects of the effect stack run in the order they're listed.
+
+use crate::effects::{CircleShape, Edits, Effect, MAX_CIRCLES};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::{
+    fmt::{self, Display},
+    fs,
+    path::{self, Path, PathBuf},
+    str::FromStr,
+};
+
+const HEADER: &str = "norm-document";
+const VERSION: u32 = 1;
+
+/// The extension of edit documents.
+pub const EXTENSION: &str = "norm";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditDocument {
+    pub source: PathBuf,
+    pub edits: Edits,
+}
+
+impl EditDocument {
+    /// A document without edits over `source`.
+    pub fn new(source: impl Into<PathBuf>) -> Self {
+        Self {
+            source: source.into(),
+            edits: Edits::default(),
+        }
+    }
+
+    pub fn with_edits(mut self, edits: Edits) -> Self {
+        self.edits = edits;
+        self
+    }
+
+    /// Reads a document, resolving its source and LUTs against the document's directory.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut document = Self::parse_in(&text, directory)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        document.source = directory.join(&document.source);
+
+        Ok(document)
+    }
+
+    /// Writes the document, with its source and LUTs relative to the document when they're
+    /// beside or below it, so they can be moved together.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+
+        let directory = path::absolute(path)?
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let relative = |path: &Path| -> Result<PathBuf> {
+            let path = path::absolute(path)?;
+            let relative = path
+                .strip_prefix(&directory)
+                .map_or_else(|_| path.clone(), Path::to_path_buf);
+
+            // Documents are text, so paths that aren't UTF-8 can't be saved without loss.
+            let text = relative
+                .to_str()
+                .ok_or_else(|| anyhow!("Expect the path {} to be UTF-8.", relative.display()))?;
+            ensure!(
+                !text.contains('\n'),
+                "Expect the path {} to fit on one line.",
+                relative.display()
+            );
+
+            Ok(relative)
+        };
+
+        let effects = self
+            .edits
+            .effects
+            .iter()
+            .map(|effect| match effect {
+                Effect::Lut {
+                    path,
+                    lut,
+                    interpolation,
+                } => Ok(Effect::Lut {
+                    path: relative(path)?,
+                    lut: lut.clone(),
+                    interpolation: *interpolation,
+                }),
+                effect => Ok(effect.clone()),
+            })
+            .collect::<Result<_>>()?;
+
+        let document = Self {
+            source: relative(&self.source)?,
+            edits: Edits {
+                effects,
+                ..self.edits.clone()
+            },
+        };
+
+        fs::write(path, document.to_string())
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Where a document for `image` is saved by default: beside it, with the `.norm` extension.
+    pub fn default_path(image: impl AsRef<Path>) -> PathBuf {
+        image.as_ref().with_extension(EXTENSION)
+    }
+}
+
+impl Display for EditDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Edits {
+            effects,
+            circles,
+            flip_x,
+            flip_y,
+        } = &self.edits;
+
+        writeln!(f, "{HEADER} {VERSION}")?;
+        writeln!(f, "source {}", self.source.display())?;
+
+        for effect in effects {
+            writeln!(f, "effect {effect}")?;
+        }
+
+        writeln!(f, "flip_x {flip_x}")?;
+        writeln!(f, "flip_y {flip_y}")?;
+
+        for CircleShape {
+            center: (x, y),
+            radius,
+        } in circles
+        {
+            writeln!(f, "circle {x} {y} {radius}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for EditDocument {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Self::parse_in(text, Path::new(""))
+    }
+}
+
+impl EditDocument {
+    /// Parses a document, loading LUTs from paths relative to `directory`.
+    fn parse_in(text: &str, directory: &Path) -> Result<Self> {
+        let lines = versioned_lines(text, HEADER, VERSION)?;
+
+        let mut source = None;
+        let mut parser = EditParser::in_directory(directory);
+
+        for (number, line) in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+
+            let parsed = if key == "source" {
+                source = Some(PathBuf::from(value));
+                Ok(())
+            } else {
+                parser
+                    .parse(key, value)
+                    .unwrap_or_else(|| Err(anyhow!("Unknown key `{key}`.")))
+            };
+
+            parsed.with_context(|| format!("Line {number}: `{line}`"))?;
+        }
+
+        let source = source.ok_or_else(|| anyhow!("Expect the document to name a source."))?;
+
+        Ok(Self {
+            source,
+            edits: parser.finish(),
+        })
+    }
+}
+
+/// Checks the `<header> <version>` line of a text format, returning its numbered lines after
+/// it, without blank lines and `#` comments.
+pub(crate) fn versioned_lines<'a>(
+    text: &'a str,
+    header: &str,
+    supported_version: u32,
+) -> Result<impl Iterator<Item = (usize, &'a str)>> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+    let (_, first_line) = lines
+        .next()
+        .ok_or_else(|| anyhow!("Expect a `{header}` header, not an empty file."))?;
+
+    let version = first_line
+        .strip_prefix(header)
+        .and_then(|version| version.trim().parse::<u32>().ok())
+        .ok_or_else(|| anyhow!("Expect a `{header} <version>` header, not `{first_line}`."))?;
+
+    ensure!(
+        version <= supported_version,
+        "Version {version} is newer than the supported version {supported_version}."
+    );
+
+    Ok(lines)
+}
+
+/// Parses edits key by key. Recipes share these keys with documents.
+#[derive(Debug, Default)]
+pub(crate) struct EditParser {
+    edits: Edits,
+    /// The directory LUTs are relative to.
+    directory: PathBuf,
+}
+
+impl EditParser {
+    pub(crate) fn in_directory(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Parses the value of an edit's key, or returns `None` for keys that aren't edits.
+    pub(crate) fn parse(&mut self, key: &str, value: &str) -> Option<Result<()>> {
+        let edits = &mut self.edits;
+
+        Some(match key {
+            "effect" => {
+                Effect::parse_in(value, &self.directory).map(|effect| edits.effects.push(effect))
+            }
+            "flip_x" => parse_into(value, &mut edits.flip_x),
+            "flip_y" => parse_into(value, &mut edits.flip_y),
+            "circle" => parse_circle(value).and_then(|circle| {
+                ensure!(
+                    edits.circles.len() < MAX_CIRCLES,
+                    "Expect at most {MAX_CIRCLES} circles."
+                );
+
+                edits.circles.push(circle);
+                Ok(())
+            }),
+            _ => return None,
+        })
+    }
+
+    pub(crate) fn finish(self) -> Edits {
+        self.edits
+    }
+}
+
+pub(crate) fn parse_into<T: FromStr>(value: &str, field: &mut T) -> Result<()>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    *field = value.parse()?;
+
+    Ok(())
+}
+
+fn parse_circle(value: &str) -> Result<CircleShape> {
+    let numbers = value
+        .split_whitespace()
+        .map(str::parse::<f32>)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let [x, y, radius] = numbers[..] else {
+        bail!("Expect a circle's center and radius.");
+    };
+
+    Ok(CircleShape {
+        center: (x, y),
+        radius,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::{CubeLut, LutInterpolation};
+    use std::sync::Arc;
+
+    fn document() -> EditDocument {
+        EditDocument::new("photos/obama.png").with_edits(Edits {
+            effects: vec![
+                Effect::Invert,
+                Effect::Gamma(150_000),
+                Effect::Sharpen {
+                    radius: 1.5,
+                    amount: 0.75,
+                    threshold: 0.0,
+                },
+                Effect::Grayscale,
+            ],
+            flip_y: true,
+            circles: vec![
+                CircleShape {
+                    center: (0.25, 0.5),
+                    radius: 0.1,
+                },
+                CircleShape {
+                    center: (1.0 / 3.0, 0.75),
+                    radius: 0.05,
+                },
+            ],
+            ..Edits::default()
+        })
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let document = document();
+        let text = document.to_string();
+
+        assert!(text.starts_with("norm-document 1\nsource photos/obama.png\n"));
+        assert!(text.contains(
+            "\neffect invert\neffect gamma 150000\neffect sharpen 1.5 0.75 0\neffect grayscale\n"
+        ));
+        assert_eq!(text.parse::<EditDocument>()?, document);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let document = "norm-document 1\n\n# Tomorrow's edits\nsource a b.png\neffect blur 10.5\n"
+            .parse::<EditDocument>()?;
+
+        assert_eq!(document.source, PathBuf::from("a b.png"));
+        assert_eq!(
+            document.edits,
+            Edits {
+                effects: vec![Effect::Blur { radius: 10.5 }],
+                ..Edits::default()
+            }
+        );
+
+        assert!("".parse::<EditDocument>().is_err());
+        assert!("norm-document 2\nsource a.png"
+            .parse::<EditDocument>()
+            .is_err());
+        assert!("norm-document 1\neffect invert"
+            .parse::<EditDocument>()
+            .is_err());
+        assert!("norm-document 1\nsource a.png\neffect vignette"
+            .parse::<EditDocument>()
+            .is_err());
+        assert!("norm-document 1\nsource a.png\ninvert true"
+            .parse::<EditDocument>()
+            .is_err());
+        assert!("norm-document 1\nsource a.png\ncircle 0.5 0.5"
+            .parse::<EditDocument>()
+            .is_err());
+
+        // The viewer holds only so many circles.
+        let circles = "circle 0.5 0.5 0.1\n".repeat(MAX_CIRCLES);
+        assert!(format!("norm-document 1\nsource a.png\n{circles}")
+            .parse::<EditDocument>()
+            .is_ok());
+        assert!(
+            format!("norm-document 1\nsource a.png\n{circles}circle 0.5 0.5 0.1")
+                .parse::<EditDocument>()
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<()> {
+        let directory = std::env::temp_dir().join("norm_test_save_and_load");
+        fs::create_dir_all(&directory)?;
+
+        let path = directory.join("session.norm");
+        let document = document();
+        let source = directory.join("photos/obama.png");
+        let mut document = EditDocument::new(&source).with_edits(document.edits);
+
+        let lut_path = directory.join("looks/identity.cube");
+        let lut = CubeLut::identity(2);
+        fs::create_dir_all(directory.join("looks"))?;
+        fs::write(
+            &lut_path,
+            format!(
+                "LUT_3D_SIZE 2\n{}",
+                lut.entries
+                    .iter()
+                    .map(|[r, g, b]| format!("{r} {g} {b}\n"))
+                    .collect::<String>()
+            ),
+        )?;
+        document.edits.effects.push(Effect::Lut {
+            path: lut_path,
+            lut: Arc::new(lut),
+            interpolation: LutInterpolation::Tetrahedral,
+        });
+
+        // Sources and LUTs beside the document are saved relative to it.
+        document.save(&path)?;
+        let text = fs::read_to_string(&path)?;
+        assert!(text.contains("\nsource photos/obama.png\n"));
+        assert!(text.contains("\neffect lut tetrahedral looks/identity.cube\n"));
+        assert_eq!(EditDocument::load(&path)?, document);
+
+        // A source that isn't UTF-8 would be saved under another name.
+        #[cfg(unix)]
+        {
+            use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+            let source = directory.join(OsStr::from_bytes(b"caf\xe9.png"));
+            assert!(EditDocument::new(source).save(&path).is_err());
+        }
+
+        fs::remove_dir_all(&directory)?;
+
+        Ok(())
+    }
+}
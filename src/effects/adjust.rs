@@ -0,0 +1,417 @@
+#![allow(clippy::suboptimal_flops)]
+
+//! Color adjustments, like `adjust_compute.wgsl`.
+//!
+//! Exposure and white balance scale linear light. The other adjustments work on straight colors
+//! encoded with the sRGB curve, the values editors show in their sliders and histograms, and
+//! clamp them to `[0, 1]`.
+
+use super::LUMA_WEIGHTS;
+use crate::{
+    color::ToneCurve,
+    image::{ImageBuffer, Rgba32F},
+};
+use anyhow::{bail, Result};
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// The number of control points of a curve, evenly spaced from 0 to 1.
+pub const CURVE_POINTS: usize = 5;
+
+/// The number of entries of the tables curves are baked into.
+pub const CURVE_TABLE_SIZE: usize = 256;
+
+/// The channels a curve maps.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CurveChannel {
+    /// Each of red, green and blue.
+    Rgb,
+    Red,
+    Green,
+    Blue,
+    /// The luma, shifting the color by its change.
+    Luma,
+}
+
+impl Display for CurveChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Rgb => "rgb",
+            Self::Red => "red",
+            Self::Green => "green",
+            Self::Blue => "blue",
+            Self::Luma => "luma",
+        })
+    }
+}
+
+impl FromStr for CurveChannel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "rgb" => Self::Rgb,
+            "red" => Self::Red,
+            "green" => Self::Green,
+            "blue" => Self::Blue,
+            "luma" => Self::Luma,
+            _ => bail!("Unknown curve channel `{s}`."),
+        })
+    }
+}
+
+/// Adds `brightness` to every channel and scales its distance from 0.5 by `1 + contrast`.
+pub fn brightness_contrast(image: &mut ImageBuffer<Rgba32F>, brightness: f32, contrast: f32) {
+    adjust_encoded(image, |rgb| {
+        rgb.map(|c| (c - 0.5).mul_add(1.0 + contrast, 0.5 + brightness))
+    });
+}
+
+/// Scales linear light by `2^stops`.
+pub fn exposure(image: &mut ImageBuffer<Rgba32F>, stops: f32) {
+    scale_linear(image, [stops.exp2(); 3]);
+}
+
+/// Stretches the channels from `black` and `white` to 0 and 1, then raises them to
+/// `1 / gamma`.
+pub fn levels(image: &mut ImageBuffer<Rgba32F>, black: f32, white: f32, gamma: f32) {
+    adjust_encoded(image, |rgb| {
+        rgb.map(|c| {
+            ((c - black) / (white - black))
+                .clamp(0.0, 1.0)
+                .powf(gamma.recip())
+        })
+    });
+}
+
+/// Maps `channel` through the curve through `points`, which are evenly spaced from 0 to 1.
+pub fn curves(
+    image: &mut ImageBuffer<Rgba32F>,
+    channel: CurveChannel,
+    points: [f32; CURVE_POINTS],
+) {
+    let tables = curve_tables(channel, points);
+
+    adjust_encoded(image, |rgb| {
+        let [r, g, b] = [0, 1, 2].map(|i| lookup(&tables, i, rgb[i]));
+        let y = luma([r, g, b]);
+        let shift = lookup(&tables, 3, y) - y;
+
+        [r + shift, g + shift, b + shift]
+    });
+}
+
+/// Rotates the hue by `hue` degrees, scales the saturation by `1 + saturation`, and moves the
+/// lightness towards white by `lightness`, or towards black when it's negative.
+pub fn hue_saturation_lightness(
+    image: &mut ImageBuffer<Rgba32F>,
+    hue: f32,
+    saturation: f32,
+    lightness: f32,
+) {
+    adjust_encoded(image, |rgb| {
+        let [h, s, l] = rgb_to_hsl(rgb);
+
+        let h = (h + hue / 360.0).rem_euclid(1.0);
+        let s = (s * (1.0 + saturation)).clamp(0.0, 1.0);
+        let l = if lightness > 0.0 {
+            (1.0 - l).mul_add(lightness, l)
+        } else {
+            l * (1.0 + lightness)
+        };
+
+        hsl_to_rgb([h, s, l])
+    });
+}
+
+/// Saturates colors by `amount`, less the more saturated they already are.
+pub fn vibrance(image: &mut ImageBuffer<Rgba32F>, amount: f32) {
+    adjust_encoded(image, |rgb| {
+        let y = luma(rgb);
+        let saturation =
+            rgb.iter().copied().fold(0.0, f32::max) - rgb.iter().copied().fold(1.0, f32::min);
+        let scale = amount.mul_add(1.0 - saturation, 1.0);
+
+        rgb.map(|c| (c - y).mul_add(scale, y))
+    });
+}
+
+/// Warms linear light by `temperature` and shifts it towards magenta by `tint`, or cools it and
+/// shifts it towards green when they're negative. The luma of white stays the same.
+pub fn white_balance(image: &mut ImageBuffer<Rgba32F>, temperature: f32, tint: f32) {
+    scale_linear(image, white_balance_gains(temperature, tint));
+}
+
+/// The factors [`white_balance`] scales red, green and blue by.
+pub fn white_balance_gains(temperature: f32, tint: f32) -> [f32; 3] {
+    let gains = [
+        (temperature / 2.0).exp2(),
+        (-tint / 2.0).exp2(),
+        (-temperature / 2.0).exp2(),
+    ];
+    let y = luma(gains);
+
+    gains.map(|gain| gain / y)
+}
+
+/// Mixes colors with their sepia tone by `amount`.
+pub fn sepia(image: &mut ImageBuffer<Rgba32F>, amount: f32) {
+    const SEPIA: [[f32; 3]; 3] = [
+        [0.393, 0.769, 0.189],
+        [0.349, 0.686, 0.168],
+        [0.272, 0.534, 0.131],
+    ];
+
+    adjust_encoded(image, |[r, g, b]| {
+        let toned = SEPIA.map(|[wr, wg, wb]| r.mul_add(wr, g.mul_add(wg, b * wb)));
+
+        let rgb = [r, g, b];
+        std::array::from_fn(|i| (toned[i] - rgb[i]).mul_add(amount, rgb[i]))
+    });
+}
+
+/// The tables of [`curves`], with red, green and blue in the first three channels and luma in
+/// the last. Channels the curve doesn't map are the identity.
+pub fn curve_tables(channel: CurveChannel, points: [f32; CURVE_POINTS]) -> Vec<[f32; 4]> {
+    let curve = monotone_cubic(points);
+
+    (0..CURVE_TABLE_SIZE)
+        .map(|i| {
+            let x = i as f32 / (CURVE_TABLE_SIZE - 1) as f32;
+            let y = curve(x);
+
+            match channel {
+                CurveChannel::Rgb => [y, y, y, x],
+                CurveChannel::Red => [y, x, x, x],
+                CurveChannel::Green => [x, y, x, x],
+                CurveChannel::Blue => [x, x, y, x],
+                CurveChannel::Luma => [x, x, x, y],
+            }
+        })
+        .collect()
+}
+
+/// Interpolates evenly spaced points with Fritsch and Carlson's monotone cubic, so the curve
+/// doesn't overshoot them.
+fn monotone_cubic(points: [f32; CURVE_POINTS]) -> impl Fn(f32) -> f32 {
+    const SEGMENTS: usize = CURVE_POINTS - 1;
+    let h = 1.0 / SEGMENTS as f32;
+
+    let slopes: [f32; SEGMENTS] = std::array::from_fn(|i| (points[i + 1] - points[i]) / h);
+
+    let mut tangents: [f32; CURVE_POINTS] = std::array::from_fn(|i| match i {
+        0 => slopes[0],
+        SEGMENTS => slopes[SEGMENTS - 1],
+        _ if slopes[i - 1] * slopes[i] <= 0.0 => 0.0,
+        _ => (slopes[i - 1] + slopes[i]) / 2.0,
+    });
+
+    for (i, &slope) in slopes.iter().enumerate() {
+        if slope == 0.0 {
+            tangents[i] = 0.0;
+            tangents[i + 1] = 0.0;
+            continue;
+        }
+
+        let (a, b) = (tangents[i] / slope, tangents[i + 1] / slope);
+        let length = a.hypot(b);
+
+        if length > 3.0 {
+            tangents[i] = 3.0 * a / length * slope;
+            tangents[i + 1] = 3.0 * b / length * slope;
+        }
+    }
+
+    move |x| {
+        let position = x.clamp(0.0, 1.0) * SEGMENTS as f32;
+        let i = (position as usize).min(SEGMENTS - 1);
+        let t = position - i as f32;
+
+        let (t2, t3) = (t * t, t * t * t);
+        let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+        let h10 = t3 - 2.0 * t2 + t;
+        let h01 = -2.0 * t3 + 3.0 * t2;
+        let h11 = t3 - t2;
+
+        (h00 * points[i] + h10 * h * tangents[i] + h01 * points[i + 1] + h11 * h * tangents[i + 1])
+            .clamp(0.0, 1.0)
+    }
+}
+
+/// Looks `x` up in the `channel` of `tables`, interpolating linearly between entries like the
+/// shader.
+fn lookup(tables: &[[f32; 4]], channel: usize, x: f32) -> f32 {
+    let position = x.clamp(0.0, 1.0) * (tables.len() - 1) as f32;
+    let i = (position as usize).min(tables.len() - 2);
+    let t = position - i as f32;
+
+    (tables[i + 1][channel] - tables[i][channel]).mul_add(t, tables[i][channel])
+}
+
+fn luma([r, g, b]: [f32; 3]) -> f32 {
+    let [wr, wg, wb] = LUMA_WEIGHTS;
+    r.mul_add(wr, g.mul_add(wg, b * wb))
+}
+
+fn rgb_to_hsl(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb;
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let chroma = max - min;
+
+    if chroma == 0.0 {
+        return [0.0, 0.0, l];
+    }
+
+    let s = chroma / (1.0 - (2.0 * l - 1.0).abs());
+    let h = if max == r {
+        ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+
+    [h / 6.0, s, l]
+}
+
+fn hsl_to_rgb([h, s, l]: [f32; 3]) -> [f32; 3] {
+    let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let h = h * 6.0;
+    let x = chroma * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+
+    let [r, g, b] = match h as u32 {
+        0 => [chroma, x, 0.0],
+        1 => [x, chroma, 0.0],
+        2 => [0.0, chroma, x],
+        3 => [0.0, x, chroma],
+        4 => [x, 0.0, chroma],
+        _ => [chroma, 0.0, x],
+    };
+    let m = l - chroma / 2.0;
+
+    [r + m, g + m, b + m]
+}
+
+/// Runs `adjust` on the sRGB encoded straight colors of every pixel with any alpha, clamping
+/// what it returns.
+pub(super) fn adjust_encoded(
+    image: &mut ImageBuffer<Rgba32F>,
+    adjust: impl Fn([f32; 3]) -> [f32; 3],
+) {
+    for pixel in image.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+
+        if a > 0.0 {
+            let encoded = [r, g, b].map(|c| ToneCurve::SRGB.eval_inverse(c / a));
+            let [r, g, b] = adjust(encoded).map(|c| ToneCurve::SRGB.eval(c) * a);
+
+            *pixel = Rgba32F([r, g, b, a]);
+        }
+    }
+}
+
+fn scale_linear(image: &mut ImageBuffer<Rgba32F>, gains: [f32; 3]) {
+    for pixel in image.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+
+        *pixel = Rgba32F([r * gains[0], g * gains[1], b * gains[2], a]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(rgb: [f32; 3], a: f32) -> ImageBuffer<Rgba32F> {
+        let [r, g, b] = rgb.map(|c| ToneCurve::SRGB.eval(c) * a);
+        ImageBuffer::from_pixel(1, 1, Rgba32F([r, g, b, a]))
+    }
+
+    /// The sRGB encoded straight color of the only pixel.
+    fn encoded(image: &ImageBuffer<Rgba32F>) -> [f32; 3] {
+        let [r, g, b, a] = image.pixel(0, 0).0;
+        [r, g, b].map(|c| ToneCurve::SRGB.eval_inverse(c / a))
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        assert!(
+            a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-4),
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn test_encoded_adjustments() {
+        let color = [0.2, 0.5, 0.8];
+
+        let mut image = pixel(color, 0.5);
+        brightness_contrast(&mut image, 0.1, 1.0);
+        assert_close(encoded(&image), [0.0, 0.6, 1.0]);
+        assert_eq!(image.pixel(0, 0).0[3], 0.5);
+
+        let mut image = pixel(color, 1.0);
+        levels(&mut image, 0.1, 0.9, 1.0);
+        assert_close(encoded(&image), [0.125, 0.5, 0.875]);
+
+        // A half turn swaps the blue for an orange of the same saturation and lightness.
+        let mut image = pixel(color, 1.0);
+        hue_saturation_lightness(&mut image, 180.0, 0.0, 0.0);
+        assert_close(encoded(&image), [0.8, 0.5, 0.2]);
+        hue_saturation_lightness(&mut image, 0.0, -1.0, 1.0);
+        assert_close(encoded(&image), [1.0, 1.0, 1.0]);
+
+        let mut image = pixel([0.5, 0.5, 0.5], 1.0);
+        vibrance(&mut image, 1.0);
+        sepia(&mut image, 0.0);
+        assert_close(encoded(&image), [0.5, 0.5, 0.5]);
+        sepia(&mut image, 1.0);
+        let [r, g, b] = encoded(&image);
+        assert!(r > g && g > b);
+    }
+
+    #[test]
+    fn test_linear_adjustments() {
+        let mut image = ImageBuffer::from_pixel(1, 1, Rgba32F([0.1, 0.2, 0.3, 0.5]));
+        exposure(&mut image, 1.0);
+        assert_eq!(image.pixel(0, 0), &Rgba32F([0.2, 0.4, 0.6, 0.5]));
+
+        let gains = white_balance_gains(0.5, 0.0);
+        assert!(gains[0] > 1.0 && gains[2] < 1.0);
+        assert!((luma(gains) - 1.0).abs() < 1e-6);
+        assert_close(white_balance_gains(0.0, 0.0), [1.0; 3]);
+    }
+
+    #[test]
+    fn test_curves() {
+        let identity = [0.0, 0.25, 0.5, 0.75, 1.0];
+        let tables = curve_tables(CurveChannel::Rgb, identity);
+        assert_eq!(tables.len(), CURVE_TABLE_SIZE);
+        assert!(tables
+            .iter()
+            .enumerate()
+            .all(|(i, entry)| entry.iter().all(|&y| (y - i as f32 / 255.0).abs() < 1e-6)));
+
+        // The curve passes through its points without overshooting them.
+        let s_curve = [0.0, 0.1, 0.5, 0.9, 1.0];
+        let tables = curve_tables(CurveChannel::Red, s_curve);
+        assert!((lookup(&tables, 0, 0.25) - 0.1).abs() < 1e-3);
+        assert!(tables.windows(2).all(|w| w[0][0] <= w[1][0]));
+        assert_eq!(tables[64][1], 64.0 / 255.0);
+
+        let mut image = pixel([0.25, 0.25, 0.75], 1.0);
+        curves(&mut image, CurveChannel::Red, s_curve);
+        let [r, g, b] = encoded(&image);
+        assert!((r - 0.1).abs() < 1e-3);
+        assert_close([g, b, 0.0], [0.25, 0.75, 0.0]);
+
+        // A luma curve shifts every channel alike.
+        let mut image = pixel([0.25, 0.25, 0.75], 1.0);
+        curves(&mut image, CurveChannel::Luma, s_curve);
+        let [r, g, b] = encoded(&image);
+        assert!(r < 0.25 && (r - g).abs() < 1e-5 && (b - r - 0.5).abs() < 1e-3);
+    }
+}
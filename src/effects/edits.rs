@@ -0,0 +1,159 @@
+use crate::{
+    effects::{from_working_space, to_working_space, Effect},
+    image::{grammar::ImageExt, ImageBuffer, Rgba32F},
+};
+use anyhow::Result;
+
+/// The most circles the viewer's shape shader holds, and so the most a document may draw.
+pub const MAX_CIRCLES: usize = 256;
+
+/// A filled circle drawn over the image, like the viewer's shapes. The center is normalized to
+/// the image's dimensions and the radius to its shorter side.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct CircleShape {
+    pub center: (f32, f32),
+    pub radius: f32,
+}
+
+/// Everything the viewer does to an image, so an edit can be rendered without a GPU.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Edits {
+    /// The effect stack, in the order it runs.
+    pub effects: Vec<Effect>,
+    pub circles: Vec<CircleShape>,
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+
+impl Edits {
+    /// Renders the edits over `image`, returning sRGB with straight alpha.
+    pub fn render(&self, image: &dyn ImageExt) -> Result<ImageBuffer<Rgba32F>> {
+        Ok(from_working_space(&self.apply(to_working_space(image)?)))
+    }
+
+    /// Applies the edits to working samples in the viewer's order: the effect stack, then the
+    /// circles, and the flips.
+    pub fn apply(&self, mut image: ImageBuffer<Rgba32F>) -> ImageBuffer<Rgba32F> {
+        for effect in &self.effects {
+            effect.apply(&mut image);
+        }
+
+        draw_circles(&mut image, &self.circles);
+
+        // Shapes are composited before the quad is transformed, so they're flipped too.
+        if self.flip_x {
+            image = image.flip_horizontal();
+        }
+
+        if self.flip_y {
+            image = image.flip_vertical();
+        }
+
+        image
+    }
+}
+
+/// Composites circles like `shape_shader.wgsl` and `image_shader.wgsl` do.
+fn draw_circles(image: &mut ImageBuffer<Rgba32F>, circles: &[CircleShape]) {
+    if circles.is_empty() {
+        return;
+    }
+
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let scale = width.min(height);
+
+    let smoothstep = |edge0: f32, edge1: f32, x: f32| {
+        let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+        t * t * 2.0f32.mul_add(-t, 3.0)
+    };
+    let mix =
+        |a: [f32; 4], b: [f32; 4], t: f32| std::array::from_fn(|c| (b[c] - a[c]).mul_add(t, a[c]));
+
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let mut color = [0.0; 4];
+
+            for circle in circles {
+                let (cx, cy) = (circle.center.0 * width, circle.center.1 * height);
+                let radius = circle.radius * scale;
+                let distance = (px - cx).hypot(py - cy);
+
+                if distance <= radius {
+                    let alpha = 1.0 - smoothstep(radius - 1.0, radius, distance);
+                    color = mix(color, [0.0, 1.0, 1.0, alpha], alpha);
+                }
+            }
+
+            let alpha = color[3];
+
+            if alpha > 0.0 {
+                // The shape layer is alpha blended over transparency, then mixed in by its alpha.
+                let layer = [color[0] * alpha, color[1] * alpha, color[2] * alpha, alpha];
+                let pixel = image.pixel_mut(x, y);
+                pixel.0 = mix(pixel.0, layer, alpha);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Rgba8;
+
+    #[test]
+    fn test_no_edits() -> Result<()> {
+        let image = ImageBuffer::from_fn(6, 4, |x, y| {
+            Rgba8([(x * 40) as u8, (y * 60) as u8, 90, 255])
+        });
+        let rendered = Edits::default().render(&image)?.convert::<Rgba8>();
+
+        assert_eq!(rendered, image);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_edits() -> Result<()> {
+        let image = ImageBuffer::from_fn(8, 8, |x, _| {
+            if x < 4 {
+                Rgba8([255, 255, 255, 255])
+            } else {
+                Rgba8([0, 0, 0, 255])
+            }
+        });
+
+        let edits = Edits {
+            effects: vec![Effect::Invert],
+            flip_x: true,
+            ..Edits::default()
+        };
+        assert_eq!(edits.render(&image)?.convert::<Rgba8>(), image);
+
+        // Blurring the edges finds them where the blur left them.
+        let edits = Edits {
+            effects: vec![
+                Effect::Sobel { threshold: 0.0 },
+                Effect::Blur { radius: 1.5 },
+            ],
+            ..Edits::default()
+        };
+        let blurred = edits.render(&image)?.convert::<Rgba8>();
+        assert_eq!(blurred.pixel(0, 0), &Rgba8([0, 0, 0, 255]));
+        assert!(blurred.pixel(2, 0).0[0] > 0 && blurred.pixel(3, 0).0[0] == 255);
+
+        let edits = Edits {
+            circles: vec![CircleShape {
+                center: (0.75, 0.5),
+                radius: 0.25,
+            }],
+            ..Edits::default()
+        };
+        let drawn = edits.render(&image)?.convert::<Rgba8>();
+        assert_eq!(drawn.pixel(6, 4), &Rgba8([0, 255, 255, 255]));
+        assert_eq!(drawn.pixel(1, 1), image.pixel(1, 1));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+#![allow(clippy::suboptimal_flops)]
+
+use super::MAX_BLUR_RADIUS;
+use crate::image::{ImageBuffer, Rgba32F};
+
+/// Blurs with a Gaussian over `radius` pixels on either side, like `blur_compute.wgsl`.
+///
+/// The sigma is half the radius. Pixels beyond the edges repeat the edge, radii under a pixel
+/// leave the image as it is and radii over [`MAX_BLUR_RADIUS`] are clamped to it. The Gaussian
+/// is separable, so it's applied as a horizontal and a vertical pass.
+pub fn gaussian_blur(image: &ImageBuffer<Rgba32F>, radius: f32) -> ImageBuffer<Rgba32F> {
+    let radius = radius.min(MAX_BLUR_RADIUS);
+    let taps = radius.floor() as i64;
+
+    if taps < 1 {
+        return image.clone();
+    }
+
+    let sigma = radius * 0.5;
+
+    let weights = (-taps..=taps)
+        .map(|offset| (-((offset * offset) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect::<Vec<_>>();
+    let total = weights.iter().sum::<f32>();
+    let weights = weights.iter().map(|w| w / total).collect::<Vec<_>>();
+
+    let blur = |image: &ImageBuffer<Rgba32F>, dx: i64, dy: i64| {
+        ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+            let mut sum = [0.0f32; 4];
+
+            for (offset, &weight) in (-taps..=taps).zip(&weights) {
+                let pixel = sample(image, x, y, offset * dx, offset * dy);
+
+                for (s, c) in sum.iter_mut().zip(pixel.0) {
+                    *s = c.mul_add(weight, *s);
+                }
+            }
+
+            Rgba32F(sum)
+        })
+    };
+
+    blur(&blur(image, 1, 0), 0, 1)
+}
+
+/// Sharpens with an unsharp mask, like `unsharp_compute.wgsl`.
+///
+/// Each color channel moves away from a [`gaussian_blur`] of `radius` by `amount` times its
+/// difference from it, where that difference is at least `threshold`. The results aren't clamped.
+pub fn unsharp_mask(
+    image: &ImageBuffer<Rgba32F>,
+    radius: f32,
+    amount: f32,
+    threshold: f32,
+) -> ImageBuffer<Rgba32F> {
+    let blurred = gaussian_blur(image, radius);
+
+    ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let color = image.pixel(x, y).0;
+        let blurred = blurred.pixel(x, y).0;
+
+        Rgba32F(std::array::from_fn(|c| {
+            let difference = color[c] - blurred[c];
+
+            if c == 3 || difference.abs() < threshold {
+                color[c]
+            } else {
+                amount.mul_add(difference, color[c])
+            }
+        }))
+    })
+}
+
+/// Detects edges with the Sobel operator on the length of each color, like `sobel_compute.wgsl`.
+/// The result is an opaque grayscale image of the gradient magnitude, zeroed under `threshold`.
+pub fn sobel(image: &ImageBuffer<Rgba32F>, threshold: f32) -> ImageBuffer<Rgba32F> {
+    ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let (gx, gy) = sobel_gradient(image, x, y);
+        let magnitude = gx.hypot(gy);
+        let magnitude = if magnitude < threshold {
+            0.0
+        } else {
+            magnitude
+        };
+
+        Rgba32F([magnitude, magnitude, magnitude, 1.0])
+    })
+}
+
+/// Detects edges with Canny's method, like `sobel_compute.wgsl` and `canny_compute.wgsl`.
+///
+/// That's a [`gaussian_blur`] of `radius`, the Sobel gradient, thinning to the local maxima
+/// along it, then a hysteresis step between the `low` and `high` magnitudes.
+///
+/// Edges are white on opaque black. Weak edges above `low` are kept when they touch a strong
+/// edge above `high`; like on the GPU, that's a single step rather than a full flood fill.
+pub fn canny(
+    image: &ImageBuffer<Rgba32F>,
+    radius: f32,
+    low: f32,
+    high: f32,
+) -> ImageBuffer<Rgba32F> {
+    let blurred = gaussian_blur(image, radius);
+
+    let gradients = ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let (gx, gy) = sobel_gradient(&blurred, x, y);
+        Rgba32F([gx.hypot(gy), gx, gy, 1.0])
+    });
+
+    const WEAK: f32 = 0.5;
+
+    let strengths = ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let [magnitude, gx, gy, _] = gradients.pixel(x, y).0;
+        let (dx, dy) = gradient_step(gx, gy);
+
+        let ahead = sample(&gradients, x, y, dx, dy).0[0];
+        let behind = sample(&gradients, x, y, -dx, -dy).0[0];
+
+        let strength = if magnitude < ahead || magnitude < behind {
+            0.0
+        } else if magnitude > high {
+            1.0
+        } else if magnitude > low {
+            WEAK
+        } else {
+            0.0
+        };
+
+        Rgba32F([strength, 0.0, 0.0, 1.0])
+    });
+
+    ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let strength = strengths.pixel(x, y).0[0];
+
+        let strong_neighbour = || {
+            (-1..=1)
+                .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                .any(|(dx, dy)| sample(&strengths, x, y, dx, dy).0[0] > 0.75)
+        };
+
+        let edge = if strength > 0.75 || (strength > 0.25 && strong_neighbour()) {
+            1.0
+        } else {
+            0.0
+        };
+
+        Rgba32F([edge, edge, edge, 1.0])
+    })
+}
+
+/// The Sobel gradient of the length of each color at `(x, y)`, with `y` growing downwards.
+fn sobel_gradient(image: &ImageBuffer<Rgba32F>, x: u32, y: u32) -> (f32, f32) {
+    let intensity = |dx: i64, dy: i64| {
+        let [r, g, b, _] = sample(image, x, y, dx, dy).0;
+        r.mul_add(r, g.mul_add(g, b * b)).sqrt()
+    };
+
+    let top_left = intensity(-1, -1);
+    let top = intensity(0, -1);
+    let top_right = intensity(1, -1);
+    let left = intensity(-1, 0);
+    let right = intensity(1, 0);
+    let bottom_left = intensity(-1, 1);
+    let bottom = intensity(0, 1);
+    let bottom_right = intensity(1, 1);
+
+    let gx = (top_right + 2.0 * right + bottom_right) - (top_left + 2.0 * left + bottom_left);
+    let gy = (bottom_left + 2.0 * bottom + bottom_right) - (top_left + 2.0 * top + top_right);
+
+    (gx, gy)
+}
+
+/// The neighbouring pixel along a gradient, quantized to one of the 8 directions.
+fn gradient_step(gx: f32, gy: f32) -> (i64, i64) {
+    // tan(22.5°), the boundary between a horizontal and a diagonal direction.
+    const TAN_22_5: f32 = 0.414_213_57;
+
+    if gy.abs() <= gx.abs() * TAN_22_5 {
+        (1, 0)
+    } else if gx.abs() <= gy.abs() * TAN_22_5 {
+        (0, 1)
+    } else if gx * gy > 0.0 {
+        (1, 1)
+    } else {
+        (1, -1)
+    }
+}
+
+/// The pixel at an offset from `(x, y)`, clamped to the edges like the compute shaders.
+fn sample(image: &ImageBuffer<Rgba32F>, x: u32, y: u32, dx: i64, dy: i64) -> Rgba32F {
+    let x = (i64::from(x) + dx).clamp(0, i64::from(image.width()) - 1);
+    let y = (i64::from(y) + dy).clamp(0, i64::from(image.height()) - 1);
+
+    *image.pixel(x as u32, y as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker() -> ImageBuffer<Rgba32F> {
+        ImageBuffer::from_fn(8, 8, |x, y| {
+            let v = ((x / 2 + y / 2) % 2) as f32;
+            Rgba32F([v, v, v, 1.0])
+        })
+    }
+
+    #[test]
+    fn test_flat_images() {
+        let flat = ImageBuffer::from_pixel(5, 4, Rgba32F([0.25, 0.5, 0.75, 1.0]));
+
+        for (a, b) in gaussian_blur(&flat, 10.5)
+            .pixels()
+            .iter()
+            .zip(flat.pixels())
+        {
+            assert!(a.0.iter().zip(b.0).all(|(x, y)| (x - y).abs() < 1e-6));
+        }
+        for (a, b) in unsharp_mask(&flat, 2.0, 4.0, 0.0)
+            .pixels()
+            .iter()
+            .zip(flat.pixels())
+        {
+            assert!(a.0.iter().zip(b.0).all(|(x, y)| (x - y).abs() < 1e-5));
+        }
+        for edges in [sobel(&flat, 0.0), canny(&flat, 2.0, 0.0, 0.1)] {
+            assert!(edges
+                .pixels()
+                .iter()
+                .all(|p| p == &Rgba32F([0.0, 0.0, 0.0, 1.0])));
+        }
+    }
+
+    #[test]
+    fn test_gaussian_blur() {
+        let mut image = ImageBuffer::from_pixel(9, 9, Rgba32F([0.0, 0.0, 0.0, 1.0]));
+        image.put_pixel(4, 4, Rgba32F([1.0, 1.0, 1.0, 1.0]));
+
+        // A blurred dot spreads evenly around its center and keeps its energy.
+        let blurred = gaussian_blur(&image, 2.5);
+        let center = blurred.pixel(4, 4).0[0];
+        assert!(center < 1.0 && center > blurred.pixel(5, 4).0[0]);
+        assert_eq!(blurred.pixel(3, 4), blurred.pixel(5, 4));
+        assert_eq!(blurred.pixel(4, 3), blurred.pixel(4, 5));
+        assert!((blurred.pixel(3, 4).0[0] - blurred.pixel(4, 5).0[0]).abs() < 1e-7);
+        assert_eq!(blurred.pixel(1, 4).0[0], 0.0);
+
+        let sum = blurred.pixels().iter().map(|p| p.0[0]).sum::<f32>();
+        assert!((sum - 1.0).abs() < 1e-5);
+
+        assert_eq!(gaussian_blur(&image, 0.5), image);
+    }
+
+    #[test]
+    fn test_unsharp_mask() {
+        let image = checker();
+        let blurred = gaussian_blur(&image, 2.0);
+
+        // Sharpening pushes both sides of an edge apart, by `amount` times the blur's change.
+        let sharpened = unsharp_mask(&image, 2.0, 1.5, 0.0);
+        let (dark, light) = (sharpened.pixel(1, 0).0[0], sharpened.pixel(2, 0).0[0]);
+        assert!(dark < 0.0 && light > 1.0);
+        assert!((dark + 1.5 * blurred.pixel(1, 0).0[0]).abs() < 1e-6);
+        assert_eq!(sharpened.pixel(1, 0).0[3], 1.0);
+
+        // Differences under the threshold are left alone.
+        assert_eq!(unsharp_mask(&image, 2.0, 1.5, 1.0), image);
+    }
+
+    #[test]
+    fn test_edges() {
+        let image = checker();
+
+        let edges = sobel(&image, 0.0);
+        assert_eq!(edges.pixel(0, 0).0[0], 0.0);
+        // Across a vertical edge of white, the Sobel response is 4 * sqrt(3).
+        let expected = 4.0 * 3.0f32.sqrt();
+        assert!((edges.pixel(1, 0).0[0] - expected).abs() < 1e-5);
+        assert!((edges.pixel(0, 1).0[0] - expected).abs() < 1e-5);
+        assert_eq!(edges.pixel(1, 0).0[3], 1.0);
+        assert_eq!(sobel(&image, 7.0).pixel(1, 0).0[0], 0.0);
+
+        // A vertical step is thinned to the columns either side of it.
+        let step = ImageBuffer::from_fn(12, 6, |x, _| {
+            let v = if x < 6 { 1.0 } else { 0.0 };
+            Rgba32F([v, v, v, 1.0])
+        });
+        let edges = canny(&step, 1.0, 0.5, 1.0);
+        for y in 0..6 {
+            let row = (0..12).map(|x| edges.pixel(x, y).0[0]).collect::<Vec<_>>();
+            assert_eq!(row, [0., 0., 0., 0., 0., 1., 1., 0., 0., 0., 0., 0.]);
+        }
+
+        assert!(canny(&step, 1.0, 10.0, 20.0)
+            .pixels()
+            .iter()
+            .all(|p| p.0[0] == 0.0));
+    }
+}
@@ -0,0 +1,242 @@
+//! Histograms of the tonal distribution, like `histogram_compute.wgsl`, and the automatic
+//! adjustments they suggest.
+//!
+//! Like the [color adjustments](super::levels), histograms count sRGB encoded straight colors,
+//! clamped to `[0, 1]`. Transparent pixels have no color and aren't counted.
+
+use super::{Effect, LUMA_WEIGHTS};
+use crate::{
+    color::ToneCurve,
+    image::{ImageBuffer, Rgba32F},
+};
+
+/// The number of bins of each channel, evenly spaced from 0 to 1.
+pub const HISTOGRAM_BINS: usize = 256;
+
+/// The share of pixels [`Histogram::auto_levels`] and [`Histogram::auto_contrast`] let clip at
+/// either end, so a few specks don't pin the black and white points.
+pub const DEFAULT_HISTOGRAM_CLIP: f32 = 0.001;
+
+/// The channels of a histogram, in the order of [`Histogram::bins`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HistogramChannel {
+    Red,
+    Green,
+    Blue,
+    Luma,
+}
+
+impl HistogramChannel {
+    pub const ALL: [Self; 4] = [Self::Red, Self::Green, Self::Blue, Self::Luma];
+}
+
+/// Pixel counts per channel and value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    /// The bins of red, green, blue and luma, from black to white.
+    pub bins: [[u32; HISTOGRAM_BINS]; 4],
+}
+
+impl Histogram {
+    /// Counts the working samples of `image`, linear with premultiplied alpha.
+    pub fn of(image: &ImageBuffer<Rgba32F>) -> Self {
+        let mut bins = [[0; HISTOGRAM_BINS]; 4];
+
+        for pixel in image.pixels() {
+            let [r, g, b, a] = pixel.0;
+
+            if a <= 0.0 {
+                continue;
+            }
+
+            let rgb = [r, g, b].map(|c| ToneCurve::SRGB.eval_inverse(c / a));
+            let [wr, wg, wb] = LUMA_WEIGHTS;
+            let luma = rgb[0].mul_add(wr, rgb[1].mul_add(wg, rgb[2] * wb));
+
+            for (channel, value) in bins.iter_mut().zip([rgb[0], rgb[1], rgb[2], luma]) {
+                channel[bin(value)] += 1;
+            }
+        }
+
+        Self { bins }
+    }
+
+    pub const fn channel(&self, channel: HistogramChannel) -> &[u32; HISTOGRAM_BINS] {
+        &self.bins[channel as usize]
+    }
+
+    /// The number of pixels counted.
+    pub fn total(&self) -> u32 {
+        self.channel(HistogramChannel::Luma).iter().sum()
+    }
+
+    /// The center of the bin holding the value that `fraction` of the pixels are at or under,
+    /// or `None` when no pixels were counted.
+    pub fn percentile(&self, channel: HistogramChannel, fraction: f32) -> Option<f32> {
+        let target = (fraction.clamp(0.0, 1.0) * self.total() as f32).max(1.0);
+        let mut count = 0;
+
+        self.channel(channel)
+            .iter()
+            .position(|&n| {
+                count += n;
+                count as f32 >= target
+            })
+            .map(|i| (i as f32 + 0.5) / HISTOGRAM_BINS as f32)
+    }
+
+    /// The values between which the pixels lie, once `clip` of them are let go at either end:
+    /// the lower edge of the darkest bin kept and the upper edge of the brightest one.
+    pub fn range(&self, channel: HistogramChannel, clip: f32) -> Option<(f32, f32)> {
+        let bins = self.channel(channel);
+        let clipped = clip.clamp(0.0, 0.5) * self.total() as f32;
+
+        let first = edge(bins.iter().enumerate(), clipped)?;
+        let last = edge(bins.iter().enumerate().rev(), clipped)?;
+
+        // Both ends can't clip past each other.
+        let (first, last) = (first.min(last), first.max(last));
+
+        Some((
+            first as f32 / HISTOGRAM_BINS as f32,
+            (last + 1) as f32 / HISTOGRAM_BINS as f32,
+        ))
+    }
+
+    /// Levels stretching the darkest and brightest colors to black and white, keeping the
+    /// channels' balance. `None` when no pixels were counted.
+    pub fn auto_contrast(&self, clip: f32) -> Option<Effect> {
+        let (black, white) = self.color_range(clip)?;
+
+        Some(Effect::Levels {
+            black,
+            white,
+            gamma: 1.0,
+        })
+    }
+
+    /// Like [`Self::auto_contrast`], also with a gamma that brings the median luma to middle
+    /// gray.
+    pub fn auto_levels(&self, clip: f32) -> Option<Effect> {
+        let (black, white) = self.color_range(clip)?;
+        let median = self.percentile(HistogramChannel::Luma, 0.5)?;
+
+        // Levels raise the stretched median to 1 / gamma, so it lands on 0.5.
+        let stretched = ((median - black) / (white - black)).clamp(0.01, 0.99);
+        let gamma = (-stretched.log2()).clamp(0.1, 10.0);
+
+        Some(Effect::Levels {
+            black,
+            white,
+            gamma,
+        })
+    }
+
+    /// The widest range of red, green and blue.
+    fn color_range(&self, clip: f32) -> Option<(f32, f32)> {
+        [
+            HistogramChannel::Red,
+            HistogramChannel::Green,
+            HistogramChannel::Blue,
+        ]
+        .into_iter()
+        .map(|channel| self.range(channel, clip))
+        .try_fold((1.0f32, 0.0f32), |(black, white), range| {
+            range.map(|(low, high)| (black.min(low), white.max(high)))
+        })
+    }
+}
+
+/// The first of `bins` where more than `clipped` pixels have been counted.
+fn edge<'a>(bins: impl Iterator<Item = (usize, &'a u32)>, clipped: f32) -> Option<usize> {
+    let mut count = 0;
+
+    bins.map(|(i, &n)| {
+        count += n;
+        (i, count)
+    })
+    .find(|&(_, count)| count > 0 && count as f32 > clipped)
+    .map(|(i, _)| i)
+}
+
+/// The bin of an encoded value, like `histogram_compute.wgsl`.
+fn bin(value: f32) -> usize {
+    ((value.clamp(0.0, 1.0) * HISTOGRAM_BINS as f32) as usize).min(HISTOGRAM_BINS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image with `count` pixels of the gray in the middle of each bin, and a transparent
+    /// pixel.
+    fn grays(bins: &[(usize, usize)]) -> ImageBuffer<Rgba32F> {
+        let pixels = bins
+            .iter()
+            .flat_map(|&(bin, count)| {
+                let c = ToneCurve::SRGB.eval((bin as f32 + 0.5) / HISTOGRAM_BINS as f32);
+                std::iter::repeat_n([c, c, c, 1.0], count)
+            })
+            .chain([[0.0; 4]])
+            .flatten()
+            .collect::<Vec<_>>();
+
+        ImageBuffer::from_raw(pixels.len() as u32 / 4, 1, &pixels).unwrap()
+    }
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::of(&grays(&[(0, 3), (128, 5), (255, 2)]));
+
+        assert_eq!(histogram.total(), 10);
+        for channel in HistogramChannel::ALL {
+            let bins = histogram.channel(channel);
+            assert_eq!((bins[0], bins[128], bins[255]), (3, 5, 2));
+        }
+
+        assert_eq!(
+            histogram.percentile(HistogramChannel::Luma, 0.5),
+            Some(128.5 / 256.0)
+        );
+        assert_eq!(
+            histogram.range(HistogramChannel::Red, 0.0),
+            Some((0.0, 1.0))
+        );
+        // Clipping 3 pixels at either end leaves the middle gray.
+        assert_eq!(
+            histogram.range(HistogramChannel::Red, 0.3),
+            Some((0.5, 129.0 / 256.0))
+        );
+
+        let empty = Histogram::of(&grays(&[]));
+        assert_eq!(empty.total(), 0);
+        assert_eq!(empty.auto_levels(0.0), None);
+    }
+
+    #[test]
+    fn test_auto_levels() {
+        let histogram = Histogram::of(&grays(&[(64, 4), (96, 4), (128, 4)]));
+
+        let Some(Effect::Levels {
+            black,
+            white,
+            gamma,
+        }) = histogram.auto_contrast(0.0)
+        else {
+            panic!("Expect levels.");
+        };
+        assert_eq!((black, white, gamma), (0.25, 129.0 / 256.0, 1.0));
+
+        // The median sits in the middle of the range, so the gamma barely moves it.
+        let Some(Effect::Levels { gamma, .. }) = histogram.auto_levels(0.0) else {
+            panic!("Expect levels.");
+        };
+        assert!((gamma - 1.0).abs() < 0.05, "{gamma}");
+
+        let dark = Histogram::of(&grays(&[(0, 1), (51, 8), (255, 1)]));
+        let Some(Effect::Levels { gamma, .. }) = dark.auto_levels(0.0) else {
+            panic!("Expect levels.");
+        };
+        assert!(gamma > 2.0, "{gamma}");
+    }
+}
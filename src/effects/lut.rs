@@ -0,0 +1,321 @@
+#![allow(clippy::suboptimal_flops)]
+
+//! 3D lookup tables from `.cube` files, the format of Adobe's and Resolve's grading looks.
+//!
+//! A `.cube` file lists the output colors of a lattice of inputs after a few keywords:
+//!
+//! ```text
+//! TITLE "Teal and orange"
+//! LUT_3D_SIZE 33
+//! DOMAIN_MIN 0 0 0
+//! DOMAIN_MAX 1 1 1
+//! 0.0 0.0 0.0
+//! 0.03 0.0 0.0
+//! ...
+//! ```
+//!
+//! Red changes fastest, then green, then blue. Looks expect display encoded colors, so they
+//! apply to the sRGB encoded straight colors like most [color adjustments](super::levels).
+
+use super::adjust::adjust_encoded;
+use crate::image::{ImageBuffer, Rgba32F};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::{
+    fmt::{self, Display},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+/// The sizes the `.cube` format allows along each axis.
+const SIZES: std::ops::RangeInclusive<usize> = 2..=256;
+
+/// How colors between the lattice points of a [`CubeLut`] are interpolated.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum LutInterpolation {
+    /// From the 8 corners of the surrounding cube.
+    Trilinear,
+    /// From the 4 corners of the tetrahedron of the surrounding cube, which keeps neutral
+    /// colors neutral.
+    #[default]
+    Tetrahedral,
+}
+
+impl Display for LutInterpolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Trilinear => "trilinear",
+            Self::Tetrahedral => "tetrahedral",
+        })
+    }
+}
+
+impl FromStr for LutInterpolation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "trilinear" => Self::Trilinear,
+            "tetrahedral" => Self::Tetrahedral,
+            _ => bail!("Unknown interpolation `{s}`."),
+        })
+    }
+}
+
+/// A 3D lookup table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeLut {
+    pub title: Option<String>,
+    /// The number of lattice points along each axis.
+    pub size: usize,
+    /// The inputs mapped to the first and last lattice points.
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+    /// The output colors, with red changing fastest, then green, then blue.
+    pub entries: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+    /// The table mapping every color to itself.
+    pub fn identity(size: usize) -> Self {
+        let scale = (size - 1) as f32;
+
+        Self {
+            title: None,
+            size,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            entries: (0..size * size * size)
+                .map(|i| [i % size, i / size % size, i / (size * size)].map(|c| c as f32 / scale))
+                .collect(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        text.parse()
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// The output for `rgb`, which is clamped to the domain.
+    pub fn sample(&self, rgb: [f32; 3], interpolation: LutInterpolation) -> [f32; 3] {
+        let scale = (self.size - 1) as f32;
+
+        let position: [f32; 3] = std::array::from_fn(|c| {
+            let (min, max) = (self.domain_min[c], self.domain_max[c]);
+            ((rgb[c] - min) / (max - min)).clamp(0.0, 1.0) * scale
+        });
+        let base = position.map(|p| (p as usize).min(self.size - 2));
+        let [fr, fg, fb]: [f32; 3] = std::array::from_fn(|c| position[c] - base[c] as f32);
+
+        let corner = |r: usize, g: usize, b: usize| {
+            self.entries
+                [(base[0] + r) + (base[1] + g) * self.size + (base[2] + b) * self.size * self.size]
+        };
+
+        match interpolation {
+            LutInterpolation::Trilinear => {
+                let lerp = |a: [f32; 3], b: [f32; 3], t: f32| {
+                    std::array::from_fn(|c| (b[c] - a[c]).mul_add(t, a[c]))
+                };
+
+                let c00 = lerp(corner(0, 0, 0), corner(1, 0, 0), fr);
+                let c10 = lerp(corner(0, 1, 0), corner(1, 1, 0), fr);
+                let c01 = lerp(corner(0, 0, 1), corner(1, 0, 1), fr);
+                let c11 = lerp(corner(0, 1, 1), corner(1, 1, 1), fr);
+
+                lerp(lerp(c00, c10, fg), lerp(c01, c11, fg), fb)
+            }
+            LutInterpolation::Tetrahedral => {
+                // The corners from black to white along the tetrahedron, with their weights.
+                let (second, third, [w1, w2, w3]) = if fr > fg {
+                    if fg > fb {
+                        (corner(1, 0, 0), corner(1, 1, 0), [fr, fg, fb])
+                    } else if fr > fb {
+                        (corner(1, 0, 0), corner(1, 0, 1), [fr, fb, fg])
+                    } else {
+                        (corner(0, 0, 1), corner(1, 0, 1), [fb, fr, fg])
+                    }
+                } else if fb > fg {
+                    (corner(0, 0, 1), corner(0, 1, 1), [fb, fg, fr])
+                } else if fb > fr {
+                    (corner(0, 1, 0), corner(0, 1, 1), [fg, fb, fr])
+                } else {
+                    (corner(0, 1, 0), corner(1, 1, 0), [fg, fr, fb])
+                };
+
+                let (first, last) = (corner(0, 0, 0), corner(1, 1, 1));
+
+                std::array::from_fn(|c| {
+                    (1.0 - w1) * first[c]
+                        + (w1 - w2) * second[c]
+                        + (w2 - w3) * third[c]
+                        + w3 * last[c]
+                })
+            }
+        }
+    }
+}
+
+impl FromStr for CubeLut {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut title = None;
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut entries = Vec::new();
+
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        for (number, line) in lines {
+            let (keyword, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let value = value.trim();
+
+            let parsed = match keyword {
+                "TITLE" => {
+                    title = Some(value.trim_matches('"').to_string());
+                    Ok(())
+                }
+                "LUT_3D_SIZE" => value.parse().map_err(Into::into).map(|n| size = Some(n)),
+                "LUT_1D_SIZE" => Err(anyhow!("Expect a 3D LUT, not a 1D one.")),
+                "DOMAIN_MIN" => triple(value).map(|min| domain_min = min),
+                "DOMAIN_MAX" => triple(value).map(|max| domain_max = max),
+                // Resolve's spelling of an equal domain on every axis.
+                "LUT_3D_INPUT_RANGE" => numbers(value).and_then(|numbers| {
+                    let [min, max] = numbers[..] else {
+                        bail!("Expect a minimum and a maximum.");
+                    };
+                    domain_min = [min; 3];
+                    domain_max = [max; 3];
+                    Ok(())
+                }),
+                _ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                    Err(anyhow!("Unknown keyword `{keyword}`."))
+                }
+                _ => triple(line).map(|entry| entries.push(entry)),
+            };
+
+            parsed.with_context(|| format!("Line {number}: `{line}`"))?;
+        }
+
+        let size = size.ok_or_else(|| anyhow!("Expect a LUT_3D_SIZE."))?;
+        ensure!(
+            SIZES.contains(&size),
+            "Expect a LUT_3D_SIZE from 2 to 256, not {size}."
+        );
+        ensure!(
+            entries.len() == size * size * size,
+            "Expect {} entries for a LUT_3D_SIZE of {size}, not {}.",
+            size * size * size,
+            entries.len()
+        );
+        ensure!(
+            (0..3).all(|c| domain_min[c] < domain_max[c]),
+            "Expect DOMAIN_MIN to be under DOMAIN_MAX."
+        );
+
+        Ok(Self {
+            title,
+            size,
+            domain_min,
+            domain_max,
+            entries,
+        })
+    }
+}
+
+/// Maps the sRGB encoded straight colors through `lut`, like `lut_compute.wgsl`.
+pub fn color_grade(
+    image: &mut ImageBuffer<Rgba32F>,
+    lut: &CubeLut,
+    interpolation: LutInterpolation,
+) {
+    adjust_encoded(image, |rgb| lut.sample(rgb, interpolation));
+}
+
+fn triple(value: &str) -> Result<[f32; 3]> {
+    numbers(value)?
+        .try_into()
+        .map_err(|numbers: Vec<_>| anyhow!("Expect 3 numbers, not {}.", numbers.len()))
+}
+
+fn numbers(value: &str) -> Result<Vec<f32>> {
+    value
+        .split_whitespace()
+        .map(|word| {
+            let number = word.parse::<f32>()?;
+            ensure!(number.is_finite(), "Expect finite numbers, not {word}.");
+            Ok(number)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LUT: &str = "# Swaps red and blue, and darkens green\n\
+        TITLE \"Swap\"\n\
+        LUT_3D_SIZE 2\n\
+        \n\
+        0 0 0\n0 0 1\n0 0.5 0\n0 0.5 1\n\
+        1 0 0\n1 0 1\n1 0.5 0\n1 0.5 1\n";
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let lut = LUT.parse::<CubeLut>()?;
+        assert_eq!(lut.title.as_deref(), Some("Swap"));
+        assert_eq!(lut.size, 2);
+        assert_eq!(lut.entries[1], [0.0, 0.0, 1.0]);
+        assert_eq!((lut.domain_min, lut.domain_max), ([0.0; 3], [1.0; 3]));
+
+        let ranged = format!("LUT_3D_INPUT_RANGE 0 2\n{LUT}").parse::<CubeLut>()?;
+        assert_eq!(ranged.domain_max, [2.0; 3]);
+
+        assert!("LUT_3D_SIZE 2\n0 0 0".parse::<CubeLut>().is_err());
+        assert!("LUT_1D_SIZE 2\n0 0 0\n1 1 1".parse::<CubeLut>().is_err());
+        assert!(LUT.replace("0 0 1\n", "0 1\n").parse::<CubeLut>().is_err());
+        assert!(LUT
+            .replace("TITLE", "LUT_SHAPER")
+            .parse::<CubeLut>()
+            .is_err());
+        assert!(LUT.replace("SIZE 2", "SIZE 1").parse::<CubeLut>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample() -> Result<()> {
+        let lut = LUT.parse::<CubeLut>()?;
+
+        for interpolation in [LutInterpolation::Trilinear, LutInterpolation::Tetrahedral] {
+            let [r, g, b] = lut.sample([0.2, 0.6, 0.9], interpolation);
+            assert!((r - 0.9).abs() < 1e-6 && (g - 0.3).abs() < 1e-6 && (b - 0.2).abs() < 1e-6);
+
+            let identity = CubeLut::identity(17);
+            let [r, g, b] = identity.sample([0.123, 0.456, 0.789], interpolation);
+            assert!(
+                (r - 0.123).abs() < 1e-6 && (g - 0.456).abs() < 1e-6 && (b - 0.789).abs() < 1e-6
+            );
+        }
+
+        // Grays only interpolate along the tetrahedra's shared diagonal.
+        let mut lut = CubeLut::identity(2);
+        lut.entries[1] = [0.0, 0.0, 1.0];
+        let [r, g, b] = lut.sample([0.5; 3], LutInterpolation::Tetrahedral);
+        assert_eq!([r, g, b], [0.5; 3]);
+        let [r, _, b] = lut.sample([0.5; 3], LutInterpolation::Trilinear);
+        assert_ne!(r, b);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+//! The viewer's effects on the CPU, for machines without a GPU and as references for the shaders.
+//!
+//! Effects work on the same samples as the shaders: linear sRGB with premultiplied alpha, in
+//! floating point. Convert images with [`to_working_space`] and back with
+//! [`from_working_space`].
+
+use crate::{
+    color::{ColorProfile, ToneCurve},
+    image::{
+        grammar::{premultiply_rgba32f, ImageExt},
+        ImageBuffer, Rgba32F,
+    },
+};
+use anyhow::Result;
+
+pub use adjust::*;
+pub use edits::*;
+pub use filter::*;
+pub use histogram::*;
+pub use lut::*;
+pub use point::*;
+pub use stack::*;
+
+mod adjust;
+mod edits;
+mod filter;
+mod histogram;
+mod lut;
+mod point;
+mod stack;
+
+/// Radii of [`Effect::Blur`], in pixels on either side.
+pub const DEFAULT_BLUR_RADIUS: f32 = 10.0;
+pub const MAX_BLUR_RADIUS: f32 = 100.0;
+pub const MIN_BLUR_RADIUS: f32 = 1.0;
+pub const BLUR_RADIUS_STEP: f32 = 1.0;
+
+/// Parameters of [`Effect::Sharpen`].
+pub const DEFAULT_SHARPEN_RADIUS: f32 = 2.0;
+pub const DEFAULT_SHARPEN_AMOUNT: f32 = 1.0;
+pub const MAX_SHARPEN_AMOUNT: f32 = 10.0;
+pub const SHARPEN_AMOUNT_STEP: f32 = 0.25;
+
+/// Parameters of [`Effect::Canny`]. Thresholds of both edge detectors are gradient magnitudes.
+pub const DEFAULT_CANNY_RADIUS: f32 = 2.0;
+pub const DEFAULT_CANNY_LOW: f32 = 0.2;
+pub const DEFAULT_CANNY_HIGH: f32 = 0.6;
+pub const EDGE_THRESHOLD_STEP: f32 = 0.05;
+
+/// The color adjustments the viewer toggles with the keys 1 to 8, with parameters that show
+/// them off.
+pub const COLOR_ADJUSTMENTS: [Effect; 8] = [
+    Effect::BrightnessContrast {
+        brightness: 0.05,
+        contrast: 0.2,
+    },
+    Effect::Exposure { stops: 0.5 },
+    Effect::Levels {
+        black: 0.05,
+        white: 0.95,
+        gamma: 1.0,
+    },
+    Effect::Curves {
+        channel: CurveChannel::Rgb,
+        points: [0.0, 0.2, 0.5, 0.8, 1.0],
+    },
+    Effect::HueSaturationLightness {
+        hue: 0.0,
+        saturation: 0.25,
+        lightness: 0.0,
+    },
+    Effect::Vibrance { amount: 0.5 },
+    Effect::WhiteBalance {
+        temperature: 0.2,
+        tint: 0.0,
+    },
+    Effect::Sepia { amount: 1.0 },
+];
+
+/// Steps of the color adjustments' parameters, most of which range over `[-1, 1]`.
+pub const ADJUSTMENT_STEP: f32 = 0.05;
+pub const EXPOSURE_STEP: f32 = 0.25;
+pub const MAX_EXPOSURE: f32 = 5.0;
+
+/// Gammas of [`Effect::Gamma`], scaled by 100000.
+pub const DEFAULT_GAMMA: u32 = 150_000;
+pub const MAX_GAMMA: u32 = 500_000;
+pub const MIN_GAMMA: u32 = 10_000;
+pub const GAMMA_STEP: u32 = 10_000;
+
+/// Converts an image to linear sRGB with premultiplied alpha, how the viewer's textures hold it.
+pub fn to_working_space(image: &dyn ImageExt) -> Result<ImageBuffer<Rgba32F>> {
+    let (width, height) = image.dimensions();
+    let mut rgba = image
+        .rgba_f32_in(&ColorProfile::linear_srgb())?
+        .into_owned();
+    premultiply_rgba32f(&mut rgba);
+
+    ImageBuffer::from_raw(width, height, &rgba)
+}
+
+/// Converts working samples back to sRGB with straight alpha, clamping them like the display
+/// would.
+pub fn from_working_space(image: &ImageBuffer<Rgba32F>) -> ImageBuffer<Rgba32F> {
+    let mut image = image.clone();
+
+    for pixel in image.pixels_mut() {
+        let a = pixel.0[3].clamp(0.0, 1.0);
+
+        let [r, g, b] = [pixel.0[0], pixel.0[1], pixel.0[2]].map(|c| {
+            if a == 0.0 {
+                0.0
+            } else {
+                ToneCurve::SRGB.eval_inverse(c.clamp(0.0, a) / a)
+            }
+        });
+
+        *pixel = Rgba32F([r, g, b, a]);
+    }
+
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Rgba8;
+
+    #[test]
+    fn test_working_space_round_trip() -> Result<()> {
+        let image = ImageBuffer::from_fn(16, 16, |x, y| {
+            Rgba8([(x * 16) as u8, (y * 16) as u8, 200, (x * 16 + y) as u8])
+        });
+        let work = to_working_space(&image)?;
+
+        let Rgba32F([r, _, _, a]) = *work.pixel(5, 3);
+        let expected = ToneCurve::SRGB.eval(80.0 / 255.0) * 83.0 / 255.0;
+        assert!((r - expected).abs() < 1e-4);
+        assert_eq!(a, 83.0 / 255.0);
+
+        let round_trip = from_working_space(&work).convert::<Rgba8>();
+        for (a, b) in round_trip.pixels().iter().zip(image.pixels()) {
+            // Colors under a zero alpha are lost.
+            if b.0[3] > 0 {
+                assert!(a.0.iter().zip(b.0).all(|(&x, y)| x.abs_diff(y) <= 1));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use crate::image::{ImageBuffer, Rgba32F};
+
+/// The weights `grayscale_compute.wgsl` takes the luma with.
+pub const LUMA_WEIGHTS: [f32; 3] = [0.29891, 0.58661, 0.11448];
+
+/// Replaces every color by its luma, keeping alpha.
+pub fn grayscale(image: &mut ImageBuffer<Rgba32F>) {
+    for pixel in image.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let [wr, wg, wb] = LUMA_WEIGHTS;
+        let y = r.mul_add(wr, g.mul_add(wg, b * wb));
+
+        *pixel = Rgba32F([y, y, y, a]);
+    }
+}
+
+/// Inverts every color, keeping alpha. Colors are premultiplied, so they're inverted against
+/// alpha.
+pub fn invert(image: &mut ImageBuffer<Rgba32F>) {
+    for pixel in image.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+
+        *pixel = Rgba32F([a - r, a - g, a - b, a]);
+    }
+}
+
+/// Raises straight colors to `1 / gamma`, where `gamma` is scaled by 100000 like the viewer's
+/// uniform. A `gamma` of 0 leaves the image as it is.
+pub fn gamma(image: &mut ImageBuffer<Rgba32F>, gamma: u32) {
+    if gamma == 0 {
+        return;
+    }
+
+    let inverse_gamma = 100_000.0 / gamma as f32;
+
+    for pixel in image.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+
+        if a > 0.0 {
+            let [r, g, b] = [r, g, b].map(|c| (c / a).powf(inverse_gamma) * a);
+            *pixel = Rgba32F([r, g, b, a]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_point_effects() {
+        let mut image = ImageBuffer::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                Rgba32F([1.0, 0.5, 0.0, 1.0])
+            } else {
+                Rgba32F([0.25, 0.125, 0.0, 0.5])
+            }
+        });
+        let original = image.clone();
+
+        invert(&mut image);
+        assert_eq!(image.pixel(0, 0), &Rgba32F([0.0, 0.5, 1.0, 1.0]));
+        assert_eq!(image.pixel(1, 0), &Rgba32F([0.25, 0.375, 0.5, 0.5]));
+        invert(&mut image);
+        assert_eq!(image, original);
+
+        gamma(&mut image, 0);
+        assert_eq!(image, original);
+
+        // The straight color of the second pixel is (0.5, 0.25, 0.0).
+        gamma(&mut image, 50_000);
+        let Rgba32F([r, g, b, a]) = *image.pixel(1, 0);
+        assert!((r - 0.125).abs() < 1e-6 && (g - 0.031_25).abs() < 1e-6);
+        assert_eq!((b, a), (0.0, 0.5));
+
+        let mut image = original;
+        grayscale(&mut image);
+        let Rgba32F([r, g, b, a]) = *image.pixel(0, 0);
+        assert!((r - (0.29891 + 0.58661 / 2.0)).abs() < 1e-6);
+        assert_eq!((r, r, a), (g, b, 1.0));
+    }
+}
@@ -0,0 +1,536 @@
+use crate::{
+    effects::{
+        brightness_contrast, canny, color_grade, curves, exposure, gamma, gaussian_blur, grayscale,
+        hue_saturation_lightness, invert, levels, sepia, sobel, unsharp_mask, vibrance,
+        white_balance, CubeLut, CurveChannel, LutInterpolation, CURVE_POINTS, MAX_BLUR_RADIUS,
+    },
+    image::{ImageBuffer, Rgba32F},
+};
+use anyhow::{anyhow, bail, ensure, Result};
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+/// An effect of the viewer's effect stack with its parameters. The stack runs its effects in
+/// order, so they can be reordered to change the result.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    /// Raises straight colors to `1 / gamma`, where `gamma` is scaled by 100000.
+    Gamma(u32),
+    Grayscale,
+    Invert,
+    /// A Gaussian blur over `radius` pixels on either side.
+    Blur {
+        radius: f32,
+    },
+    /// An unsharp mask, adding `amount` times the difference from a blur of `radius` where it's
+    /// at least `threshold`.
+    Sharpen {
+        radius: f32,
+        amount: f32,
+        threshold: f32,
+    },
+    /// The Sobel gradient magnitude, zeroed under `threshold`.
+    Sobel {
+        threshold: f32,
+    },
+    /// Canny edges of the image blurred by `radius`, with hysteresis between `low` and `high`.
+    Canny {
+        radius: f32,
+        low: f32,
+        high: f32,
+    },
+    /// Adds `brightness` to the encoded colors and scales their contrast by `1 + contrast`.
+    BrightnessContrast {
+        brightness: f32,
+        contrast: f32,
+    },
+    /// Scales linear light by `2^stops`.
+    Exposure {
+        stops: f32,
+    },
+    /// Stretches the encoded colors from `black` and `white`, then applies `gamma`.
+    Levels {
+        black: f32,
+        white: f32,
+        gamma: f32,
+    },
+    /// Maps `channel` through the monotone curve through `points`, evenly spaced from 0 to 1.
+    Curves {
+        channel: CurveChannel,
+        points: [f32; CURVE_POINTS],
+    },
+    /// Rotates the hue by `hue` degrees and scales the saturation and lightness.
+    HueSaturationLightness {
+        hue: f32,
+        saturation: f32,
+        lightness: f32,
+    },
+    /// Saturates the less saturated colors by `amount`.
+    Vibrance {
+        amount: f32,
+    },
+    /// Warms linear light by `temperature` and shifts it towards magenta by `tint`.
+    WhiteBalance {
+        temperature: f32,
+        tint: f32,
+    },
+    /// Mixes the colors with their sepia tone by `amount`.
+    Sepia {
+        amount: f32,
+    },
+    /// Maps the encoded colors through the 3D LUT loaded from the `.cube` file at `path`.
+    Lut {
+        path: PathBuf,
+        lut: Arc<CubeLut>,
+        interpolation: LutInterpolation,
+    },
+}
+
+impl Effect {
+    /// The effect's name in documents and recipes.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Gamma(_) => "gamma",
+            Self::Grayscale => "grayscale",
+            Self::Invert => "invert",
+            Self::Blur { .. } => "blur",
+            Self::Sharpen { .. } => "sharpen",
+            Self::Sobel { .. } => "sobel",
+            Self::Canny { .. } => "canny",
+            Self::BrightnessContrast { .. } => "brightness_contrast",
+            Self::Exposure { .. } => "exposure",
+            Self::Levels { .. } => "levels",
+            Self::Curves { .. } => "curves",
+            Self::HueSaturationLightness { .. } => "hsl",
+            Self::Vibrance { .. } => "vibrance",
+            Self::WhiteBalance { .. } => "white_balance",
+            Self::Sepia { .. } => "sepia",
+            Self::Lut { .. } => "lut",
+        }
+    }
+
+    pub fn apply(&self, image: &mut ImageBuffer<Rgba32F>) {
+        match *self {
+            Self::Gamma(value) => gamma(image, value),
+            Self::Grayscale => grayscale(image),
+            Self::Invert => invert(image),
+            Self::Blur { radius } => *image = gaussian_blur(image, radius),
+            Self::Sharpen {
+                radius,
+                amount,
+                threshold,
+            } => *image = unsharp_mask(image, radius, amount, threshold),
+            Self::Sobel { threshold } => *image = sobel(image, threshold),
+            Self::Canny { radius, low, high } => *image = canny(image, radius, low, high),
+            Self::BrightnessContrast {
+                brightness,
+                contrast,
+            } => brightness_contrast(image, brightness, contrast),
+            Self::Exposure { stops } => exposure(image, stops),
+            Self::Levels {
+                black,
+                white,
+                gamma,
+            } => levels(image, black, white, gamma),
+            Self::Curves { channel, points } => curves(image, channel, points),
+            Self::HueSaturationLightness {
+                hue,
+                saturation,
+                lightness,
+            } => hue_saturation_lightness(image, hue, saturation, lightness),
+            Self::Vibrance { amount } => vibrance(image, amount),
+            Self::WhiteBalance { temperature, tint } => white_balance(image, temperature, tint),
+            Self::Sepia { amount } => sepia(image, amount),
+            Self::Lut {
+                ref lut,
+                interpolation,
+                ..
+            } => color_grade(image, lut, interpolation),
+        }
+    }
+
+    /// The parameters after the name, in the order they're written.
+    fn parameters(&self) -> Vec<f32> {
+        match *self {
+            Self::Gamma(_) | Self::Grayscale | Self::Invert | Self::Lut { .. } => Vec::new(),
+            Self::Blur { radius } => vec![radius],
+            Self::Sharpen {
+                radius,
+                amount,
+                threshold,
+            } => vec![radius, amount, threshold],
+            Self::Sobel { threshold } => vec![threshold],
+            Self::Canny { radius, low, high } => vec![radius, low, high],
+            Self::BrightnessContrast {
+                brightness,
+                contrast,
+            } => vec![brightness, contrast],
+            Self::Exposure { stops } => vec![stops],
+            Self::Levels {
+                black,
+                white,
+                gamma,
+            } => vec![black, white, gamma],
+            Self::Curves { points, .. } => points.to_vec(),
+            Self::HueSaturationLightness {
+                hue,
+                saturation,
+                lightness,
+            } => vec![hue, saturation, lightness],
+            Self::Vibrance { amount } | Self::Sepia { amount } => vec![amount],
+            Self::WhiteBalance { temperature, tint } => vec![temperature, tint],
+        }
+    }
+}
+
+/// Writes the effect as its name, followed by its parameters. Curves name their channel first,
+/// and LUTs their interpolation and path.
+impl Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())?;
+
+        match self {
+            Self::Gamma(value) => write!(f, " {value}")?,
+            Self::Curves { channel, .. } => write!(f, " {channel}")?,
+            Self::Lut {
+                path,
+                interpolation,
+                ..
+            } => write!(f, " {interpolation} {}", path.display())?,
+            _ => {}
+        }
+
+        for parameter in self.parameters() {
+            write!(f, " {parameter}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Effect {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse_in(s, Path::new(""))
+    }
+}
+
+impl Effect {
+    /// Parses an effect, loading LUTs from paths relative to `directory`.
+    pub fn parse_in(s: &str, directory: &Path) -> Result<Self> {
+        let mut words = s.split_whitespace();
+        let name = words.next().ok_or_else(|| anyhow!("Expect an effect."))?;
+        let words = words.collect::<Vec<_>>();
+
+        Ok(match name {
+            "gamma" => {
+                let [value] = words[..] else {
+                    bail!("Expect a gamma scaled by 100000.");
+                };
+
+                Self::Gamma(value.parse()?)
+            }
+            "grayscale" => parameters::<0>(name, &words).map(|[]| Self::Grayscale)?,
+            "invert" => parameters::<0>(name, &words).map(|[]| Self::Invert)?,
+            "blur" => {
+                let [radius] = parameters(name, &words)?;
+
+                Self::Blur {
+                    radius: blur_radius(name, radius)?,
+                }
+            }
+            "sharpen" => {
+                let [radius, amount, threshold] = parameters(name, &words)?;
+
+                Self::Sharpen {
+                    radius: blur_radius(name, radius)?,
+                    amount,
+                    threshold,
+                }
+            }
+            "sobel" => parameters(name, &words).map(|[threshold]| Self::Sobel { threshold })?,
+            "canny" => {
+                let [radius, low, high] = parameters(name, &words)?;
+
+                Self::Canny {
+                    radius: blur_radius(name, radius)?,
+                    low,
+                    high,
+                }
+            }
+            "brightness_contrast" => {
+                let [brightness, contrast] = numbers(name, &words)?;
+                ensure!(
+                    (-1.0..=1.0).contains(&brightness) && contrast >= -1.0,
+                    "Expect a brightness from -1 to 1 and a contrast of at least -1."
+                );
+
+                Self::BrightnessContrast {
+                    brightness,
+                    contrast,
+                }
+            }
+            "exposure" => numbers(name, &words).map(|[stops]| Self::Exposure { stops })?,
+            "levels" => {
+                let [black, white, gamma] = parameters(name, &words)?;
+                ensure!(
+                    black < white && white <= 1.0 && gamma > 0.0,
+                    "Expect levels with 0 <= black < white <= 1 and a positive gamma."
+                );
+
+                Self::Levels {
+                    black,
+                    white,
+                    gamma,
+                }
+            }
+            "curves" => {
+                let (channel, points) = words
+                    .split_first()
+                    .ok_or_else(|| anyhow!("Expect a channel after the curves effect."))?;
+                let points = parameters(name, points)?;
+                ensure!(
+                    points.iter().all(|&point| point <= 1.0),
+                    "Expect the points of a curve to be from 0 to 1."
+                );
+
+                Self::Curves {
+                    channel: channel.parse()?,
+                    points,
+                }
+            }
+            "hsl" => {
+                let [hue, saturation, lightness] = numbers(name, &words)?;
+                ensure!(
+                    (-1.0..=1.0).contains(&saturation) && (-1.0..=1.0).contains(&lightness),
+                    "Expect a saturation and a lightness from -1 to 1."
+                );
+
+                Self::HueSaturationLightness {
+                    hue,
+                    saturation,
+                    lightness,
+                }
+            }
+            "vibrance" => {
+                let [amount] = numbers(name, &words)?;
+                ensure!(amount >= -1.0, "Expect a vibrance of at least -1.");
+
+                Self::Vibrance { amount }
+            }
+            "white_balance" => numbers(name, &words)
+                .map(|[temperature, tint]| Self::WhiteBalance { temperature, tint })?,
+            "sepia" => {
+                let [amount] = parameters(name, &words)?;
+                ensure!(amount <= 1.0, "Expect a sepia amount from 0 to 1.");
+
+                Self::Sepia { amount }
+            }
+            "lut" => {
+                // The path is the rest of the line, spaces and all.
+                let (interpolation, path) = s.trim()[name.len()..]
+                    .trim_start()
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| anyhow!("Expect an interpolation and a path after lut."))?;
+                let path = directory.join(path.trim());
+
+                Self::Lut {
+                    lut: Arc::new(CubeLut::load(&path)?),
+                    path,
+                    interpolation: interpolation.parse()?,
+                }
+            }
+            _ => bail!("Unknown effect `{name}`."),
+        })
+    }
+}
+
+/// Parses `N` non-negative parameters of an effect.
+fn parameters<const N: usize>(name: &str, words: &[&str]) -> Result<[f32; N]> {
+    let parameters = numbers(name, words)?;
+
+    for (parameter, word) in parameters.iter().zip(words) {
+        ensure!(
+            *parameter >= 0.0,
+            "Expect the {name} effect's parameters to be non-negative, not {word}."
+        );
+    }
+
+    Ok(parameters)
+}
+
+/// Checks the radius of an effect's blur, whose taps the shaders loop over.
+fn blur_radius(name: &str, radius: f32) -> Result<f32> {
+    ensure!(
+        radius <= MAX_BLUR_RADIUS,
+        "Expect the {name} effect's radius to be at most {MAX_BLUR_RADIUS}, not {radius}."
+    );
+
+    Ok(radius)
+}
+
+/// Parses `N` finite parameters of an effect.
+fn numbers<const N: usize>(name: &str, words: &[&str]) -> Result<[f32; N]> {
+    ensure!(
+        words.len() == N,
+        "Expect {N} parameters after the {name} effect, not {}.",
+        words.len()
+    );
+
+    let numbers = words
+        .iter()
+        .map(|word| {
+            let number = word.parse::<f32>()?;
+            ensure!(
+                number.is_finite(),
+                "Expect the {name} effect's parameters to be finite, not {word}."
+            );
+            Ok(number)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(numbers.try_into().expect("the length was checked"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effect_round_trip() -> Result<()> {
+        for effect in [
+            Effect::Gamma(220_000),
+            Effect::Grayscale,
+            Effect::Invert,
+            Effect::Blur { radius: 10.5 },
+            Effect::Sharpen {
+                radius: 2.0,
+                amount: 0.75,
+                threshold: 0.01,
+            },
+            Effect::Sobel { threshold: 0.0 },
+            Effect::Canny {
+                radius: 1.5,
+                low: 0.2,
+                high: 0.6,
+            },
+            Effect::BrightnessContrast {
+                brightness: -0.1,
+                contrast: 0.25,
+            },
+            Effect::Exposure { stops: -1.5 },
+            Effect::Levels {
+                black: 0.05,
+                white: 0.9,
+                gamma: 1.2,
+            },
+            Effect::Curves {
+                channel: CurveChannel::Luma,
+                points: [0.0, 0.2, 0.5, 0.8, 1.0],
+            },
+            Effect::HueSaturationLightness {
+                hue: -30.0,
+                saturation: 0.5,
+                lightness: 0.0,
+            },
+            Effect::Vibrance { amount: 0.4 },
+            Effect::WhiteBalance {
+                temperature: 0.3,
+                tint: -0.1,
+            },
+            Effect::Sepia { amount: 1.0 },
+        ] {
+            assert_eq!(effect.to_string().parse::<Effect>()?, effect);
+        }
+
+        assert_eq!(
+            "sharpen 2 1 0".parse::<Effect>()?.to_string(),
+            "sharpen 2 1 0"
+        );
+
+        assert!("gamma".parse::<Effect>().is_err());
+        assert!("invert 1".parse::<Effect>().is_err());
+        assert!("blur".parse::<Effect>().is_err());
+        assert!("blur -1".parse::<Effect>().is_err());
+        assert!("blur 100".parse::<Effect>().is_ok());
+        assert!("blur 1e9".parse::<Effect>().is_err());
+        assert!("sharpen 101 1 0".parse::<Effect>().is_err());
+        assert!("canny 1000 0.2 0.6".parse::<Effect>().is_err());
+        assert!("sobel NaN".parse::<Effect>().is_err());
+        assert!("canny 1 2".parse::<Effect>().is_err());
+        assert_eq!(
+            "curves red 0 0.25 0.5 0.75 1"
+                .parse::<Effect>()?
+                .to_string(),
+            "curves red 0 0.25 0.5 0.75 1"
+        );
+        assert!("exposure -2".parse::<Effect>().is_ok());
+        assert!("levels 0.5 0.5 1".parse::<Effect>().is_err());
+        assert!("curves 0 0.25 0.5 0.75 1".parse::<Effect>().is_err());
+        assert!("curves cyan 0 0.25 0.5 0.75 1".parse::<Effect>().is_err());
+        assert!("curves rgb 0 0.25 0.5 0.75 2".parse::<Effect>().is_err());
+        assert!("sepia 1.5".parse::<Effect>().is_err());
+        assert!("vignette".parse::<Effect>().is_err());
+        assert!("lut tetrahedral".parse::<Effect>().is_err());
+        assert!("lut tetrahedral missing.cube".parse::<Effect>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lut_effect() -> Result<()> {
+        let directory = std::env::temp_dir().join("norm_test_lut_effect");
+        std::fs::create_dir_all(&directory)?;
+        std::fs::write(
+            directory.join("a look.cube"),
+            "LUT_3D_SIZE 2\n0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n",
+        )?;
+
+        let effect = Effect::parse_in("lut trilinear a look.cube", &directory)?;
+        let Effect::Lut {
+            ref path,
+            ref lut,
+            interpolation,
+        } = effect
+        else {
+            panic!("Expect a LUT, not {effect}.");
+        };
+        assert_eq!(path, &directory.join("a look.cube"));
+        assert_eq!(**lut, CubeLut::identity(2));
+        assert_eq!(interpolation, LutInterpolation::Trilinear);
+
+        assert_eq!(effect.to_string().parse::<Effect>()?, effect);
+        assert!(Effect::parse_in("lut linear a look.cube", &directory).is_err());
+
+        std::fs::remove_dir_all(&directory)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_effect_order() {
+        let image = ImageBuffer::from_pixel(1, 1, Rgba32F([1.0, 0.0, 0.0, 1.0]));
+
+        let apply = |effects: &[Effect]| {
+            let mut image = image.clone();
+            effects.iter().for_each(|effect| effect.apply(&mut image));
+            image.pixel(0, 0).0
+        };
+
+        // Gamma lifts the inverted channels, which are 0 and 1 before grayscale mixes them.
+        let gamma = Effect::Gamma(200_000);
+        assert_ne!(
+            apply(&[Effect::Invert, gamma.clone(), Effect::Grayscale]),
+            apply(&[Effect::Invert, Effect::Grayscale, gamma])
+        );
+        assert_eq!(
+            apply(&[Effect::Invert, Effect::Invert]),
+            image.pixel(0, 0).0
+        );
+    }
+}
@@ -0,0 +1,735 @@
+use crate::{
+    image::{
+        grammar::{ColorType, ImageExt},
+        pixel::{expand_to_rgba, Pixel, Rgb8, Rgba16, Rgba32F, Rgba8, Subpixel},
+    },
+    jpeg::grammar::{Jpeg, JpegColorSpace},
+    png::grammar::{ImageHeader, Png},
+};
+use anyhow::{ensure, Result};
+use std::{borrow::Cow, slice::ChunksExact};
+
+/// An owned image of pixels in row-major order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageBuffer<P: Pixel> {
+    width: u32,
+    height: u32,
+    pixels: Vec<P>,
+}
+
+impl<P: Pixel> ImageBuffer<P> {
+    /// Creates an image with every channel zeroed.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::from_pixel(width, height, P::zeroed())
+    }
+
+    pub fn from_pixel(width: u32, height: u32, pixel: P) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![pixel; width as usize * height as usize],
+        }
+    }
+
+    pub fn from_fn(width: u32, height: u32, mut f: impl FnMut(u32, u32) -> P) -> Self {
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| f(x, y))
+            .collect();
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<P>) -> Result<Self> {
+        ensure!(
+            pixels.len() == width as usize * height as usize,
+            "Expected {}x{} pixels, found {}.",
+            width,
+            height,
+            pixels.len()
+        );
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Creates an image from interleaved channel values.
+    pub fn from_raw(width: u32, height: u32, raw: &[P::Subpixel]) -> Result<Self> {
+        ensure!(
+            raw.len() == width as usize * height as usize * P::CHANNEL_COUNT,
+            "Expected {} channel values for a {}x{} image, found {}.",
+            width as usize * height as usize * P::CHANNEL_COUNT,
+            width,
+            height,
+            raw.len()
+        );
+
+        Self::from_pixels(
+            width,
+            height,
+            raw.chunks_exact(P::CHANNEL_COUNT)
+                .map(P::from_channels)
+                .collect(),
+        )
+    }
+
+    /// Copies any image at its full precision, converting the pixels to `P`.
+    pub fn from_image(image: &dyn ImageExt) -> Self {
+        let (width, height) = image.dimensions();
+
+        Self {
+            width,
+            height,
+            pixels: image
+                .rgba_f32()
+                .chunks_exact(4)
+                .map(|rgba| P::from_rgba([rgba[0], rgba[1], rgba[2], rgba[3]]))
+                .collect(),
+        }
+    }
+
+    /// Creates an image from big-endian samples of the given PNG color type and bit depth,
+    /// converting them to `P`.
+    fn from_samples(
+        width: u32,
+        height: u32,
+        color_type: ColorType,
+        bit_depth: u8,
+        samples: &[u8],
+    ) -> Result<Self> {
+        ensure!(
+            color_type != ColorType::Palette,
+            "Palette images must be expanded first."
+        );
+        ensure!(
+            matches!(bit_depth, 8 | 16),
+            "Unsupported bit depth: {bit_depth}"
+        );
+
+        let channel_count = color_type.num_channels() as usize;
+        let sample_count = width as usize * height as usize * channel_count;
+
+        ensure!(
+            samples.len() == sample_count * bit_depth as usize / 8,
+            "Expected {sample_count} samples of {bit_depth} bits."
+        );
+
+        // Matching formats are copied as they are.
+        if P::COLOR_TYPE == color_type && P::BIT_DEPTH == bit_depth {
+            return match bit_depth {
+                8 => Self::from_raw(width, height, bytemuck::cast_slice(samples)),
+                _ => {
+                    let samples = samples
+                        .chunks_exact(2)
+                        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                        .collect::<Vec<_>>();
+
+                    Self::from_raw(width, height, bytemuck::cast_slice(&samples))
+                }
+            };
+        }
+
+        let normalized = match bit_depth {
+            8 => samples.iter().map(|&s| s.to_f32()).collect::<Vec<_>>(),
+            _ => samples
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]).to_f32())
+                .collect(),
+        };
+
+        Self::from_pixels(
+            width,
+            height,
+            normalized
+                .chunks_exact(channel_count)
+                .map(|channels| P::from_rgba(expand_to_rgba(color_type, channels)))
+                .collect(),
+        )
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub const fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Panics if `(x, y)` lies outside the image.
+    pub fn pixel(&self, x: u32, y: u32) -> &P {
+        &self.pixels[self.index(x, y)]
+    }
+
+    /// Panics if `(x, y)` lies outside the image.
+    pub fn pixel_mut(&mut self, x: u32, y: u32) -> &mut P {
+        let index = self.index(x, y);
+        &mut self.pixels[index]
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<&P> {
+        (x < self.width && y < self.height).then(|| self.pixel(x, y))
+    }
+
+    /// Panics if `(x, y)` lies outside the image.
+    pub fn put_pixel(&mut self, x: u32, y: u32, pixel: P) {
+        *self.pixel_mut(x, y) = pixel;
+    }
+
+    /// Panics if `y` lies outside the image.
+    pub fn row(&self, y: u32) -> &[P] {
+        assert!(y < self.height, "{y} lies outside the image");
+
+        let start = y as usize * self.width as usize;
+        &self.pixels[start..start + self.width as usize]
+    }
+
+    /// Panics if `y` lies outside the image.
+    pub fn row_mut(&mut self, y: u32) -> &mut [P] {
+        assert!(y < self.height, "{y} lies outside the image");
+
+        let start = y as usize * self.width as usize;
+        &mut self.pixels[start..start + self.width as usize]
+    }
+
+    pub fn rows(&self) -> ChunksExact<'_, P> {
+        self.pixels.chunks_exact(self.width.max(1) as usize)
+    }
+
+    pub fn pixels(&self) -> &[P] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [P] {
+        &mut self.pixels
+    }
+
+    /// The interleaved channel values.
+    pub fn as_raw(&self) -> &[P::Subpixel] {
+        bytemuck::cast_slice(&self.pixels)
+    }
+
+    /// The interleaved channel values in native byte order, e.g. for uploading to the GPU.
+    pub fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.pixels)
+    }
+
+    /// Borrows the `width` by `height` rectangle at `(x, y)`.
+    pub fn view(&self, x: u32, y: u32, width: u32, height: u32) -> Result<SubImage<'_, P>> {
+        self.ensure_contains(x, y, width, height)?;
+
+        Ok(SubImage {
+            buffer: self,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Mutably borrows the `width` by `height` rectangle at `(x, y)`.
+    pub fn view_mut(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<SubImageMut<'_, P>> {
+        self.ensure_contains(x, y, width, height)?;
+
+        Ok(SubImageMut {
+            buffer: self,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Converts every pixel to another format.
+    pub fn convert<Q: Pixel>(&self) -> ImageBuffer<Q> {
+        ImageBuffer {
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .pixels
+                .iter()
+                .map(|pixel| Q::from_rgba(pixel.to_rgba()))
+                .collect(),
+        }
+    }
+
+    /// Multiplies every color by its alpha. Images without alpha are left as they are.
+    pub fn premultiply_alpha(&mut self) {
+        if P::COLOR_TYPE.has_alpha() {
+            for pixel in &mut self.pixels {
+                *pixel = pixel.premultiplied();
+            }
+        }
+    }
+
+    /// Divides every color by its alpha. Images without alpha are left as they are.
+    pub fn unpremultiply_alpha(&mut self) {
+        if P::COLOR_TYPE.has_alpha() {
+            for pixel in &mut self.pixels {
+                *pixel = pixel.unpremultiplied();
+            }
+        }
+    }
+
+    fn ensure_contains(&self, x: u32, y: u32, width: u32, height: u32) -> Result<()> {
+        ensure!(
+            x as u64 + width as u64 <= self.width as u64
+                && y as u64 + height as u64 <= self.height as u64,
+            "The {width}x{height} rectangle at ({x}, {y}) exceeds the {}x{} image.",
+            self.width,
+            self.height
+        );
+
+        Ok(())
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(
+            x < self.width && y < self.height,
+            "({x}, {y}) lies outside the {}x{} image",
+            self.width,
+            self.height
+        );
+
+        y as usize * self.width as usize + x as usize
+    }
+}
+
+/// A rectangular view into an [`ImageBuffer`]. Coordinates are relative to the view.
+#[derive(Debug, Clone, Copy)]
+pub struct SubImage<'a, P: Pixel> {
+    buffer: &'a ImageBuffer<P>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl<'a, P: Pixel> SubImage<'a, P> {
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub const fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Panics if `(x, y)` lies outside the view.
+    pub fn pixel(&self, x: u32, y: u32) -> &'a P {
+        assert!(x < self.width, "{x} lies outside the view");
+        &self.row(y)[x as usize]
+    }
+
+    /// Panics if `y` lies outside the view.
+    pub fn row(&self, y: u32) -> &'a [P] {
+        assert!(y < self.height, "{y} lies outside the view");
+
+        let row = self.buffer.row(self.y + y);
+        &row[self.x as usize..(self.x + self.width) as usize]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &'a [P]> + '_ {
+        (0..self.height).map(|y| self.row(y))
+    }
+
+    /// Copies the view into an image of its own.
+    pub fn to_image(&self) -> ImageBuffer<P> {
+        ImageBuffer {
+            width: self.width,
+            height: self.height,
+            pixels: self.rows().flatten().copied().collect(),
+        }
+    }
+}
+
+/// A mutable rectangular view into an [`ImageBuffer`]. Coordinates are relative to the view.
+#[derive(Debug)]
+pub struct SubImageMut<'a, P: Pixel> {
+    buffer: &'a mut ImageBuffer<P>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl<P: Pixel> SubImageMut<'_, P> {
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub const fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Panics if `(x, y)` lies outside the view.
+    pub fn pixel(&self, x: u32, y: u32) -> &P {
+        assert!(x < self.width, "{x} lies outside the view");
+        &self.row(y)[x as usize]
+    }
+
+    /// Panics if `(x, y)` lies outside the view.
+    pub fn pixel_mut(&mut self, x: u32, y: u32) -> &mut P {
+        assert!(x < self.width, "{x} lies outside the view");
+        &mut self.row_mut(y)[x as usize]
+    }
+
+    /// Panics if `y` lies outside the view.
+    pub fn row(&self, y: u32) -> &[P] {
+        assert!(y < self.height, "{y} lies outside the view");
+
+        let row = self.buffer.row(self.y + y);
+        &row[self.x as usize..(self.x + self.width) as usize]
+    }
+
+    /// Panics if `y` lies outside the view.
+    pub fn row_mut(&mut self, y: u32) -> &mut [P] {
+        assert!(y < self.height, "{y} lies outside the view");
+
+        let (x, width) = (self.x as usize, self.width as usize);
+        let row = self.buffer.row_mut(self.y + y);
+
+        &mut row[x..x + width]
+    }
+
+    pub fn fill(&mut self, pixel: P) {
+        for y in 0..self.height {
+            self.row_mut(y).fill(pixel);
+        }
+    }
+
+    /// Copies `image`, which must have the same dimensions as the view, into it.
+    pub fn copy_from(&mut self, image: &ImageBuffer<P>) -> Result<()> {
+        ensure!(
+            image.dimensions() == self.dimensions(),
+            "Expected a {}x{} image.",
+            self.width,
+            self.height
+        );
+
+        for (y, row) in (0..self.height).zip(image.rows()) {
+            self.row_mut(y).copy_from_slice(row);
+        }
+
+        Ok(())
+    }
+
+    pub fn to_image(&self) -> ImageBuffer<P> {
+        ImageBuffer {
+            width: self.width,
+            height: self.height,
+            pixels: (0..self.height)
+                .flat_map(|y| self.row(y))
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+impl<P: Pixel> TryFrom<&Png> for ImageBuffer<P> {
+    type Error = anyhow::Error;
+
+    fn try_from(png: &Png) -> Result<Self> {
+        let image_header = &png.image_header;
+
+        Self::from_samples(
+            image_header.width,
+            image_header.height,
+            image_header.color_type,
+            image_header.bit_depth,
+            &png.pixel_buffer,
+        )
+    }
+}
+
+impl<P: Pixel> TryFrom<&Jpeg> for ImageBuffer<P> {
+    type Error = anyhow::Error;
+
+    fn try_from(jpeg: &Jpeg) -> Result<Self> {
+        Self::from_samples(
+            jpeg.width,
+            jpeg.height,
+            jpeg.color_type,
+            jpeg.bit_depth,
+            &jpeg.pixel_buffer,
+        )
+    }
+}
+
+impl<P: Pixel> From<&ImageBuffer<P>> for Png {
+    /// Floating point images are stored with 16 bits per channel.
+    fn from(image: &ImageBuffer<P>) -> Self {
+        let pixel_buffer = match P::BIT_DEPTH {
+            8 => image.as_bytes().to_vec(),
+            16 => bytemuck::cast_slice::<_, u16>(image.as_raw())
+                .iter()
+                .flat_map(|sample| sample.to_be_bytes())
+                .collect(),
+            _ => return Self::from(&image.convert::<Rgba16>()),
+        };
+
+        Self {
+            image_header: ImageHeader {
+                width: image.width,
+                height: image.height,
+                bit_depth: P::BIT_DEPTH,
+                color_type: P::COLOR_TYPE,
+                compression_method: 0,
+                filter_method: 0,
+                interlace_method: false,
+            },
+            gamma: 0,
+            chromaticities: None,
+            srgb_intent: None,
+            icc_profile: None,
+            pixel_buffer,
+        }
+    }
+}
+
+impl<P: Pixel> From<&ImageBuffer<P>> for Jpeg {
+    /// JPEGs have no alpha channel, so alpha is dropped. Images with more than 8 bits per
+    /// channel keep 16.
+    fn from(image: &ImageBuffer<P>) -> Self {
+        let grayscale = matches!(
+            P::COLOR_TYPE,
+            ColorType::Grayscale | ColorType::GrayscaleAlpha
+        );
+        let channel_count = if grayscale { 1 } else { 3 };
+        let bit_depth = if P::BIT_DEPTH == 8 { 8 } else { 16 };
+
+        let mut pixel_buffer =
+            Vec::with_capacity(image.pixels.len() * channel_count * bit_depth as usize / 8);
+
+        for pixel in &image.pixels {
+            for &channel in &pixel.to_rgba()[..channel_count] {
+                match bit_depth {
+                    8 => pixel_buffer.push(u8::from_f32(channel)),
+                    _ => pixel_buffer.extend_from_slice(&u16::from_f32(channel).to_be_bytes()),
+                }
+            }
+        }
+
+        let (color_type, color_space) = if grayscale {
+            (ColorType::Grayscale, JpegColorSpace::Grayscale)
+        } else {
+            (ColorType::RGB, JpegColorSpace::RGB)
+        };
+
+        Self {
+            width: image.width,
+            height: image.height,
+            bit_depth,
+            color_type,
+            color_space,
+            icc_profile: None,
+            pixel_buffer,
+        }
+    }
+}
+
+impl<P: Pixel> ImageExt for ImageBuffer<P> {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn gamma(&self) -> u32 {
+        0
+    }
+
+    fn color_type(&self) -> ColorType {
+        P::COLOR_TYPE
+    }
+
+    fn bit_depth(&self) -> u8 {
+        P::BIT_DEPTH
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        if P::COLOR_TYPE == ColorType::RGB && P::BIT_DEPTH == 8 {
+            return Cow::from(self.as_bytes());
+        }
+
+        Cow::from(self.convert::<Rgb8>().as_bytes().to_vec())
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        if P::COLOR_TYPE == ColorType::RGBA && P::BIT_DEPTH == 8 {
+            return Cow::from(self.as_bytes());
+        }
+
+        Cow::from(self.convert::<Rgba8>().as_bytes().to_vec())
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        let b = self
+            .convert::<Rgba8>()
+            .pixels
+            .iter()
+            .map(|&Rgba8([r, g, b, a])| u32::from_be_bytes([a, r, g, b]))
+            .collect::<Vec<_>>();
+
+        Cow::from(b)
+    }
+
+    fn rgba_f32(&self) -> Cow<'_, [f32]> {
+        if P::COLOR_TYPE == ColorType::RGBA && P::BIT_DEPTH == 32 {
+            return Cow::from(bytemuck::cast_slice(self.as_raw()));
+        }
+
+        Cow::from(self.convert::<Rgba32F>().as_raw().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::pixel::{Luma16, Luma8, LumaA8},
+        jpeg::JpegDecoder,
+        png::{PngDecoder, PngEncoder},
+    };
+
+    #[test]
+    fn test_accessors_and_views() -> Result<()> {
+        let mut image = ImageBuffer::from_fn(4, 3, |x, y| Luma8([(y * 4 + x) as u8]));
+
+        assert_eq!(image.pixel(1, 2), &Luma8([9]));
+        assert_eq!(image.get_pixel(4, 0), None);
+        assert_eq!(
+            image.row(1),
+            &[Luma8([4]), Luma8([5]), Luma8([6]), Luma8([7])]
+        );
+        assert_eq!(image.as_raw(), (0..12).collect::<Vec<u8>>());
+
+        image.put_pixel(0, 0, Luma8([100]));
+        assert_eq!(image.pixel(0, 0), &Luma8([100]));
+
+        let view = image.view(1, 1, 2, 2)?;
+        assert_eq!(view.pixel(1, 1), &Luma8([10]));
+        assert_eq!(
+            view.to_image().as_raw(),
+            &[5, 6, 9, 10],
+            "views copy only their rectangle"
+        );
+        assert!(image.view(3, 0, 2, 1).is_err());
+
+        let mut view = image.view_mut(2, 0, 2, 3)?;
+        view.fill(Luma8([255]));
+        view.pixel_mut(0, 2).0[0] = 1;
+
+        assert_eq!(
+            image.row(0),
+            &[Luma8([100]), Luma8([1]), Luma8([255]), Luma8([255])]
+        );
+        assert_eq!(
+            image.row(2),
+            &[Luma8([8]), Luma8([9]), Luma8([1]), Luma8([255])]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_pixels() {
+        let image =
+            ImageBuffer::from_pixels(2, 1, vec![Rgb8([255, 0, 0]), Rgb8([10, 20, 30])]).unwrap();
+
+        // Opaque formats gain opaque alpha, and grayscale uses the Rec. 601 weights.
+        let rgba = image.convert::<Rgba8>();
+        assert_eq!(
+            rgba.pixels(),
+            &[Rgba8([255, 0, 0, 255]), Rgba8([10, 20, 30, 255])]
+        );
+
+        let luma = image.convert::<LumaA8>();
+        assert_eq!(luma.pixels(), &[LumaA8([76, 255]), LumaA8([18, 255])]);
+
+        let wide = image
+            .convert::<Rgba16>()
+            .convert::<Rgba32F>()
+            .convert::<Rgb8>();
+        assert_eq!(wide, image);
+    }
+
+    #[test]
+    fn test_premultiply_alpha() -> Result<()> {
+        let mut image = ImageBuffer::<Rgba8>::from_raw(2, 1, &[200, 100, 20, 128, 50, 50, 50, 0])?;
+        image.premultiply_alpha();
+        assert_eq!(image.pixels(), &[Rgba8([100, 50, 10, 128]), Rgba8([0; 4])]);
+
+        image.unpremultiply_alpha();
+        assert_eq!(image.pixels(), &[Rgba8([199, 100, 20, 128]), Rgba8([0; 4])]);
+
+        let mut image = ImageBuffer::from_pixel(1, 1, Rgb8([1, 2, 3]));
+        image.premultiply_alpha();
+        assert_eq!(image.pixel(0, 0), &Rgb8([1, 2, 3]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_png_round_trip() -> Result<()> {
+        let data = std::fs::read("./tests/obama.png")?;
+        let png = PngDecoder::new(&data).decode()?;
+
+        let image = ImageBuffer::<Rgb8>::try_from(&png)?;
+        assert_eq!(image.dimensions(), (png.width(), png.height()));
+        assert_eq!(Png::from(&image).pixel_buffer, png.pixel_buffer);
+
+        // 16-bit images survive encoding.
+        let wide = image.convert::<Rgba16>();
+        let mut encoded = Vec::new();
+        PngEncoder::new(&mut encoded).encode(&Png::from(&wide))?;
+
+        let decoded = PngDecoder::new(&encoded).decode()?;
+        assert_eq!(ImageBuffer::<Rgba16>::try_from(&decoded)?, wide);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jpeg_conversions() -> Result<()> {
+        let data = std::fs::read("./tests/lossless_gray_16bit_p7.jpg")?;
+        let jpeg = JpegDecoder::new(&data).decode()?;
+
+        let image = ImageBuffer::<Luma16>::try_from(&jpeg)?;
+        assert_eq!(Jpeg::from(&image), jpeg);
+
+        let data = std::fs::read("./tests/arithmetic_reference.jpg")?;
+        let jpeg = JpegDecoder::new(&data).decode()?;
+
+        let image = ImageBuffer::<Rgba8>::try_from(&jpeg)?;
+        assert_eq!(image.rgb8(), jpeg.rgb8());
+        assert_eq!(Jpeg::from(&image).pixel_buffer, jpeg.pixel_buffer);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use crate::image::{
+    grammar::{ColorType, ImageExt},
+    ImageBuffer, Rgb8, Subpixel,
+};
+use anyhow::{ensure, Result};
+
+/// A pixel-by-pixel comparison of two images of the same dimensions.
+///
+/// Images are compared as normalized straight RGBA at their full precision, so images of
+/// different formats and bit depths can be compared. Errors are reported in 8-bit levels.
+#[derive(Debug, Clone)]
+pub struct ImageDiff {
+    width: u32,
+    height: u32,
+    /// The largest absolute difference of any channel of each pixel, normalized.
+    errors: Vec<f32>,
+    /// The luma of the reference, which the heatmap is drawn over.
+    reference_lumas: Vec<f32>,
+    squared_error_sum: f64,
+    absolute_error_sum: f64,
+    tolerance: f32,
+}
+
+impl ImageDiff {
+    /// Compares `test` against `reference`. Pixels differ when any channel differs by more than
+    /// `tolerance`, in 8-bit levels.
+    pub fn new(reference: &dyn ImageExt, test: &dyn ImageExt, tolerance: f32) -> Result<Self> {
+        ensure!(
+            reference.dimensions() == test.dimensions(),
+            "Expect reference and test images to have identical dimensions, not {:?} and {:?}.",
+            reference.dimensions(),
+            test.dimensions()
+        );
+
+        let (width, height) = reference.dimensions();
+        let reference = reference.rgba_f32();
+        let test = test.rgba_f32();
+
+        let mut errors = Vec::with_capacity(reference.len() / 4);
+        let mut reference_lumas = Vec::with_capacity(reference.len() / 4);
+        let mut squared_error_sum = 0.0;
+        let mut absolute_error_sum = 0.0;
+
+        for (a, b) in reference.chunks_exact(4).zip(test.chunks_exact(4)) {
+            let mut max_error = 0.0f32;
+
+            for (&x, &y) in a.iter().zip(b) {
+                let error = (x - y).abs();
+
+                max_error = max_error.max(error);
+                absolute_error_sum += f64::from(error);
+                squared_error_sum += f64::from(error * error);
+            }
+
+            errors.push(max_error);
+            reference_lumas.push(0.114f32.mul_add(a[2], 0.299f32.mul_add(a[0], 0.587 * a[1])));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            errors,
+            reference_lumas,
+            squared_error_sum,
+            absolute_error_sum,
+            tolerance: tolerance / 255.0,
+        })
+    }
+
+    /// The largest difference of any channel, in 8-bit levels.
+    pub fn max_error(&self) -> f32 {
+        self.errors.iter().copied().fold(0.0, f32::max) * 255.0
+    }
+
+    /// The mean absolute difference over every channel, in 8-bit levels.
+    pub fn mean_error(&self) -> f64 {
+        self.absolute_error_sum / self.sample_count() * 255.0
+    }
+
+    /// The peak signal-to-noise ratio in decibels, infinite for identical images.
+    pub fn psnr(&self) -> f64 {
+        let mean_squared_error = self.squared_error_sum / self.sample_count();
+
+        if mean_squared_error == 0.0 {
+            return f64::INFINITY;
+        }
+
+        -10.0 * mean_squared_error.log10()
+    }
+
+    pub fn differing_pixels(&self) -> usize {
+        self.errors
+            .iter()
+            .filter(|&&error| error > self.tolerance)
+            .count()
+    }
+
+    pub fn is_identical(&self) -> bool {
+        self.differing_pixels() == 0
+    }
+
+    /// Draws differing pixels from dark red for the smallest errors through yellow to white
+    /// for the largest, over a dimmed grayscale copy of the reference.
+    pub fn heatmap(&self) -> ImageBuffer<Rgb8> {
+        let max_error = self.errors.iter().copied().fold(0.0, f32::max);
+
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let i = y as usize * self.width as usize + x as usize;
+            let error = self.errors[i];
+
+            let rgb = if error > self.tolerance {
+                let t = 2.0 * error / max_error;
+                [
+                    1.0,
+                    (t - 1.0).clamp(0.0, 1.0),
+                    (t - 1.5).clamp(0.0, 0.5) * 2.0,
+                ]
+                .map(|v| v * t.clamp(0.5, 1.0))
+            } else {
+                [self.reference_lumas[i] * 0.25; 3]
+            };
+
+            Rgb8(rgb.map(u8::from_f32))
+        })
+    }
+
+    fn sample_count(&self) -> f64 {
+        (self.errors.len() * ColorType::RGBA.num_channels() as usize).max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{ImageReader, Rgba16, Rgba8};
+
+    #[test]
+    fn test_identical() -> Result<()> {
+        let image = ImageReader::read_from_path("./tests/obama.png", None)?;
+        let diff = ImageDiff::new(image.as_ref(), image.as_ref(), 0.0)?;
+
+        assert!(diff.is_identical());
+        assert_eq!(diff.max_error(), 0.0);
+        assert_eq!(diff.psnr(), f64::INFINITY);
+
+        // The same pixels in another format and bit depth are identical too.
+        let png = ImageReader::read_from_path("./test_suite/basn2c08.png", None)?;
+        let wide = ImageBuffer::<Rgba16>::from_raw(
+            png.width(),
+            png.height(),
+            &png.rgba8()
+                .iter()
+                .map(|&s| u16::from(s) * 257)
+                .collect::<Vec<_>>(),
+        )?;
+        assert!(ImageDiff::new(png.as_ref(), &wide, 0.0)?.is_identical());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_differences() -> Result<()> {
+        let reference = ImageBuffer::from_pixel(4, 2, Rgba8([100, 100, 100, 255]));
+        let mut test = reference.clone();
+        test.put_pixel(1, 0, Rgba8([110, 100, 100, 255]));
+        test.put_pixel(3, 1, Rgba8([100, 102, 100, 255]));
+
+        let diff = ImageDiff::new(&reference, &test, 0.0)?;
+        assert_eq!(diff.differing_pixels(), 2);
+        assert!((diff.max_error() - 10.0).abs() < 1e-4);
+        assert!((diff.mean_error() - 12.0 / 32.0).abs() < 1e-4);
+
+        let mse: f64 = (100.0 + 4.0) / 32.0 / (255.0 * 255.0);
+        let expected = -10.0 * mse.log10();
+        assert!((diff.psnr() - expected).abs() < 1e-3);
+
+        // Differences within the tolerance are ignored.
+        assert_eq!(
+            ImageDiff::new(&reference, &test, 2.0)?.differing_pixels(),
+            1
+        );
+
+        let heatmap = diff.heatmap();
+        assert_eq!(heatmap.pixel(1, 0), &Rgb8([255, 255, 255]));
+        assert_eq!(heatmap.pixel(0, 0), &Rgb8([25, 25, 25]));
+        assert_eq!(heatmap.pixel(3, 1).0[0], 128);
+
+        assert!(ImageDiff::new(&reference, &ImageBuffer::<Rgba8>::new(2, 4), 0.0).is_err());
+
+        Ok(())
+    }
+}
@@ -1,12 +1,54 @@
-use anyhow::bail;
-use std::borrow::Cow;
+use crate::{
+    color::{ColorProfile, ColorTransform, ProfileColorSpace, ToneCurve},
+    image::Subpixel,
+};
+use anyhow::{bail, Result};
+use std::{borrow::Cow, fmt::Debug, path::Path};
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ImageKind {
     Png,
     Jpeg,
 }
 
+impl ImageKind {
+    /// The leading bytes that identify each format.
+    const SIGNATURES: [(&'static [u8], Self); 2] = [
+        (b"\x89PNG\r\n\x1A\n", Self::Png),
+        // Start of image, followed by the first marker.
+        (&[0xFF, 0xD8, 0xFF], Self::Jpeg),
+    ];
+
+    /// Identifies the format from the signature at the start of `data`.
+    pub fn from_magic_bytes(data: &[u8]) -> Option<Self> {
+        Self::SIGNATURES
+            .iter()
+            .find(|(signature, _)| data.starts_with(signature))
+            .map(|&(_, kind)| kind)
+    }
+
+    pub fn from_extension(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" | "jpe" | "jfif" => Some(Self::Jpeg),
+            _ => None,
+        }
+    }
+}
+
+/// What can be learned from an image's header without decoding any pixels. The color type and
+/// bit depth describe the decoded image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub kind: ImageKind,
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ColorType {
     Grayscale = 0,
@@ -26,6 +68,10 @@ impl ColorType {
             Self::RGBA => 4,
         }
     }
+
+    pub const fn has_alpha(&self) -> bool {
+        matches!(self, Self::GrayscaleAlpha | Self::RGBA)
+    }
 }
 
 impl TryFrom<u8> for ColorType {
@@ -47,7 +93,7 @@ impl TryFrom<u8> for ColorType {
 
 pub type Image = Box<dyn ImageExt>;
 
-pub trait ImageExt: Send + Sync {
+pub trait ImageExt: Debug + Send + Sync {
     fn width(&self) -> u32;
 
     fn height(&self) -> u32;
@@ -60,9 +106,136 @@ pub trait ImageExt: Send + Sync {
 
     fn color_type(&self) -> ColorType;
 
+    /// The number of bits per channel of the decoded samples.
+    fn bit_depth(&self) -> u8 {
+        8
+    }
+
     fn rgb8(&self) -> Cow<'_, [u8]>;
 
+    /// RGBA samples with straight alpha. Images without alpha are opaque.
     fn rgba8(&self) -> Cow<'_, [u8]>;
 
+    /// Like [`Self::rgba8`], but with each color multiplied by its alpha in linear light, which
+    /// is what filtering and blending on the GPU expect.
+    fn rgba8_premultiplied(&self) -> Cow<'_, [u8]> {
+        let rgba = self.rgba8();
+
+        if !self.color_type().has_alpha() {
+            return rgba;
+        }
+
+        let mut rgba = rgba.into_owned();
+        premultiply_srgb8(&mut rgba);
+
+        Cow::from(rgba)
+    }
+
+    /// Packs pixels as `0xAARRGGBB` with straight alpha. Images without alpha are opaque.
     fn bitmap(&self) -> Cow<'_, [u32]>;
+
+    /// The color profile the samples are encoded in. Images without one are sRGB.
+    fn color_profile(&self) -> Option<ColorProfile> {
+        None
+    }
+
+    /// Like [`Self::rgba8`], but converted from the image's color profile to `destination`.
+    fn rgba8_in(&self, destination: &ColorProfile) -> Result<Cow<'_, [u8]>> {
+        let source = self.color_profile().unwrap_or_else(ColorProfile::srgb);
+        let rgba = self.rgba8();
+
+        if source == *destination {
+            return Ok(rgba);
+        }
+
+        let mut rgba = rgba.into_owned();
+        ColorTransform::new(&source, destination)?.apply_rgba8(&mut rgba);
+
+        Ok(Cow::from(rgba))
+    }
+
+    /// RGBA samples with straight alpha, normalized to [0.0, 1.0] at the image's full
+    /// precision. Images without alpha are opaque.
+    fn rgba_f32(&self) -> Cow<'_, [f32]> {
+        Cow::from(self.rgba8().iter().map(|&s| s.to_f32()).collect::<Vec<_>>())
+    }
+
+    /// Like [`Self::rgba_f32`], but converted from the image's color profile to `destination`.
+    fn rgba_f32_in(&self, destination: &ColorProfile) -> Result<Cow<'_, [f32]>> {
+        let source = self.color_profile().unwrap_or_else(ColorProfile::srgb);
+        let rgba = self.rgba_f32();
+
+        if source == *destination {
+            return Ok(rgba);
+        }
+
+        let mut rgba = rgba.into_owned();
+        ColorTransform::new(&source, destination)?.apply_rgba32f(&mut rgba);
+
+        Ok(Cow::from(rgba))
+    }
+}
+
+/// Parses an ICC profile embedded in an image. Profiles that are malformed or don't match the
+/// color type are ignored with a warning, leaving the image in sRGB.
+pub fn embedded_color_profile(icc_profile: &[u8], color_type: ColorType) -> Option<ColorProfile> {
+    let profile = ColorProfile::from_icc(icc_profile)
+        .inspect_err(|err| log::warn!("Ignoring the embedded ICC profile: {err}"))
+        .ok()?;
+
+    let grayscale = matches!(color_type, ColorType::Grayscale | ColorType::GrayscaleAlpha);
+
+    if grayscale != (profile.color_space() == ProfileColorSpace::Gray) {
+        log::warn!("Ignoring an embedded ICC profile that doesn't match {color_type:?}.");
+        return None;
+    }
+
+    Some(profile)
+}
+
+/// Multiplies the colors of sRGB encoded RGBA samples by their alpha in linear light, in place,
+/// so sampling them from an sRGB texture yields linear premultiplied colors.
+pub fn premultiply_srgb8(rgba: &mut [u8]) {
+    let linear = std::array::from_fn::<f32, 256, _>(|i| ToneCurve::SRGB.eval(i as f32 / 255.0));
+
+    for pixel in rgba.chunks_exact_mut(4) {
+        let alpha = pixel[3];
+
+        if alpha == u8::MAX {
+            continue;
+        }
+
+        for channel in &mut pixel[..3] {
+            let premultiplied = linear[*channel as usize] * f32::from(alpha) / 255.0;
+            *channel = u8::from_f32(ToneCurve::SRGB.eval_inverse(premultiplied));
+        }
+    }
+}
+
+/// Multiplies the colors of normalized RGBA samples by their alpha, in place, in the space they
+/// are in. Textures premultiply linear samples, so in linear light like [`premultiply_srgb8`].
+pub fn premultiply_rgba32f(rgba: &mut [f32]) {
+    for pixel in rgba.chunks_exact_mut(4) {
+        let alpha = pixel[3];
+
+        for channel in &mut pixel[..3] {
+            *channel *= alpha;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_premultiply_srgb8() {
+        let mut rgba = vec![255, 128, 0, 255, 255, 128, 0, 128, 200, 100, 50, 0];
+        premultiply_srgb8(&mut rgba);
+
+        // Half of full intensity is 0.5 in linear light, which sRGB encodes as 188.
+        assert_eq!(&rgba[..4], [255, 128, 0, 255]);
+        assert_eq!(&rgba[4..8], [188, 93, 0, 128]);
+        assert_eq!(&rgba[8..], [0, 0, 0, 0]);
+    }
 }
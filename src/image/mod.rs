@@ -1,5 +1,13 @@
 pub mod grammar;
 
+pub use buffer::*;
+pub use diff::*;
+pub use pixel::*;
 pub use reader::*;
+pub use transform::*;
 
+mod buffer;
+mod diff;
+mod pixel;
 mod reader;
+mod transform;
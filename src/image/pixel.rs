@@ -0,0 +1,164 @@
+use crate::image::grammar::ColorType;
+use bytemuck::{Pod, Zeroable};
+use std::fmt::Debug;
+
+/// A single channel value. Integer channels span their whole range, floating point channels are
+/// normalized to [0.0, 1.0].
+pub trait Subpixel: Pod + Debug + PartialEq + Send + Sync {
+    fn to_f32(self) -> f32;
+
+    /// Converts a normalized value, clamping integer channels to their range.
+    fn from_f32(value: f32) -> Self;
+}
+
+impl Subpixel for u8 {
+    fn to_f32(self) -> f32 {
+        self as f32 / 255.0
+    }
+
+    fn from_f32(value: f32) -> Self {
+        (value.clamp(0.0, 1.0) * 255.0).round() as Self
+    }
+}
+
+impl Subpixel for u16 {
+    fn to_f32(self) -> f32 {
+        self as f32 / 65535.0
+    }
+
+    fn from_f32(value: f32) -> Self {
+        (value.clamp(0.0, 1.0) * 65535.0).round() as Self
+    }
+}
+
+impl Subpixel for f32 {
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+}
+
+/// A pixel format: a fixed number of channels of one [`Subpixel`] type, laid out like the PNG
+/// color type with the same channels.
+///
+/// Pixels convert between formats through normalized RGBA. Formats without alpha are opaque,
+/// and grayscale is derived from color with the Rec. 601 luma weights, like JPEG's YCbCr.
+pub trait Pixel: Pod + Debug + PartialEq + Send + Sync {
+    type Subpixel: Subpixel;
+
+    const CHANNEL_COUNT: usize;
+    const COLOR_TYPE: ColorType;
+    const BIT_DEPTH: u8;
+
+    fn channels(&self) -> &[Self::Subpixel] {
+        bytemuck::cast_slice(std::slice::from_ref(self))
+    }
+
+    fn channels_mut(&mut self) -> &mut [Self::Subpixel] {
+        bytemuck::cast_slice_mut(std::slice::from_mut(self))
+    }
+
+    /// Panics if `channels` doesn't hold exactly [`Self::CHANNEL_COUNT`] values.
+    fn from_channels(channels: &[Self::Subpixel]) -> Self {
+        let mut pixel = Self::zeroed();
+        pixel.channels_mut().copy_from_slice(channels);
+
+        pixel
+    }
+
+    fn to_rgba(&self) -> [f32; 4] {
+        let mut channels = [0.0; 4];
+
+        for (channel, &subpixel) in channels.iter_mut().zip(self.channels()) {
+            *channel = subpixel.to_f32();
+        }
+
+        expand_to_rgba(Self::COLOR_TYPE, &channels[..Self::CHANNEL_COUNT])
+    }
+
+    fn from_rgba([r, g, b, a]: [f32; 4]) -> Self {
+        let luma = 0.114f32.mul_add(b, 0.299f32.mul_add(r, 0.587 * g));
+
+        // Only the first `CHANNEL_COUNT` channels are used.
+        let channels = match Self::COLOR_TYPE {
+            ColorType::Grayscale => [luma, 0.0, 0.0, 0.0],
+            ColorType::GrayscaleAlpha => [luma, a, 0.0, 0.0],
+            ColorType::RGB => [r, g, b, 0.0],
+            _ => [r, g, b, a],
+        };
+
+        let mut pixel = Self::zeroed();
+
+        for (subpixel, channel) in pixel.channels_mut().iter_mut().zip(channels) {
+            *subpixel = Self::Subpixel::from_f32(channel);
+        }
+
+        pixel
+    }
+
+    /// Multiplies the color by alpha, in the space the samples are in.
+    fn premultiplied(&self) -> Self {
+        let [r, g, b, a] = self.to_rgba();
+        Self::from_rgba([r * a, g * a, b * a, a])
+    }
+
+    /// Divides the color by alpha. Fully transparent pixels become transparent black.
+    fn unpremultiplied(&self) -> Self {
+        let [r, g, b, a] = self.to_rgba();
+
+        if a == 0.0 {
+            return Self::from_rgba([0.0; 4]);
+        }
+
+        Self::from_rgba([r / a, g / a, b / a, a])
+    }
+}
+
+/// Widens the normalized channels of a color type to RGBA.
+pub(super) fn expand_to_rgba(color_type: ColorType, channels: &[f32]) -> [f32; 4] {
+    match (color_type, channels) {
+        (ColorType::Grayscale, &[y]) => [y, y, y, 1.0],
+        (ColorType::GrayscaleAlpha, &[y, a]) => [y, y, y, a],
+        (ColorType::RGB, &[r, g, b]) => [r, g, b, 1.0],
+        (ColorType::RGBA, &[r, g, b, a]) => [r, g, b, a],
+        (color_type, channels) => {
+            unreachable!("{color_type:?} doesn't have {} channels", channels.len())
+        }
+    }
+}
+
+macro_rules! define_pixel {
+    ($(#[$doc:meta])* $name:ident, $subpixel:ty, $channel_count:literal, $color_type:ident) => {
+        $(#[$doc])*
+        // Floating point pixels can't be `Eq`.
+        #[allow(clippy::derive_partial_eq_without_eq)]
+        #[repr(transparent)]
+        #[derive(Debug, Default, Clone, Copy, PartialEq, Pod, Zeroable)]
+        pub struct $name(pub [$subpixel; $channel_count]);
+
+        impl Pixel for $name {
+            type Subpixel = $subpixel;
+
+            const CHANNEL_COUNT: usize = $channel_count;
+            const COLOR_TYPE: ColorType = ColorType::$color_type;
+            const BIT_DEPTH: u8 = (std::mem::size_of::<$subpixel>() * 8) as u8;
+        }
+    };
+}
+
+define_pixel!(Luma8, u8, 1, Grayscale);
+define_pixel!(LumaA8, u8, 2, GrayscaleAlpha);
+define_pixel!(Rgb8, u8, 3, RGB);
+define_pixel!(Rgba8, u8, 4, RGBA);
+define_pixel!(Luma16, u16, 1, Grayscale);
+define_pixel!(Rgba16, u16, 4, RGBA);
+define_pixel!(
+    /// Floating point RGBA, normalized to [0.0, 1.0] but not clamped to it.
+    Rgba32F,
+    f32,
+    4,
+    RGBA
+);
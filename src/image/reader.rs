@@ -1,25 +1,223 @@
 use crate::{
-    image::grammar::{Image, ImageExt, ImageKind},
+    image::grammar::{ColorType, Image, ImageExt, ImageInfo, ImageKind},
     jpeg::JpegDecoder,
     png::PngDecoder,
 };
-use anyhow::Result;
-use std::path::Path;
+use anyhow::{anyhow, Result};
+use std::{
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
 
 #[derive(Debug)]
 pub struct ImageReader;
 
 impl ImageReader {
+    /// Reads and decodes the image at `path`. Without an explicit `image_kind`, the format is
+    /// detected from the file's signature, falling back to its extension.
     pub fn read_from_path(path: impl AsRef<Path>, image_kind: Option<ImageKind>) -> Result<Image> {
+        let path = path.as_ref();
         let data = std::fs::read(path)?;
 
-        let image_kind = image_kind.expect("how do you infer which image decoder to run?");
+        Self::decode(&data, Self::detect(&data, image_kind, Some(path))?)
+    }
+
+    /// Like [`Self::read_from_path`], but maps the file into memory instead of reading it.
+    #[cfg(feature = "mmap")]
+    pub fn read_from_mmap(path: impl AsRef<Path>, image_kind: Option<ImageKind>) -> Result<Image> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)?;
+
+        // SAFETY: The map is only read while decoding. Like any memory map, it's undefined
+        // behavior if another process truncates the file in the meantime.
+        let data = unsafe { memmap2::Mmap::map(&file)? };
+
+        Self::decode(&data, Self::detect(&data, image_kind, Some(path))?)
+    }
+
+    /// Decodes an image held in memory. Without an explicit `image_kind`, the format is detected
+    /// from its signature.
+    pub fn read_from_bytes(data: &[u8], image_kind: Option<ImageKind>) -> Result<Image> {
+        Self::decode(data, Self::detect(data, image_kind, None)?)
+    }
+
+    /// Reads the rest of `reader` and decodes it like [`Self::read_from_bytes`].
+    pub fn read_from_reader(
+        mut reader: impl Read + Seek,
+        image_kind: Option<ImageKind>,
+    ) -> Result<Image> {
+        let start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(start))?;
+
+        let mut data = Vec::with_capacity(end.saturating_sub(start) as usize);
+        reader.read_to_end(&mut data)?;
+
+        Self::read_from_bytes(&data, image_kind)
+    }
+
+    /// Reads the dimensions, color type and format of an image from its header alone, so
+    /// oversized images can be rejected before any pixels are decoded.
+    pub fn probe(data: &[u8]) -> Result<ImageInfo> {
+        let kind = Self::detect(data, None, None)?;
+
+        let info = match kind {
+            ImageKind::Png => {
+                let image_header = PngDecoder::new(data).decode_header()?;
+
+                ImageInfo {
+                    kind,
+                    width: image_header.width,
+                    height: image_header.height,
+                    bit_depth: image_header.bit_depth,
+                    color_type: image_header.color_type,
+                }
+            }
+            ImageKind::Jpeg => {
+                let start_of_frame = JpegDecoder::new(data).decode_header()?;
+
+                let bit_depth = if start_of_frame.sample_precision == 8
+                    && !start_of_frame.encoding_process.is_lossless()
+                {
+                    8
+                } else {
+                    16
+                };
+
+                let color_type = if start_of_frame.components.len() == 1 {
+                    ColorType::Grayscale
+                } else {
+                    ColorType::RGB
+                };
+
+                ImageInfo {
+                    kind,
+                    width: start_of_frame.samples_per_line as u32,
+                    height: start_of_frame.lines as u32,
+                    bit_depth,
+                    color_type,
+                }
+            }
+        };
+
+        Ok(info)
+    }
+
+    fn detect(
+        data: &[u8],
+        image_kind: Option<ImageKind>,
+        path: Option<&Path>,
+    ) -> Result<ImageKind> {
+        image_kind
+            .or_else(|| ImageKind::from_magic_bytes(data))
+            .or_else(|| path.and_then(ImageKind::from_extension))
+            .ok_or_else(|| {
+                path.map_or_else(
+                    || anyhow!("Unsupported image format."),
+                    |path| anyhow!("Unsupported image format: {}", path.display()),
+                )
+            })
+    }
 
+    fn decode(data: &[u8], image_kind: ImageKind) -> Result<Image> {
         let image: Box<dyn ImageExt> = match image_kind {
-            ImageKind::Png => Box::new(PngDecoder::new(&data).decode()?),
-            ImageKind::Jpeg => Box::new(JpegDecoder::new(&data).decode()?),
+            ImageKind::Png => Box::new(PngDecoder::new(data).decode()?),
+            ImageKind::Jpeg => Box::new(JpegDecoder::new(data).decode()?),
         };
 
         Ok(image)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_detect_format() -> Result<()> {
+        for (path, kind) in [
+            ("./tests/obama.png", ImageKind::Png),
+            ("./tests/taxi_zone_map_manhattan.jpg", ImageKind::Jpeg),
+        ] {
+            let data = std::fs::read(path)?;
+            assert_eq!(ImageKind::from_magic_bytes(&data), Some(kind));
+        }
+
+        assert_eq!(ImageKind::from_magic_bytes(b"GIF89a"), None);
+        assert_eq!(
+            ImageKind::from_extension("photo.JPEG"),
+            Some(ImageKind::Jpeg)
+        );
+        assert_eq!(ImageKind::from_extension("notes.txt"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_without_image_kind() -> Result<()> {
+        let image = ImageReader::read_from_path("./tests/ycbcr_12bit.jpg", None)?;
+        assert!(image.width() > 0);
+
+        let Err(error) = ImageReader::read_from_path("./Cargo.toml", None) else {
+            panic!("Cargo.toml isn't an image");
+        };
+        assert!(error.to_string().contains("Unsupported image format"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_from_bytes_and_reader() -> Result<()> {
+        let data = std::fs::read("./tests/obama.png")?;
+        let from_path = ImageReader::read_from_path("./tests/obama.png", None)?;
+        let from_bytes = ImageReader::read_from_bytes(&data, None)?;
+
+        // Readers are read from their current position.
+        let mut reader = Cursor::new([b"junk".as_slice(), &data].concat());
+        reader.seek(SeekFrom::Start(4))?;
+        let from_reader = ImageReader::read_from_reader(reader, None)?;
+
+        assert_eq!(from_bytes.rgba8(), from_path.rgba8());
+        assert_eq!(from_reader.rgba8(), from_path.rgba8());
+
+        assert!(ImageReader::read_from_bytes(b"not an image", None).is_err());
+
+        #[cfg(feature = "mmap")]
+        assert_eq!(
+            ImageReader::read_from_mmap("./tests/obama.png", None)?.rgba8(),
+            from_path.rgba8()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_probe() -> Result<()> {
+        for (path, kind, bit_depth) in [
+            ("./tests/obama.png", ImageKind::Png, 8),
+            ("./tests/cmyk_adobe.jpg", ImageKind::Jpeg, 8),
+            ("./tests/lossless_gray_12bit_p5.jpg", ImageKind::Jpeg, 16),
+        ] {
+            let data = std::fs::read(path)?;
+            let image = ImageReader::read_from_bytes(&data, None)?;
+
+            // Only the header is needed.
+            let info = ImageReader::probe(&data[..data.len().min(1024)])?;
+
+            assert_eq!(
+                info,
+                ImageInfo {
+                    kind,
+                    width: image.width(),
+                    height: image.height(),
+                    bit_depth,
+                    color_type: image.color_type(),
+                },
+                "{path}"
+            );
+        }
+
+        Ok(())
+    }
+}
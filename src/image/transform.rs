@@ -0,0 +1,439 @@
+use crate::{
+    color::ToneCurve,
+    image::{ImageBuffer, Pixel, Rgba32F},
+};
+use anyhow::{ensure, Result};
+use std::f32::consts::PI;
+
+/// How pixels are interpolated when an image is resized or rotated by an arbitrary angle.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ResampleFilter {
+    /// Picks the closest pixel, without blending.
+    Nearest,
+    Bilinear,
+    /// The Catmull-Rom spline, sharper than bilinear with little ringing.
+    #[default]
+    Bicubic,
+    /// A windowed sinc over three lobes, the sharpest and slowest.
+    Lanczos3,
+}
+
+impl ResampleFilter {
+    /// How far the kernel reaches, in source pixels.
+    const fn support(self) -> f32 {
+        match self {
+            Self::Nearest => 0.5,
+            Self::Bilinear => 1.0,
+            Self::Bicubic => 2.0,
+            Self::Lanczos3 => 3.0,
+        }
+    }
+
+    fn kernel(self, x: f32) -> f32 {
+        let x = x.abs();
+
+        match self {
+            Self::Nearest => f32::from(x < 0.5),
+            Self::Bilinear => (1.0 - x).max(0.0),
+            Self::Bicubic => {
+                // The Keys cubic with a = -0.5.
+                if x < 1.0 {
+                    (1.5f32.mul_add(x, -2.5) * x).mul_add(x, 1.0)
+                } else if x < 2.0 {
+                    (-0.5f32).mul_add(x, 2.5).mul_add(x, -4.0).mul_add(x, 2.0)
+                } else {
+                    0.0
+                }
+            }
+            Self::Lanczos3 => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Options for resizing and rotating.
+///
+/// Colors are always filtered with premultiplied alpha, so transparent pixels don't bleed into
+/// their neighbours. By default, they're also filtered in linear light, which keeps edges and
+/// downscaled detail from darkening. Samples are taken to be encoded with the sRGB curve.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Resample {
+    filter: ResampleFilter,
+    linear_light: bool,
+}
+
+impl Default for Resample {
+    fn default() -> Self {
+        Self::new(ResampleFilter::default())
+    }
+}
+
+impl Resample {
+    pub const fn new(filter: ResampleFilter) -> Self {
+        Self {
+            filter,
+            linear_light: true,
+        }
+    }
+
+    /// Filters the encoded samples as they are, like most image editors do.
+    pub const fn with_linear_light(mut self, linear_light: bool) -> Self {
+        self.linear_light = linear_light;
+        self
+    }
+
+    /// Converts to linear, premultiplied RGBA.
+    fn decode<P: Pixel>(&self, image: &ImageBuffer<P>) -> ImageBuffer<Rgba32F> {
+        let mut work = image.convert::<Rgba32F>();
+
+        for pixel in work.pixels_mut() {
+            let [r, g, b, a] = pixel.0;
+            let [r, g, b] = [r, g, b].map(|c| self.linearize(c) * a);
+
+            *pixel = Rgba32F([r, g, b, a]);
+        }
+
+        work
+    }
+
+    /// The inverse of [`Self::decode`], clamping the overshoot of sharper filters.
+    fn encode<P: Pixel>(&self, mut work: ImageBuffer<Rgba32F>) -> ImageBuffer<P> {
+        for pixel in work.pixels_mut() {
+            let a = pixel.0[3].clamp(0.0, 1.0);
+
+            let [r, g, b] = [pixel.0[0], pixel.0[1], pixel.0[2]].map(|c| {
+                if a == 0.0 {
+                    0.0
+                } else {
+                    self.delinearize(c.clamp(0.0, a) / a)
+                }
+            });
+
+            *pixel = Rgba32F([r, g, b, a]);
+        }
+
+        work.convert()
+    }
+
+    fn linearize(&self, value: f32) -> f32 {
+        if self.linear_light {
+            ToneCurve::SRGB.eval(value)
+        } else {
+            value
+        }
+    }
+
+    fn delinearize(&self, value: f32) -> f32 {
+        if self.linear_light {
+            ToneCurve::SRGB.eval_inverse(value)
+        } else {
+            value
+        }
+    }
+}
+
+/// The source pixels and weights that make up one output pixel along an axis.
+#[derive(Debug)]
+struct Taps {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// Computes the taps of each of `destination` pixels resampled from `source` pixels. When
+/// shrinking, the kernel is stretched to cover every source pixel.
+fn taps(source: u32, destination: u32, filter: ResampleFilter) -> Vec<Taps> {
+    let scale = source as f32 / destination as f32;
+    let kernel_scale = scale.max(1.0);
+    let support = filter.support() * kernel_scale;
+
+    (0..destination)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+
+            if filter == ResampleFilter::Nearest {
+                return Taps {
+                    start: (center as usize).min(source as usize - 1),
+                    weights: vec![1.0],
+                };
+            }
+
+            let start = (center - support).floor().max(0.0) as usize;
+            let end = ((center + support).ceil() as usize).min(source as usize);
+
+            let mut weights = (start..end)
+                .map(|j| filter.kernel((j as f32 + 0.5 - center) / kernel_scale))
+                .collect::<Vec<_>>();
+            let sum = weights.iter().sum::<f32>();
+
+            if sum != 0.0 {
+                weights.iter_mut().for_each(|w| *w /= sum);
+            }
+
+            Taps { start, weights }
+        })
+        .collect()
+}
+
+fn weighted_sum<'a>(pixels: impl Iterator<Item = &'a Rgba32F>, weights: &[f32]) -> Rgba32F {
+    let mut sum = [0.0; 4];
+
+    for (pixel, &weight) in pixels.zip(weights) {
+        for (channel, &value) in sum.iter_mut().zip(&pixel.0) {
+            *channel = weight.mul_add(value, *channel);
+        }
+    }
+
+    Rgba32F(sum)
+}
+
+impl<P: Pixel> ImageBuffer<P> {
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Self> {
+        Ok(self.view(x, y, width, height)?.to_image())
+    }
+
+    pub fn flip_horizontal(&self) -> Self {
+        Self::from_fn(self.width(), self.height(), |x, y| {
+            *self.pixel(self.width() - 1 - x, y)
+        })
+    }
+
+    pub fn flip_vertical(&self) -> Self {
+        Self::from_fn(self.width(), self.height(), |x, y| {
+            *self.pixel(x, self.height() - 1 - y)
+        })
+    }
+
+    /// Mirrors the image along its main diagonal.
+    pub fn transpose(&self) -> Self {
+        Self::from_fn(self.height(), self.width(), |x, y| *self.pixel(y, x))
+    }
+
+    /// Rotates the image clockwise.
+    pub fn rotate90(&self) -> Self {
+        Self::from_fn(self.height(), self.width(), |x, y| {
+            *self.pixel(y, self.height() - 1 - x)
+        })
+    }
+
+    pub fn rotate180(&self) -> Self {
+        Self::from_fn(self.width(), self.height(), |x, y| {
+            *self.pixel(self.width() - 1 - x, self.height() - 1 - y)
+        })
+    }
+
+    pub fn rotate270(&self) -> Self {
+        Self::from_fn(self.height(), self.width(), |x, y| {
+            *self.pixel(self.width() - 1 - y, x)
+        })
+    }
+
+    /// Resamples the image to new dimensions, filtering horizontally, then vertically.
+    pub fn resize(&self, width: u32, height: u32, resample: Resample) -> Result<Self> {
+        ensure!(width > 0 && height > 0, "Can't resize to {width}x{height}.");
+        ensure!(
+            self.width() > 0 && self.height() > 0,
+            "Can't resize an empty image."
+        );
+
+        let source = resample.decode(self);
+
+        let horizontal_taps = taps(self.width(), width, resample.filter);
+        let horizontal = ImageBuffer::from_fn(width, self.height(), |x, y| {
+            let taps = &horizontal_taps[x as usize];
+            weighted_sum(source.row(y)[taps.start..].iter(), &taps.weights)
+        });
+
+        let vertical_taps = taps(self.height(), height, resample.filter);
+        let resized = ImageBuffer::from_fn(width, height, |x, y| {
+            let taps = &vertical_taps[y as usize];
+            let column = (taps.start..taps.start + taps.weights.len())
+                .map(|y| horizontal.pixel(x, y as u32));
+
+            weighted_sum(column, &taps.weights)
+        });
+
+        Ok(resample.encode(resized))
+    }
+
+    /// Rotates the image clockwise by any angle, growing the canvas to fit. Uncovered corners
+    /// are transparent, or black for formats without alpha.
+    pub fn rotate(&self, degrees: f32, resample: Resample) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let (width, height) = (self.width() as f32, self.height() as f32);
+
+        // Tolerate rounding, so right angles keep their exact dimensions.
+        let fit = |extent: f32| (extent - 1e-3).ceil().max(1.0) as u32;
+        let rotated_width = fit(width.mul_add(cos.abs(), height * sin.abs()));
+        let rotated_height = fit(width.mul_add(sin.abs(), height * cos.abs()));
+
+        let source = resample.decode(self);
+        let filter = resample.filter;
+        let support = filter.support().ceil() as i64;
+
+        let rotated = ImageBuffer::from_fn(rotated_width, rotated_height, |x, y| {
+            let dx = x as f32 + 0.5 - rotated_width as f32 / 2.0;
+            let dy = y as f32 + 0.5 - rotated_height as f32 / 2.0;
+
+            // Map the output pixel back into the source, where pixel centers lie at halves.
+            let sx = dx.mul_add(cos, dy * sin) + width / 2.0 - 0.5;
+            let sy = dy.mul_add(cos, -dx * sin) + height / 2.0 - 0.5;
+
+            let sample = |x: i64, y: i64| {
+                if x < 0 || y < 0 || x >= self.width() as i64 || y >= self.height() as i64 {
+                    Rgba32F([0.0; 4])
+                } else {
+                    *source.pixel(x as u32, y as u32)
+                }
+            };
+
+            if filter == ResampleFilter::Nearest {
+                return sample(sx.round() as i64, sy.round() as i64);
+            }
+
+            // Samples outside the image are transparent, which antialiases the edges.
+            let (left, top) = (sx.floor() as i64, sy.floor() as i64);
+            let mut sum = [0.0; 4];
+
+            for j in top - support + 1..=top + support {
+                let wy = filter.kernel(sy - j as f32);
+
+                for i in left - support + 1..=left + support {
+                    let weight = filter.kernel(sx - i as f32) * wy;
+
+                    if weight == 0.0 {
+                        continue;
+                    }
+
+                    for (channel, &value) in sum.iter_mut().zip(&sample(i, j).0) {
+                        *channel = weight.mul_add(value, *channel);
+                    }
+                }
+            }
+
+            Rgba32F(sum)
+        });
+
+        resample.encode(rotated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{ImageReader, Luma8, Rgb8, Rgba8};
+
+    /// A 3x2 image whose pixels are numbered in reading order.
+    fn numbered() -> ImageBuffer<Luma8> {
+        ImageBuffer::from_fn(3, 2, |x, y| Luma8([(y * 3 + x) as u8]))
+    }
+
+    fn values(image: &ImageBuffer<Luma8>) -> Vec<u8> {
+        image.pixels().iter().map(|p| p.0[0]).collect()
+    }
+
+    #[test]
+    fn test_lossless_transforms() -> Result<()> {
+        let image = numbered();
+
+        assert_eq!(values(&image.flip_horizontal()), [2, 1, 0, 5, 4, 3]);
+        assert_eq!(values(&image.flip_vertical()), [3, 4, 5, 0, 1, 2]);
+        assert_eq!(values(&image.transpose()), [0, 3, 1, 4, 2, 5]);
+        assert_eq!(values(&image.rotate90()), [3, 0, 4, 1, 5, 2]);
+        assert_eq!(values(&image.rotate180()), [5, 4, 3, 2, 1, 0]);
+        assert_eq!(values(&image.rotate270()), [2, 5, 1, 4, 0, 3]);
+        assert_eq!(image.rotate90().dimensions(), (2, 3));
+
+        assert_eq!(image.rotate90().rotate270(), image);
+        assert_eq!(image.rotate90().rotate90(), image.rotate180());
+
+        assert_eq!(values(&image.crop(1, 0, 2, 2)?), [1, 2, 4, 5]);
+        assert!(image.crop(2, 1, 2, 1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resize_identity() -> Result<()> {
+        let image = ImageReader::read_from_path("./test_suite/basn6a08.png", None)?;
+        let image = ImageBuffer::<Rgba8>::from_image(image.as_ref());
+
+        for filter in [
+            ResampleFilter::Nearest,
+            ResampleFilter::Bilinear,
+            ResampleFilter::Bicubic,
+            ResampleFilter::Lanczos3,
+        ] {
+            let resized = image.resize(image.width(), image.height(), Resample::new(filter))?;
+
+            // Transparent pixels lose their color, which premultiplying can't keep.
+            for (pixel, expected) in resized.pixels().iter().zip(image.pixels()) {
+                if expected.0[3] > 0 {
+                    assert_eq!(pixel, expected, "{filter:?}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resize_filtering() -> Result<()> {
+        let checker =
+            ImageBuffer::from_fn(2, 2, |x, y| Luma8([if (x + y) % 2 == 0 { 255 } else { 0 }]));
+
+        // Half of the light of white is brighter than half of the sRGB value.
+        let linear = checker.resize(1, 1, Resample::new(ResampleFilter::Bilinear))?;
+        assert_eq!(linear.pixel(0, 0), &Luma8([188]));
+
+        let encoded = Resample::new(ResampleFilter::Bilinear).with_linear_light(false);
+        assert_eq!(checker.resize(1, 1, encoded)?.pixel(0, 0), &Luma8([128]));
+
+        // The color of a transparent pixel doesn't bleed into its neighbour.
+        let edge = ImageBuffer::from_fn(2, 1, |x, _| {
+            [Rgba8([255, 0, 0, 255]), Rgba8([0, 255, 0, 0])][x as usize]
+        });
+        let blended = edge.resize(1, 1, Resample::new(ResampleFilter::Lanczos3))?;
+        assert_eq!(blended.pixel(0, 0), &Rgba8([255, 0, 0, 128]));
+
+        // Upscaling keeps flat areas flat.
+        let flat = ImageBuffer::from_pixel(3, 3, Rgb8([10, 100, 200]));
+        let upscaled = flat.resize(7, 5, Resample::default())?;
+        assert!(upscaled.pixels().iter().all(|&p| p == Rgb8([10, 100, 200])));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotate() {
+        let image = ImageBuffer::from_fn(5, 3, |x, y| Rgba8([(y * 5 + x) as u8, 0, 0, 255]));
+
+        for (degrees, expected) in [
+            (90.0, image.rotate90()),
+            (180.0, image.rotate180()),
+            (-90.0, image.rotate270()),
+        ] {
+            let rotated = image.rotate(degrees, Resample::new(ResampleFilter::Nearest));
+            assert_eq!(rotated, expected, "{degrees}");
+        }
+
+        let rotated = image.rotate(45.0, Resample::default());
+        assert_eq!(rotated.dimensions(), (6, 6));
+
+        // The center stays opaque and the corners fall outside the source.
+        assert_eq!(rotated.pixel(3, 3).0[3], 255);
+        assert_eq!(rotated.pixel(0, 0).0[3], 0);
+    }
+}
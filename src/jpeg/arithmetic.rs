@@ -0,0 +1,659 @@
+use crate::jpeg::{
+    grammar::{ArithmeticConditioning, StartOfScan, ZIGZAG},
+    lossless::DifferenceDecoder,
+    scan::BlockDecoder,
+};
+use anyhow::{bail, ensure, Result};
+
+/// The probability estimation state machine from Table D.2, as `(Qe, next index after an MPS,
+/// next index after an LPS, whether an LPS switches the sense of the MPS)`. The last entry is not
+/// part of the table; it is a fixed estimate of 0.5 that never adapts.
+#[rustfmt::skip]
+const QE_TABLE: [(u16, u8, u8, bool); 114] = [
+    (0x5A1D, 1, 1, true),
+    (0x2586, 2, 14, false),
+    (0x1114, 3, 16, false),
+    (0x080B, 4, 18, false),
+    (0x03D8, 5, 20, false),
+    (0x01DA, 6, 23, false),
+    (0x00E5, 7, 25, false),
+    (0x006F, 8, 28, false),
+    (0x0036, 9, 30, false),
+    (0x001A, 10, 33, false),
+    (0x000D, 11, 35, false),
+    (0x0006, 12, 9, false),
+    (0x0003, 13, 10, false),
+    (0x0001, 13, 12, false),
+    (0x5A7F, 15, 15, true),
+    (0x3F25, 16, 36, false),
+    (0x2CF2, 17, 38, false),
+    (0x207C, 18, 39, false),
+    (0x17B9, 19, 40, false),
+    (0x1182, 20, 42, false),
+    (0x0CEF, 21, 43, false),
+    (0x09A1, 22, 45, false),
+    (0x072F, 23, 46, false),
+    (0x055C, 24, 48, false),
+    (0x0406, 25, 49, false),
+    (0x0303, 26, 51, false),
+    (0x0240, 27, 52, false),
+    (0x01B1, 28, 54, false),
+    (0x0144, 29, 56, false),
+    (0x00F5, 30, 57, false),
+    (0x00B7, 31, 59, false),
+    (0x008A, 32, 60, false),
+    (0x0068, 33, 62, false),
+    (0x004E, 34, 63, false),
+    (0x003B, 35, 32, false),
+    (0x002C, 9, 33, false),
+    (0x5AE1, 37, 37, true),
+    (0x484C, 38, 64, false),
+    (0x3A0D, 39, 65, false),
+    (0x2EF1, 40, 67, false),
+    (0x261F, 41, 68, false),
+    (0x1F33, 42, 69, false),
+    (0x19A8, 43, 70, false),
+    (0x1518, 44, 72, false),
+    (0x1177, 45, 73, false),
+    (0x0E74, 46, 74, false),
+    (0x0BFB, 47, 75, false),
+    (0x09F8, 48, 77, false),
+    (0x0861, 49, 78, false),
+    (0x0706, 50, 79, false),
+    (0x05CD, 51, 48, false),
+    (0x04DE, 52, 50, false),
+    (0x040F, 53, 50, false),
+    (0x0363, 54, 51, false),
+    (0x02D4, 55, 52, false),
+    (0x025C, 56, 53, false),
+    (0x01F8, 57, 54, false),
+    (0x01A4, 58, 55, false),
+    (0x0160, 59, 56, false),
+    (0x0125, 60, 57, false),
+    (0x00F6, 61, 58, false),
+    (0x00CB, 62, 59, false),
+    (0x00AB, 63, 61, false),
+    (0x008F, 32, 61, false),
+    (0x5B12, 65, 65, true),
+    (0x4D04, 66, 80, false),
+    (0x412C, 67, 81, false),
+    (0x37D8, 68, 82, false),
+    (0x2FE8, 69, 83, false),
+    (0x293C, 70, 84, false),
+    (0x2379, 71, 86, false),
+    (0x1EDF, 72, 87, false),
+    (0x1AA9, 73, 87, false),
+    (0x174E, 74, 72, false),
+    (0x1424, 75, 72, false),
+    (0x119C, 76, 74, false),
+    (0x0F6B, 77, 74, false),
+    (0x0D51, 78, 75, false),
+    (0x0BB6, 79, 77, false),
+    (0x0A40, 48, 77, false),
+    (0x5832, 81, 80, true),
+    (0x4D1C, 82, 88, false),
+    (0x438E, 83, 89, false),
+    (0x3BDD, 84, 90, false),
+    (0x34EE, 85, 91, false),
+    (0x2EAE, 86, 92, false),
+    (0x299A, 87, 93, false),
+    (0x2516, 71, 86, false),
+    (0x5570, 89, 88, true),
+    (0x4CA9, 90, 95, false),
+    (0x44D9, 91, 96, false),
+    (0x3E22, 92, 97, false),
+    (0x3824, 93, 99, false),
+    (0x32B4, 94, 99, false),
+    (0x2E17, 86, 93, false),
+    (0x56A8, 96, 95, true),
+    (0x4F46, 97, 101, false),
+    (0x47E5, 98, 102, false),
+    (0x41CF, 99, 103, false),
+    (0x3C3D, 100, 104, false),
+    (0x375E, 93, 99, false),
+    (0x5231, 102, 105, false),
+    (0x4C0F, 103, 106, false),
+    (0x4639, 104, 107, false),
+    (0x415E, 99, 103, false),
+    (0x5627, 106, 105, true),
+    (0x50E7, 107, 108, false),
+    (0x4B85, 103, 109, false),
+    (0x5597, 109, 110, false),
+    (0x504F, 107, 111, false),
+    (0x5A10, 111, 110, true),
+    (0x5522, 109, 112, false),
+    (0x59EB, 111, 112, true),
+    (0x5A1D, 113, 113, false),
+];
+
+/// The index of the fixed 0.5 probability estimate in [`QE_TABLE`].
+const FIXED_ESTIMATE: u8 = 113;
+
+const DC_STAT_BINS: usize = 64;
+const AC_STAT_BINS: usize = 256;
+const LOSSLESS_STAT_BINS: usize = 158;
+
+/// The QM-coder decoder from Annex D, following the register layout of figure D.14. Statistics
+/// bins hold the index of their probability estimate in the low 7 bits and the sense of the more
+/// probable symbol in the high bit.
+#[derive(Debug)]
+pub struct QmDecoder<'a> {
+    data: &'a [u8],
+    cursor: usize,
+    c: i64,
+    a: i64,
+    ct: i32,
+}
+
+impl<'a> QmDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            cursor: 0,
+            c: 0,
+            a: 0,
+            // Forces two bytes to be read into C before the first decision.
+            ct: -16,
+        }
+    }
+
+    /// Reads the next byte of entropy-coded data, removing stuffed zero bytes. Reaching a marker
+    /// is not an error: the decoder is fed zeros from then on (D.2.6).
+    fn next_byte(&mut self) -> u8 {
+        match self.data.get(self.cursor) {
+            Some(0xFF) => {
+                let mut next = self.cursor + 1;
+
+                while self.data.get(next) == Some(&0xFF) {
+                    next += 1;
+                }
+
+                if self.data.get(next) == Some(&0) {
+                    self.cursor = next + 1;
+                    0xFF
+                } else {
+                    0
+                }
+            }
+            Some(&byte) => {
+                self.cursor += 1;
+                byte
+            }
+            None => 0,
+        }
+    }
+
+    /// Decodes one binary decision with the statistics in `bin` and updates its estimate
+    /// (D.2.4 and D.2.5).
+    pub fn decode(&mut self, bin: &mut u8) -> u8 {
+        // Renormalization and data input (D.2.6)
+        while self.a < 0x8000 {
+            self.ct -= 1;
+
+            if self.ct < 0 {
+                self.c = (self.c << 8) | self.next_byte() as i64;
+                self.ct += 8;
+
+                if self.ct < 0 {
+                    self.ct += 1;
+
+                    if self.ct == 0 {
+                        // Both initial bytes are in; A becomes 0x10000 below.
+                        self.a = 0x8000;
+                    }
+                }
+            }
+
+            self.a <<= 1;
+        }
+
+        let state = *bin;
+        let mps = state >> 7;
+        let (qe, next_mps, next_lps, switch) = QE_TABLE[(state & 0x7F) as usize];
+        let qe = qe as i64;
+
+        let after_mps = (mps << 7) | next_mps;
+        let after_lps = ((mps ^ switch as u8) << 7) | next_lps;
+
+        self.a -= qe;
+        let temp = self.a << self.ct;
+
+        if self.c >= temp {
+            self.c -= temp;
+
+            // Conditional exchange: the LPS sub-interval is the larger one.
+            let is_mps = self.a < qe;
+            self.a = qe;
+
+            if is_mps {
+                *bin = after_mps;
+                mps
+            } else {
+                *bin = after_lps;
+                mps ^ 1
+            }
+        } else {
+            if self.a < 0x8000 {
+                if self.a < qe {
+                    *bin = after_lps;
+                    return mps ^ 1;
+                }
+
+                *bin = after_mps;
+            }
+
+            mps
+        }
+    }
+
+    /// Skips to the end of the current interval and steps over the restart marker that should
+    /// follow.
+    pub fn reset(&mut self, expected_restart: u8) -> Result<()> {
+        loop {
+            match self.data.get(self.cursor..self.cursor + 2) {
+                Some([0xFF, 0x00]) => self.cursor += 2,
+                Some([0xFF, 0xFF]) => self.cursor += 1,
+                Some(&[0xFF, marker]) if marker == 0xD0 + expected_restart => {
+                    self.cursor += 2;
+                    break;
+                }
+                Some([byte, _]) if *byte != 0xFF => self.cursor += 1,
+                found => bail!(
+                    "Expected restart marker RST{}, found {:X?}",
+                    expected_restart,
+                    found
+                ),
+            }
+        }
+
+        self.c = 0;
+        self.a = 0;
+        self.ct = -16;
+
+        Ok(())
+    }
+}
+
+/// Classifies a difference as zero, small positive, small negative, large positive or large
+/// negative (F.1.4.4.1.2), numbered 0 through 4.
+const fn classify(difference: i32, (lower, upper): (u8, u8)) -> usize {
+    let magnitude = difference.unsigned_abs();
+
+    if magnitude <= (1 << lower) >> 1 {
+        0
+    } else if magnitude <= 1 << upper {
+        if difference > 0 {
+            1
+        } else {
+            2
+        }
+    } else if difference > 0 {
+        3
+    } else {
+        4
+    }
+}
+
+/// Decodes a DC difference (figures F.19 to F.24) with its zero, sign and first magnitude
+/// decisions at `s0` and the magnitude categories starting at `x1`. Lossless differences use the
+/// same procedure.
+fn decode_difference(
+    decoder: &mut QmDecoder,
+    stats: &mut [u8],
+    s0: usize,
+    x1: usize,
+) -> Result<i32> {
+    if decoder.decode(&mut stats[s0]) == 0 {
+        return Ok(0);
+    }
+
+    let sign = decoder.decode(&mut stats[s0 + 1]);
+    let mut st = s0 + 2 + sign as usize;
+
+    let mut m = decoder.decode(&mut stats[st]) as i32;
+
+    if m != 0 {
+        st = x1;
+
+        while decoder.decode(&mut stats[st]) == 1 {
+            m <<= 1;
+            ensure!(m != 0x8000, "Difference magnitude category out of range.");
+            st += 1;
+        }
+    }
+
+    Ok(decode_magnitude(decoder, stats, st + 14, m, sign))
+}
+
+/// Decodes the bits below the leading one of a magnitude `m` (figure F.24) and applies the sign.
+fn decode_magnitude(decoder: &mut QmDecoder, stats: &mut [u8], st: usize, m: i32, sign: u8) -> i32 {
+    let mut value = m;
+    let mut bit = m >> 1;
+
+    while bit != 0 {
+        if decoder.decode(&mut stats[st]) == 1 {
+            value |= bit;
+        }
+
+        bit >>= 1;
+    }
+
+    value += 1;
+
+    if sign == 1 {
+        -value
+    } else {
+        value
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScanKind {
+    Sequential,
+    DcFirst,
+    DcRefine,
+    AcFirst,
+    AcRefine,
+}
+
+/// Decodes the blocks of an arithmetic-coded sequential (F.2.4) or progressive (G.1.3) DCT scan.
+#[derive(Debug)]
+pub struct ArithmeticDecoder<'a> {
+    decoder: QmDecoder<'a>,
+    kind: ScanKind,
+    spectral_start: usize,
+    spectral_end: usize,
+    point_transform: u8,
+    tables: Vec<(usize, usize)>,
+    conditioning: ArithmeticConditioning,
+    dc_stats: [[u8; DC_STAT_BINS]; 4],
+    ac_stats: [[u8; AC_STAT_BINS]; 4],
+    fixed_bin: u8,
+    predictors: Vec<i32>,
+    dc_contexts: Vec<usize>,
+}
+
+impl<'a> ArithmeticDecoder<'a> {
+    pub fn new(
+        image_data: &'a [u8],
+        start_of_scan: &StartOfScan,
+        conditioning: ArithmeticConditioning,
+        progressive: bool,
+    ) -> Result<Self> {
+        let (mut spectral_start, mut spectral_end) = (
+            *start_of_scan.spectral_select.start() as usize,
+            *start_of_scan.spectral_select.end() as usize,
+        );
+
+        let kind = if progressive {
+            ensure!(
+                spectral_start <= spectral_end && spectral_end <= 63,
+                "Invalid spectral selection: {spectral_start}..={spectral_end}"
+            );
+            ensure!(
+                (spectral_start == 0) == (spectral_end == 0),
+                "Progressive scans hold either DC or AC coefficients."
+            );
+            ensure!(
+                spectral_start == 0 || start_of_scan.components.len() == 1,
+                "Progressive AC scans must hold a single component."
+            );
+
+            match (
+                spectral_start,
+                start_of_scan.successive_approximation_high(),
+            ) {
+                (0, 0) => ScanKind::DcFirst,
+                (0, _) => ScanKind::DcRefine,
+                (_, 0) => ScanKind::AcFirst,
+                _ => ScanKind::AcRefine,
+            }
+        } else {
+            (spectral_start, spectral_end) = (1, 63);
+            ScanKind::Sequential
+        };
+
+        let tables = start_of_scan
+            .components
+            .iter()
+            .map(|&(_, selector)| {
+                let (dc, ac) = ((selector >> 4) as usize, (selector & 0b1111) as usize);
+                ensure!(
+                    dc < 4 && ac < 4,
+                    "Conditioning table identifier out of range."
+                );
+
+                Ok((dc, ac))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            decoder: QmDecoder::new(image_data),
+            kind,
+            spectral_start,
+            spectral_end,
+            point_transform: start_of_scan.successive_approximation_low(),
+            conditioning,
+            dc_stats: [[0; DC_STAT_BINS]; 4],
+            ac_stats: [[0; AC_STAT_BINS]; 4],
+            fixed_bin: FIXED_ESTIMATE,
+            predictors: vec![0; tables.len()],
+            dc_contexts: vec![0; tables.len()],
+            tables,
+        })
+    }
+
+    fn decode_dc(&mut self, scan_component: usize, block: &mut [i16; 64]) -> Result<()> {
+        let (dc, _) = self.tables[scan_component];
+
+        let difference = decode_difference(
+            &mut self.decoder,
+            &mut self.dc_stats[dc],
+            self.dc_contexts[scan_component],
+            20,
+        )?;
+
+        self.dc_contexts[scan_component] =
+            4 * classify(difference, self.conditioning.dc_bounds[dc]);
+
+        let predictor = &mut self.predictors[scan_component];
+        *predictor = (*predictor + difference) & 0xFFFF;
+        block[0] = (*predictor << self.point_transform) as u16 as i16;
+
+        Ok(())
+    }
+
+    fn decode_ac(&mut self, scan_component: usize, block: &mut [i16; 64]) -> Result<()> {
+        let (_, ac) = self.tables[scan_component];
+        let kx = self.conditioning.ac_kx[ac] as usize;
+        let stats = &mut self.ac_stats[ac];
+
+        let mut k = self.spectral_start;
+
+        while k <= self.spectral_end {
+            let mut st = 3 * (k - 1);
+
+            // End of block
+            if self.decoder.decode(&mut stats[st]) == 1 {
+                break;
+            }
+
+            while self.decoder.decode(&mut stats[st + 1]) == 0 {
+                st += 3;
+                k += 1;
+                ensure!(k <= self.spectral_end, "AC coefficient index out of range.");
+            }
+
+            let sign = self.decoder.decode(&mut self.fixed_bin);
+            st += 2;
+
+            let mut m = self.decoder.decode(&mut stats[st]) as i32;
+
+            if m != 0 && self.decoder.decode(&mut stats[st]) == 1 {
+                m <<= 1;
+                st = if k <= kx { 189 } else { 217 };
+
+                while self.decoder.decode(&mut stats[st]) == 1 {
+                    m <<= 1;
+                    ensure!(m != 0x8000, "AC magnitude category out of range.");
+                    st += 1;
+                }
+            }
+
+            let value = decode_magnitude(&mut self.decoder, stats, st + 14, m, sign);
+            block[ZIGZAG[k]] = (value << self.point_transform) as i16;
+
+            k += 1;
+        }
+
+        Ok(())
+    }
+
+    fn refine_ac(&mut self, scan_component: usize, block: &mut [i16; 64]) -> Result<()> {
+        let (_, ac) = self.tables[scan_component];
+        let stats = &mut self.ac_stats[ac];
+
+        let p1 = 1_i16 << self.point_transform;
+        let m1 = -1_i16 << self.point_transform;
+
+        // The end of block of the previous stages
+        let eob = (1..=self.spectral_end)
+            .rev()
+            .find(|&k| block[ZIGZAG[k]] != 0)
+            .unwrap_or(0);
+
+        let mut k = self.spectral_start;
+
+        while k <= self.spectral_end {
+            let mut st = 3 * (k - 1);
+
+            if k > eob && self.decoder.decode(&mut stats[st]) == 1 {
+                break;
+            }
+
+            loop {
+                let coefficient = &mut block[ZIGZAG[k]];
+
+                if *coefficient != 0 {
+                    if self.decoder.decode(&mut stats[st + 2]) == 1 {
+                        *coefficient += if *coefficient < 0 { m1 } else { p1 };
+                    }
+
+                    break;
+                }
+
+                if self.decoder.decode(&mut stats[st + 1]) == 1 {
+                    *coefficient = if self.decoder.decode(&mut self.fixed_bin) == 1 {
+                        m1
+                    } else {
+                        p1
+                    };
+
+                    break;
+                }
+
+                st += 3;
+                k += 1;
+                ensure!(k <= self.spectral_end, "AC coefficient index out of range.");
+            }
+
+            k += 1;
+        }
+
+        Ok(())
+    }
+}
+
+impl BlockDecoder for ArithmeticDecoder<'_> {
+    fn restart(&mut self, restart_index: u8) -> Result<()> {
+        self.decoder.reset(restart_index)?;
+
+        self.dc_stats = [[0; DC_STAT_BINS]; 4];
+        self.ac_stats = [[0; AC_STAT_BINS]; 4];
+        self.predictors.fill(0);
+        self.dc_contexts.fill(0);
+
+        Ok(())
+    }
+
+    fn decode_block(&mut self, scan_component: usize, block: &mut [i16; 64]) -> Result<()> {
+        match self.kind {
+            ScanKind::Sequential => {
+                self.decode_dc(scan_component, block)?;
+                self.decode_ac(scan_component, block)
+            }
+            ScanKind::DcFirst => self.decode_dc(scan_component, block),
+            ScanKind::DcRefine => {
+                if self.decoder.decode(&mut self.fixed_bin) == 1 {
+                    block[0] |= 1 << self.point_transform;
+                }
+
+                Ok(())
+            }
+            ScanKind::AcFirst => self.decode_ac(scan_component, block),
+            ScanKind::AcRefine => self.refine_ac(scan_component, block),
+        }
+    }
+}
+
+/// Decodes the differences of an arithmetic-coded lossless scan (H.1.4.3), conditioned on the
+/// differences to the left and above.
+#[derive(Debug)]
+pub struct ArithmeticDifferenceDecoder<'a> {
+    decoder: QmDecoder<'a>,
+    tables: Vec<usize>,
+    conditioning: ArithmeticConditioning,
+    stats: [[u8; LOSSLESS_STAT_BINS]; 4],
+}
+
+impl<'a> ArithmeticDifferenceDecoder<'a> {
+    pub fn new(
+        image_data: &'a [u8],
+        start_of_scan: &StartOfScan,
+        conditioning: ArithmeticConditioning,
+    ) -> Result<Self> {
+        let tables = start_of_scan
+            .components
+            .iter()
+            .map(|&(_, selector)| {
+                let table = (selector >> 4) as usize;
+                ensure!(table < 4, "Conditioning table identifier out of range.");
+
+                Ok(table)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            decoder: QmDecoder::new(image_data),
+            tables,
+            conditioning,
+            stats: [[0; LOSSLESS_STAT_BINS]; 4],
+        })
+    }
+}
+
+impl DifferenceDecoder for ArithmeticDifferenceDecoder<'_> {
+    fn restart(&mut self, restart_index: u8) -> Result<()> {
+        self.decoder.reset(restart_index)?;
+        self.stats = [[0; LOSSLESS_STAT_BINS]; 4];
+
+        Ok(())
+    }
+
+    fn decode_difference(&mut self, scan_component: usize, left: i32, above: i32) -> Result<i32> {
+        let table = self.tables[scan_component];
+        let bounds = self.conditioning.dc_bounds[table];
+
+        // The 5x5 conditioning array of figure H.2, four bins per state, followed by two sets of
+        // magnitude bins selected by the difference above.
+        let (left, above) = (classify(left, bounds), classify(above, bounds));
+        let x1 = if above >= 3 { 129 } else { 100 };
+
+        decode_difference(
+            &mut self.decoder,
+            &mut self.stats[table],
+            20 * left + 4 * above,
+            x1,
+        )
+    }
+}
@@ -0,0 +1,178 @@
+#![allow(clippy::suboptimal_flops)]
+
+use crate::{
+    image::grammar::ColorType,
+    jpeg::grammar::{Jpeg, JpegColorSpace},
+};
+use anyhow::{ensure, Result};
+
+/// The reconstructed samples of one component, at the component's own resolution.
+#[derive(Debug)]
+pub struct Plane {
+    pub width: usize,
+    pub height: usize,
+    pub horizontal_sampling_factor: u8,
+    pub vertical_sampling_factor: u8,
+    pub samples: Vec<u16>,
+}
+
+/// Where an output pixel samples a subsampled plane: the two neighbouring source indices and the
+/// weight of the second one. Samples are centered, so 2x upsampling reproduces the 3/4, 1/4
+/// triangle filter used by libjpeg.
+fn sample_positions(output_len: usize, source_len: usize, ratio: f32) -> Vec<(usize, usize, f32)> {
+    (0..output_len)
+        .map(|i| {
+            let position = ((i as f32 + 0.5) * ratio - 0.5).max(0.0);
+            let i0 = (position as usize).min(source_len - 1);
+            let i1 = (i0 + 1).min(source_len - 1);
+
+            (i0, i1, position - i0 as f32)
+        })
+        .collect()
+}
+
+/// Upsamples each plane to the full image resolution row by row and converts the samples to
+/// grayscale or RGB, with either 8 or 16 bits per output sample.
+#[allow(clippy::too_many_arguments)]
+pub fn convert_planes(
+    planes: &[Plane],
+    color_space: JpegColorSpace,
+    inverted_cmyk: bool,
+    width: usize,
+    height: usize,
+    max_horizontal_sampling_factor: u8,
+    max_vertical_sampling_factor: u8,
+    sample_precision: u8,
+    bit_depth: u8,
+) -> Result<Jpeg> {
+    let expected_planes = match color_space {
+        JpegColorSpace::Grayscale => 1,
+        JpegColorSpace::YCbCr | JpegColorSpace::RGB => 3,
+        JpegColorSpace::CMYK | JpegColorSpace::YCCK => 4,
+    };
+
+    ensure!(
+        planes.len() == expected_planes,
+        "Expected {} components for {:?}, found {}",
+        expected_planes,
+        color_space,
+        planes.len()
+    );
+
+    let (color_type, num_channels) = match color_space {
+        JpegColorSpace::Grayscale => (ColorType::Grayscale, 1),
+        _ => (ColorType::RGB, 3),
+    };
+
+    let bit_depth = bit_depth as usize;
+    let max = ((1_u32 << sample_precision) - 1) as f32;
+    let half = (1_u32 << (sample_precision - 1)) as f32;
+
+    let positions = planes
+        .iter()
+        .map(|plane| {
+            let x_ratio =
+                plane.horizontal_sampling_factor as f32 / max_horizontal_sampling_factor as f32;
+            let y_ratio =
+                plane.vertical_sampling_factor as f32 / max_vertical_sampling_factor as f32;
+
+            (
+                sample_positions(width, plane.width, x_ratio),
+                sample_positions(height, plane.height, y_ratio),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut rows = vec![vec![0.0_f32; width]; planes.len()];
+    let mut pixel_buffer = Vec::with_capacity(width * height * num_channels * bit_depth / 8);
+
+    let mut push_sample = |value: f32| {
+        let value = value.clamp(0.0, max);
+
+        if bit_depth == 8 {
+            pixel_buffer.push(value.round() as u8);
+        } else {
+            // Scaled in double precision so that lossless samples map exactly.
+            let value = (value as f64 * 65535.0 / max as f64).round() as u16;
+            pixel_buffer.extend_from_slice(&value.to_be_bytes());
+        }
+    };
+
+    for y in 0..height {
+        for ((plane, (x_positions, y_positions)), row) in
+            planes.iter().zip(&positions).zip(rows.iter_mut())
+        {
+            let (y0, y1, fy) = y_positions[y];
+            let (line0, line1) = (
+                &plane.samples[y0 * plane.width..],
+                &plane.samples[y1 * plane.width..],
+            );
+
+            for (out, &(x0, x1, fx)) in row.iter_mut().zip(x_positions) {
+                let top = line0[x0] as f32 * (1.0 - fx) + line0[x1] as f32 * fx;
+                let bottom = line1[x0] as f32 * (1.0 - fx) + line1[x1] as f32 * fx;
+
+                *out = top * (1.0 - fy) + bottom * fy;
+            }
+        }
+
+        #[allow(clippy::needless_range_loop)]
+        for x in 0..width {
+            match color_space {
+                JpegColorSpace::Grayscale => push_sample(rows[0][x]),
+                JpegColorSpace::RGB => {
+                    push_sample(rows[0][x]);
+                    push_sample(rows[1][x]);
+                    push_sample(rows[2][x]);
+                }
+                JpegColorSpace::YCbCr => {
+                    let [r, g, b] = ycbcr_to_rgb(rows[0][x], rows[1][x], rows[2][x], half);
+
+                    push_sample(r);
+                    push_sample(g);
+                    push_sample(b);
+                }
+                JpegColorSpace::CMYK | JpegColorSpace::YCCK => {
+                    let inks = if color_space == JpegColorSpace::YCCK {
+                        // YCCK encodes (max - C, max - M, max - Y) as YCbCr.
+                        ycbcr_to_rgb(rows[0][x], rows[1][x], rows[2][x], half)
+                            .map(|s| max - s.clamp(0.0, max))
+                    } else {
+                        [rows[0][x], rows[1][x], rows[2][x]]
+                    };
+
+                    let k = rows[3][x];
+
+                    for ink in inks {
+                        push_sample(if inverted_cmyk {
+                            ink * k / max
+                        } else {
+                            (max - ink) * (max - k) / max
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(Jpeg {
+        width: width as u32,
+        height: height as u32,
+        bit_depth: bit_depth as u8,
+        color_type,
+        color_space,
+        icc_profile: None,
+        pixel_buffer,
+    })
+}
+
+#[inline]
+fn ycbcr_to_rgb(y: f32, cb: f32, cr: f32, half: f32) -> [f32; 3] {
+    let (cb, cr) = (cb - half, cr - half);
+
+    [
+        y + 1.402 * cr,
+        y - 0.344_136 * cb - 0.714_136 * cr,
+        y + 1.772 * cb,
+    ]
+}
@@ -0,0 +1,364 @@
+#![allow(clippy::suboptimal_flops)]
+
+use crate::{
+    image::{ImageBuffer, Rgba8},
+    jpeg::grammar::{
+        ApplicationHeader, ComponentCoefficients, JpegCoefficients, QuantizationTable, ZIGZAG,
+    },
+};
+use anyhow::{ensure, Result};
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+/// The example luminance quantization table for a quality of 50 (K.1), in natural order.
+#[rustfmt::skip]
+const LUMINANCE_TABLE: [u16; 64] = [
+    16, 11, 10, 16,  24,  40,  51,  61,
+    12, 12, 14, 19,  26,  58,  60,  55,
+    14, 13, 16, 24,  40,  57,  69,  56,
+    14, 17, 22, 29,  51,  87,  80,  62,
+    18, 22, 37, 56,  68, 109, 103,  77,
+    24, 35, 55, 64,  81, 104, 113,  92,
+    49, 64, 78, 87, 103, 121, 120, 101,
+    72, 92, 95, 98, 112, 100, 103,  99,
+];
+
+/// The example chrominance quantization table for a quality of 50 (K.1), in natural order.
+#[rustfmt::skip]
+const CHROMINANCE_TABLE: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99,
+    18, 21, 26, 66, 99, 99, 99, 99,
+    24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+pub const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+/// How a new JPEG is compressed: its quality, from 1 to 100 like libjpeg's, and whether the
+/// chroma is subsampled by two in both directions (4:2:0).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JpegOptions {
+    pub quality: u8,
+    pub subsample_chroma: bool,
+}
+
+impl Default for JpegOptions {
+    fn default() -> Self {
+        Self {
+            quality: DEFAULT_JPEG_QUALITY,
+            subsample_chroma: true,
+        }
+    }
+}
+
+impl JpegOptions {
+    pub const fn with_quality(mut self, quality: u8) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    pub const fn with_subsample_chroma(mut self, subsample_chroma: bool) -> Self {
+        self.subsample_chroma = subsample_chroma;
+        self
+    }
+}
+
+impl JpegCoefficients {
+    /// Transforms and quantizes an sRGB image into baseline coefficients, ready for
+    /// [`crate::jpeg::JpegEncoder`]. Gray images get a single component. JPEGs have no alpha,
+    /// so it's ignored; flatten translucent images first.
+    pub fn from_image(image: &ImageBuffer<Rgba8>, options: JpegOptions) -> Result<Self> {
+        ensure!(
+            (1..=100).contains(&options.quality),
+            "The JPEG quality must be between 1 and 100."
+        );
+
+        let (width, height) = image.dimensions();
+        ensure!(
+            (1..=u32::from(u16::MAX)).contains(&width)
+                && (1..=u32::from(u16::MAX)).contains(&height),
+            "JPEGs must be between 1 and 65535 pixels wide and tall."
+        );
+
+        let gray = image
+            .pixels()
+            .iter()
+            .all(|&Rgba8([r, g, b, _])| r == g && g == b);
+
+        // The color conversion of JFIF (section 7).
+        let plane = |f: fn(f32, f32, f32) -> f32| {
+            image
+                .pixels()
+                .iter()
+                .map(|&Rgba8([r, g, b, _])| f(r as f32, g as f32, b as f32))
+                .collect::<Vec<_>>()
+        };
+        let luma = plane(|r, g, b| 0.299 * r + 0.587 * g + 0.114 * b);
+
+        let width = width as usize;
+        let height = height as usize;
+
+        let quantization_tables = if gray {
+            vec![quantization_table(0, &LUMINANCE_TABLE, options.quality)]
+        } else {
+            vec![
+                quantization_table(0, &LUMINANCE_TABLE, options.quality),
+                quantization_table(1, &CHROMINANCE_TABLE, options.quality),
+            ]
+        };
+        let tables = quantization_tables
+            .iter()
+            .map(QuantizationTable::natural_order)
+            .collect::<Vec<_>>();
+
+        let components = if gray {
+            // Non-interleaved scans cover whole blocks, so single components need no MCU padding.
+            vec![component(
+                1,
+                (1, 1),
+                0,
+                &luma,
+                (width, height),
+                (1, 1),
+                &tables[0],
+            )]
+        } else {
+            let cb = plane(|r, g, b| -0.168_736 * r - 0.331_264 * g + 0.5 * b + 128.0);
+            let cr = plane(|r, g, b| 0.5 * r - 0.418_688 * g - 0.081_312 * b + 128.0);
+
+            let (luma_factors, mcu_size) = if options.subsample_chroma {
+                ((2, 2), 16)
+            } else {
+                ((1, 1), 8)
+            };
+            let mcus = (width.div_ceil(mcu_size), height.div_ceil(mcu_size));
+            let (cb, cr, chroma_dimensions) = if options.subsample_chroma {
+                (
+                    downsample(&cb, width, height),
+                    downsample(&cr, width, height),
+                    (width.div_ceil(2), height.div_ceil(2)),
+                )
+            } else {
+                (cb, cr, (width, height))
+            };
+
+            vec![
+                component(1, luma_factors, 0, &luma, (width, height), mcus, &tables[0]),
+                component(2, (1, 1), 1, &cb, chroma_dimensions, mcus, &tables[1]),
+                component(3, (1, 1), 1, &cr, chroma_dimensions, mcus, &tables[1]),
+            ]
+        };
+
+        Ok(Self {
+            width: width as u16,
+            height: height as u16,
+            sample_precision: 8,
+            application_header: Some(ApplicationHeader {
+                version: (1, 1),
+                unit: 0,
+                density: (1, 1),
+                thumbnail: (0, 0),
+            }),
+            adobe_header: None,
+            icc_profile: None,
+            quantization_tables,
+            components,
+        })
+    }
+}
+
+/// Scales an example table to `quality` like libjpeg does, storing it in zigzag order.
+fn quantization_table(identifier: u8, table: &[u16; 64], quality: u8) -> QuantizationTable {
+    let quality = u32::from(quality);
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - 2 * quality
+    };
+
+    let mut table_elements = [0; QuantizationTable::NUM_ELEMENTS];
+
+    for (k, element) in table_elements.iter_mut().enumerate() {
+        *element = ((u32::from(table[ZIGZAG[k]]) * scale + 50) / 100).clamp(1, 255) as u16;
+    }
+
+    QuantizationTable {
+        flag: identifier,
+        table_elements,
+    }
+}
+
+/// Averages each 2x2 square of a plane, repeating the last row and column of odd dimensions.
+fn downsample(plane: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let sample = |x: usize, y: usize| plane[y.min(height - 1) * width + x.min(width - 1)];
+
+    (0..height.div_ceil(2))
+        .flat_map(|y| {
+            (0..width.div_ceil(2)).map(move |x| {
+                let (x, y) = (2 * x, 2 * y);
+                (sample(x, y) + sample(x + 1, y) + sample(x, y + 1) + sample(x + 1, y + 1)) / 4.0
+            })
+        })
+        .collect()
+}
+
+/// Transforms a plane into the blocks of one component, padded to whole MCUs by repeating
+/// its last row and column (A.2.4).
+fn component(
+    identifier: u8,
+    (horizontal_sampling_factor, vertical_sampling_factor): (u8, u8),
+    quantization_table_selector: u8,
+    plane: &[f32],
+    (width, height): (usize, usize),
+    (mcus_x, mcus_y): (usize, usize),
+    quantization_table: &[u16; 64],
+) -> ComponentCoefficients {
+    let blocks_per_line = (mcus_x * horizontal_sampling_factor as usize).max(width.div_ceil(8));
+    let blocks_per_column = (mcus_y * vertical_sampling_factor as usize).max(height.div_ceil(8));
+
+    let fdct = Fdct::new();
+    let mut blocks = Vec::with_capacity(blocks_per_line * blocks_per_column);
+
+    for block_y in 0..blocks_per_column {
+        for block_x in 0..blocks_per_line {
+            let mut samples = [0.0; 64];
+
+            for (i, sample) in samples.iter_mut().enumerate() {
+                let x = (block_x * 8 + i % 8).min(width - 1);
+                let y = (block_y * 8 + i / 8).min(height - 1);
+                *sample = plane[y * width + x];
+            }
+
+            blocks.push(fdct.transform(&samples, quantization_table));
+        }
+    }
+
+    ComponentCoefficients {
+        identifier,
+        horizontal_sampling_factor,
+        vertical_sampling_factor,
+        quantization_table_selector,
+        blocks_per_line,
+        blocks_per_column,
+        blocks,
+    }
+}
+
+/// A separable floating point forward DCT (A.3.3), the transpose of [`super::idct::Idct`].
+struct Fdct {
+    // basis[x][u] = C(u) / 2 * cos((2x + 1)uπ / 16)
+    basis: [[f32; 8]; 8],
+}
+
+impl Fdct {
+    fn new() -> Self {
+        let mut basis = [[0.0; 8]; 8];
+
+        for (x, row) in basis.iter_mut().enumerate() {
+            for (u, b) in row.iter_mut().enumerate() {
+                let c = if u == 0 { FRAC_1_SQRT_2 } else { 1.0 };
+                *b = c / 2.0 * ((2 * x + 1) as f32 * u as f32 * PI / 16.0).cos();
+            }
+        }
+
+        Self { basis }
+    }
+
+    /// Level shifts 8-bit `samples`, transforms them and quantizes the coefficients with
+    /// `quantization_table`, all in natural order.
+    fn transform(&self, samples: &[f32; 64], quantization_table: &[u16; 64]) -> [i16; 64] {
+        // Rows: tmp[y][u] = Σ_x f[y][x] basis[x][u]
+        let mut tmp = [0.0_f32; 64];
+
+        for y in 0..8 {
+            for u in 0..8 {
+                tmp[y * 8 + u] = (0..8)
+                    .map(|x| (samples[y * 8 + x] - 128.0) * self.basis[x][u])
+                    .sum();
+            }
+        }
+
+        // Columns: F[v][u] = Σ_y tmp[y][u] basis[y][v]
+        let mut block = [0; 64];
+
+        for v in 0..8 {
+            for u in 0..8 {
+                let coefficient = (0..8)
+                    .map(|y| tmp[y * 8 + u] * self.basis[y][v])
+                    .sum::<f32>();
+
+                block[v * 8 + u] =
+                    (coefficient / quantization_table[v * 8 + u] as f32).round() as i16;
+            }
+        }
+
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::grammar::ImageExt,
+        jpeg::{JpegDecoder, JpegEncoder},
+        metrics::psnr,
+    };
+
+    fn encode(image: &ImageBuffer<Rgba8>, options: JpegOptions) -> Result<Vec<u8>> {
+        let mut encoded = Vec::new();
+        JpegEncoder::new(&mut encoded).encode(&JpegCoefficients::from_image(image, options)?)?;
+
+        Ok(encoded)
+    }
+
+    #[test]
+    fn test_compress_round_trip() -> Result<()> {
+        // Odd dimensions exercise the padding of partial MCUs.
+        let image = ImageBuffer::from_fn(37, 21, |x, y| {
+            Rgba8([(x * 6) as u8, (y * 12) as u8, ((x + y) * 4) as u8, 255])
+        });
+
+        for options in [
+            JpegOptions::default(),
+            JpegOptions::default()
+                .with_quality(95)
+                .with_subsample_chroma(false),
+        ] {
+            let jpeg = JpegDecoder::new(&encode(&image, options)?).decode()?;
+
+            assert_eq!(jpeg.dimensions(), image.dimensions());
+            assert!(psnr(&image, &jpeg)? > 35.0, "{options:?}");
+        }
+
+        // Lower qualities quantize harder.
+        let small = encode(&image, JpegOptions::default().with_quality(10))?;
+        assert!(small.len() < encode(&image, JpegOptions::default())?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compress_gray() -> Result<()> {
+        let image = ImageBuffer::from_fn(16, 9, |x, y| {
+            let v = (x * 16 + y) as u8;
+            Rgba8([v, v, v, 255])
+        });
+        let coefficients = JpegCoefficients::from_image(&image, JpegOptions::default())?;
+        assert_eq!(coefficients.components().len(), 1);
+
+        let mut encoded = Vec::new();
+        JpegEncoder::new(&mut encoded).encode(&coefficients)?;
+        let jpeg = JpegDecoder::new(&encoded).decode()?;
+        assert!(psnr(&image, &jpeg)? > 35.0);
+
+        assert!(
+            JpegCoefficients::from_image(&image, JpegOptions::default().with_quality(0)).is_err()
+        );
+
+        Ok(())
+    }
+}
@@ -1,93 +1,531 @@
 use crate::{
     impl_read_for_datatype, impl_read_slice,
-    jpeg::grammar::{
-        ApplicationHeader, Component, EncodingProcess, HuffmanTable, Jpeg, Marker, Precision,
-        QuantizationTable, StartOfFrame, StartOfScan, JFIF,
+    jpeg::{
+        arithmetic::{ArithmeticDecoder, ArithmeticDifferenceDecoder},
+        color_convert::{convert_planes, Plane},
+        grammar::{
+            AdobeHeader, ApplicationHeader, ArithmeticConditioning, Component,
+            ComponentCoefficients, EncodingProcess, HuffmanTable, Jpeg, JpegCoefficients,
+            JpegScale, Marker, Precision, QuantizationTable, Scan, StartOfFrame, StartOfScan, JFIF,
+        },
+        huffman::HuffmanTables,
+        idct::Idct,
+        lossless::{self, HuffmanDifferenceDecoder},
+        scan::decode_blocks,
+        sequential::SequentialDecoder,
     },
 };
 
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{anyhow, bail, ensure, Result};
 use std::ops::RangeInclusive;
 
 #[derive(Debug)]
 pub struct JpegDecoder<'a> {
     cursor: usize,
     data: &'a [u8],
+    scale: JpegScale,
 }
 
 impl<'a> JpegDecoder<'a> {
     pub const fn new(data: &'a [u8]) -> Self {
-        Self { cursor: 0, data }
+        Self {
+            cursor: 0,
+            data,
+            scale: JpegScale::Full,
+        }
+    }
+
+    /// Downscales DCT-based images by inverse transforming only the low frequency coefficients
+    /// of each block. Lossless images have no coefficients and are always decoded at full size.
+    pub const fn with_scale(mut self, scale: JpegScale) -> Self {
+        self.scale = scale;
+        self
     }
 
     pub fn decode(&mut self) -> Result<Jpeg> {
-        let _jfif = self.parse_jfif()?;
+        let jfif = self.parse_jfif()?;
+        let start_of_frame = &jfif.start_of_frame;
+
+        let (planes, bit_depth, scale) = if start_of_frame.encoding_process.is_lossless() {
+            (Self::decode_lossless(&jfif)?, 16, JpegScale::Full)
+        } else {
+            let coefficients = Self::decode_scans(&jfif)?;
+            let bit_depth = if start_of_frame.sample_precision == 8 {
+                8
+            } else {
+                16
+            };
+
+            let planes = Self::reconstruct_planes(&jfif, &coefficients, self.scale)?;
+
+            (planes, bit_depth, self.scale)
+        };
+
+        let mut jpeg = convert_planes(
+            &planes,
+            jfif.color_space()?,
+            jfif.adobe_header.is_some(),
+            (start_of_frame.samples_per_line as usize).div_ceil(scale.denominator()),
+            (start_of_frame.lines as usize).div_ceil(scale.denominator()),
+            start_of_frame.max_horizontal_sampling_factor(),
+            start_of_frame.max_vertical_sampling_factor(),
+            start_of_frame.sample_precision,
+            bit_depth,
+        )?;
 
-        dbg!(_jfif);
+        jpeg.icc_profile = jfif.icc_profile;
 
-        todo!();
+        Ok(jpeg)
+    }
+
+    /// Reads markers up to the start of frame, without touching any scans. Frames [`Self::decode`]
+    /// can't decode are rejected here too, so probing and decoding agree.
+    pub fn decode_header(&mut self) -> Result<StartOfFrame> {
+        ensure!(self.read_marker()? == 0xFFD8, "Expected start of image.");
+
+        loop {
+            match self.next_marker()? {
+                start_of_frame_marker @ (0xFFC0..=0xFFC3
+                | 0xFFC5..=0xFFC7
+                | 0xFFC9..=0xFFCB
+                | 0xFFCD..=0xFFCF) => {
+                    let start_of_frame = self.parse_start_of_frame(start_of_frame_marker as u8)?;
+                    Self::ensure_supported(&start_of_frame)?;
+
+                    return Ok(start_of_frame);
+                }
+                0xFFD9 | 0xFFDA => bail!("Expected start of frame before any scan."),
+                _ => self.skip_segment()?,
+            }
+        }
+    }
+
+    /// Reads the quantized DCT coefficients without reconstructing any samples, so they can be
+    /// transformed and encoded again without loss.
+    pub fn decode_coefficients(&mut self) -> Result<JpegCoefficients> {
+        let jfif = self.parse_jfif()?;
+        let start_of_frame = &jfif.start_of_frame;
+
+        ensure!(
+            !start_of_frame.encoding_process.is_lossless(),
+            "Lossless JPEGs have no DCT coefficients."
+        );
+
+        let components = Self::decode_scans(&jfif)?;
+
+        let mut quantization_tables = Vec::new();
+
+        for component in &components {
+            let selector = component.quantization_table_selector;
+
+            if quantization_tables
+                .iter()
+                .any(|qt: &QuantizationTable| qt.table_identifier() == selector)
+            {
+                continue;
+            }
+
+            let quantization_table = jfif
+                .quantization_table(selector)
+                .ok_or_else(|| anyhow!("Missing quantization table {selector}"))?;
+
+            quantization_tables.push(quantization_table.clone());
+        }
+
+        Ok(JpegCoefficients {
+            width: start_of_frame.samples_per_line,
+            height: start_of_frame.lines,
+            sample_precision: start_of_frame.sample_precision,
+            application_header: jfif.application_header,
+            adobe_header: jfif.adobe_header,
+            icc_profile: jfif.icc_profile,
+            quantization_tables,
+            components,
+        })
+    }
+
+    /// Checks that the frame's encoding process and sample precision are ones the decoder
+    /// handles. Huffman-coded progressive frames aren't.
+    fn ensure_supported(start_of_frame: &StartOfFrame) -> Result<()> {
+        match start_of_frame.encoding_process {
+            EncodingProcess::BaselineDCT => ensure!(
+                start_of_frame.sample_precision == 8,
+                "Baseline JPEGs must have a sample precision of 8 bits."
+            ),
+            EncodingProcess::HuffmanExtendedSequentialDCT
+            | EncodingProcess::ArithmeticExtendedSequentialDCT
+            | EncodingProcess::ArithmeticProgressiveDCT => ensure!(
+                matches!(start_of_frame.sample_precision, 8 | 12),
+                "Extended JPEGs must have a sample precision of 8 or 12 bits."
+            ),
+            EncodingProcess::HuffmanLossless | EncodingProcess::ArithmeticLossless => ensure!(
+                (2..=16).contains(&start_of_frame.sample_precision),
+                "Lossless JPEGs must have a sample precision between 2 and 16 bits."
+            ),
+            foreign => bail!("Unsupported encoding process: {:?}", foreign),
+        }
+
+        Ok(())
+    }
+
+    /// Entropy decodes every scan into the quantized DCT coefficients of each component.
+    fn decode_scans(jfif: &JFIF) -> Result<Vec<ComponentCoefficients>> {
+        let start_of_frame = &jfif.start_of_frame;
+
+        Self::ensure_supported(start_of_frame)?;
+
+        let (mcus_x, mcus_y) = start_of_frame.mcu_dimensions();
+
+        let mut coefficients = start_of_frame
+            .components
+            .iter()
+            .map(|component| {
+                let h = component.horizontal_sampling_factor();
+                let v = component.vertical_sampling_factor();
+
+                ensure!(
+                    (1..=4).contains(&h) && (1..=4).contains(&v),
+                    "Sampling factors must be between 1 and 4."
+                );
+
+                let blocks_per_line = mcus_x * h as usize;
+                let blocks_per_column = mcus_y * v as usize;
+
+                Ok(ComponentCoefficients {
+                    identifier: component.identifier,
+                    horizontal_sampling_factor: h,
+                    vertical_sampling_factor: v,
+                    quantization_table_selector: component.quantization_table_destination_selector,
+                    blocks_per_line,
+                    blocks_per_column,
+                    blocks: vec![[0; 64]; blocks_per_line * blocks_per_column],
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        ensure!(!jfif.scans.is_empty(), "Expected at least one scan.");
+
+        for scan in &jfif.scans {
+            if start_of_frame.encoding_process.is_arithmetic() {
+                let mut decoder = ArithmeticDecoder::new(
+                    scan.image_data,
+                    &scan.start_of_scan,
+                    scan.arithmetic_conditioning,
+                    start_of_frame.encoding_process.is_progressive(),
+                )?;
+
+                decode_blocks(
+                    start_of_frame,
+                    &scan.start_of_scan,
+                    scan.restart_interval,
+                    &mut coefficients,
+                    &mut decoder,
+                )?;
+            } else {
+                let huffman_tables = HuffmanTables::new(&scan.huffman_tables)?;
+                let mut decoder = SequentialDecoder::new(
+                    scan.image_data,
+                    &huffman_tables,
+                    &scan.start_of_scan.components,
+                )?;
+
+                decode_blocks(
+                    start_of_frame,
+                    &scan.start_of_scan,
+                    scan.restart_interval,
+                    &mut coefficients,
+                    &mut decoder,
+                )?;
+            }
+        }
+
+        Ok(coefficients)
+    }
+
+    /// Decodes every scan of a lossless frame into planes of full precision samples.
+    fn decode_lossless(jfif: &JFIF) -> Result<Vec<Plane>> {
+        let start_of_frame = &jfif.start_of_frame;
+
+        Self::ensure_supported(start_of_frame)?;
+
+        let (mcus_x, mcus_y) = start_of_frame.mcu_dimensions();
+
+        let mut planes = start_of_frame
+            .components
+            .iter()
+            .map(|component| {
+                let h = component.horizontal_sampling_factor();
+                let v = component.vertical_sampling_factor();
+
+                ensure!(
+                    (1..=4).contains(&h) && (1..=4).contains(&v),
+                    "Sampling factors must be between 1 and 4."
+                );
+
+                let (width, height) = (mcus_x * h as usize, mcus_y * v as usize);
+
+                Ok(Plane {
+                    width,
+                    height,
+                    horizontal_sampling_factor: h,
+                    vertical_sampling_factor: v,
+                    samples: vec![0; width * height],
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        ensure!(!jfif.scans.is_empty(), "Expected at least one scan.");
+
+        for scan in &jfif.scans {
+            if start_of_frame.encoding_process.is_arithmetic() {
+                let mut decoder = ArithmeticDifferenceDecoder::new(
+                    scan.image_data,
+                    &scan.start_of_scan,
+                    scan.arithmetic_conditioning,
+                )?;
+
+                lossless::decode_scan(
+                    start_of_frame,
+                    &scan.start_of_scan,
+                    scan.restart_interval,
+                    &mut planes,
+                    &mut decoder,
+                )?;
+            } else {
+                let huffman_tables = HuffmanTables::new(&scan.huffman_tables)?;
+                let mut decoder = HuffmanDifferenceDecoder::new(
+                    scan.image_data,
+                    &huffman_tables,
+                    &scan.start_of_scan.components,
+                )?;
+
+                lossless::decode_scan(
+                    start_of_frame,
+                    &scan.start_of_scan,
+                    scan.restart_interval,
+                    &mut planes,
+                    &mut decoder,
+                )?;
+            }
+        }
+
+        // Drop the padding of partial MCUs.
+        Ok(planes
+            .into_iter()
+            .zip(&start_of_frame.components)
+            .map(|(plane, component)| {
+                let (width, height) = start_of_frame.component_dimensions(component);
+
+                Plane {
+                    samples: plane
+                        .samples
+                        .chunks_exact(plane.width)
+                        .take(height)
+                        .flat_map(|row| &row[..width])
+                        .copied()
+                        .collect(),
+                    width,
+                    height,
+                    ..plane
+                }
+            })
+            .collect())
+    }
+
+    /// Dequantizes and inverse transforms the coefficients of each component, downscaled by
+    /// `scale`.
+    ///
+    /// Like libjpeg, subsampled components use a larger transform when downscaling, as far as
+    /// their sampling factors allow, so they don't lose more detail than the full resolution
+    /// components.
+    fn reconstruct_planes(
+        jfif: &JFIF,
+        coefficients: &[ComponentCoefficients],
+        scale: JpegScale,
+    ) -> Result<Vec<Plane>> {
+        let start_of_frame = &jfif.start_of_frame;
+        let max_sample = ((1_u32 << start_of_frame.sample_precision) - 1) as u16;
+
+        let h_max = start_of_frame.max_horizontal_sampling_factor() as usize;
+        let v_max = start_of_frame.max_vertical_sampling_factor() as usize;
+
+        coefficients
+            .iter()
+            .map(|component| {
+                let quantization_table = jfif
+                    .quantization_table(component.quantization_table_selector)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Missing quantization table {}",
+                            component.quantization_table_selector
+                        )
+                    })?
+                    .natural_order();
+
+                let h = component.horizontal_sampling_factor as usize;
+                let v = component.vertical_sampling_factor as usize;
+
+                let mut factor = 1;
+
+                while scale.block_size() * factor * 2 <= 8
+                    && h_max.is_multiple_of(h * factor * 2)
+                    && v_max.is_multiple_of(v * factor * 2)
+                {
+                    factor *= 2;
+                }
+
+                let block_size = scale.block_size() * factor;
+                let idct = Idct::new(block_size);
+
+                let width = component.blocks_per_line * block_size;
+                let height = component.blocks_per_column * block_size;
+                let mut samples = vec![0; width * height];
+
+                for y in 0..component.blocks_per_column {
+                    for x in 0..component.blocks_per_line {
+                        idct.transform(
+                            component.block(x, y),
+                            &quantization_table,
+                            max_sample,
+                            &mut samples[(y * width + x) * block_size..],
+                            width,
+                        );
+                    }
+                }
+
+                Ok(Plane {
+                    width,
+                    height,
+                    horizontal_sampling_factor: (h * factor) as u8,
+                    vertical_sampling_factor: (v * factor) as u8,
+                    samples,
+                })
+            })
+            .collect()
     }
 
     fn parse_jfif(&mut self) -> Result<JFIF<'a>> {
-        ensure!(self.read_marker()? == 0xFFD8);
+        ensure!(self.read_marker()? == 0xFFD8, "Expected start of image.");
 
         let mut application_header = None;
+        let mut adobe_header = None;
+        let mut icc_chunks = Vec::new();
         let mut quantization_tables = Vec::with_capacity(4);
-        let mut huffman_tables = Vec::new();
+        let mut huffman_tables: Vec<HuffmanTable> = Vec::new();
+        let mut arithmetic_conditioning = ArithmeticConditioning::default();
+        let mut restart_interval = 0;
         let mut start_of_frame = None;
-        let mut start_of_scan = None;
-        let mut image_data = None;
+        let mut scans = Vec::new();
 
         loop {
-            match self.read_marker()? {
+            match self.next_marker()? {
+                0xFFD9 => break,
                 0xFFE0 => {
-                    application_header = Some(self.parse_application_header()?);
+                    let header = self.parse_application_header()?;
+                    application_header = application_header.or(header);
+                }
+                0xFFEE => {
+                    let header = self.parse_adobe_header()?;
+                    adobe_header = adobe_header.or(header);
                 }
+                0xFFE2 => {
+                    icc_chunks.extend(self.parse_icc_chunk()?);
+                }
+                // Other application segments and comments
+                0xFFE1..=0xFFEF | 0xFFFE => self.skip_segment()?,
                 0xFFDB => {
-                    quantization_tables.push(self.parse_quantization_table()?);
+                    quantization_tables.extend(self.parse_quantization_tables()?);
                 }
                 0xFFC4 => {
-                    huffman_tables.push(self.parse_huffman_table()?);
+                    for table in self.parse_huffman_tables()? {
+                        huffman_tables.retain(|t| t.flag != table.flag);
+                        huffman_tables.push(table);
+                    }
+                }
+                0xFFDD => {
+                    restart_interval = self.parse_restart_interval()?;
                 }
                 0xFFDA => {
-                    ensure!(start_of_scan.is_none() && image_data.is_none());
-                    start_of_scan = Some(self.parse_start_of_scan()?);
-                    image_data = Some(self.parse_image_data()?);
+                    ensure!(
+                        start_of_frame.is_some(),
+                        "Expected start of frame before start of scan."
+                    );
 
-                    break;
+                    scans.push(Scan {
+                        start_of_scan: self.parse_start_of_scan()?,
+                        huffman_tables: huffman_tables.clone(),
+                        arithmetic_conditioning,
+                        restart_interval,
+                        image_data: self.parse_image_data()?,
+                    });
                 }
-                start_of_frame_marker
-                    if start_of_frame_marker >> 8 == 0xFF
-                        && (start_of_frame_marker as u8 & 0xF0) == 0xC0 =>
-                {
-                    ensure!(start_of_frame.is_none());
+                0xFFCC => {
+                    self.parse_arithmetic_conditioning(&mut arithmetic_conditioning)?;
+                }
+                start_of_frame_marker @ (0xFFC0..=0xFFC3
+                | 0xFFC5..=0xFFC7
+                | 0xFFC9..=0xFFCB
+                | 0xFFCD..=0xFFCF) => {
+                    ensure!(start_of_frame.is_none(), "Expected a single frame.");
                     start_of_frame = Some(self.parse_start_of_frame(start_of_frame_marker as u8)?);
                 }
-                foreign => unimplemented!("{:X}", foreign),
+                foreign => bail!("Unsupported marker: {:X}", foreign),
             };
         }
 
-        ensure!(self.read_marker()? == 0xFFD9);
+        // Profiles too large for one segment are split into numbered chunks.
+        icc_chunks.sort_by_key(|&(sequence_number, _)| sequence_number);
+        let icc_profile = (!icc_chunks.is_empty()).then(|| {
+            icc_chunks
+                .iter()
+                .flat_map(|&(_, chunk)| chunk)
+                .copied()
+                .collect()
+        });
 
         Ok(JFIF {
-            application_header: application_header
-                .ok_or_else(|| anyhow!("expected application header"))?,
+            application_header,
+            adobe_header,
+            icc_profile,
             quantization_tables,
-            huffman_tables: {
-                ensure!(huffman_tables.len() == 4);
-                huffman_tables
-            },
             start_of_frame: start_of_frame.ok_or_else(|| anyhow!("expected start of frame"))?,
-            start_of_scan: start_of_scan.ok_or_else(|| anyhow!("expected start of scan"))?,
-            image_data: image_data.ok_or_else(|| anyhow!("expected image data"))?,
+            scans,
         })
     }
 
-    fn parse_application_header(&mut self) -> Result<ApplicationHeader> {
+    /// Reads the next marker, skipping any fill bytes that precede it.
+    fn next_marker(&mut self) -> Result<Marker> {
+        while self.peek_slice(2)? == [0xFF, 0xFF] {
+            self.cursor += 1;
+        }
+
+        let marker = self.read_marker()?;
+        ensure!(marker >> 8 == 0xFF, "Expected a marker, found {:X}", marker);
+
+        Ok(marker)
+    }
+
+    fn skip_segment(&mut self) -> Result<()> {
+        let length = self.read_u16()? as usize;
+        ensure!(length >= 2, "Invalid segment length.");
+
+        self.read_slice(length - 2)?;
+
+        Ok(())
+    }
+
+    fn parse_application_header(&mut self) -> Result<Option<ApplicationHeader>> {
         let offset = self.cursor;
         let length = self.read_u16()? as usize;
 
-        ensure!(self.read_slice(5)? == b"JFIF\0");
+        if self.peek_slice(5)? != b"JFIF\0" {
+            self.cursor = offset;
+            self.skip_segment()?;
+
+            return Ok(None);
+        }
+
+        self.read_slice(5)?;
 
         let app_header = ApplicationHeader {
             version: (self.read_u8()?, self.read_u8()?),
@@ -96,34 +534,89 @@ impl<'a> JpegDecoder<'a> {
             thumbnail: (self.read_u8()?, self.read_u8()?),
         };
 
-        ensure!(self.cursor == offset + length);
+        // Skip the thumbnail, if any.
+        ensure!(self.cursor <= offset + length);
+        self.cursor = offset + length;
 
-        Ok(app_header)
+        Ok(Some(app_header))
     }
 
-    fn parse_quantization_table(&mut self) -> Result<QuantizationTable> {
+    /// Reads one chunk of an ICC profile and its sequence number from an APP2 segment.
+    fn parse_icc_chunk(&mut self) -> Result<Option<(u8, &'a [u8])>> {
         let offset = self.cursor;
         let length = self.read_u16()? as usize;
 
-        let flag = self.read_u8()?;
+        if length < 16 || self.peek_slice(12)? != b"ICC_PROFILE\0" {
+            self.cursor = offset;
+            self.skip_segment()?;
+
+            return Ok(None);
+        }
 
-        let precision = Precision::from((flag >> 4) == 1);
+        self.read_slice(12)?;
+        let sequence_number = self.read_u8()?;
+        let _chunk_count = self.read_u8()?;
 
-        ensure!(
-            self.cursor + (precision as usize * QuantizationTable::NUM_ELEMENTS) == offset + length
-        );
+        Ok(Some((sequence_number, self.read_slice(length - 16)?)))
+    }
 
-        let table_elements = match precision {
-            Precision::Eight => {
-                self.read_fixed_array::<64, _>(|this| this.read_u8().map(|b| b as u16))?
-            }
-            Precision::Sixteen => self.read_fixed_array::<64, _>(Self::read_u16)?,
+    fn parse_adobe_header(&mut self) -> Result<Option<AdobeHeader>> {
+        let offset = self.cursor;
+        let length = self.read_u16()? as usize;
+
+        if length < 14 || self.peek_slice(5)? != b"Adobe" {
+            self.cursor = offset;
+            self.skip_segment()?;
+
+            return Ok(None);
+        }
+
+        self.read_slice(5)?;
+
+        let adobe_header = AdobeHeader {
+            version: self.read_u16()?,
+            flags0: self.read_u16()?,
+            flags1: self.read_u16()?,
+            transform: self.read_u8()?,
         };
 
-        Ok(QuantizationTable {
-            flag,
-            table_elements,
-        })
+        self.cursor = offset + length;
+
+        Ok(Some(adobe_header))
+    }
+
+    fn parse_quantization_tables(&mut self) -> Result<Vec<QuantizationTable>> {
+        let offset = self.cursor;
+        let length = self.read_u16()? as usize;
+
+        let mut tables = Vec::new();
+
+        while self.cursor < offset + length {
+            let flag = self.read_u8()?;
+
+            let precision = Precision::from((flag >> 4) == 1);
+
+            ensure!(
+                self.cursor + (precision as usize * QuantizationTable::NUM_ELEMENTS)
+                    <= offset + length
+            );
+
+            let table_elements = match precision {
+                Precision::Eight => {
+                    self.read_fixed_array::<64, _>(|this| this.read_u8().map(|b| b as u16))?
+                }
+                Precision::Sixteen => self.read_fixed_array::<64, _>(Self::read_u16)?,
+            };
+
+            tables.push(QuantizationTable {
+                flag,
+                table_elements,
+            });
+        }
+
+        ensure!(self.cursor == offset + length);
+
+        Ok(tables)
     }
 
     fn parse_start_of_frame(&mut self, start_of_frame: u8) -> Result<StartOfFrame> {
@@ -144,6 +637,10 @@ impl<'a> JpegDecoder<'a> {
         };
 
         ensure!(self.cursor == offset + length as usize);
+        ensure!(
+            start_of_frame.lines > 0 && start_of_frame.samples_per_line > 0,
+            "Image dimensions must be non-zero."
+        );
 
         Ok(start_of_frame)
     }
@@ -156,24 +653,72 @@ impl<'a> JpegDecoder<'a> {
         })
     }
 
-    fn parse_huffman_table(&mut self) -> Result<HuffmanTable> {
+    fn parse_huffman_tables(&mut self) -> Result<Vec<HuffmanTable>> {
         let offset = self.cursor;
         let length = self.read_u16()? as usize;
 
-        let flag = self.read_u8()?;
-        let code_lengths = self.read_fixed_array::<16, _>(Self::read_u8)?;
-        let num_values = code_lengths.iter().sum::<u8>();
-        let values = self.read_vec(num_values as usize, Self::read_u8)?;
+        let mut tables = Vec::new();
 
-        let ht = HuffmanTable {
-            flag,
-            code_lengths,
-            values,
-        };
+        while self.cursor < offset + length {
+            let flag = self.read_u8()?;
+            let code_lengths = self.read_fixed_array::<16, _>(Self::read_u8)?;
+            let num_values = code_lengths.iter().map(|&l| l as usize).sum::<usize>();
+            let values = self.read_vec(num_values, Self::read_u8)?;
+
+            tables.push(HuffmanTable {
+                flag,
+                code_lengths,
+                values,
+            });
+        }
+
+        ensure!(self.cursor == offset + length);
+
+        Ok(tables)
+    }
+
+    fn parse_arithmetic_conditioning(
+        &mut self,
+        conditioning: &mut ArithmeticConditioning,
+    ) -> Result<()> {
+        let offset = self.cursor;
+        let length = self.read_u16()? as usize;
+
+        while self.cursor < offset + length {
+            let flag = self.read_u8()?;
+            let value = self.read_u8()?;
+
+            let id = (flag & 0b1111) as usize;
+            ensure!(id < 4, "Conditioning table identifier out of range: {id}");
+
+            match flag >> 4 {
+                0 => {
+                    let (lower, upper) = (value & 0b1111, value >> 4);
+                    ensure!(lower <= upper, "Invalid DC conditioning bounds.");
+
+                    conditioning.dc_bounds[id] = (lower, upper);
+                }
+                1 => {
+                    ensure!(
+                        (1..=63).contains(&value),
+                        "Invalid AC conditioning: {value}"
+                    );
+
+                    conditioning.ac_kx[id] = value;
+                }
+                class => bail!("Invalid conditioning table class: {class}"),
+            }
+        }
 
         ensure!(self.cursor == offset + length);
 
-        Ok(ht)
+        Ok(())
+    }
+
+    fn parse_restart_interval(&mut self) -> Result<u16> {
+        ensure!(self.read_u16()? == 4, "Invalid restart interval length.");
+
+        self.read_u16()
     }
 
     fn parse_start_of_scan(&mut self) -> Result<StartOfScan> {
@@ -196,37 +741,357 @@ impl<'a> JpegDecoder<'a> {
         Ok(start_of_scan)
     }
 
+    /// Reads entropy-coded data up to the next marker that isn't a restart marker.
     fn parse_image_data(&mut self) -> Result<&'a [u8]> {
-        let len = self.count_until(|this| Ok(this.peek_slice(2)? != [0xFF, 0xD9]))?;
+        let start = self.cursor;
+
+        loop {
+            match self.peek_slice(2)? {
+                [0xFF, 0x00] | [0xFF, 0xD0..=0xD7] => self.cursor += 2,
+                [0xFF, _] => break,
+                _ => self.cursor += 1,
+            }
+        }
 
-        self.read_slice(len)
+        Ok(&self.data[start..self.cursor])
     }
 
     impl_read_for_datatype!(read_u8, u8);
     impl_read_for_datatype!(read_u16, u16);
     impl_read_for_datatype!(read_marker, Marker);
     impl_read_slice!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::grammar::{ColorType, ImageExt};
+    use crate::jpeg::grammar::JpegColorSpace;
+
+    /// The mean absolute difference between two equally sized sample buffers.
+    fn mean_absolute_error(a: &[u8], b: &[u8]) -> f64 {
+        assert_eq!(a.len(), b.len());
+
+        a.iter()
+            .zip(b)
+            .map(|(&x, &y)| (x as f64 - y as f64).abs())
+            .sum::<f64>()
+            / a.len() as f64
+    }
+
+    // The synthetic fixtures are 32x24 gradients, encoded with near-lossless quantization
+    // tables. Their ink coverage is defined per pixel below.
+    fn fixture_ink(x: usize, y: usize) -> [f64; 4] {
+        [
+            (x * 255 / 31) as f64,
+            (y * 255 / 23) as f64,
+            ((x + y) * 255 / 54) as f64,
+            (64 + 2 * x) as f64,
+        ]
+    }
+
+    fn expected_cmyk_rgb8() -> Vec<u8> {
+        (0..24)
+            .flat_map(|y| (0..32).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                let [c, m, ye, k] = fixture_ink(x, y);
+
+                [c, m, ye].map(|ink| ((255.0 - ink) * (255.0 - k) / 255.0).round() as u8)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_taxi_zone_map_manhattan() -> Result<()> {
+        let data = std::fs::read("./tests/taxi_zone_map_manhattan.jpg")?;
+
+        let jpeg = JpegDecoder::new(&data).decode()?;
+        let reference = image::load_from_memory(&data)?.to_rgb8();
+
+        assert_eq!(jpeg.dimensions(), reference.dimensions());
+        assert_eq!(jpeg.color_space(), JpegColorSpace::YCbCr);
+
+        let error = mean_absolute_error(&jpeg.rgb8(), reference.as_raw());
+        assert!(error < 1.0, "mean absolute error: {error}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reject_huffman_progressive() -> Result<()> {
+        // The same frame, marked as Huffman-coded progressive (SOF2).
+        let mut data = std::fs::read("./tests/cmyk_adobe.jpg")?;
+        let marker = data
+            .windows(2)
+            .position(|bytes| bytes == [0xFF, 0xC0])
+            .expect("a baseline start of frame");
+        data[marker + 1] = 0xC2;
+
+        assert!(JpegDecoder::new(&data).decode_header().is_err());
+        assert!(JpegDecoder::new(&data).decode().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_adobe_cmyk() -> Result<()> {
+        let data = std::fs::read("./tests/cmyk_adobe.jpg")?;
+        let jpeg = JpegDecoder::new(&data).decode()?;
+
+        assert_eq!(jpeg.color_space(), JpegColorSpace::CMYK);
+        assert_eq!(jpeg.color_type(), ColorType::RGB);
+
+        let error = mean_absolute_error(&jpeg.rgb8(), &expected_cmyk_rgb8());
+        assert!(error < 1.5, "mean absolute error: {error}");
+
+        Ok(())
+    }
 
-    /// counts the number of bytes until the reader reaches term function
-    fn count_until(&self, term_fn: impl Fn(&Self) -> Result<bool>) -> Result<usize> {
-        let mut len = 0;
+    #[test]
+    fn test_decode_adobe_ycck() -> Result<()> {
+        let data = std::fs::read("./tests/ycck_adobe.jpg")?;
+        let jpeg = JpegDecoder::new(&data).decode()?;
+
+        assert_eq!(jpeg.color_space(), JpegColorSpace::YCCK);
+
+        let error = mean_absolute_error(&jpeg.rgb8(), &expected_cmyk_rgb8());
+        assert!(error < 1.5, "mean absolute error: {error}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_12_bit_grayscale() -> Result<()> {
+        let data = std::fs::read("./tests/gray_12bit.jpg")?;
+        let jpeg = JpegDecoder::new(&data).decode()?;
+
+        assert_eq!(jpeg.bit_depth(), 16);
+        assert_eq!(jpeg.color_type(), ColorType::Grayscale);
+        assert_eq!(jpeg.dimensions(), (32, 24));
+
+        // The fixture holds the 12-bit ramp x * 128 + y * 4.
+        let error = jpeg
+            .pixel_buffer
+            .chunks_exact(2)
+            .enumerate()
+            .map(|(i, b)| {
+                let (x, y) = (i % 32, i / 32);
+                let expected = (x * 128 + y * 4) as f64 * 65535.0 / 4095.0;
+
+                (u16::from_be_bytes([b[0], b[1]]) as f64 - expected).abs()
+            })
+            .sum::<f64>()
+            / (32.0 * 24.0);
+
+        // Within 2 levels of 12-bit precision.
+        assert!(error < 2.0 * 16.0, "mean absolute error: {error}");
+
+        // Narrowing to 8 bits rounds, like it does for 16-bit PNGs.
+        let rounded = jpeg
+            .pixel_buffer
+            .chunks_exact(2)
+            .map(|b| (f64::from(u16::from_be_bytes([b[0], b[1]])) / 257.0).round() as u8)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            jpeg.rgba8().iter().step_by(4).copied().collect::<Vec<_>>(),
+            rounded
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_12_bit_ycbcr_with_restarts() -> Result<()> {
+        let data = std::fs::read("./tests/ycbcr_12bit.jpg")?;
+        let jpeg = JpegDecoder::new(&data).decode()?;
+
+        assert_eq!(jpeg.bit_depth(), 16);
+        assert_eq!(jpeg.color_type(), ColorType::RGB);
+
+        let expected = (0..24)
+            .flat_map(|y| (0..32).map(move |x| (x, y)))
+            .flat_map(|(x, y)| [x * 8, y * 10, 255 - x * 4].map(|s| s as u8))
+            .collect::<Vec<_>>();
+
+        let error = mean_absolute_error(&jpeg.rgb8(), &expected);
+        assert!(error < 1.5, "mean absolute error: {error}");
+
+        Ok(())
+    }
+
+    fn samples16(jpeg: &Jpeg) -> Vec<u16> {
+        jpeg.pixel_buffer
+            .chunks_exact(2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .collect()
+    }
 
-        while !term_fn(self)? {
-            len += 1;
+    /// Scales a sample of the given precision to 16 bits, the way the decoder does.
+    fn scale_to_16_bit(value: usize, precision: u8) -> u16 {
+        (value as f64 * 65535.0 / ((1 << precision) - 1) as f64).round() as u16
+    }
+
+    #[test]
+    fn test_decode_lossless_grayscale_predictors() -> Result<()> {
+        for (predictor, precision) in (1..=7).zip([2, 4, 8, 10, 12, 14, 16]) {
+            let path = format!("./tests/lossless_gray_{precision}bit_p{predictor}.jpg");
+            let data = std::fs::read(&path)?;
+            let jpeg = JpegDecoder::new(&data).decode()?;
+
+            assert_eq!(jpeg.bit_depth(), 16);
+            assert_eq!(jpeg.color_type(), ColorType::Grayscale);
+            assert_eq!(jpeg.dimensions(), (16, 12));
+
+            let expected = (0..12)
+                .flat_map(|y| (0..16).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    let value = match (x, y, precision) {
+                        // Produces a difference of 32768, which has no additional bits.
+                        (1, 0, 16) => 32768,
+                        _ => (x * 7919 + y * 104729 + x * y * 31) % (1 << precision),
+                    };
+
+                    scale_to_16_bit(value, precision)
+                })
+                .collect::<Vec<_>>();
+
+            assert_eq!(samples16(&jpeg), expected, "{path}");
         }
 
-        Ok(len)
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    // use super::*;
+    #[test]
+    fn test_decode_lossless_rgb_with_point_transform_and_restarts() -> Result<()> {
+        // Interleaved 12-bit RGB with predictor 7, a point transform of 2, and a restart
+        // every 3 lines.
+        let data = std::fs::read("./tests/lossless_rgb_12bit.jpg")?;
+        let jpeg = JpegDecoder::new(&data).decode()?;
+
+        assert_eq!(jpeg.bit_depth(), 16);
+        assert_eq!(jpeg.color_type(), ColorType::RGB);
+        assert_eq!(jpeg.color_space(), JpegColorSpace::RGB);
+
+        let expected = (0..12)
+            .flat_map(|y| (0..16).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                (0..3).map(move |c| {
+                    let value = ((x * 200 + y * 50 + c * 1000) * (c + 1)) % 4096;
+                    scale_to_16_bit(value >> 2 << 2, 12)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(samples16(&jpeg), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_arithmetic_matches_huffman() -> Result<()> {
+        // The arithmetic-coded fixtures were transcoded from the Huffman-coded reference without
+        // touching the coefficients, so they must decode to identical pixels. The sequential one
+        // also uses restarts and non-default conditioning tables, the progressive one includes
+        // successive approximation scans.
+        let data = std::fs::read("./tests/arithmetic_reference.jpg")?;
+        let reference = JpegDecoder::new(&data).decode()?;
+
+        for path in [
+            "./tests/arithmetic_sequential.jpg",
+            "./tests/arithmetic_progressive.jpg",
+        ] {
+            let data = std::fs::read(path)?;
+            let jpeg = JpegDecoder::new(&data).decode()?;
+
+            assert_eq!(jpeg, reference, "{path}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_arithmetic_lossless() -> Result<()> {
+        // 12-bit grayscale with predictor 7 and a point transform of 1.
+        let data = std::fs::read("./tests/lossless_arithmetic_gray_12bit.jpg")?;
+        let jpeg = JpegDecoder::new(&data).decode()?;
+
+        let expected = (0..12)
+            .flat_map(|y| (0..16).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let value = (x * 7919 + y * 104729 + x * y * 31) % 4096;
+                scale_to_16_bit(value >> 1 << 1, 12)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(samples16(&jpeg), expected);
+
+        // Interleaved 16-bit RGB with predictor 6, restarts every 4 lines and conditioning
+        // bounds of (1, 4).
+        let data = std::fs::read("./tests/lossless_arithmetic_rgb_16bit.jpg")?;
+        let jpeg = JpegDecoder::new(&data).decode()?;
+
+        assert_eq!(jpeg.color_space(), JpegColorSpace::RGB);
+
+        let expected = (0..12)
+            .flat_map(|y| (0..16).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                (0..3).map(move |c| match (x, y, c) {
+                    (1, 0, 0) => 32768,
+                    _ => ((x * 2000 + y * 3000 * (c + 1) + (x ^ y) * 97) % 65536) as u16,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(samples16(&jpeg), expected);
+
+        Ok(())
+    }
 
     #[test]
-    fn test_decode_taxi_zone_map_manhattan() {
-        let _data = std::fs::read("./tests/taxi_zone_map_manhattan.jpg").unwrap();
+    fn test_decode_downscaled() -> Result<()> {
+        // A smooth 4:2:0 gradient, so a box filtered full size decode is a good reference.
+        let data = std::fs::read("./tests/ycbcr_12bit.jpg")?;
+        let full = JpegDecoder::new(&data).decode()?;
+        let (width, height) = (full.width() as usize, full.height() as usize);
+        let full = full.rgb8();
+
+        for (scale, max_error) in [
+            (JpegScale::Half, 1.0),
+            (JpegScale::Quarter, 2.5),
+            (JpegScale::Eighth, 4.0),
+        ] {
+            let jpeg = JpegDecoder::new(&data).with_scale(scale).decode()?;
+            let d = scale.denominator();
+
+            assert_eq!(
+                jpeg.dimensions(),
+                (width.div_ceil(d) as u32, height.div_ceil(d) as u32)
+            );
+
+            // Compare against a box filtered full size decode.
+            let expected = (0..height.div_ceil(d))
+                .flat_map(|y| (0..width.div_ceil(d)).map(move |x| (x, y)))
+                .flat_map(|(x, y)| {
+                    let pixels = (y * d..((y + 1) * d).min(height))
+                        .flat_map(|sy| (x * d..((x + 1) * d).min(width)).map(move |sx| (sx, sy)))
+                        .collect::<Vec<_>>();
+
+                    let full = &full;
+                    (0..3).map(move |c| {
+                        let sum = pixels
+                            .iter()
+                            .map(|&(sx, sy)| full[(sy * width + sx) * 3 + c] as f64)
+                            .sum::<f64>();
+
+                        (sum / pixels.len() as f64).round() as u8
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let error = mean_absolute_error(&jpeg.rgb8(), &expected);
+            assert!(error < max_error, "{scale:?}: mean absolute error {error}");
+        }
 
-        // let _ = JpegDecoder::new(&data).decode().unwrap();
+        Ok(())
     }
 }
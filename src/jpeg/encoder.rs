@@ -0,0 +1,543 @@
+use crate::jpeg::grammar::{ComponentCoefficients, JpegCoefficients, ZIGZAG};
+use anyhow::{ensure, Result};
+use std::io::Write;
+
+/// The most blocks an MCU of an interleaved scan may hold (B.2.3).
+const MAX_BLOCKS_IN_MCU: usize = 10;
+
+/// Writes quantized DCT coefficients as a Huffman-coded sequential JPEG. The coefficients are
+/// written as they are, with their original quantization tables, so nothing is lost.
+///
+/// Each scan gets optimal Huffman tables (K.2): one pair for the first component and one shared
+/// by the others.
+pub struct JpegEncoder<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JpegEncoder<W> {
+    pub const fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn encode(&mut self, coefficients: &JpegCoefficients) -> Result<()> {
+        ensure!(
+            (1..=4).contains(&coefficients.components.len()),
+            "JPEGs must have between 1 and 4 components."
+        );
+
+        self.write_marker(0xFFD8)?;
+
+        if let Some(header) = coefficients.application_header {
+            self.write_segment(0xFFE0, |segment| {
+                segment.extend_from_slice(b"JFIF\0");
+                segment.extend_from_slice(&[header.version.0, header.version.1, header.unit]);
+                segment.extend_from_slice(&header.density.0.to_be_bytes());
+                segment.extend_from_slice(&header.density.1.to_be_bytes());
+                // Thumbnails aren't carried over.
+                segment.extend_from_slice(&[0, 0]);
+            })?;
+        }
+
+        if let Some(header) = coefficients.adobe_header {
+            self.write_segment(0xFFEE, |segment| {
+                segment.extend_from_slice(b"Adobe");
+                segment.extend_from_slice(&header.version.to_be_bytes());
+                segment.extend_from_slice(&header.flags0.to_be_bytes());
+                segment.extend_from_slice(&header.flags1.to_be_bytes());
+                segment.push(header.transform);
+            })?;
+        }
+
+        if let Some(icc_profile) = &coefficients.icc_profile {
+            // Each APP2 segment holds a chunk of at most 65519 bytes after its own header.
+            let chunks = icc_profile.chunks(65519);
+            let chunk_count = chunks.len();
+
+            ensure!(chunk_count <= 255, "The ICC profile is too large to embed.");
+
+            for (i, chunk) in chunks.enumerate() {
+                self.write_segment(0xFFE2, |segment| {
+                    segment.extend_from_slice(b"ICC_PROFILE\0");
+                    segment.extend_from_slice(&[i as u8 + 1, chunk_count as u8]);
+                    segment.extend_from_slice(chunk);
+                })?;
+            }
+        }
+
+        for quantization_table in &coefficients.quantization_tables {
+            self.write_segment(0xFFDB, |segment| {
+                segment.push(quantization_table.flag);
+
+                for &element in &quantization_table.table_elements {
+                    if quantization_table.flag >> 4 == 1 {
+                        segment.extend_from_slice(&element.to_be_bytes());
+                    } else {
+                        segment.push(element as u8);
+                    }
+                }
+            })?;
+        }
+
+        // Baseline frames are limited to 8-bit samples and quantization tables.
+        let baseline = coefficients.sample_precision == 8
+            && coefficients
+                .quantization_tables
+                .iter()
+                .all(|qt| qt.flag >> 4 == 0);
+
+        self.write_segment(if baseline { 0xFFC0 } else { 0xFFC1 }, |segment| {
+            segment.push(coefficients.sample_precision);
+            segment.extend_from_slice(&coefficients.height.to_be_bytes());
+            segment.extend_from_slice(&coefficients.width.to_be_bytes());
+            segment.push(coefficients.components.len() as u8);
+
+            for component in &coefficients.components {
+                segment.extend_from_slice(&[
+                    component.identifier,
+                    component.horizontal_sampling_factor << 4 | component.vertical_sampling_factor,
+                    component.quantization_table_selector,
+                ]);
+            }
+        })?;
+
+        let blocks_in_mcu = coefficients
+            .components
+            .iter()
+            .map(|c| c.horizontal_sampling_factor as usize * c.vertical_sampling_factor as usize)
+            .sum::<usize>();
+
+        if blocks_in_mcu <= MAX_BLOCKS_IN_MCU {
+            let indices = (0..coefficients.components.len()).collect::<Vec<_>>();
+            self.write_scan(coefficients, &indices)?;
+        } else {
+            for index in 0..coefficients.components.len() {
+                self.write_scan(coefficients, &[index])?;
+            }
+        }
+
+        self.write_marker(0xFFD9)?;
+
+        Ok(())
+    }
+
+    fn write_scan(&mut self, coefficients: &JpegCoefficients, indices: &[usize]) -> Result<()> {
+        let table_ids = indices
+            .iter()
+            .map(|&index| (index > 0) as usize)
+            .collect::<Vec<_>>();
+
+        // Gather symbol frequencies with a dry run of the scan.
+        let mut frequencies = [[[0; 257]; 2]; 2];
+
+        for_each_block(coefficients, indices, |scan_component, block, predictor| {
+            let table_id = table_ids[scan_component];
+
+            encode_block(block, predictor, |class, symbol, _, _| {
+                frequencies[class][table_id][symbol as usize] += 1;
+            });
+        });
+
+        let mut tables = [[None, None], [None, None]];
+
+        self.write_segment(0xFFC4, |segment| {
+            for (class, class_tables) in tables.iter_mut().enumerate() {
+                for (table_id, table) in class_tables.iter_mut().enumerate() {
+                    if !table_ids.contains(&table_id) {
+                        continue;
+                    }
+
+                    let (code_lengths, values) = optimal_table(&frequencies[class][table_id]);
+
+                    segment.push((class as u8) << 4 | table_id as u8);
+                    segment.extend_from_slice(&code_lengths);
+                    segment.extend_from_slice(&values);
+
+                    *table = Some(HuffmanCodes::new(&code_lengths, &values));
+                }
+            }
+        })?;
+
+        self.write_segment(0xFFDA, |segment| {
+            segment.push(indices.len() as u8);
+
+            for (&index, &table_id) in indices.iter().zip(&table_ids) {
+                let table_id = table_id as u8;
+                segment.extend_from_slice(&[
+                    coefficients.components[index].identifier,
+                    table_id << 4 | table_id,
+                ]);
+            }
+
+            // Sequential scans span the whole spectrum without successive approximation.
+            segment.extend_from_slice(&[0, 63, 0]);
+        })?;
+
+        let mut writer = BitWriter::default();
+
+        for_each_block(coefficients, indices, |scan_component, block, predictor| {
+            let table_id = table_ids[scan_component];
+
+            encode_block(block, predictor, |class, symbol, bits, size| {
+                let codes = tables[class][table_id]
+                    .as_ref()
+                    .expect("tables exist for every component of the scan");
+                let (code, code_size) = codes.codes[symbol as usize];
+
+                writer.write_bits(code as u32, code_size);
+                writer.write_bits(bits, size);
+            });
+        });
+
+        self.writer.write_all(&writer.finish())?;
+
+        Ok(())
+    }
+
+    fn write_marker(&mut self, marker: u16) -> Result<()> {
+        self.writer.write_all(&marker.to_be_bytes())?;
+
+        Ok(())
+    }
+
+    /// Writes a marker segment, prefixing the payload built by `payload` with its length.
+    fn write_segment(&mut self, marker: u16, payload: impl FnOnce(&mut Vec<u8>)) -> Result<()> {
+        let mut segment = Vec::new();
+        payload(&mut segment);
+
+        ensure!(segment.len() + 2 <= u16::MAX as usize, "Segment too long.");
+
+        self.write_marker(marker)?;
+        self.writer
+            .write_all(&(segment.len() as u16 + 2).to_be_bytes())?;
+        self.writer.write_all(&segment)?;
+
+        Ok(())
+    }
+}
+
+/// Walks every block of a scan in encoding order, like the decoder does, handing each one to
+/// `visit` along with the DC predictor of its component.
+fn for_each_block(
+    coefficients: &JpegCoefficients,
+    indices: &[usize],
+    mut visit: impl FnMut(usize, &[i16; 64], &mut i32),
+) {
+    let mut predictors = vec![0; indices.len()];
+
+    if let [index] = indices[..] {
+        let component = &coefficients.components[index];
+        let (width, height) = component_dimensions(coefficients, component);
+
+        for y in 0..height.div_ceil(8) {
+            for x in 0..width.div_ceil(8) {
+                visit(0, component.block(x, y), &mut predictors[0]);
+            }
+        }
+
+        return;
+    }
+
+    let (mcu_width, mcu_height) = coefficients.mcu_size();
+    let mcus_x = (coefficients.width as usize).div_ceil(mcu_width);
+    let mcus_y = (coefficients.height as usize).div_ceil(mcu_height);
+
+    for mcu_y in 0..mcus_y {
+        for mcu_x in 0..mcus_x {
+            for (scan_component, &index) in indices.iter().enumerate() {
+                let component = &coefficients.components[index];
+                let h = component.horizontal_sampling_factor as usize;
+                let v = component.vertical_sampling_factor as usize;
+
+                for dy in 0..v {
+                    for dx in 0..h {
+                        let block = component.block(mcu_x * h + dx, mcu_y * v + dy);
+                        visit(scan_component, block, &mut predictors[scan_component]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The dimensions of a component's sample grid, before padding to whole blocks.
+fn component_dimensions(
+    coefficients: &JpegCoefficients,
+    component: &ComponentCoefficients,
+) -> (usize, usize) {
+    let (h_max, v_max) = coefficients.max_sampling_factors();
+
+    (
+        (coefficients.width as usize * component.horizontal_sampling_factor as usize)
+            .div_ceil(h_max as usize),
+        (coefficients.height as usize * component.vertical_sampling_factor as usize)
+            .div_ceil(v_max as usize),
+    )
+}
+
+/// Codes one block (F.1.2), calling `emit` with the table class (0 for DC, 1 for AC), the
+/// Huffman symbol and the additional bits that follow it.
+fn encode_block(block: &[i16; 64], predictor: &mut i32, mut emit: impl FnMut(usize, u8, u32, u8)) {
+    let difference = block[0] as i32 - *predictor;
+    *predictor = block[0] as i32;
+
+    let (size, bits) = magnitude(difference);
+    emit(0, size, bits, size);
+
+    let mut run = 0;
+
+    for &index in &ZIGZAG[1..] {
+        let coefficient = block[index] as i32;
+
+        if coefficient == 0 {
+            run += 1;
+            continue;
+        }
+
+        while run > 15 {
+            emit(1, 0xF0, 0, 0);
+            run -= 16;
+        }
+
+        let (size, bits) = magnitude(coefficient);
+        emit(1, run << 4 | size, bits, size);
+        run = 0;
+    }
+
+    if run > 0 {
+        emit(1, 0x00, 0, 0);
+    }
+}
+
+/// The category of a value and its additional bits, the inverse of EXTEND (F.1.2.1).
+const fn magnitude(value: i32) -> (u8, u32) {
+    let size = 32 - value.unsigned_abs().leading_zeros();
+    let bits = if value < 0 { value - 1 } else { value };
+
+    (size as u8, bits as u32 & ((1 << size) - 1))
+}
+
+/// Generates code lengths of at most 16 bits for the given symbol frequencies (K.2). The
+/// 257th entry is reserved so that no code consists of only 1-bits.
+fn optimal_table(frequencies: &[u32; 257]) -> ([u8; 16], Vec<u8>) {
+    let mut frequencies = frequencies.map(|f| f as u64);
+    frequencies[256] = 1;
+
+    let mut code_sizes = [0_usize; 257];
+    let mut others = [None; 257];
+
+    loop {
+        // The least frequent symbol, preferring the largest value on ties, and the next one.
+        let least = |exclude: Option<usize>| {
+            (0..257)
+                .filter(|&i| frequencies[i] > 0 && Some(i) != exclude)
+                .min_by_key(|&i| (frequencies[i], std::cmp::Reverse(i)))
+        };
+
+        let Some(mut v1) = least(None) else { break };
+        let Some(mut v2) = least(Some(v1)) else {
+            break;
+        };
+
+        frequencies[v1] += frequencies[v2];
+        frequencies[v2] = 0;
+
+        code_sizes[v1] += 1;
+
+        while let Some(next) = others[v1] {
+            v1 = next;
+            code_sizes[v1] += 1;
+        }
+
+        others[v1] = Some(v2);
+        code_sizes[v2] += 1;
+
+        while let Some(next) = others[v2] {
+            v2 = next;
+            code_sizes[v2] += 1;
+        }
+    }
+
+    let mut bits = [0_u32; 33];
+
+    for &size in &code_sizes {
+        if size > 0 {
+            bits[size] += 1;
+        }
+    }
+
+    // Limit code lengths to 16 bits (K.3).
+    for i in (17..33).rev() {
+        while bits[i] > 0 {
+            let mut j = i - 2;
+
+            while bits[j] == 0 {
+                j -= 1;
+            }
+
+            bits[i] -= 2;
+            bits[i - 1] += 1;
+            bits[j + 1] += 2;
+            bits[j] -= 1;
+        }
+    }
+
+    // Remove the reserved symbol, which has the longest code.
+    let longest = (1..=16).rev().find(|&i| bits[i] > 0).unwrap_or(1);
+    bits[longest] -= 1;
+
+    let mut values = Vec::new();
+
+    for size in 1..=32 {
+        values.extend((0..256).filter(|&i| code_sizes[i] == size).map(|i| i as u8));
+    }
+
+    let mut code_lengths = [0; 16];
+
+    for (length, &count) in code_lengths.iter_mut().zip(&bits[1..=16]) {
+        *length = count as u8;
+    }
+
+    (code_lengths, values)
+}
+
+/// The code and code size of every symbol of a Huffman table (C.2).
+#[derive(Debug)]
+struct HuffmanCodes {
+    codes: [(u16, u8); 256],
+}
+
+impl HuffmanCodes {
+    fn new(code_lengths: &[u8; 16], values: &[u8]) -> Self {
+        let mut codes = [(0, 0); 256];
+        let mut values = values.iter();
+        let mut code = 0_u16;
+
+        for (i, &count) in code_lengths.iter().enumerate() {
+            for _ in 0..count {
+                if let Some(&value) = values.next() {
+                    codes[value as usize] = (code, i as u8 + 1);
+                }
+
+                code += 1;
+            }
+
+            code <<= 1;
+        }
+
+        Self { codes }
+    }
+}
+
+/// Packs bits most significant first, stuffing a zero byte after every 0xFF.
+#[derive(Debug, Default)]
+struct BitWriter {
+    data: Vec<u8>,
+    bits: u64,
+    num_bits: u32,
+}
+
+impl BitWriter {
+    fn write_bits(&mut self, bits: u32, size: u8) {
+        if size == 0 {
+            return;
+        }
+
+        self.bits = self.bits << size | (bits & ((1 << size) - 1)) as u64;
+        self.num_bits += size as u32;
+
+        while self.num_bits >= 8 {
+            self.num_bits -= 8;
+            let byte = (self.bits >> self.num_bits) as u8;
+
+            self.data.push(byte);
+
+            if byte == 0xFF {
+                self.data.push(0x00);
+            }
+        }
+    }
+
+    /// Pads the last byte with 1-bits.
+    fn finish(mut self) -> Vec<u8> {
+        if self.num_bits > 0 {
+            let padding = 8 - self.num_bits as u8;
+            self.write_bits((1 << padding) - 1, padding);
+        }
+
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image::grammar::ImageExt, jpeg::JpegDecoder, png::PngDecoder};
+
+    #[test]
+    fn test_encode_round_trip() -> Result<()> {
+        for path in [
+            "./tests/taxi_zone_map_manhattan.jpg",
+            "./tests/arithmetic_progressive.jpg",
+            "./tests/ycbcr_12bit.jpg",
+            "./tests/cmyk_adobe.jpg",
+        ] {
+            let data = std::fs::read(path)?;
+            let coefficients = JpegDecoder::new(&data).decode_coefficients()?;
+
+            let mut encoded = Vec::new();
+            JpegEncoder::new(&mut encoded).encode(&coefficients)?;
+
+            let decoded = JpegDecoder::new(&encoded).decode_coefficients()?;
+            assert_eq!(decoded.components, coefficients.components, "{path}");
+
+            let jpeg = JpegDecoder::new(&encoded).decode()?;
+            assert_eq!(jpeg, JpegDecoder::new(&data).decode()?, "{path}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encoded_jpeg_decodes_elsewhere() -> Result<()> {
+        let data = std::fs::read("./tests/arithmetic_reference.jpg")?;
+        let coefficients = JpegDecoder::new(&data).decode_coefficients()?;
+
+        let mut encoded = Vec::new();
+        JpegEncoder::new(&mut encoded).encode(&coefficients)?;
+
+        let reference = image::load_from_memory(&data)?.to_rgb8();
+        let decoded = image::load_from_memory(&encoded)?.to_rgb8();
+
+        assert_eq!(decoded, reference);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_icc_profile_round_trip() -> Result<()> {
+        let data = std::fs::read("./tests/arithmetic_reference.jpg")?;
+        let mut coefficients = JpegDecoder::new(&data).decode_coefficients()?;
+
+        // Large profiles span several APP2 segments.
+        let large_profile = (0..150_000).map(|i| i as u8).collect::<Vec<_>>();
+        coefficients.icc_profile = Some(large_profile.clone());
+
+        let mut encoded = Vec::new();
+        JpegEncoder::new(&mut encoded).encode(&coefficients)?;
+
+        let decoded = JpegDecoder::new(&encoded).decode_coefficients()?;
+        assert_eq!(decoded.icc_profile, Some(large_profile));
+
+        let png = PngDecoder::new(&std::fs::read("./tests/obama.png")?).decode()?;
+        coefficients.icc_profile = png.icc_profile;
+
+        let mut encoded = Vec::new();
+        JpegEncoder::new(&mut encoded).encode(&coefficients)?;
+
+        let jpeg = JpegDecoder::new(&encoded).decode()?;
+        let profile = jpeg.color_profile().expect("The profile is embedded");
+        assert_eq!(profile.description(), Some("sRGB"));
+
+        Ok(())
+    }
+}
@@ -1,10 +1,29 @@
-use crate::image::grammar::{ColorType, ImageExt};
-use anyhow::bail;
+use crate::{
+    color::ColorProfile,
+    image::{
+        grammar::{embedded_color_profile, ColorType, ImageExt},
+        ImageBuffer, Rgba32F, Subpixel,
+    },
+};
+use anyhow::{bail, Result};
 use std::{borrow::Cow, ops::RangeInclusive};
 
 pub type Marker = u16;
 
-#[derive(Debug)]
+/// Maps the zigzag sequence index of a coefficient to its natural (row-major) index.
+#[rustfmt::skip]
+pub const ZIGZAG: [usize; 64] = [
+     0,  1,  8, 16,  9,  2,  3, 10,
+    17, 24, 32, 25, 18, 11,  4,  5,
+    12, 19, 26, 33, 40, 48, 41, 34,
+    27, 20, 13,  6,  7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36,
+    29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46,
+    53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+#[derive(Debug, Clone, Copy)]
 pub struct ApplicationHeader {
     pub version: (u8, u8),
     pub unit: u8,
@@ -12,6 +31,22 @@ pub struct ApplicationHeader {
     pub thumbnail: (u8, u8),
 }
 
+/// The APP14 segment written by Adobe applications. Its presence also signals that 4-component
+/// images store inverted CMYK samples.
+#[derive(Debug, Clone, Copy)]
+pub struct AdobeHeader {
+    pub version: u16,
+    pub flags0: u16,
+    pub flags1: u16,
+    pub transform: u8,
+}
+
+impl AdobeHeader {
+    pub const TRANSFORM_NONE: u8 = 0;
+    pub const TRANSFORM_YCBCR: u8 = 1;
+    pub const TRANSFORM_YCCK: u8 = 2;
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum Precision {
     Eight = 1,
@@ -28,10 +63,10 @@ impl From<bool> for Precision {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct QuantizationTable {
     pub flag: u8,
-    pub table_elements: [u16; QuantizationTable::NUM_ELEMENTS],
+    pub table_elements: [u16; Self::NUM_ELEMENTS],
 }
 
 impl QuantizationTable {
@@ -45,6 +80,17 @@ impl QuantizationTable {
     pub const fn table_identifier(&self) -> u8 {
         self.flag & 0b1111
     }
+
+    /// The table elements are stored in zigzag order. This returns them in natural order.
+    pub fn natural_order(&self) -> [u16; Self::NUM_ELEMENTS] {
+        let mut table = [0; Self::NUM_ELEMENTS];
+
+        for (k, &element) in self.table_elements.iter().enumerate() {
+            table[ZIGZAG[k]] = element;
+        }
+
+        table
+    }
 }
 
 pub enum HuffmanTableClass {
@@ -52,7 +98,7 @@ pub enum HuffmanTableClass {
     DC,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HuffmanTable {
     pub flag: u8,
     pub code_lengths: [u8; 16],
@@ -73,7 +119,7 @@ impl HuffmanTable {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EncodingProcess {
     BaselineDCT = 0,
     HuffmanExtendedSequentialDCT = 1,
@@ -103,6 +149,28 @@ impl TryFrom<u8> for EncodingProcess {
     }
 }
 
+impl EncodingProcess {
+    pub const fn is_lossless(self) -> bool {
+        matches!(self, Self::HuffmanLossless | Self::ArithmeticLossless)
+    }
+
+    pub const fn is_progressive(self) -> bool {
+        matches!(
+            self,
+            Self::HuffmanProgressiveDCT | Self::ArithmeticProgressiveDCT
+        )
+    }
+
+    pub const fn is_arithmetic(self) -> bool {
+        matches!(
+            self,
+            Self::ArithmeticExtendedSequentialDCT
+                | Self::ArithmeticProgressiveDCT
+                | Self::ArithmeticLossless
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct StartOfFrame {
     pub encoding_process: EncodingProcess,
@@ -112,6 +180,58 @@ pub struct StartOfFrame {
     pub components: Vec<Component>,
 }
 
+impl StartOfFrame {
+    pub fn max_horizontal_sampling_factor(&self) -> u8 {
+        self.components
+            .iter()
+            .map(Component::horizontal_sampling_factor)
+            .max()
+            .unwrap_or(1)
+    }
+
+    pub fn max_vertical_sampling_factor(&self) -> u8 {
+        self.components
+            .iter()
+            .map(Component::vertical_sampling_factor)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// The side length of a data unit: an 8x8 block for DCT processes, a single sample for
+    /// lossless ones.
+    pub const fn data_unit_size(&self) -> usize {
+        if self.encoding_process.is_lossless() {
+            1
+        } else {
+            8
+        }
+    }
+
+    /// The number of MCUs per line and per column of an interleaved scan.
+    pub fn mcu_dimensions(&self) -> (usize, usize) {
+        let mcu_width = self.data_unit_size() * self.max_horizontal_sampling_factor() as usize;
+        let mcu_height = self.data_unit_size() * self.max_vertical_sampling_factor() as usize;
+
+        (
+            (self.samples_per_line as usize).div_ceil(mcu_width),
+            (self.lines as usize).div_ceil(mcu_height),
+        )
+    }
+
+    /// The dimensions of a component's sample grid, before padding to whole blocks.
+    pub fn component_dimensions(&self, component: &Component) -> (usize, usize) {
+        let h = component.horizontal_sampling_factor() as usize;
+        let v = component.vertical_sampling_factor() as usize;
+        let h_max = self.max_horizontal_sampling_factor() as usize;
+        let v_max = self.max_vertical_sampling_factor() as usize;
+
+        (
+            (self.samples_per_line as usize * h).div_ceil(h_max),
+            (self.lines as usize * v).div_ceil(v_max),
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct Component {
     pub identifier: u8,
@@ -119,6 +239,16 @@ pub struct Component {
     pub quantization_table_destination_selector: u8,
 }
 
+impl Component {
+    pub const fn horizontal_sampling_factor(&self) -> u8 {
+        self.sampling_factor >> 4
+    }
+
+    pub const fn vertical_sampling_factor(&self) -> u8 {
+        self.sampling_factor & 0b1111
+    }
+}
+
 #[derive(Debug)]
 pub struct StartOfScan {
     pub components: Vec<(u8, u8)>,
@@ -126,45 +256,312 @@ pub struct StartOfScan {
     pub approximation: u8,
 }
 
+impl StartOfScan {
+    pub const fn successive_approximation_high(&self) -> u8 {
+        self.approximation >> 4
+    }
+
+    pub const fn successive_approximation_low(&self) -> u8 {
+        self.approximation & 0b1111
+    }
+}
+
+/// The conditioning tables defined by DAC segments (B.2.4.3). DC tables hold the lower and upper
+/// bounds `(L, U)` used to classify differences, AC tables hold the `Kx` band split.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArithmeticConditioning {
+    pub dc_bounds: [(u8, u8); 4],
+    pub ac_kx: [u8; 4],
+}
+
+impl Default for ArithmeticConditioning {
+    fn default() -> Self {
+        Self {
+            dc_bounds: [(0, 1); 4],
+            ac_kx: [5; 4],
+        }
+    }
+}
+
+/// A scan together with the table state that was in effect when it began.
 #[derive(Debug)]
-pub struct JFIF<'a> {
-    pub application_header: ApplicationHeader,
-    pub quantization_tables: Vec<QuantizationTable>,
-    pub huffman_tables: Vec<HuffmanTable>,
-    pub start_of_frame: StartOfFrame,
+pub struct Scan<'a> {
     pub start_of_scan: StartOfScan,
+    pub huffman_tables: Vec<HuffmanTable>,
+    pub arithmetic_conditioning: ArithmeticConditioning,
+    pub restart_interval: u16,
     pub image_data: &'a [u8],
 }
 
 #[derive(Debug)]
-pub struct Jpeg {}
+pub struct JFIF<'a> {
+    pub application_header: Option<ApplicationHeader>,
+    pub adobe_header: Option<AdobeHeader>,
+    pub icc_profile: Option<Vec<u8>>,
+    pub quantization_tables: Vec<QuantizationTable>,
+    pub start_of_frame: StartOfFrame,
+    pub scans: Vec<Scan<'a>>,
+}
+
+impl JFIF<'_> {
+    pub fn quantization_table(&self, identifier: u8) -> Option<&QuantizationTable> {
+        self.quantization_tables
+            .iter()
+            .rev()
+            .find(|qt| qt.table_identifier() == identifier)
+    }
+
+    /// Infers the color space of the encoded samples from the component count, the Adobe
+    /// transform flag and, failing that, the component identifiers.
+    pub fn color_space(&self) -> Result<JpegColorSpace> {
+        let components = &self.start_of_frame.components;
+
+        let color_space = match (components.len(), self.adobe_header) {
+            (1, _) => JpegColorSpace::Grayscale,
+            (3, Some(adobe)) if adobe.transform == AdobeHeader::TRANSFORM_NONE => {
+                JpegColorSpace::RGB
+            }
+            (3, None)
+                if components
+                    .iter()
+                    .map(|c| c.identifier)
+                    .eq(b"RGB".iter().copied()) =>
+            {
+                JpegColorSpace::RGB
+            }
+            (3, _) => JpegColorSpace::YCbCr,
+            (4, Some(adobe)) if adobe.transform == AdobeHeader::TRANSFORM_YCCK => {
+                JpegColorSpace::YCCK
+            }
+            (4, _) => JpegColorSpace::CMYK,
+            (foreign, _) => bail!("Unsupported number of image components: {foreign}"),
+        };
+
+        Ok(color_space)
+    }
+}
+
+/// The factor by which DCT-based images are downscaled while decoding.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum JpegScale {
+    #[default]
+    Full,
+    Half,
+    Quarter,
+    Eighth,
+}
+
+impl JpegScale {
+    pub const fn denominator(self) -> usize {
+        match self {
+            Self::Full => 1,
+            Self::Half => 2,
+            Self::Quarter => 4,
+            Self::Eighth => 8,
+        }
+    }
+
+    /// The side length of a block after its inverse transform.
+    pub const fn block_size(self) -> usize {
+        8 / self.denominator()
+    }
+}
+
+/// The color space the samples were encoded in. Decoded images are always converted to
+/// grayscale or RGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JpegColorSpace {
+    Grayscale,
+    YCbCr,
+    RGB,
+    CMYK,
+    YCCK,
+}
+
+/// The quantized DCT coefficients of a single component, stored in natural (row-major) order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentCoefficients {
+    pub identifier: u8,
+    pub horizontal_sampling_factor: u8,
+    pub vertical_sampling_factor: u8,
+    pub quantization_table_selector: u8,
+
+    /// The number of blocks per line and per column, padded to whole MCUs.
+    pub blocks_per_line: usize,
+    pub blocks_per_column: usize,
+    pub blocks: Vec<[i16; 64]>,
+}
+
+impl ComponentCoefficients {
+    pub fn block(&self, x: usize, y: usize) -> &[i16; 64] {
+        &self.blocks[y * self.blocks_per_line + x]
+    }
+
+    pub fn block_mut(&mut self, x: usize, y: usize) -> &mut [i16; 64] {
+        &mut self.blocks[y * self.blocks_per_line + x]
+    }
+}
+
+/// The quantized DCT coefficients of a whole image, along with the headers and quantization
+/// tables needed to encode them again.
+#[derive(Debug, Clone)]
+pub struct JpegCoefficients {
+    pub(crate) width: u16,
+    pub(crate) height: u16,
+    pub(crate) sample_precision: u8,
+    pub(crate) application_header: Option<ApplicationHeader>,
+    pub(crate) adobe_header: Option<AdobeHeader>,
+    pub(crate) icc_profile: Option<Vec<u8>>,
+    pub(crate) quantization_tables: Vec<QuantizationTable>,
+    pub(crate) components: Vec<ComponentCoefficients>,
+}
+
+impl JpegCoefficients {
+    pub const fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u16 {
+        self.height
+    }
+
+    pub fn components(&self) -> &[ComponentCoefficients] {
+        &self.components
+    }
+
+    pub fn max_sampling_factors(&self) -> (u8, u8) {
+        self.components.iter().fold((1, 1), |(h, v), component| {
+            (
+                h.max(component.horizontal_sampling_factor),
+                v.max(component.vertical_sampling_factor),
+            )
+        })
+    }
+
+    /// The width and height of an MCU in samples.
+    pub fn mcu_size(&self) -> (usize, usize) {
+        let (h_max, v_max) = self.max_sampling_factors();
+
+        (8 * h_max as usize, 8 * v_max as usize)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Jpeg {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) bit_depth: u8,
+    pub(crate) color_type: ColorType,
+    pub(crate) color_space: JpegColorSpace,
+    /// The ICC profile reassembled from its APP2 segments.
+    pub(crate) icc_profile: Option<Vec<u8>>,
+    pub(crate) pixel_buffer: Vec<u8>,
+}
+
+impl Jpeg {
+    pub const fn bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+
+    /// The color space of the encoded samples, before conversion to grayscale or RGB.
+    pub const fn color_space(&self) -> JpegColorSpace {
+        self.color_space
+    }
+
+    /// Returns the samples narrowed to 8 bits, rounding 16-bit samples to the nearest value like
+    /// PNGs do.
+    fn samples8(&self) -> Cow<'_, [u8]> {
+        match self.bit_depth {
+            16 => Cow::from(
+                self.pixel_buffer
+                    .chunks_exact(2)
+                    .map(|b| u8::from_f32(u16::from_be_bytes([b[0], b[1]]).to_f32()))
+                    .collect::<Vec<_>>(),
+            ),
+            _ => Cow::from(&self.pixel_buffer),
+        }
+    }
+}
 
 impl ImageExt for Jpeg {
     fn width(&self) -> u32 {
-        todo!()
+        self.width
     }
 
     fn height(&self) -> u32 {
-        todo!()
+        self.height
     }
 
     fn gamma(&self) -> u32 {
-        todo!()
+        0
     }
 
     fn color_type(&self) -> ColorType {
-        todo!()
+        self.color_type
+    }
+
+    fn bit_depth(&self) -> u8 {
+        self.bit_depth
     }
 
     fn rgb8(&self) -> Cow<'_, [u8]> {
-        todo!()
+        let samples = self.samples8();
+
+        match self.color_type {
+            ColorType::Grayscale => {
+                Cow::from(samples.iter().flat_map(|&y| [y, y, y]).collect::<Vec<_>>())
+            }
+            _ => samples,
+        }
     }
 
     fn rgba8(&self) -> Cow<'_, [u8]> {
-        todo!()
+        let samples = self.samples8();
+
+        let b = match self.color_type {
+            ColorType::Grayscale => samples.iter().flat_map(|&y| [y, y, y, 255]).collect(),
+            _ => samples
+                .chunks_exact(3)
+                .flat_map(|b| [b[0], b[1], b[2], 255])
+                .collect::<Vec<_>>(),
+        };
+
+        Cow::from(b)
     }
 
     fn bitmap(&self) -> Cow<'_, [u32]> {
-        todo!()
+        let samples = self.samples8();
+
+        let b = match self.color_type {
+            ColorType::Grayscale => samples
+                .iter()
+                .map(|&y| u32::from_be_bytes([255, y, y, y]))
+                .collect(),
+            _ => samples
+                .chunks_exact(3)
+                .map(|b| u32::from_be_bytes([255, b[0], b[1], b[2]]))
+                .collect::<Vec<_>>(),
+        };
+
+        Cow::from(b)
+    }
+
+    fn rgba_f32(&self) -> Cow<'_, [f32]> {
+        let image = ImageBuffer::<Rgba32F>::try_from(self)
+            .expect("Decoded JPEGs have 8 or 16-bit gray or RGB samples");
+
+        Cow::from(image.as_raw().to_vec())
+    }
+
+    fn color_profile(&self) -> Option<ColorProfile> {
+        // CMYK profiles can't describe samples that were already converted to RGB.
+        if matches!(
+            self.color_space,
+            JpegColorSpace::CMYK | JpegColorSpace::YCCK
+        ) {
+            return None;
+        }
+
+        embedded_color_profile(self.icc_profile.as_deref()?, self.color_type)
     }
 }
@@ -0,0 +1,301 @@
+use crate::jpeg::grammar::{HuffmanTable, HuffmanTableClass};
+use anyhow::{anyhow, bail, ensure, Result};
+
+/// The number of bits resolved by a single lookup in [`HuffmanLookup::fast`].
+const FAST_BITS: u32 = 9;
+
+/// Reads bits out of an entropy-coded segment, removing stuffed zero bytes. Once a marker is
+/// reached, the reader keeps producing zero bits until it is reset.
+#[derive(Debug)]
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    cursor: usize,
+    bits: u64,
+    num_bits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            cursor: 0,
+            bits: 0,
+            num_bits: 0,
+        }
+    }
+
+    fn fill(&mut self) {
+        while self.num_bits <= 56 {
+            let byte = match self.data.get(self.cursor..) {
+                Some([0xFF, 0x00, ..]) => {
+                    self.cursor += 2;
+                    0xFF
+                }
+                Some([0xFF, ..]) | Some([]) | None => 0,
+                Some([byte, ..]) => {
+                    self.cursor += 1;
+                    *byte
+                }
+            };
+
+            self.bits |= (byte as u64) << (56 - self.num_bits);
+            self.num_bits += 8;
+        }
+    }
+
+    pub fn peek_bits(&mut self, n: u32) -> u32 {
+        debug_assert!(n > 0 && n <= 32);
+
+        if self.num_bits < n {
+            self.fill();
+        }
+
+        (self.bits >> (64 - n)) as u32
+    }
+
+    pub fn consume_bits(&mut self, n: u32) {
+        debug_assert!(n <= self.num_bits);
+
+        self.bits <<= n;
+        self.num_bits -= n;
+    }
+
+    pub fn read_bits(&mut self, n: u8) -> u32 {
+        if n == 0 {
+            return 0;
+        }
+
+        let value = self.peek_bits(n as u32);
+        self.consume_bits(n as u32);
+
+        value
+    }
+
+    /// The RECEIVE and EXTEND procedures from F.2.2.1: reads an `s`-bit magnitude and sign
+    /// extends it into a signed difference.
+    pub fn receive_extend(&mut self, s: u8) -> i32 {
+        if s == 0 {
+            return 0;
+        }
+
+        let value = self.read_bits(s) as i32;
+
+        if value < 1 << (s - 1) {
+            value - (1 << s) + 1
+        } else {
+            value
+        }
+    }
+
+    /// Discards any buffered bits and steps over the restart marker that should follow.
+    pub fn reset(&mut self, expected_restart: u8) -> Result<()> {
+        self.bits = 0;
+        self.num_bits = 0;
+
+        // Tolerate any fill bytes preceding the marker.
+        while self.data.get(self.cursor..self.cursor + 2) == Some(&[0xFF, 0xFF]) {
+            self.cursor += 1;
+        }
+
+        match self.data.get(self.cursor..self.cursor + 2) {
+            Some(&[0xFF, marker]) if marker == 0xD0 + expected_restart => {
+                self.cursor += 2;
+                Ok(())
+            }
+            found => bail!(
+                "Expected restart marker RST{}, found {:X?}",
+                expected_restart,
+                found
+            ),
+        }
+    }
+}
+
+/// A decoding table derived from a [`HuffmanTable`] following F.2.2.3, with a prefix lookup for
+/// short codes.
+#[derive(Debug)]
+pub struct HuffmanLookup {
+    fast: Vec<(u8, u8)>,
+    max_code: [i32; 17],
+    value_offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl HuffmanLookup {
+    pub fn new(table: &HuffmanTable) -> Result<Self> {
+        ensure!(
+            table.values.len() <= 256,
+            "Huffman table holds more than 256 values."
+        );
+
+        let mut fast = vec![(0, 0); 1 << FAST_BITS];
+        let mut max_code = [-1; 17];
+        let mut value_offset = [0; 17];
+
+        let mut code = 0_u32;
+        let mut k = 0_usize;
+
+        for length in 1..=16 {
+            let count = table.code_lengths[length - 1] as usize;
+
+            value_offset[length] = k as i32 - code as i32;
+
+            for _ in 0..count {
+                let value = *table
+                    .values
+                    .get(k)
+                    .ok_or_else(|| anyhow!("Huffman table is missing values."))?;
+
+                // Checked before filling the fast table, which an extra code would overrun.
+                ensure!(
+                    code < 1 << length,
+                    "Huffman code lengths are oversubscribed."
+                );
+
+                if length as u32 <= FAST_BITS {
+                    let shift = FAST_BITS - length as u32;
+                    let start = (code << shift) as usize;
+
+                    for entry in &mut fast[start..start + (1 << shift)] {
+                        *entry = (length as u8, value);
+                    }
+                }
+
+                code += 1;
+                k += 1;
+            }
+
+            if count > 0 {
+                max_code[length] = code as i32 - 1;
+            }
+
+            code <<= 1;
+        }
+
+        Ok(Self {
+            fast,
+            max_code,
+            value_offset,
+            values: table.values.clone(),
+        })
+    }
+
+    pub fn decode(&self, reader: &mut BitReader) -> Result<u8> {
+        let (length, value) = self.fast[reader.peek_bits(FAST_BITS) as usize];
+
+        if length > 0 {
+            reader.consume_bits(length as u32);
+            return Ok(value);
+        }
+
+        let bits = reader.peek_bits(16);
+
+        for length in (FAST_BITS as usize + 1)..=16 {
+            let code = (bits >> (16 - length)) as i32;
+
+            if code <= self.max_code[length] {
+                reader.consume_bits(length as u32);
+                return Ok(self.values[(self.value_offset[length] + code) as usize]);
+            }
+        }
+
+        bail!("Encountered an invalid Huffman code.")
+    }
+}
+
+/// The DC and AC decoding tables selectable by a scan, indexed by table identifier.
+#[derive(Debug, Default)]
+pub struct HuffmanTables {
+    dc: [Option<HuffmanLookup>; 4],
+    ac: [Option<HuffmanLookup>; 4],
+}
+
+impl HuffmanTables {
+    pub fn new(tables: &[HuffmanTable]) -> Result<Self> {
+        let mut lookups = Self::default();
+
+        for table in tables {
+            let id = table.table_identifier() as usize;
+            ensure!(id < 4, "Huffman table identifier out of range: {id}");
+
+            let slot = match table.table_class() {
+                HuffmanTableClass::DC => &mut lookups.dc[id],
+                HuffmanTableClass::AC => &mut lookups.ac[id],
+            };
+
+            *slot = Some(HuffmanLookup::new(table)?);
+        }
+
+        Ok(lookups)
+    }
+
+    pub fn dc(&self, id: u8) -> Result<&HuffmanLookup> {
+        self.dc
+            .get(id as usize)
+            .and_then(Option::as_ref)
+            .ok_or_else(|| anyhow!("Missing DC Huffman table {id}"))
+    }
+
+    pub fn ac(&self, id: u8) -> Result<&HuffmanLookup> {
+        self.ac
+            .get(id as usize)
+            .and_then(Option::as_ref)
+            .ok_or_else(|| anyhow!("Missing AC Huffman table {id}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_codes_across_lengths() -> Result<()> {
+        // One 2-bit code, then twelve codes ending in a 12-bit code to exercise the slow path.
+        let mut code_lengths = [0; 16];
+        code_lengths[1] = 1;
+        code_lengths[11] = 1;
+
+        let table = HuffmanTable {
+            flag: 0,
+            code_lengths,
+            values: vec![7, 42],
+        };
+
+        let lookup = HuffmanLookup::new(&table)?;
+
+        // codes: 00 -> 7, 010000000000 -> 42
+        let data = [0b0001_0000, 0b0000_0000, 0b0011_1111];
+        let mut reader = BitReader::new(&data);
+
+        assert_eq!(lookup.decode(&mut reader)?, 7);
+        assert_eq!(lookup.decode(&mut reader)?, 42);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_oversubscribed_code_lengths() {
+        // Three 1-bit codes don't fit in the two a single bit has.
+        let mut code_lengths = [0; 16];
+        code_lengths[0] = 3;
+
+        let table = HuffmanTable {
+            flag: 0,
+            code_lengths,
+            values: vec![0, 1, 2],
+        };
+
+        assert!(HuffmanLookup::new(&table).is_err());
+    }
+
+    #[test]
+    fn test_reader_unstuffs_and_stops_at_markers() {
+        let data = [0xFF, 0x00, 0xAB, 0xFF, 0xD9];
+        let mut reader = BitReader::new(&data);
+
+        assert_eq!(reader.read_bits(8), 0xFF);
+        assert_eq!(reader.read_bits(8), 0xAB);
+        assert_eq!(reader.read_bits(8), 0);
+        assert_eq!(reader.receive_extend(3), -7);
+    }
+}
@@ -0,0 +1,119 @@
+#![allow(clippy::suboptimal_flops)]
+
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+/// A separable floating point inverse DCT (A.3.3). Reduced sizes only transform the top-left
+/// `size` x `size` coefficients, producing a downscaled block directly.
+#[derive(Debug)]
+pub struct Idct {
+    size: usize,
+    // basis[x][u] = C(u) / 2 * cos((2x + 1)uπ / 2N)
+    basis: [[f32; 8]; 8],
+}
+
+impl Idct {
+    /// Creates a transform producing `size` x `size` blocks, where `size` is 1, 2, 4 or 8.
+    pub fn new(size: usize) -> Self {
+        debug_assert!(matches!(size, 1 | 2 | 4 | 8));
+
+        let mut basis = [[0.0; 8]; 8];
+
+        for (x, row) in basis.iter_mut().enumerate().take(size) {
+            for (u, b) in row.iter_mut().enumerate().take(size) {
+                let c = if u == 0 { FRAC_1_SQRT_2 } else { 1.0 };
+                *b = c / 2.0 * ((2 * x + 1) as f32 * u as f32 * PI / (2 * size) as f32).cos();
+            }
+        }
+
+        Self { size, basis }
+    }
+
+    /// Dequantizes `block` with `quantization_table` (both in natural order) and writes the
+    /// level shifted samples, clamped to `[0, max_sample]`, into `out` with the given stride.
+    pub fn transform(
+        &self,
+        block: &[i16; 64],
+        quantization_table: &[u16; 64],
+        max_sample: u16,
+        out: &mut [u16],
+        stride: usize,
+    ) {
+        let size = self.size;
+        let mut coefficients = [0.0_f32; 64];
+
+        for i in 0..64 {
+            coefficients[i] = block[i] as f32 * quantization_table[i] as f32;
+        }
+
+        // Rows: tmp[v][x] = Σ_u F[v][u] basis[x][u]
+        let mut tmp = [0.0_f32; 64];
+
+        for v in 0..size {
+            let row = &coefficients[v * 8..v * 8 + size];
+
+            if row[1..].iter().all(|&c| c == 0.0) {
+                tmp[v * 8..v * 8 + size].fill(row[0] * self.basis[0][0]);
+                continue;
+            }
+
+            for x in 0..size {
+                tmp[v * 8 + x] = (0..size).map(|u| row[u] * self.basis[x][u]).sum();
+            }
+        }
+
+        // Columns: f[y][x] = Σ_v tmp[v][x] basis[y][v]
+        let level_shift = (max_sample as f32 + 1.0) / 2.0;
+
+        for y in 0..size {
+            for x in 0..size {
+                let sample = (0..size)
+                    .map(|v| tmp[v * 8 + x] * self.basis[y][v])
+                    .sum::<f32>()
+                    + level_shift;
+
+                out[y * stride + x] = sample.round().clamp(0.0, max_sample as f32) as u16;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dc_only_block_is_flat() {
+        let idct = Idct::new(8);
+
+        let mut block = [0; 64];
+        block[0] = 80; // mean of +10 over the block after the 1/8 DC scale
+
+        let mut out = [0; 64];
+        idct.transform(&block, &[1; 64], 255, &mut out, 8);
+
+        assert!(out.iter().all(|&s| s == 138));
+    }
+
+    #[test]
+    fn test_reduced_sizes_preserve_the_block_mean() {
+        let mut block = [0; 64];
+        block[0] = 80;
+        block[1] = -12;
+        block[9] = 30;
+
+        let mut full = [0; 64];
+        Idct::new(8).transform(&block, &[1; 64], 255, &mut full, 8);
+        let mean = full.iter().map(|&s| s as f32).sum::<f32>() / 64.0;
+
+        for size in [1, 2, 4] {
+            let mut out = [0; 64];
+            Idct::new(size).transform(&block, &[1; 64], 255, &mut out, size);
+
+            let samples = &out[..size * size];
+            let reduced_mean =
+                samples.iter().map(|&s| s as f32).sum::<f32>() / samples.len() as f32;
+
+            assert!((reduced_mean - mean).abs() < 1.0, "size {size}");
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use crate::jpeg::{
+    color_convert::Plane,
+    grammar::{StartOfFrame, StartOfScan},
+    huffman::{BitReader, HuffmanLookup, HuffmanTables},
+    scan::scan_component_indices,
+};
+use anyhow::{ensure, Result};
+
+/// Decodes the sample differences of a lossless scan. Implementations own the entropy decoder
+/// state.
+pub trait DifferenceDecoder {
+    /// Called between restart intervals with the index of the expected `RSTn` marker.
+    fn restart(&mut self, restart_index: u8) -> Result<()>;
+
+    /// Decodes the next difference of the scan component at `scan_component`. `left` and `above`
+    /// are the differences decoded for the neighbouring samples, or zero at the start of a line
+    /// and on the first line after a reset.
+    fn decode_difference(&mut self, scan_component: usize, left: i32, above: i32) -> Result<i32>;
+}
+
+/// Decodes the differences of a Huffman-coded lossless scan (H.1.2.2).
+#[derive(Debug)]
+pub struct HuffmanDifferenceDecoder<'a, 'b> {
+    reader: BitReader<'a>,
+    tables: Vec<&'b HuffmanLookup>,
+}
+
+impl<'a, 'b> HuffmanDifferenceDecoder<'a, 'b> {
+    pub fn new(
+        image_data: &'a [u8],
+        huffman_tables: &'b HuffmanTables,
+        table_selectors: &[(u8, u8)],
+    ) -> Result<Self> {
+        let tables = table_selectors
+            .iter()
+            .map(|&(_, selector)| huffman_tables.dc(selector >> 4))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            reader: BitReader::new(image_data),
+            tables,
+        })
+    }
+}
+
+impl DifferenceDecoder for HuffmanDifferenceDecoder<'_, '_> {
+    fn restart(&mut self, restart_index: u8) -> Result<()> {
+        self.reader.reset(restart_index)
+    }
+
+    fn decode_difference(&mut self, scan_component: usize, _: i32, _: i32) -> Result<i32> {
+        let s = self.tables[scan_component].decode(&mut self.reader)?;
+        ensure!(s <= 16, "Lossless difference category out of range: {s}");
+
+        // Category 16 has no additional bits and always means 32768 (H.1.2.2).
+        if s == 16 {
+            return Ok(32768);
+        }
+
+        Ok(self.reader.receive_extend(s))
+    }
+}
+
+/// The differences of one scan component, laid out over the sample grid it covers.
+#[derive(Debug)]
+struct Differences {
+    width: usize,
+    height: usize,
+    values: Vec<i32>,
+    // Rows that begin at the start of the scan or right after a restart, which are predicted
+    // like the first line of the image.
+    reset_rows: Vec<bool>,
+}
+
+impl Differences {
+    fn new(width: usize, height: usize) -> Self {
+        let mut reset_rows = vec![false; height];
+        reset_rows[0] = true;
+
+        Self {
+            width,
+            height,
+            values: vec![0; width * height],
+            reset_rows,
+        }
+    }
+}
+
+/// Decodes a predictive lossless scan (H.1) and writes the reconstructed samples, scaled back up
+/// by the point transform, into the planes of the components it covers.
+///
+/// Planes must span whole MCUs: interleaved scans predict from the padding as well.
+pub fn decode_scan(
+    start_of_frame: &StartOfFrame,
+    start_of_scan: &StartOfScan,
+    restart_interval: u16,
+    planes: &mut [Plane],
+    decoder: &mut impl DifferenceDecoder,
+) -> Result<()> {
+    let indices = scan_component_indices(start_of_frame, start_of_scan)?;
+    ensure!(
+        !indices.is_empty() && indices.len() <= 4,
+        "Scans must hold between 1 and 4 components."
+    );
+
+    let predictor = *start_of_scan.spectral_select.start();
+    ensure!(
+        (1..=7).contains(&predictor),
+        "Unsupported lossless predictor: {predictor}"
+    );
+
+    let precision = start_of_frame.sample_precision;
+    let point_transform = start_of_scan.successive_approximation_low();
+    ensure!(
+        point_transform < precision,
+        "Point transform must be less than the sample precision."
+    );
+
+    let mut differences = Vec::with_capacity(indices.len());
+
+    // For a single component, every sample is an MCU and the scan covers the component's
+    // samples exactly. Otherwise, samples are visited MCU by MCU.
+    let (mcus_x, mcus_y) = if let [index] = indices[..] {
+        let (width, height) =
+            start_of_frame.component_dimensions(&start_of_frame.components[index]);
+        differences.push((Differences::new(width, height), 1, 1));
+
+        (width, height)
+    } else {
+        for &index in &indices {
+            let plane = &planes[index];
+            let h = plane.horizontal_sampling_factor as usize;
+            let v = plane.vertical_sampling_factor as usize;
+
+            differences.push((Differences::new(plane.width, plane.height), h, v));
+        }
+
+        start_of_frame.mcu_dimensions()
+    };
+
+    let restart_interval = restart_interval as usize;
+    let mut mcu_count = 0;
+
+    for mcu_y in 0..mcus_y {
+        for mcu_x in 0..mcus_x {
+            for (scan_component, (component, h, v)) in differences.iter_mut().enumerate() {
+                for dy in 0..*v {
+                    for dx in 0..*h {
+                        let (x, y) = (mcu_x * *h + dx, mcu_y * *v + dy);
+                        let i = y * component.width + x;
+
+                        let left = if x > 0 { component.values[i - 1] } else { 0 };
+                        let above = if component.reset_rows[y] {
+                            0
+                        } else {
+                            component.values[i - component.width]
+                        };
+
+                        component.values[i] =
+                            decoder.decode_difference(scan_component, left, above)?;
+                    }
+                }
+            }
+
+            mcu_count += 1;
+
+            let mcus_left = mcu_y + 1 < mcus_y || mcu_x + 1 < mcus_x;
+
+            if restart_interval > 0 && mcus_left && mcu_count % restart_interval == 0 {
+                ensure!(
+                    mcu_count % mcus_x == 0,
+                    "Lossless restart intervals must span whole MCU rows."
+                );
+
+                decoder.restart(((mcu_count / restart_interval - 1) % 8) as u8)?;
+
+                for (component, _, v) in &mut differences {
+                    component.reset_rows[mcu_count / mcus_x * *v] = true;
+                }
+            }
+        }
+    }
+
+    for ((component, _, _), &index) in differences.iter().zip(&indices) {
+        undifference(
+            component,
+            predictor,
+            precision,
+            point_transform,
+            &mut planes[index],
+        );
+    }
+
+    Ok(())
+}
+
+/// Adds each difference to its prediction (H.1.2.1), modulo 2^16.
+fn undifference(
+    differences: &Differences,
+    predictor: u8,
+    precision: u8,
+    point_transform: u8,
+    plane: &mut Plane,
+) {
+    let initial_prediction = 1_i32 << (precision - point_transform - 1);
+    let stride = plane.width;
+    let samples = &mut plane.samples;
+
+    for y in 0..differences.height {
+        for x in 0..differences.width {
+            let sample = |x: usize, y: usize| samples[y * stride + x] as i32;
+
+            let prediction = match (differences.reset_rows[y], x) {
+                (true, 0) => initial_prediction,
+                (true, _) => sample(x - 1, y),
+                (false, 0) => sample(x, y - 1),
+                (false, _) => {
+                    let (ra, rb, rc) = (sample(x - 1, y), sample(x, y - 1), sample(x - 1, y - 1));
+
+                    match predictor {
+                        1 => ra,
+                        2 => rb,
+                        3 => rc,
+                        4 => ra + rb - rc,
+                        5 => ra + ((rb - rc) >> 1),
+                        6 => rb + ((ra - rc) >> 1),
+                        _ => (ra + rb) >> 1,
+                    }
+                }
+            };
+
+            let difference = differences.values[y * differences.width + x];
+            samples[y * stride + x] = ((prediction + difference) & 0xFFFF) as u16;
+        }
+    }
+
+    if point_transform > 0 {
+        for y in 0..differences.height {
+            for sample in &mut samples[y * stride..y * stride + differences.width] {
+                *sample <<= point_transform;
+            }
+        }
+    }
+}
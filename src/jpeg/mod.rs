@@ -1,4 +1,17 @@
+mod arithmetic;
+mod color_convert;
+mod compress;
 mod decoder;
+mod encoder;
+mod huffman;
+mod idct;
+mod lossless;
+mod scan;
+mod sequential;
+mod transform;
 
 pub mod grammar;
+pub use compress::*;
 pub use decoder::*;
+pub use encoder::*;
+pub use transform::*;
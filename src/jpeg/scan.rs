@@ -0,0 +1,99 @@
+use crate::jpeg::grammar::{ComponentCoefficients, StartOfFrame, StartOfScan};
+use anyhow::{anyhow, ensure, Result};
+
+/// Decodes the entropy-coded blocks of a single scan. Implementations own the bit reader and any
+/// per-scan state like DC predictors.
+pub trait BlockDecoder {
+    /// Called between restart intervals with the index of the expected `RSTn` marker.
+    fn restart(&mut self, restart_index: u8) -> Result<()>;
+
+    /// Decodes one block of the scan component at `scan_component` into `block`.
+    fn decode_block(&mut self, scan_component: usize, block: &mut [i16; 64]) -> Result<()>;
+}
+
+/// Maps each scan component selector to its index in the frame.
+pub fn scan_component_indices(
+    start_of_frame: &StartOfFrame,
+    start_of_scan: &StartOfScan,
+) -> Result<Vec<usize>> {
+    start_of_scan
+        .components
+        .iter()
+        .map(|&(selector, _)| {
+            start_of_frame
+                .components
+                .iter()
+                .position(|c| c.identifier == selector)
+                .ok_or_else(|| anyhow!("Scan references unknown component {selector}"))
+        })
+        .collect()
+}
+
+/// Walks every block of a scan in encoding order, handing each one to `decoder`.
+///
+/// A scan with a single component is not interleaved and covers only the blocks that hold image
+/// samples. Otherwise, blocks are visited MCU by MCU.
+pub fn decode_blocks(
+    start_of_frame: &StartOfFrame,
+    start_of_scan: &StartOfScan,
+    restart_interval: u16,
+    coefficients: &mut [ComponentCoefficients],
+    decoder: &mut impl BlockDecoder,
+) -> Result<()> {
+    let indices = scan_component_indices(start_of_frame, start_of_scan)?;
+    ensure!(
+        !indices.is_empty() && indices.len() <= 4,
+        "Scans must hold between 1 and 4 components."
+    );
+
+    let restart_interval = restart_interval as usize;
+    let mut mcu_count = 0;
+
+    let mut next_mcu = |decoder: &mut dyn BlockDecoder, mcus_left: bool| -> Result<()> {
+        mcu_count += 1;
+
+        if restart_interval > 0 && mcus_left && mcu_count % restart_interval == 0 {
+            decoder.restart(((mcu_count / restart_interval - 1) % 8) as u8)?;
+        }
+
+        Ok(())
+    };
+
+    if let [index] = indices[..] {
+        let (width, height) =
+            start_of_frame.component_dimensions(&start_of_frame.components[index]);
+        let (blocks_x, blocks_y) = (width.div_ceil(8), height.div_ceil(8));
+
+        for y in 0..blocks_y {
+            for x in 0..blocks_x {
+                decoder.decode_block(0, coefficients[index].block_mut(x, y))?;
+                next_mcu(decoder, y + 1 < blocks_y || x + 1 < blocks_x)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    let (mcus_x, mcus_y) = start_of_frame.mcu_dimensions();
+
+    for mcu_y in 0..mcus_y {
+        for mcu_x in 0..mcus_x {
+            for (scan_component, &index) in indices.iter().enumerate() {
+                let component = &mut coefficients[index];
+                let h = component.horizontal_sampling_factor as usize;
+                let v = component.vertical_sampling_factor as usize;
+
+                for dy in 0..v {
+                    for dx in 0..h {
+                        let block = component.block_mut(mcu_x * h + dx, mcu_y * v + dy);
+                        decoder.decode_block(scan_component, block)?;
+                    }
+                }
+            }
+
+            next_mcu(decoder, mcu_y + 1 < mcus_y || mcu_x + 1 < mcus_x)?;
+        }
+    }
+
+    Ok(())
+}
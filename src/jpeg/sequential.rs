@@ -0,0 +1,80 @@
+use crate::jpeg::{
+    grammar::ZIGZAG,
+    huffman::{BitReader, HuffmanLookup, HuffmanTables},
+    scan::BlockDecoder,
+};
+use anyhow::{ensure, Result};
+
+/// Decodes the blocks of a Huffman-coded sequential DCT scan (F.2.2).
+#[derive(Debug)]
+pub struct SequentialDecoder<'a, 'b> {
+    reader: BitReader<'a>,
+    tables: Vec<(&'b HuffmanLookup, &'b HuffmanLookup)>,
+    predictors: Vec<i32>,
+}
+
+impl<'a, 'b> SequentialDecoder<'a, 'b> {
+    pub fn new(
+        image_data: &'a [u8],
+        huffman_tables: &'b HuffmanTables,
+        table_selectors: &[(u8, u8)],
+    ) -> Result<Self> {
+        let tables = table_selectors
+            .iter()
+            .map(|&(_, selector)| {
+                Ok((
+                    huffman_tables.dc(selector >> 4)?,
+                    huffman_tables.ac(selector & 0b1111)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            reader: BitReader::new(image_data),
+            predictors: vec![0; tables.len()],
+            tables,
+        })
+    }
+}
+
+impl BlockDecoder for SequentialDecoder<'_, '_> {
+    fn restart(&mut self, restart_index: u8) -> Result<()> {
+        self.predictors.fill(0);
+        self.reader.reset(restart_index)
+    }
+
+    fn decode_block(&mut self, scan_component: usize, block: &mut [i16; 64]) -> Result<()> {
+        let (dc_table, ac_table) = self.tables[scan_component];
+
+        let s = dc_table.decode(&mut self.reader)?;
+        ensure!(s <= 16, "DC difference category out of range: {s}");
+
+        let predictor = &mut self.predictors[scan_component];
+        *predictor += self.reader.receive_extend(s);
+        block[0] = *predictor as i16;
+
+        let mut k = 1;
+
+        while k < 64 {
+            let rs = ac_table.decode(&mut self.reader)?;
+            let (r, s) = (rs >> 4, rs & 0b1111);
+
+            if s == 0 {
+                if r != 15 {
+                    break;
+                }
+
+                k += 16;
+                continue;
+            }
+
+            k += r as usize;
+            ensure!(k < 64, "AC coefficient index out of range.");
+
+            block[ZIGZAG[k]] = self.reader.receive_extend(s) as i16;
+            k += 1;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,370 @@
+use crate::jpeg::grammar::{ComponentCoefficients, JpegCoefficients, ZIGZAG};
+use anyhow::{ensure, Result};
+
+/// A lossless transform of the DCT coefficients, like those of `jpegtran`.
+///
+/// Mirroring moves the partial MCUs at the right or bottom edge to the opposite side, where they
+/// can't be represented. Like `jpegtran -trim`, those edges are dropped instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JpegTransform {
+    FlipHorizontal,
+    FlipVertical,
+    /// Mirrors the image along its main diagonal.
+    Transpose,
+    /// Rotates the image clockwise.
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    /// Crops the image to a rectangle whose top left corner lies on an MCU boundary.
+    Crop {
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+    },
+}
+
+impl JpegCoefficients {
+    pub fn transform(&self, transform: JpegTransform) -> Result<Self> {
+        let transformed = match transform {
+            JpegTransform::FlipHorizontal => self.trim(true, false)?.flip_horizontal(),
+            JpegTransform::FlipVertical => self.trim(false, true)?.flip_vertical(),
+            JpegTransform::Transpose => self.transpose(),
+            JpegTransform::Rotate90 => self.transpose().trim(true, false)?.flip_horizontal(),
+            JpegTransform::Rotate180 => self.trim(true, true)?.flip_horizontal().flip_vertical(),
+            JpegTransform::Rotate270 => self.transpose().trim(false, true)?.flip_vertical(),
+            JpegTransform::Crop {
+                x,
+                y,
+                width,
+                height,
+            } => self.crop(x, y, width, height)?,
+        };
+
+        Ok(transformed)
+    }
+
+    fn crop(&self, x: u16, y: u16, width: u16, height: u16) -> Result<Self> {
+        let (mcu_width, mcu_height) = self.mcu_size();
+
+        ensure!(width > 0 && height > 0, "Crop dimensions must be non-zero.");
+        ensure!(
+            x as usize + width as usize <= self.width as usize
+                && y as usize + height as usize <= self.height as usize,
+            "Crop exceeds the image bounds."
+        );
+        ensure!(
+            (x as usize).is_multiple_of(mcu_width) && (y as usize).is_multiple_of(mcu_height),
+            "Crop offsets must be multiples of the MCU size ({mcu_width}x{mcu_height})."
+        );
+
+        let (mcus_x, mcus_y) = (x as usize / mcu_width, y as usize / mcu_height);
+
+        Ok(
+            self.rebuild(width, height, false, |component, block_x, block_y| {
+                let block_x = mcus_x * component.horizontal_sampling_factor as usize + block_x;
+                let block_y = mcus_y * component.vertical_sampling_factor as usize + block_y;
+
+                if block_x < component.blocks_per_line && block_y < component.blocks_per_column {
+                    *component.block(block_x, block_y)
+                } else {
+                    [0; 64]
+                }
+            }),
+        )
+    }
+
+    /// Drops the partial MCUs at the right and/or bottom edge.
+    fn trim(&self, horizontal: bool, vertical: bool) -> Result<Self> {
+        let (mcu_width, mcu_height) = self.mcu_size();
+
+        let mut width = self.width;
+        let mut height = self.height;
+
+        if horizontal {
+            width -= width % mcu_width as u16;
+        }
+
+        if vertical {
+            height -= height % mcu_height as u16;
+        }
+
+        ensure!(
+            width > 0 && height > 0,
+            "The image is smaller than a single MCU and can't be mirrored."
+        );
+
+        self.crop(0, 0, width, height)
+    }
+
+    /// Mirrors blocks left to right. The width must span whole MCUs.
+    fn flip_horizontal(&self) -> Self {
+        self.rebuild(self.width, self.height, false, |component, x, y| {
+            let mut block = *component.block(component.blocks_per_line - 1 - x, y);
+
+            // Odd horizontal frequencies change sign when mirrored.
+            for (i, coefficient) in block.iter_mut().enumerate() {
+                if i % 2 == 1 {
+                    *coefficient = -*coefficient;
+                }
+            }
+
+            block
+        })
+    }
+
+    /// Mirrors blocks top to bottom. The height must span whole MCUs.
+    fn flip_vertical(&self) -> Self {
+        self.rebuild(self.width, self.height, false, |component, x, y| {
+            let mut block = *component.block(x, component.blocks_per_column - 1 - y);
+
+            // Odd vertical frequencies change sign when mirrored.
+            for (i, coefficient) in block.iter_mut().enumerate() {
+                if (i / 8) % 2 == 1 {
+                    *coefficient = -*coefficient;
+                }
+            }
+
+            block
+        })
+    }
+
+    /// Mirrors blocks along the main diagonal. The quantization tables are transposed with
+    /// the coefficients.
+    fn transpose(&self) -> Self {
+        let mut transposed = self.rebuild(self.height, self.width, true, |component, x, y| {
+            let source = component.block(y, x);
+            let mut block = [0; 64];
+
+            for (i, coefficient) in block.iter_mut().enumerate() {
+                *coefficient = source[transposed_index(i)];
+            }
+
+            block
+        });
+
+        for quantization_table in &mut transposed.quantization_tables {
+            let natural_order = quantization_table.natural_order();
+
+            for (k, element) in quantization_table.table_elements.iter_mut().enumerate() {
+                *element = natural_order[transposed_index(ZIGZAG[k])];
+            }
+        }
+
+        transposed
+    }
+
+    /// Lays out new components for an image of the given size, taking each block from
+    /// `block(component, x, y)`. Transposed images swap their sampling factors.
+    fn rebuild(
+        &self,
+        width: u16,
+        height: u16,
+        transposed: bool,
+        block: impl Fn(&ComponentCoefficients, usize, usize) -> [i16; 64],
+    ) -> Self {
+        let (mut h_max, mut v_max) = self.max_sampling_factors();
+
+        if transposed {
+            (h_max, v_max) = (v_max, h_max);
+        }
+
+        let mcus_x = (width as usize).div_ceil(8 * h_max as usize);
+        let mcus_y = (height as usize).div_ceil(8 * v_max as usize);
+
+        let components = self
+            .components
+            .iter()
+            .map(|component| {
+                let (mut h, mut v) = (
+                    component.horizontal_sampling_factor,
+                    component.vertical_sampling_factor,
+                );
+
+                if transposed {
+                    (h, v) = (v, h);
+                }
+
+                let blocks_per_line = mcus_x * h as usize;
+                let blocks_per_column = mcus_y * v as usize;
+
+                let blocks = (0..blocks_per_column)
+                    .flat_map(|y| (0..blocks_per_line).map(move |x| (x, y)))
+                    .map(|(x, y)| block(component, x, y))
+                    .collect();
+
+                ComponentCoefficients {
+                    horizontal_sampling_factor: h,
+                    vertical_sampling_factor: v,
+                    blocks_per_line,
+                    blocks_per_column,
+                    blocks,
+                    ..*component
+                }
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            components,
+            icc_profile: self.icc_profile.clone(),
+            quantization_tables: self.quantization_tables.clone(),
+            ..*self
+        }
+    }
+}
+
+/// Maps the natural index of a coefficient to that of its transpose.
+const fn transposed_index(i: usize) -> usize {
+    (i % 8) * 8 + i / 8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::{grammar::Jpeg, JpegDecoder, JpegEncoder};
+
+    fn reference() -> Result<JpegCoefficients> {
+        // 100x70 with 4:2:0 subsampling, so neither dimension spans whole MCUs.
+        let data = std::fs::read("./tests/arithmetic_reference.jpg")?;
+        JpegDecoder::new(&data).decode_coefficients()
+    }
+
+    fn decode(coefficients: &JpegCoefficients) -> Result<Jpeg> {
+        let mut encoded = Vec::new();
+        JpegEncoder::new(&mut encoded).encode(coefficients)?;
+
+        JpegDecoder::new(&encoded).decode()
+    }
+
+    /// Maps a pixel of the transformed image to the pixel of the original it came from.
+    type Source = fn(usize, usize) -> (usize, usize);
+
+    /// Compares `jpeg` against `reference` sampled at `source(x, y)` for every pixel of `jpeg`.
+    fn mean_absolute_error(
+        jpeg: &Jpeg,
+        reference: &Jpeg,
+        source: impl Fn(usize, usize) -> (usize, usize),
+    ) -> f64 {
+        let (width, height) = (jpeg.width as usize, jpeg.height as usize);
+        let mut error = 0.0;
+
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = source(x, y);
+                let i = (y * width + x) * 3;
+                let j = (sy * reference.width as usize + sx) * 3;
+
+                for c in 0..3 {
+                    error += (jpeg.pixel_buffer[i + c] as f64
+                        - reference.pixel_buffer[j + c] as f64)
+                        .abs();
+                }
+            }
+        }
+
+        error / (width * height * 3) as f64
+    }
+
+    #[test]
+    fn test_transforms_compose() -> Result<()> {
+        let coefficients = reference()?;
+        let aligned = coefficients.transform(JpegTransform::Crop {
+            x: 0,
+            y: 0,
+            width: 96,
+            height: 64,
+        })?;
+
+        let mut rotated = coefficients.clone();
+
+        for _ in 0..4 {
+            rotated = rotated.transform(JpegTransform::Rotate90)?;
+        }
+
+        assert_eq!((rotated.width, rotated.height), (96, 64));
+        assert_eq!(rotated.components, aligned.components);
+
+        let flipped = coefficients
+            .transform(JpegTransform::FlipHorizontal)?
+            .transform(JpegTransform::FlipVertical)?;
+        let rotated = coefficients.transform(JpegTransform::Rotate180)?;
+        assert_eq!(flipped.components, rotated.components);
+
+        let transposed = coefficients
+            .transform(JpegTransform::Transpose)?
+            .transform(JpegTransform::Transpose)?;
+        assert_eq!(transposed.components, coefficients.components);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transforms_match_pixels() -> Result<()> {
+        let coefficients = reference()?;
+        let original = decode(&coefficients)?;
+
+        let cases: [(JpegTransform, (u32, u32), Source); 6] = [
+            (JpegTransform::FlipHorizontal, (96, 70), |x, y| (95 - x, y)),
+            (JpegTransform::FlipVertical, (100, 64), |x, y| (x, 63 - y)),
+            (JpegTransform::Transpose, (70, 100), |x, y| (y, x)),
+            (JpegTransform::Rotate90, (64, 100), |x, y| (y, 63 - x)),
+            (JpegTransform::Rotate180, (96, 64), |x, y| (95 - x, 63 - y)),
+            (JpegTransform::Rotate270, (70, 96), |x, y| (95 - y, x)),
+        ];
+
+        for (transform, dimensions, source) in cases {
+            let jpeg = decode(&coefficients.transform(transform)?)?;
+
+            assert_eq!((jpeg.width, jpeg.height), dimensions, "{transform:?}");
+
+            // Only chroma upsampling along the trimmed edges can differ.
+            let error = mean_absolute_error(&jpeg, &original, source);
+            assert!(error < 0.5, "{transform:?}: {error}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crop() -> Result<()> {
+        let coefficients = reference()?;
+        let original = decode(&coefficients)?;
+
+        let cropped = coefficients.transform(JpegTransform::Crop {
+            x: 16,
+            y: 32,
+            width: 50,
+            height: 38,
+        })?;
+        let jpeg = decode(&cropped)?;
+
+        assert_eq!((jpeg.width, jpeg.height), (50, 38));
+
+        // Away from the edges, where chroma is upsampled from different neighbours, the crop
+        // is exact.
+        for y in 2..36 {
+            for x in 2..48 {
+                let i = (y * 50 + x) * 3;
+                let j = ((y + 32) * 100 + x + 16) * 3;
+
+                assert_eq!(
+                    jpeg.pixel_buffer[i..i + 3],
+                    original.pixel_buffer[j..j + 3],
+                    "({x}, {y})"
+                );
+            }
+        }
+
+        let misaligned = coefficients.transform(JpegTransform::Crop {
+            x: 8,
+            y: 0,
+            width: 16,
+            height: 16,
+        });
+        assert!(misaligned.is_err());
+
+        Ok(())
+    }
+}
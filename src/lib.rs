@@ -3,10 +3,15 @@
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+pub mod color;
+pub mod document;
+pub mod effects;
 pub mod font;
 pub mod image;
 pub mod jpeg;
+pub mod metrics;
 pub mod png;
+pub mod recipe;
 pub mod renderer;
 
 pub mod event_log;
@@ -1,27 +1,40 @@
 use anyhow::{anyhow, Result};
 use norm::{
-    image::{grammar::ImageKind, ImageReader},
+    document::{self, EditDocument},
+    image::ImageReader,
     renderer,
 };
 use pollster::block_on;
+use std::path::{Path, PathBuf};
 
 fn main() -> Result<()> {
     let mut args = std::env::args().skip(1);
-    let image_path = args
-        .next()
-        .ok_or_else(|| anyhow!("Failed to read image path"))?;
+    let path = PathBuf::from(
+        args.next()
+            .ok_or_else(|| anyhow!("Failed to read image path"))?,
+    );
 
-    let image_kind = args
-        .next()
-        .map(|t| match t.as_bytes() {
-            b"jpg" | b"jpeg" | b"j" => ImageKind::Jpeg,
-            _ => ImageKind::Png,
-        })
-        .unwrap_or(ImageKind::Png);
+    // Opening a document picks its edits up over the image it names. Ctrl+Shift+S saves the
+    // edits to it, or beside the image when one is opened directly.
+    let (document, document_path) = if path
+        .extension()
+        .is_some_and(|ext| ext == document::EXTENSION)
+    {
+        (EditDocument::load(&path)?, path)
+    } else {
+        (EditDocument::new(&path), EditDocument::default_path(&path))
+    };
 
-    let image = ImageReader::read_from_path(&image_path, Some(image_kind))?;
+    let image = ImageReader::read_from_path(&document.source, None)?;
 
-    let _ = block_on(renderer::run(image));
+    // Ctrl+S saves the edited image next to the original.
+    let image_path = Path::new(&document.source);
+    let stem = image_path
+        .file_stem()
+        .ok_or_else(|| anyhow!("Failed to read image file name"))?;
+    let export_path = image_path.with_file_name(format!("{}-edited.png", stem.to_string_lossy()));
+
+    let _ = block_on(renderer::run(image, document, document_path, export_path));
 
     Ok(())
 }
@@ -0,0 +1,186 @@
+#![allow(clippy::suboptimal_flops)]
+
+use crate::{
+    image::{grammar::ImageExt, ImageBuffer, Luma8, Subpixel},
+    metrics::{ensure_same_dimensions, lab::lab_pixels},
+};
+use anyhow::Result;
+
+/// The CIEDE2000 color difference of every pixel of two images, in CIELAB relative to D50. A
+/// difference of about 1.0 is just noticeable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeltaEMap {
+    width: u32,
+    height: u32,
+    values: Vec<f32>,
+}
+
+impl DeltaEMap {
+    pub fn new(reference: &dyn ImageExt, test: &dyn ImageExt) -> Result<Self> {
+        let (width, height) = ensure_same_dimensions(reference, test)?;
+
+        let values = lab_pixels(reference)?
+            .into_iter()
+            .zip(lab_pixels(test)?)
+            .map(|(a, b)| ciede2000(a, b))
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            values,
+        })
+    }
+
+    pub const fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn value(&self, x: u32, y: u32) -> f32 {
+        self.values[y as usize * self.width as usize + x as usize]
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    pub fn mean(&self) -> f32 {
+        let sum = self.values.iter().map(|&v| f64::from(v)).sum::<f64>();
+
+        (sum / self.values.len().max(1) as f64) as f32
+    }
+
+    pub fn max(&self) -> f32 {
+        self.values.iter().copied().fold(0.0, f32::max)
+    }
+
+    /// Renders the map in grayscale, from black for identical colors to white for differences
+    /// of `scale` or more.
+    pub fn to_image(&self, scale: f32) -> ImageBuffer<Luma8> {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            Luma8([u8::from_f32(self.value(x, y) / scale)])
+        })
+    }
+}
+
+/// The CIEDE2000 difference of two CIELAB colors.
+///
+/// <https://hajim.rochester.edu/ece/sites/gsharma/ciede2000/ciede2000noteCRNA.pdf>
+pub fn ciede2000(reference: [f32; 3], test: [f32; 3]) -> f32 {
+    let [l1, a1, b1] = reference.map(f64::from);
+    let [l2, a2, b2] = test.map(f64::from);
+
+    let pow7 = |x: f64| x.powi(7);
+    let chroma_weight = |c: f64| (pow7(c) / (pow7(c) + pow7(25.0))).sqrt();
+
+    // Stretch a* so that near-neutral colors get more hue resolution.
+    let mean_chroma = (a1.hypot(b1) + a2.hypot(b2)) / 2.0;
+    let g = 0.5 * (1.0 - chroma_weight(mean_chroma));
+
+    let (a1, a2) = ((1.0 + g) * a1, (1.0 + g) * a2);
+    let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+
+    let hue = |a: f64, b: f64| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).to_degrees().rem_euclid(360.0)
+        }
+    };
+    let (h1, h2) = (hue(a1, b1), hue(a2, b2));
+
+    let delta_l = l2 - l1;
+    let delta_c = c2 - c1;
+    let delta_h = if c1 * c2 == 0.0 {
+        0.0
+    } else {
+        match h2 - h1 {
+            d if d > 180.0 => d - 360.0,
+            d if d < -180.0 => d + 360.0,
+            d => d,
+        }
+    };
+    let delta_big_h = 2.0 * (c1 * c2).sqrt() * (delta_h / 2.0).to_radians().sin();
+
+    let mean_l = (l1 + l2) / 2.0;
+    let mean_c = (c1 + c2) / 2.0;
+    let mean_h = if c1 * c2 == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) / 2.0
+    } else {
+        (h1 + h2 - 360.0) / 2.0
+    };
+
+    let cos = |degrees: f64| degrees.to_radians().cos();
+    let t =
+        1.0 - 0.17 * cos(mean_h - 30.0) + 0.24 * cos(2.0 * mean_h) + 0.32 * cos(3.0 * mean_h + 6.0)
+            - 0.20 * cos(4.0 * mean_h - 63.0);
+
+    let delta_theta = 30.0 * (-((mean_h - 275.0) / 25.0).powi(2)).exp();
+    let rotation = -2.0 * chroma_weight(mean_c) * (2.0 * delta_theta).to_radians().sin();
+
+    let l_offset = (mean_l - 50.0).powi(2);
+    let s_l = 1.0 + 0.015 * l_offset / (20.0 + l_offset).sqrt();
+    let s_c = 1.0 + 0.045 * mean_c;
+    let s_h = 1.0 + 0.015 * mean_c * t;
+
+    let (l, c, h) = (delta_l / s_l, delta_c / s_c, delta_big_h / s_h);
+
+    (l * l + c * c + h * h + rotation * c * h).sqrt() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{ImageReader, Rgb8};
+
+    #[test]
+    fn test_ciede2000() {
+        // Pairs from Sharma, Wu and Dalal's test data.
+        let pairs = [
+            ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+            ([50.0, 3.1571, -77.2803], [50.0, 0.0, -82.7485], 2.8615),
+            ([50.0, 2.8361, -74.0200], [50.0, 0.0, -82.7485], 3.4412),
+            ([50.0, 0.0, 0.0], [50.0, -1.0, 2.0], 2.3669),
+            ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+            (
+                [60.2574, -34.0099, 36.2677],
+                [60.4626, -34.1751, 39.4387],
+                1.2644,
+            ),
+        ];
+
+        for (reference, test, expected) in pairs {
+            let delta_e = ciede2000(reference, test);
+            assert!(
+                (delta_e - expected).abs() < 1e-4,
+                "{reference:?}: {delta_e}"
+            );
+
+            // The difference is symmetric.
+            assert!((ciede2000(test, reference) - delta_e).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_delta_e_map() -> Result<()> {
+        let image = ImageReader::read_from_path("./test_suite/basn2c08.png", None)?;
+        let map = DeltaEMap::new(image.as_ref(), image.as_ref())?;
+        assert_eq!(map.max(), 0.0);
+
+        let reference = ImageBuffer::from_pixel(2, 2, Rgb8([128, 128, 128]));
+        let mut test = reference.clone();
+        test.put_pixel(1, 1, Rgb8([128, 128, 160]));
+
+        let map = DeltaEMap::new(&reference, &test)?;
+        assert_eq!(map.value(0, 0), 0.0);
+        assert!(map.value(1, 1) > 5.0);
+        assert!((map.mean() - map.max() / 4.0).abs() < 1e-5);
+        assert_eq!(map.to_image(map.max()).pixel(1, 1), &Luma8([255]));
+
+        Ok(())
+    }
+}
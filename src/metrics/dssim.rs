@@ -0,0 +1,62 @@
+use crate::{
+    image::{grammar::ImageExt, ImageBuffer, Rgba32F},
+    metrics::{ensure_same_dimensions, lab::lab_pixels, Ssim, SsimChannels},
+};
+use anyhow::Result;
+
+/// The structural dissimilarity of two images, `1 / SSIM - 1`.
+///
+/// SSIM is averaged over the CIELAB lightness and both chroma channels. Identical images score
+/// 0, and the score grows without bound as they diverge.
+pub fn dssim(reference: &dyn ImageExt, test: &dyn ImageExt) -> Result<f32> {
+    let (width, height) = ensure_same_dimensions(reference, test)?;
+
+    let lab_image = |image: &dyn ImageExt| -> Result<ImageBuffer<Rgba32F>> {
+        let pixels = lab_pixels(image)?
+            .into_iter()
+            .map(|[l, a, b]| Rgba32F([l / 100.0, (a + 128.0) / 255.0, (b + 128.0) / 255.0, 1.0]))
+            .collect();
+
+        ImageBuffer::from_pixels(width, height, pixels)
+    };
+
+    let ssim = Ssim::new()
+        .with_channels(SsimChannels::Rgb)
+        .map(&lab_image(reference)?, &lab_image(test)?)?
+        .mean();
+
+    Ok(1.0 / ssim.max(f32::EPSILON) - 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{ImageReader, Rgba8};
+
+    #[test]
+    fn test_dssim() -> Result<()> {
+        let image = ImageReader::read_from_path("./test_suite/basn2c08.png", None)?;
+        let reference =
+            ImageBuffer::<Rgba8>::from_raw(image.width(), image.height(), &image.rgba8())?;
+        assert!(dssim(&reference, &reference)?.abs() < 1e-5);
+
+        // Shifting the hue keeps the structure of lightness but not of chroma.
+        let mut swapped = reference.clone();
+        swapped
+            .pixels_mut()
+            .iter_mut()
+            .for_each(|pixel| pixel.0.swap(0, 2));
+
+        let mut darker = reference.clone();
+        darker
+            .pixels_mut()
+            .iter_mut()
+            .for_each(|pixel| pixel.0[..3].iter_mut().for_each(|c| *c /= 2));
+
+        let swapped_dssim = dssim(&reference, &swapped)?;
+        assert!(swapped_dssim > 0.01);
+        assert!(dssim(&reference, &darker)? > 0.0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use crate::{
+    color::{xyz_to_lab, ColorProfile},
+    image::grammar::ImageExt,
+};
+use anyhow::Result;
+
+/// Converts every pixel to CIELAB relative to D50, through the image's color profile. Alpha is
+/// ignored.
+pub fn lab_pixels(image: &dyn ImageExt) -> Result<Vec<[f32; 3]>> {
+    let linear_srgb = ColorProfile::linear_srgb();
+    let matrix = *linear_srgb
+        .rgb_to_xyz()
+        .expect("Linear sRGB is a matrix profile");
+
+    let rgba = image.rgba_f32_in(&linear_srgb)?;
+
+    Ok(rgba
+        .chunks_exact(4)
+        .map(|p| {
+            let xyz = matrix.map(|row| row[2].mul_add(p[2], row[1].mul_add(p[1], row[0] * p[0])));
+
+            xyz_to_lab(xyz)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{ImageBuffer, Rgb8};
+
+    #[test]
+    fn test_lab_pixels() -> Result<()> {
+        let image = ImageBuffer::from_fn(3, 1, |x, _| {
+            [Rgb8([255, 255, 255]), Rgb8([0, 0, 0]), Rgb8([255, 0, 0])][x as usize]
+        });
+
+        let lab = lab_pixels(&image)?;
+
+        // White is L = 100 with no chroma, and sRGB red as published for D50.
+        let expected = [[100.0, 0.0, 0.0], [0.0, 0.0, 0.0], [54.29, 80.80, 69.89]];
+
+        for (pixel, expected) in lab.iter().zip(expected) {
+            for (value, expected) in pixel.iter().zip(expected) {
+                assert!((value - expected).abs() < 0.1, "{pixel:?}");
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+use crate::image::grammar::ImageExt;
+use anyhow::{ensure, Result};
+
+pub use delta_e::*;
+pub use dssim::*;
+pub use psnr::*;
+pub use ssim::*;
+
+mod delta_e;
+mod dssim;
+mod lab;
+mod psnr;
+mod ssim;
+
+fn ensure_same_dimensions(reference: &dyn ImageExt, test: &dyn ImageExt) -> Result<(u32, u32)> {
+    ensure!(
+        reference.dimensions() == test.dimensions(),
+        "Expect reference and test images to have identical dimensions."
+    );
+
+    Ok(reference.dimensions())
+}
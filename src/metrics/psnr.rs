@@ -0,0 +1,47 @@
+use crate::{
+    image::grammar::ImageExt,
+    metrics::{ensure_same_dimensions, lab::lab_pixels},
+};
+use anyhow::Result;
+
+/// The peak signal-to-noise ratio of the CIELAB lightness of two images in decibels, with a peak
+/// of L = 100. Lightness is perceptually uniform, unlike encoded RGB. Identical images score
+/// infinity.
+pub fn psnr(reference: &dyn ImageExt, test: &dyn ImageExt) -> Result<f64> {
+    ensure_same_dimensions(reference, test)?;
+
+    let reference = lab_pixels(reference)?;
+    let test = lab_pixels(test)?;
+
+    let squared_error_sum = reference
+        .iter()
+        .zip(&test)
+        .map(|(a, b)| f64::from(a[0] - b[0]).powi(2))
+        .sum::<f64>();
+    let mean_squared_error = squared_error_sum / reference.len().max(1) as f64;
+
+    if mean_squared_error == 0.0 {
+        return Ok(f64::INFINITY);
+    }
+
+    Ok(10.0 * (100.0 * 100.0 / mean_squared_error).log10())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{ImageBuffer, Luma8};
+
+    #[test]
+    fn test_psnr() -> Result<()> {
+        let white = ImageBuffer::from_pixel(4, 4, Luma8([255]));
+        let mut test = white.clone();
+        assert_eq!(psnr(&white, &test)?, f64::INFINITY);
+
+        // One black pixel of 16 is an error of 100 in L, for 10·log10(16) dB.
+        test.put_pixel(0, 0, Luma8([0]));
+        assert!((psnr(&white, &test)? - 12.0412).abs() < 1e-2);
+
+        Ok(())
+    }
+}
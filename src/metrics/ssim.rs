@@ -0,0 +1,499 @@
+#![allow(clippy::suboptimal_flops)]
+
+use crate::{
+    image::{grammar::ImageExt, ImageBuffer, Luma8, Subpixel},
+    metrics::ensure_same_dimensions,
+};
+use anyhow::{ensure, Result};
+
+const K1: f32 = 0.01;
+const K2: f32 = 0.03;
+
+// Samples are normalized, so the dynamic range is 1.
+const C1: f32 = K1 * K1;
+const C2: f32 = K2 * K2;
+
+/// The luma weights MATLAB's `rgb2gray` uses.
+const LUMA_WEIGHTS: [f32; 3] = [0.29891, 0.58661, 0.11448];
+
+/// The exponent of each scale of MS-SSIM, from the finest to the coarsest.
+const MS_SSIM_WEIGHTS: [f32; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+/// Which planes of the images are compared.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum SsimChannels {
+    #[default]
+    Luma,
+    /// Red, green and blue are compared separately, and their scores averaged.
+    Rgb,
+}
+
+/// The structural similarity index of Wang et al., computed over a Gaussian window around every
+/// pixel. Alpha is ignored.
+///
+/// <https://www.cns.nyu.edu/pub/lcv/wang03-preprint.pdf>
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ssim {
+    sigma: f32,
+    channels: SsimChannels,
+}
+
+impl Default for Ssim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ssim {
+    /// Compares luma over an 11x11 window with a standard deviation of 1.5, like the reference
+    /// implementation.
+    pub const fn new() -> Self {
+        Self {
+            sigma: 1.5,
+            channels: SsimChannels::Luma,
+        }
+    }
+
+    /// The standard deviation of the window, which spans three of them on each side.
+    pub const fn with_sigma(mut self, sigma: f32) -> Self {
+        self.sigma = sigma;
+        self
+    }
+
+    pub const fn with_channels(mut self, channels: SsimChannels) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Computes the local SSIM around every pixel. Edges are extended by repeating the outermost
+    /// pixels, so the map has the dimensions of the images.
+    pub fn map(&self, reference: &dyn ImageExt, test: &dyn ImageExt) -> Result<SsimMap> {
+        let (width, height) = ensure_same_dimensions(reference, test)?;
+        let window = self.window()?;
+
+        let mut values = vec![0.0; width as usize * height as usize];
+        let mut channel_means = Vec::new();
+
+        let channels = self.planes(reference).into_iter().zip(self.planes(test));
+        let channel_count = channels.len() as f32;
+
+        for (x, y) in channels {
+            let statistics = Statistics::new(&x, &y, &window);
+            let ssim = statistics.ssim();
+
+            for (value, channel_value) in values.iter_mut().zip(&ssim) {
+                *value += channel_value / channel_count;
+            }
+
+            channel_means.push(mean(&ssim));
+        }
+
+        Ok(SsimMap {
+            width,
+            height,
+            values,
+            channel_means,
+        })
+    }
+
+    /// Computes multi-scale SSIM over five scales, each half the size of the last. Contrast and
+    /// structure are compared at every scale and luminance only at the coarsest.
+    ///
+    /// <https://www.cns.nyu.edu/pub/eero/wang03b.pdf>
+    pub fn multi_scale(&self, reference: &dyn ImageExt, test: &dyn ImageExt) -> Result<f32> {
+        let (width, height) = ensure_same_dimensions(reference, test)?;
+        let window = self.window()?;
+
+        let min_size = window.len() << (MS_SSIM_WEIGHTS.len() - 1);
+        ensure!(
+            width.min(height) as usize >= min_size,
+            "MS-SSIM needs images at least {min_size} pixels on each side."
+        );
+
+        let channels = self.planes(reference).into_iter().zip(self.planes(test));
+        let channel_count = channels.len() as f32;
+        let mut score = 0.0;
+
+        for (mut x, mut y) in channels {
+            let mut channel_score = 1.0;
+
+            for (scale, &weight) in MS_SSIM_WEIGHTS.iter().enumerate() {
+                let statistics = Statistics::new(&x, &y, &window);
+
+                // Negative similarities can't be raised to a fractional power.
+                let similarity = if scale == MS_SSIM_WEIGHTS.len() - 1 {
+                    mean(&statistics.ssim())
+                } else {
+                    x = x.downsample();
+                    y = y.downsample();
+
+                    mean(&statistics.contrast_structure())
+                };
+
+                channel_score *= similarity.max(0.0).powf(weight);
+            }
+
+            score += channel_score / channel_count;
+        }
+
+        Ok(score)
+    }
+
+    fn window(&self) -> Result<Vec<f32>> {
+        ensure!(
+            self.sigma > 0.0,
+            "The window's standard deviation must be positive."
+        );
+
+        let radius = (3.0 * self.sigma).ceil() as i32;
+        let window = (-radius..=radius)
+            .map(|i| (-(i * i) as f32 / (2.0 * self.sigma * self.sigma)).exp())
+            .collect::<Vec<_>>();
+        let sum = window.iter().sum::<f32>();
+
+        Ok(window.into_iter().map(|w| w / sum).collect())
+    }
+
+    fn planes(&self, image: &dyn ImageExt) -> Vec<Plane> {
+        let (width, height) = image.dimensions();
+        let rgba = image.rgba_f32();
+
+        let plane = |f: &dyn Fn(&[f32]) -> f32| Plane {
+            width: width as usize,
+            height: height as usize,
+            values: rgba.chunks_exact(4).map(f).collect(),
+        };
+
+        match self.channels {
+            SsimChannels::Luma => vec![plane(&|p| {
+                LUMA_WEIGHTS[0] * p[0] + LUMA_WEIGHTS[1] * p[1] + LUMA_WEIGHTS[2] * p[2]
+            })],
+            SsimChannels::Rgb => (0..3).map(|c| plane(&|p| p[c])).collect(),
+        }
+    }
+}
+
+/// The local SSIM around every pixel of two images, between -1.0 and 1.0 where 1.0 means the
+/// neighbourhoods are identical.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SsimMap {
+    width: u32,
+    height: u32,
+    values: Vec<f32>,
+    channel_means: Vec<f32>,
+}
+
+impl SsimMap {
+    pub const fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn value(&self, x: u32, y: u32) -> f32 {
+        self.values[y as usize * self.width as usize + x as usize]
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    /// The mean SSIM, which is what's usually reported as the SSIM of two images.
+    pub fn mean(&self) -> f32 {
+        mean(&self.values)
+    }
+
+    /// The mean SSIM of each compared channel.
+    pub fn channel_means(&self) -> &[f32] {
+        &self.channel_means
+    }
+
+    /// Renders the map in grayscale, where white is identical and negative values are black.
+    pub fn to_image(&self) -> ImageBuffer<Luma8> {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            Luma8([u8::from_f32(self.value(x, y))])
+        })
+    }
+}
+
+/// A single channel of normalized samples.
+#[derive(Debug, Clone)]
+struct Plane {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+}
+
+impl Plane {
+    fn zip_map(&self, other: &Self, f: impl Fn(f32, f32) -> f32) -> Self {
+        Self {
+            values: self
+                .values
+                .iter()
+                .zip(&other.values)
+                .map(|(&a, &b)| f(a, b))
+                .collect(),
+            ..*self
+        }
+    }
+
+    /// Convolves with `window` horizontally, then vertically, repeating the edge pixels.
+    fn blur(&self, window: &[f32]) -> Self {
+        let radius = (window.len() / 2) as isize;
+        let (width, height) = (self.width as isize, self.height as isize);
+
+        let convolve = |sample: &dyn Fn(isize, isize) -> f32, dx: isize, dy: isize| {
+            let mut values = Vec::with_capacity(self.values.len());
+
+            for y in 0..height {
+                for x in 0..width {
+                    let value = window.iter().enumerate().fold(0.0, |sum, (i, &w)| {
+                        let offset = i as isize - radius;
+                        let sx = (x + offset * dx).clamp(0, width - 1);
+                        let sy = (y + offset * dy).clamp(0, height - 1);
+
+                        sum + w * sample(sx, sy)
+                    });
+
+                    values.push(value);
+                }
+            }
+
+            values
+        };
+
+        let horizontal = convolve(&|x, y| self.values[(y * width + x) as usize], 1, 0);
+        let vertical = convolve(&|x, y| horizontal[(y * width + x) as usize], 0, 1);
+
+        Self {
+            values: vertical,
+            ..*self
+        }
+    }
+
+    /// Halves the dimensions by averaging 2x2 blocks. An odd last row or column is dropped.
+    fn downsample(&self) -> Self {
+        let (width, height) = (self.width / 2, self.height / 2);
+        let mut values = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let at = |dx: usize, dy: usize| self.values[(2 * y + dy) * self.width + 2 * x + dx];
+
+                values.push((at(0, 0) + at(1, 0) + at(0, 1) + at(1, 1)) / 4.0);
+            }
+        }
+
+        Self {
+            width,
+            height,
+            values,
+        }
+    }
+}
+
+/// The local means, variances and covariance of two planes.
+struct Statistics {
+    mean_x: Plane,
+    mean_y: Plane,
+    variance_x: Plane,
+    variance_y: Plane,
+    covariance: Plane,
+}
+
+impl Statistics {
+    fn new(x: &Plane, y: &Plane, window: &[f32]) -> Self {
+        let mean_x = x.blur(window);
+        let mean_y = y.blur(window);
+
+        let variance_x = x
+            .zip_map(x, |a, b| a * b)
+            .blur(window)
+            .zip_map(&mean_x, |xx, m| xx - m * m);
+        let variance_y = y
+            .zip_map(y, |a, b| a * b)
+            .blur(window)
+            .zip_map(&mean_y, |yy, m| yy - m * m);
+        let covariance = x
+            .zip_map(y, |a, b| a * b)
+            .blur(window)
+            .zip_map(&mean_x.zip_map(&mean_y, |a, b| a * b), |xy, m| xy - m);
+
+        Self {
+            mean_x,
+            mean_y,
+            variance_x,
+            variance_y,
+            covariance,
+        }
+    }
+
+    fn contrast_structure(&self) -> Vec<f32> {
+        (0..self.covariance.values.len())
+            .map(|i| {
+                (2.0 * self.covariance.values[i] + C2)
+                    / (self.variance_x.values[i] + self.variance_y.values[i] + C2)
+            })
+            .collect()
+    }
+
+    fn ssim(&self) -> Vec<f32> {
+        self.contrast_structure()
+            .into_iter()
+            .enumerate()
+            .map(|(i, contrast_structure)| {
+                let (m, n) = (self.mean_x.values[i], self.mean_y.values[i]);
+                let luminance = (2.0 * m * n + C1) / (m * m + n * n + C1);
+
+                luminance * contrast_structure
+            })
+            .collect()
+    }
+}
+
+fn mean(values: &[f32]) -> f32 {
+    values.iter().map(|&v| f64::from(v)).sum::<f64>() as f32 / values.len().max(1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{ImageReader, Rgb8, Rgba8};
+
+    /// Adds deterministic noise of up to `amplitude` levels to every channel.
+    fn add_noise(image: &ImageBuffer<Rgba8>, amplitude: i32) -> ImageBuffer<Rgba8> {
+        let mut state = 0x2545_F491u32;
+        let mut noisy = image.clone();
+
+        for pixel in noisy.pixels_mut() {
+            for channel in &mut pixel.0[..3] {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+
+                let offset = (state % (2 * amplitude as u32 + 1)) as i32 - amplitude;
+                *channel = (*channel as i32 + offset).clamp(0, 255) as u8;
+            }
+        }
+
+        noisy
+    }
+
+    fn read(path: &str) -> Result<ImageBuffer<Rgba8>> {
+        let image = ImageReader::read_from_path(path, None)?;
+
+        ImageBuffer::from_raw(image.width(), image.height(), &image.rgba8())
+    }
+
+    #[test]
+    fn test_constant_images() -> Result<()> {
+        let a = ImageBuffer::from_pixel(16, 16, Luma8([128]));
+        let b = ImageBuffer::from_pixel(16, 16, Luma8([64]));
+
+        // Without any structure, only luminance differs.
+        let (m, n) = (128.0 / 255.0, 64.0 / 255.0);
+        let expected = (2.0 * m * n + C1) / (m * m + n * n + C1);
+
+        let map = Ssim::new().map(&a, &b)?;
+        assert_eq!(map.dimensions(), (16, 16));
+        assert!(map.values().iter().all(|v| (v - expected).abs() < 1e-5));
+        assert!((Ssim::new().map(&a, &a)?.mean() - 1.0).abs() < 1e-6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_windowed_statistics() {
+        // The separable blur matches a direct 2D convolution.
+        let window = Ssim::new().with_sigma(1.0).window().unwrap();
+        let plane = Plane {
+            width: 7,
+            height: 5,
+            values: (0..35).map(|i| ((i * 37) % 11) as f32 / 10.0).collect(),
+        };
+
+        let blurred = plane.blur(&window);
+        let radius = (window.len() / 2) as isize;
+
+        for y in 0..5isize {
+            for x in 0..7isize {
+                let mut expected = 0.0;
+
+                for (j, &wy) in window.iter().enumerate() {
+                    for (i, &wx) in window.iter().enumerate() {
+                        let sx = (x + i as isize - radius).clamp(0, 6);
+                        let sy = (y + j as isize - radius).clamp(0, 4);
+
+                        expected += wx * wy * plane.values[(sy * 7 + sx) as usize];
+                    }
+                }
+
+                let value = blurred.values[(y * 7 + x) as usize];
+                assert!((value - expected).abs() < 1e-5, "({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn test_grayscale_matches_rgb() -> Result<()> {
+        let gray = ImageReader::read_from_path("./test_suite/basn0g08.png", None)?;
+        let rgb = ImageBuffer::<Rgb8>::from_raw(gray.width(), gray.height(), &gray.rgb8())?;
+
+        let darker = |image: &dyn ImageExt| {
+            let rgba = image.rgba8().iter().map(|&s| s / 2).collect::<Vec<_>>();
+            ImageBuffer::<Rgba8>::from_raw(image.width(), image.height(), &rgba)
+        };
+
+        // Grayscale and RGB samples are normalized alike.
+        let gray_ssim = Ssim::new().map(gray.as_ref(), &darker(gray.as_ref())?)?;
+        let rgb_ssim = Ssim::new().map(&rgb, &darker(&rgb)?)?;
+
+        assert!(gray_ssim.mean() < 0.99);
+        assert!((gray_ssim.mean() - rgb_ssim.mean()).abs() < 1e-6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_noise() -> Result<()> {
+        let reference = read("./test_suite/basn2c08.png")?;
+        let slightly_noisy = add_noise(&reference, 8);
+        let noisy = add_noise(&reference, 48);
+
+        let luma = Ssim::new();
+        let slight = luma.map(&reference, &slightly_noisy)?.mean();
+        let strong = luma.map(&reference, &noisy)?.mean();
+        assert!(1.0 > slight && slight > strong, "{slight} {strong}");
+
+        let color = Ssim::new().with_channels(SsimChannels::Rgb);
+        let map = color.map(&reference, &noisy)?;
+        assert_eq!(map.channel_means().len(), 3);
+
+        let channel_mean = map.channel_means().iter().sum::<f32>() / 3.0;
+        assert!((map.mean() - channel_mean).abs() < 1e-5);
+
+        let image = map.to_image();
+        assert_eq!(image.dimensions(), reference.dimensions());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_scale() -> Result<()> {
+        let reference = read("./tests/obama.png")?;
+        let noisy = add_noise(&reference, 32);
+        let noisier = add_noise(&reference, 96);
+
+        let ssim = Ssim::new();
+        assert!((ssim.multi_scale(&reference, &reference)? - 1.0).abs() < 1e-5);
+
+        let score = ssim.multi_scale(&reference, &noisy)?;
+        assert!(score < 1.0 && score > ssim.multi_scale(&reference, &noisier)?);
+
+        // Noise hurts fine scales the most, so single-scale SSIM is harsher.
+        assert!(score > ssim.map(&reference, &noisy)?.mean());
+
+        let small = ImageBuffer::from_pixel(64, 64, Luma8([0]));
+        assert!(ssim.multi_scale(&small, &small).is_err());
+
+        Ok(())
+    }
+}
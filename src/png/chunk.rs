@@ -66,6 +66,54 @@ impl PngChunk for IHDRChunk<'_> {
     }
 }
 
+#[derive(Debug)]
+pub struct GammaChunk(pub u32);
+
+impl PngChunk for GammaChunk {
+    const NAME: [u8; 4] = *b"gAMA";
+
+    fn data(&self) -> Result<Vec<u8>> {
+        Ok(self.0.to_be_bytes().to_vec())
+    }
+}
+
+#[derive(Debug)]
+pub struct ChromaticitiesChunk<'a>(pub &'a [u32; 8]);
+
+impl PngChunk for ChromaticitiesChunk<'_> {
+    const NAME: [u8; 4] = *b"cHRM";
+
+    fn data(&self) -> Result<Vec<u8>> {
+        Ok(self.0.iter().flat_map(|c| c.to_be_bytes()).collect())
+    }
+}
+
+#[derive(Debug)]
+pub struct StandardRgbChunk(pub u8);
+
+impl PngChunk for StandardRgbChunk {
+    const NAME: [u8; 4] = *b"sRGB";
+
+    fn data(&self) -> Result<Vec<u8>> {
+        Ok(vec![self.0])
+    }
+}
+
+#[derive(Debug)]
+pub struct IccProfileChunk<'a>(pub &'a [u8]);
+
+impl PngChunk for IccProfileChunk<'_> {
+    const NAME: [u8; 4] = *b"iCCP";
+
+    fn data(&self) -> Result<Vec<u8>> {
+        // The profile name, its null separator and compression method 0.
+        let mut encoder = ZlibEncoder::new(b"ICC profile\0\0".to_vec(), Compression::default());
+        encoder.write_all(self.0)?;
+
+        Ok(encoder.finish()?)
+    }
+}
+
 // #[derive(Debug)]
 // pub struct PLTEChunk; // todo!, how does the palette chunk serialize?
 
@@ -52,6 +52,17 @@ impl<'a> PngDecoder<'a> {
             "Compression method should always be 0"
         );
 
+        ensure!(
+            image_header.color_type != ColorType::Palette,
+            "Palette PNGs aren't supported."
+        );
+
+        ensure!(
+            matches!(image_header.bit_depth, 8 | 16),
+            "PNGs with {} bits per sample aren't supported.",
+            image_header.bit_depth
+        );
+
         let mut chunks = chunks.peekable();
 
         // There may be multiple image data chunks. If so, they shall appear
@@ -60,16 +71,30 @@ impl<'a> PngDecoder<'a> {
         let mut compressed_stream = Vec::new();
 
         let mut gamma = 0;
+        let mut chromaticities = None;
+        let mut srgb_intent = None;
+        let mut icc_profile = None;
 
         while let Some(chunk) = chunks.peek() {
             // todo, how would you collect palettes if ColorType::Palette?
-            // todo, how do you collect ancillary chunks?
-            if let &Chunk::Gamma(g) = chunk {
-                gamma = g;
-            }
-
-            if let &Chunk::ImageData(sub_data) = chunk {
-                compressed_stream.extend_from_slice(sub_data);
+            match *chunk {
+                Chunk::Gamma(g) => gamma = g,
+                Chunk::Chromaticities(c) => chromaticities = Some(c),
+                Chunk::StandardRgb(intent) => srgb_intent = Some(intent),
+                Chunk::IccProfile(name, compressed_profile) => {
+                    let mut profile = Vec::new();
+
+                    // A broken profile shouldn't keep the image from decoding.
+                    match ZlibDecoder::new(compressed_profile).read_to_end(&mut profile) {
+                        Ok(_) => icc_profile = Some(profile),
+                        Err(err) => log::warn!(
+                            "Ignoring ICC profile {:?}: {err}",
+                            String::from_utf8_lossy(name)
+                        ),
+                    }
+                }
+                Chunk::ImageData(sub_data) => compressed_stream.extend_from_slice(sub_data),
+                _ => {}
             }
 
             chunks.next();
@@ -108,10 +133,40 @@ impl<'a> PngDecoder<'a> {
         Ok(Png {
             image_header,
             gamma,
+            chromaticities,
+            srgb_intent,
+            icc_profile,
             pixel_buffer,
         })
     }
 
+    /// Reads only the signature and the image header, without touching the image data.
+    pub fn decode_header(&mut self) -> Result<ImageHeader> {
+        ensure!(
+            self.read_slice(8)? == b"\x89PNG\r\n\x1A\n",
+            "Invalid PNG file: incorrect signature.",
+        );
+
+        ensure!(
+            self.read_u32()? == 13 && self.read_slice(4)? == b"IHDR",
+            "Expected image header chunk."
+        );
+
+        self.parse_image_header()
+    }
+
+    fn parse_image_header(&mut self) -> Result<ImageHeader> {
+        Ok(ImageHeader {
+            width: self.read_u32()?,
+            height: self.read_u32()?,
+            bit_depth: self.read_u8()?,
+            color_type: self.read_u8()?.try_into()?,
+            compression_method: self.read_u8()?,
+            filter_method: self.read_u8()?,
+            interlace_method: self.read_u8()? == 1,
+        })
+    }
+
     fn validate_crc(&self, chunk_type: &'a [u8], chunk_data: &'a [u8], expected_crc: u32) -> bool {
         expected_crc == compute_crc(chunk_type, chunk_data)
     }
@@ -145,15 +200,7 @@ impl<'a> PngDecoder<'a> {
                 b"IHDR" => {
                     ensure!(chunks.is_empty(), "ImageHeader chunk must appear first.");
 
-                    Chunk::ImageHeader(ImageHeader {
-                        width: self.read_u32()?,
-                        height: self.read_u32()?,
-                        bit_depth: self.read_u8()?,
-                        color_type: self.read_u8()?.try_into()?,
-                        compression_method: self.read_u8()?,
-                        filter_method: self.read_u8()?,
-                        interlace_method: self.read_u8()? == 1,
-                    })
+                    Chunk::ImageHeader(self.parse_image_header()?)
                 }
                 b"PLTE" => {
                     ensure!(length.is_multiple_of(3), "Chunk length not divisible by 3.");
@@ -184,7 +231,24 @@ impl<'a> PngDecoder<'a> {
                 b"IDAT" => Chunk::ImageData(self.read_slice(length)?),
                 b"IEND" => break,
                 b"gAMA" => Chunk::Gamma(self.read_u32()?),
-                // b"sRGB" => todo!("Parse srgb chunks"),
+                b"cHRM" => Chunk::Chromaticities(self.read_fixed_array(Self::read_u32)?),
+                b"sRGB" => Chunk::StandardRgb(self.read_u8()?),
+                b"iCCP" => {
+                    let cursor_start = self.cursor;
+                    let name = self.read_slice_until(0)?;
+
+                    let name_and_null_bytes = self.cursor - cursor_start;
+                    ensure!(
+                        length > name_and_null_bytes,
+                        "Profile name should not exceed chunk length."
+                    );
+                    ensure!(
+                        self.read_u8()? == 0,
+                        "Only compression method 0 is defined for ICC profiles."
+                    );
+
+                    Chunk::IccProfile(name, self.read_slice(length - name_and_null_bytes - 1)?)
+                }
                 b"tEXt" => {
                     let cursor_start = self.cursor;
                     let keyword = Cow::from(self.read_slice_until(0)?);
@@ -243,7 +307,11 @@ impl<'a> PngDecoder<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{image::grammar::ImageExt, test_file_parser::parse_test_file};
+    use crate::{
+        color::{ColorProfile, ToneCurve},
+        image::grammar::ImageExt,
+        test_file_parser::parse_test_file,
+    };
     use anyhow::anyhow;
     use image::ImageReader;
     use pretty_assertions::assert_eq;
@@ -289,6 +357,119 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_rgba8_alpha() -> Result<()> {
+        for image_title in [
+            "basn0g08", "basn2c08", "basn4a08", "basn6a08", "basn0g16", "basn2c16", "basn4a16",
+            "basn6a16",
+        ] {
+            let path = format!("./test_suite/{}.png", image_title);
+            let reference = ImageReader::open(&path)?.decode()?.to_rgba8();
+
+            let content = std::fs::read(&path)?;
+            let png = PngDecoder::new(&content).decode()?;
+
+            // Images without alpha are opaque.
+            assert_eq!(png.rgba8().to_vec(), reference.to_vec(), "{image_title}");
+
+            let alphas = png
+                .bitmap()
+                .iter()
+                .map(|p| (p >> 24) as u8)
+                .collect::<Vec<_>>();
+            let reference_alphas = reference.pixels().map(|p| p.0[3]).collect::<Vec<_>>();
+            assert_eq!(alphas, reference_alphas, "{image_title}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rgba_f32_16_bit() -> Result<()> {
+        for image_title in ["basn0g16", "basn2c16", "basn4a16", "basn6a16"] {
+            let path = format!("./test_suite/{}.png", image_title);
+            let reference = ImageReader::open(&path)?.decode()?.to_rgba32f();
+
+            let content = std::fs::read(&path)?;
+            let png = PngDecoder::new(&content).decode()?;
+
+            let rgba = png.rgba_f32();
+            assert_eq!(rgba.len(), reference.len());
+
+            for (value, expected) in rgba.iter().zip(reference.iter()) {
+                assert!((value - expected).abs() < 1e-6, "{image_title}");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_pngs() -> Result<()> {
+        for image_title in ["basn3p08", "basn0g04", "basi2c08"] {
+            let content = std::fs::read(format!("./test_suite/{}.png", image_title))?;
+            assert!(PngDecoder::new(&content).decode().is_err(), "{image_title}");
+        }
+
+        // Whatever decodes decodes correctly, and the rest fails without panicking.
+        for entry in std::fs::read_dir("./test_suite")? {
+            let path = entry?.path();
+
+            if path.extension().is_none_or(|ext| ext != "png") {
+                continue;
+            }
+
+            let content = std::fs::read(&path)?;
+
+            if let Ok(png) = PngDecoder::new(&content).decode() {
+                let reference = ImageReader::open(&path)?.decode()?.to_rgb8();
+                assert_eq!(png.rgb8().to_vec(), reference.to_vec(), "{path:?}");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_color_chunks() -> Result<()> {
+        let decode = |path: &str| PngDecoder::new(&std::fs::read(path)?).decode();
+
+        let png = decode("./test_suite/ccwn2c08.png")?;
+        assert_eq!(png.gamma, 100000);
+        assert_eq!(
+            png.chromaticities,
+            Some([31270, 32900, 64000, 33000, 30000, 60000, 15000, 6000])
+        );
+        assert!(png.icc_profile.is_none());
+
+        // Linear samples with sRGB primaries and a D65 white.
+        let linear = png.rgba8_in(&ColorProfile::linear_srgb())?;
+        for (&value, &expected) in linear.iter().zip(png.rgba8().iter()) {
+            assert!(value.abs_diff(expected) <= 1);
+        }
+
+        // A file gamma of 0.35 darkens the midtones once decoded.
+        let png = decode("./test_suite/g03n0g16.png")?;
+        assert_eq!(png.gamma, 35000);
+        assert_eq!(
+            png.color_profile(),
+            Some(ColorProfile::gray(ToneCurve::Gamma(100000.0 / 35000.0)))
+        );
+
+        // The iCCP chunk overrides cHRM.
+        let png = decode("./tests/obama.png")?;
+        assert!(png.chromaticities.is_some());
+        let profile = png.color_profile().expect("obama.png has an ICC profile");
+        assert_eq!(profile.description(), Some("sRGB"));
+
+        let srgb = png.rgba8_in(&ColorProfile::srgb())?;
+        for (&value, &expected) in srgb.iter().zip(png.rgba8().iter()) {
+            assert!(value.abs_diff(expected) <= 1);
+        }
+
+        Ok(())
+    }
+
     // A note about the following test cases, these images were hand checked. This way, binary blobs
     // can be generated with confidence, not hubris.
 
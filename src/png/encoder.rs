@@ -1,5 +1,8 @@
 use crate::png::{
-    chunk::{IDATChunk, IENDChunk, IHDRChunk, PngChunk},
+    chunk::{
+        ChromaticitiesChunk, GammaChunk, IDATChunk, IENDChunk, IHDRChunk, IccProfileChunk,
+        PngChunk, StandardRgbChunk,
+    },
     grammar::Png,
 };
 use anyhow::Result;
@@ -19,13 +22,33 @@ impl<W: Write> PngEncoder<W> {
 
         let Png {
             image_header,
+            gamma,
+            chromaticities,
+            srgb_intent,
+            icc_profile,
             pixel_buffer,
-            ..
         } = png;
 
         let image_header_chunk = IHDRChunk { image_header };
         image_header_chunk.write(&mut self.writer)?;
 
+        // Color information has to come before the image data.
+        if *gamma != 0 {
+            GammaChunk(*gamma).write(&mut self.writer)?;
+        }
+
+        if let Some(chromaticities) = chromaticities {
+            ChromaticitiesChunk(chromaticities).write(&mut self.writer)?;
+        }
+
+        if let Some(intent) = *srgb_intent {
+            StandardRgbChunk(intent).write(&mut self.writer)?;
+        }
+
+        if let Some(icc_profile) = icc_profile {
+            IccProfileChunk(icc_profile).write(&mut self.writer)?;
+        }
+
         // let palette_chunk = PLTEChunk;
         // palette_chunk.write(&mut self.writer)?;
 
@@ -45,7 +68,11 @@ impl<W: Write> PngEncoder<W> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::png::PngDecoder;
+    use crate::{
+        color::{ColorProfile, ColorTransform},
+        image::{grammar::ImageExt, ImageBuffer, Rgba32F},
+        png::PngDecoder,
+    };
     use std::fs::File;
 
     #[test]
@@ -53,11 +80,14 @@ mod tests {
         let data = std::fs::read("./tests/obama.png")?;
         let png = PngDecoder::new(&data).decode()?;
 
-        let file = File::create("./tests/obama_encoded.png")?;
+        let path = std::env::temp_dir().join("norm_test_encode.png");
+        let file = File::create(&path)?;
         let mut encoder = PngEncoder::new(file);
 
         encoder.encode(&png)?;
 
+        std::fs::remove_file(&path)?;
+
         Ok(())
     }
 
@@ -66,15 +96,51 @@ mod tests {
         let data = std::fs::read("./tests/obama.png")?;
         let png = PngDecoder::new(&data).decode()?;
 
-        let file = File::create("./tests/obama_encoded.png")?;
+        let path = std::env::temp_dir().join("norm_test_encode_round_trip.png");
+        let file = File::create(&path)?;
         let mut encoder = PngEncoder::new(file);
         encoder.encode(&png)?;
 
-        let data = std::fs::read("./tests/obama_encoded.png")?;
+        let data = std::fs::read(&path)?;
+        std::fs::remove_file(&path)?;
         let from_encoded_png = PngDecoder::new(&data).decode()?;
 
         assert_eq!(png, from_encoded_png);
 
         Ok(())
     }
+
+    #[test]
+    fn test_encode_16_bit_working_copy() -> Result<()> {
+        let data = std::fs::read("./test_suite/basn6a16.png")?;
+        let png = PngDecoder::new(&data).decode()?;
+        assert_eq!(png.bit_depth(), 16);
+
+        // Edit in linear float and convert back to the image's profile.
+        let profile = png.color_profile().unwrap_or_else(ColorProfile::srgb);
+        let mut rgba = png.rgba_f32_in(&ColorProfile::linear_srgb())?.into_owned();
+        ColorTransform::new(&ColorProfile::linear_srgb(), &profile)?.apply_rgba32f(&mut rgba);
+
+        let image = ImageBuffer::<Rgba32F>::from_raw(png.width(), png.height(), &rgba)?;
+
+        let mut encoded = Vec::new();
+        PngEncoder::new(&mut encoded).encode(&Png::from(&image))?;
+        let decoded = PngDecoder::new(&encoded).decode()?;
+
+        assert_eq!(decoded.image_header.bit_depth, 16);
+
+        let samples = |png: &Png| {
+            png.pixel_buffer
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as i32)
+                .collect::<Vec<_>>()
+        };
+
+        // The samples survive with 16-bit precision, far finer than 8 bits.
+        for (sample, expected) in samples(&decoded).into_iter().zip(samples(&png)) {
+            assert!((sample - expected).abs() <= 2, "{sample} != {expected}");
+        }
+
+        Ok(())
+    }
 }
@@ -1,4 +1,10 @@
-use crate::image::grammar::{ColorType, ImageExt};
+use crate::{
+    color::{ColorProfile, ToneCurve, D65_CHROMATICITY, SRGB_PRIMARIES},
+    image::{
+        grammar::{embedded_color_profile, ColorType, ImageExt},
+        ImageBuffer, Rgba32F, Subpixel,
+    },
+};
 use anyhow::{bail, Result};
 #[cfg(test)]
 use std::io::Write;
@@ -13,6 +19,10 @@ pub enum Chunk<'a> {
     ImageData(&'a [u8]),
     TextData(BTreeMap<Cow<'a, [u8]>, Cow<'a, [u8]>>),
     Gamma(u32),
+    Chromaticities([u32; 8]),
+    StandardRgb(u8),
+    /// The profile name and the compressed profile.
+    IccProfile(&'a [u8], &'a [u8]),
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -66,9 +76,30 @@ impl TryFrom<u8> for Filter {
 pub struct Png {
     pub(crate) image_header: ImageHeader,
     pub(crate) gamma: u32,
+    /// The white point and red, green and blue primaries as x, y pairs, times 100000.
+    pub(crate) chromaticities: Option<[u32; 8]>,
+    /// The rendering intent of an image in sRGB.
+    pub(crate) srgb_intent: Option<u8>,
+    /// The decompressed ICC profile.
+    pub(crate) icc_profile: Option<Vec<u8>>,
     pub(crate) pixel_buffer: Vec<u8>,
 }
 
+impl Png {
+    /// Returns the samples narrowed to 8 bits, rounding 16-bit samples to the nearest value.
+    fn samples8(&self) -> Cow<'_, [u8]> {
+        match self.image_header.bit_depth {
+            16 => Cow::from(
+                self.pixel_buffer
+                    .chunks_exact(2)
+                    .map(|b| u8::from_f32(u16::from_be_bytes([b[0], b[1]]).to_f32()))
+                    .collect::<Vec<_>>(),
+            ),
+            _ => Cow::from(&self.pixel_buffer),
+        }
+    }
+}
+
 impl ImageExt for Png {
     fn width(&self) -> u32 {
         self.image_header.width
@@ -86,12 +117,17 @@ impl ImageExt for Png {
         self.image_header.color_type
     }
 
+    fn bit_depth(&self) -> u8 {
+        self.image_header.bit_depth
+    }
+
     fn rgb8(&self) -> Cow<'_, [u8]> {
+        let samples = self.samples8();
+
         match self.color_type() {
-            ColorType::RGB => Cow::from(&self.pixel_buffer),
+            ColorType::RGB => samples,
             ColorType::RGBA => {
-                let b = self
-                    .pixel_buffer
+                let b = samples
                     .chunks_exact(4)
                     .flat_map(|b| [b[0], b[1], b[2]])
                     .collect::<Vec<_>>();
@@ -99,8 +135,7 @@ impl ImageExt for Png {
                 Cow::from(b)
             }
             ColorType::GrayscaleAlpha => {
-                let b = self
-                    .pixel_buffer
+                let b = samples
                     .chunks_exact(2)
                     .flat_map(|b| [b[0], b[0], b[0]])
                     .collect::<Vec<u8>>();
@@ -108,66 +143,61 @@ impl ImageExt for Png {
                 Cow::from(b)
             }
             ColorType::Grayscale => {
-                let b = self
-                    .pixel_buffer
-                    .iter()
-                    .flat_map(|&y| [y, y, y])
-                    .collect::<Vec<u8>>();
+                let b = samples.iter().flat_map(|&y| [y, y, y]).collect::<Vec<u8>>();
 
                 Cow::from(b)
             }
-            foreign => unimplemented!("{:?}", foreign),
+            ColorType::Palette => unreachable!("the decoder rejects palette PNGs"),
         }
     }
 
     fn rgba8(&self) -> Cow<'_, [u8]> {
+        let samples = self.samples8();
+
         match self.color_type() {
-            ColorType::RGBA => Cow::from(&self.pixel_buffer),
+            ColorType::RGBA => samples,
             ColorType::RGB => {
-                let b = self
-                    .pixel_buffer
+                let b = samples
                     .chunks_exact(3)
-                    .flat_map(|b| [b[0], b[1], b[2], 0])
+                    .flat_map(|b| [b[0], b[1], b[2], 255])
                     .collect::<Vec<_>>();
 
                 Cow::from(b)
             }
             ColorType::Grayscale => {
-                let b = self
-                    .pixel_buffer
+                let b = samples
                     .iter()
-                    .flat_map(|&y| [y, y, y, 0])
+                    .flat_map(|&y| [y, y, y, 255])
                     .collect::<Vec<_>>();
 
                 Cow::from(b)
             }
             ColorType::GrayscaleAlpha => {
-                let b = self
-                    .pixel_buffer
+                let b = samples
                     .chunks_exact(2)
                     .flat_map(|b| [b[0], b[0], b[0], b[1]])
                     .collect::<Vec<_>>();
 
                 Cow::from(b)
             }
-            foreign => unimplemented!("{:?}", foreign),
+            ColorType::Palette => unreachable!("the decoder rejects palette PNGs"),
         }
     }
 
     fn bitmap(&self) -> Cow<'_, [u32]> {
+        let samples = self.samples8();
+
         match self.color_type() {
             ColorType::RGB => {
-                let b = self
-                    .pixel_buffer
+                let b = samples
                     .chunks_exact(3)
-                    .map(|b| u32::from_be_bytes([0, b[0], b[1], b[2]]))
+                    .map(|b| u32::from_be_bytes([255, b[0], b[1], b[2]]))
                     .collect::<Vec<u32>>();
 
                 Cow::from(b)
             }
             ColorType::RGBA => {
-                let b = self
-                    .pixel_buffer
+                let b = samples
                     .chunks_exact(4)
                     .map(|b| u32::from_be_bytes([b[3], b[0], b[1], b[2]]))
                     .collect::<Vec<u32>>();
@@ -175,25 +205,74 @@ impl ImageExt for Png {
                 Cow::from(b)
             }
             ColorType::Grayscale => {
-                let l = self
-                    .pixel_buffer
+                let l = samples
                     .iter()
-                    .map(|&b| u32::from_be_bytes([0, b, b, b]))
+                    .map(|&b| u32::from_be_bytes([255, b, b, b]))
                     .collect::<Vec<u32>>();
 
                 Cow::from(l)
             }
             ColorType::GrayscaleAlpha => {
-                let l = self
-                    .pixel_buffer
+                let l = samples
                     .chunks_exact(2)
                     .map(|b| u32::from_be_bytes([b[1], b[0], b[0], b[0]]))
                     .collect::<Vec<u32>>();
 
                 Cow::from(l)
             }
-            _ => todo!("What do other color type pixels look like?"),
+            ColorType::Palette => unreachable!("the decoder rejects palette PNGs"),
+        }
+    }
+
+    fn rgba_f32(&self) -> Cow<'_, [f32]> {
+        ImageBuffer::<Rgba32F>::try_from(self).map_or_else(
+            // Packed samples have no more than 8 bits to keep.
+            |_| Cow::from(self.rgba8().iter().map(|&s| s.to_f32()).collect::<Vec<_>>()),
+            |image| Cow::from(image.as_raw().to_vec()),
+        )
+    }
+
+    /// An `iCCP` or `sRGB` chunk overrides `gAMA` and `cHRM`, as the PNG specification asks.
+    fn color_profile(&self) -> Option<ColorProfile> {
+        let color_type = self.color_type();
+
+        if let Some(icc_profile) = &self.icc_profile {
+            if let Some(profile) = embedded_color_profile(icc_profile, color_type) {
+                return Some(profile);
+            }
+        }
+
+        if self.srgb_intent.is_some() {
+            return None;
         }
+
+        // Encoders commonly write a gamma of 1/2.2 alone to mean sRGB.
+        if self.chromaticities.is_none() && matches!(self.gamma, 0 | 45454 | 45455) {
+            return None;
+        }
+
+        let curve = match self.gamma {
+            0 => ToneCurve::SRGB,
+            gamma => ToneCurve::Gamma(100000.0 / gamma as f32),
+        };
+
+        if matches!(color_type, ColorType::Grayscale | ColorType::GrayscaleAlpha) {
+            return Some(ColorProfile::gray(curve));
+        }
+
+        let [white, primaries @ ..] = self.chromaticities.map_or(
+            [
+                D65_CHROMATICITY,
+                SRGB_PRIMARIES[0],
+                SRGB_PRIMARIES[1],
+                SRGB_PRIMARIES[2],
+            ],
+            |c| [0, 1, 2, 3].map(|i| [c[2 * i] as f32 / 100000.0, c[2 * i + 1] as f32 / 100000.0]),
+        );
+
+        ColorProfile::from_chromaticities(white, primaries, curve)
+            .inspect_err(|err| log::warn!("Ignoring the cHRM chunk: {err}"))
+            .ok()
     }
 }
 
@@ -211,7 +290,7 @@ impl Png {
         file.write_all(&self.image_header.filter_method.to_be_bytes())?;
         file.write_all(&(self.image_header.interlace_method as u8).to_be_bytes())?;
 
-        file.write(&self.gamma.to_be_bytes())?;
+        file.write_all(&self.gamma.to_be_bytes())?;
         file.write_all(&self.pixel_buffer)?;
 
         Ok(())
@@ -258,6 +337,9 @@ impl Png {
                 interlace_method: interlace_method[0] != 0,
             },
             gamma: u32::from_be_bytes(gamma),
+            chromaticities: None,
+            srgb_intent: None,
+            icc_profile: None,
             pixel_buffer,
         })
     }
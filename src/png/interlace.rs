@@ -44,38 +44,38 @@ pub fn compute_pass_counts(width: u32, height: u32) -> [Pass; 7] {
             compute_y: Box::new(|y| 8 * y),
         },
         Pass {
-            width: (width - 4).div_ceil(8),
+            width: width.saturating_sub(4).div_ceil(8),
             height: height.div_ceil(8),
             compute_x: Box::new(|x| 8 * x + 4),
             compute_y: Box::new(|y| 8 * y),
         },
         Pass {
             width: width.div_ceil(4),
-            height: (height - 4).div_ceil(8),
+            height: height.saturating_sub(4).div_ceil(8),
             compute_x: Box::new(|x| 4 * x),
             compute_y: Box::new(|y| 8 * y + 4),
         },
         Pass {
-            width: (width - 2).div_ceil(4),
+            width: width.saturating_sub(2).div_ceil(4),
             height: height.div_ceil(4),
             compute_x: Box::new(|x| 4 * x + 2),
             compute_y: Box::new(|y| 4 * y),
         },
         Pass {
             width: width.div_ceil(2),
-            height: (height - 2).div_ceil(4),
+            height: height.saturating_sub(2).div_ceil(4),
             compute_x: Box::new(|x| 2 * x),
             compute_y: Box::new(|y| 4 * y + 2),
         },
         Pass {
-            width: (width - 1).div_ceil(2),
+            width: width.saturating_sub(1).div_ceil(2),
             height: height.div_ceil(2),
             compute_x: Box::new(|x| 2 * x + 1),
             compute_y: Box::new(|y| 2 * y),
         },
         Pass {
             width,
-            height: (height - 1).div_ceil(2),
+            height: height.saturating_sub(1).div_ceil(2),
             compute_x: Box::new(|x| x),
             compute_y: Box::new(|y| 2 * y + 1),
         },
@@ -4,7 +4,7 @@ use crate::png::{
     grammar::{Filter, ImageHeader},
     interlace::compute_pass_counts,
 };
-use anyhow::Result;
+use anyhow::{bail, ensure, Result};
 
 #[derive(Debug)]
 pub struct ScanlineReader<'a> {
@@ -13,14 +13,7 @@ pub struct ScanlineReader<'a> {
 }
 
 impl<'a> ScanlineReader<'a> {
-    pub(crate) fn new(input_buffer: &'a [u8], image_header: &'a ImageHeader) -> Self {
-        let row_bytes = image_header.num_bytes_per_pixel() * image_header.width as usize;
-
-        debug_assert_eq!(
-            input_buffer.len() - image_header.height as usize,
-            row_bytes * image_header.height as usize
-        );
-
+    pub(crate) const fn new(input_buffer: &'a [u8], image_header: &'a ImageHeader) -> Self {
         Self {
             input_buffer,
             image_header,
@@ -38,11 +31,19 @@ impl<'a> ScanlineReader<'a> {
 
 impl<'a> ScanlineReader<'a> {
     fn non_interlaced(&self) -> Result<Vec<u8>> {
-        let mut pixel_buffer =
-            vec![0_u8; self.input_buffer.len() - self.image_header.height as usize];
-
         let bytes_per_pixel = self.image_header.num_bytes_per_pixel();
         let bytes_per_row = bytes_per_pixel * self.image_header.width as usize;
+        let height = self.image_header.height as usize;
+
+        // Every row starts with its filter type.
+        ensure!(
+            self.input_buffer.len() == height * (1 + bytes_per_row),
+            "Expected {} bytes of scanlines, found {}.",
+            height * (1 + bytes_per_row),
+            self.input_buffer.len()
+        );
+
+        let mut pixel_buffer = vec![0_u8; height * bytes_per_row];
 
         for i in 0..self.image_header.height as usize {
             let mut row_start_idx = i * (1 + bytes_per_row);
@@ -178,9 +179,23 @@ impl<'a> ScanlineReader<'a> {
             ];
 
         let pass_counts = compute_pass_counts(self.image_header.width, self.image_header.height);
+
+        // Every row of a pass starts with its filter type, and empty passes have no rows.
+        let scanline_len = pass_counts
+            .iter()
+            .filter(|pass| pass.width > 0)
+            .map(|pass| pass.height * (1 + bytes_per_pixel * pass.width))
+            .sum::<usize>();
+
+        ensure!(
+            self.input_buffer.len() == scanline_len,
+            "Expected {scanline_len} bytes of scanlines, found {}.",
+            self.input_buffer.len()
+        );
+
         let mut cursor = 0;
 
-        for pass in pass_counts.into_iter() {
+        for pass in pass_counts.into_iter().filter(|pass| pass.width > 0) {
             let bytes_per_row = bytes_per_pixel * pass.width;
 
             for i in 0..pass.height {
@@ -198,7 +213,8 @@ impl<'a> ScanlineReader<'a> {
                         for (j, pixel) in row.chunks_exact(bytes_per_pixel).enumerate() {
                             let pixel_x = (pass.compute_x)(j);
 
-                            let index = (pixel_y * self.image_header.width as usize) + pixel_x;
+                            let index = (pixel_y * self.image_header.width as usize + pixel_x)
+                                * bytes_per_pixel;
                             pixel_buffer[index..index + bytes_per_pixel].copy_from_slice(pixel);
                         }
                     }
@@ -213,14 +229,15 @@ impl<'a> ScanlineReader<'a> {
                         for (j, pixel) in new_row.chunks_exact(bytes_per_pixel).enumerate() {
                             let pixel_x = (pass.compute_x)(j);
 
-                            let index = (pixel_y * self.image_header.width as usize) + pixel_x;
+                            let index = (pixel_y * self.image_header.width as usize + pixel_x)
+                                * bytes_per_pixel;
                             pixel_buffer[index..index + bytes_per_pixel].copy_from_slice(pixel);
                         }
                     }
-                    _ => todo!("What do other filters look like?"),
+                    foreign => {
+                        bail!("Interlaced PNGs with the {foreign:?} filter aren't supported.")
+                    }
                 }
-
-                dbg!(filter_type);
             }
 
             cursor += (1 + bytes_per_row) * pass.height;
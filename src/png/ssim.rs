@@ -1,10 +1,14 @@
+//! The global SSIM `Png` offered before [`crate::metrics::Ssim`], which compares one mean,
+//! variance and covariance over the whole image. Deprecated, but computed as it always was.
+
 #![allow(clippy::suboptimal_flops)]
+#![allow(deprecated)]
 
 use crate::{
     image::grammar::{ColorType, ImageExt},
     png::grammar::Png,
 };
-use anyhow::ensure;
+use anyhow::{bail, ensure};
 
 const K1: f32 = 0.01;
 const K2: f32 = 0.03;
@@ -15,8 +19,8 @@ const C3: f32 = C2 / 2.0;
 
 impl Png {
     /// Return luma values normalized to [0.0, 1.0] and the mean intensity.
-    fn luma_buffer(&self) -> LumaBuffer {
-        match self.color_type() {
+    fn luma_buffer(&self) -> anyhow::Result<LumaBuffer> {
+        Ok(match self.color_type() {
             ColorType::Grayscale => {
                 let mut lumas = vec![0.0; self.pixel_buffer.len()];
                 let mut mean_intensity = 0.0;
@@ -79,23 +83,24 @@ impl Png {
                 mean_intensity /= lumas.len() as f32;
                 LumaBuffer::new(lumas, mean_intensity)
             }
-            foreign => unimplemented!(
-                "What does a luma buffer look like for palette: {:?}",
-                foreign
-            ),
-        }
+            foreign => bail!("Expect a grayscale or RGB image, not {foreign:?}."),
+        })
     }
 
     /// `compute_ssim` takes a full-reference image to calculate the global structural similarity
     /// index. A value closer to 1 indicates better image quality.
+    #[deprecated(
+        since = "0.1.2",
+        note = "use `metrics::Ssim`, which compares any image over a sliding window"
+    )]
     pub fn compute_sim(&self, reference_image: &Self) -> anyhow::Result<f32> {
         ensure!(
             self.dimensions() == reference_image.dimensions(),
             "Expect reference and test images to have identical dimensions."
         );
 
-        let reference_luma_buffer = reference_image.luma_buffer();
-        let test_luma_buffer = self.luma_buffer();
+        let reference_luma_buffer = reference_image.luma_buffer()?;
+        let test_luma_buffer = self.luma_buffer()?;
 
         debug_assert_eq!(
             reference_luma_buffer.lumas.len(),
@@ -106,6 +111,11 @@ impl Png {
     }
 }
 
+/// Luma values and their statistics, compared over the whole image at once.
+#[deprecated(
+    since = "0.1.2",
+    note = "use `metrics::Ssim`, which compares images over a sliding window"
+)]
 #[derive(Debug)]
 pub struct LumaBuffer {
     pub(crate) mean_intensity: f32,
@@ -183,81 +193,20 @@ impl LumaBuffer {
     }
 }
 
-/*
 #[cfg(test)]
 mod tests {
-    /*
-    The following tests uses the Matlab SSIM implementation to assert correctness.
-    https://www.mathworks.com/help/images/ref/ssim.html
-     */
-    use crate::{Decoder, Png};
+    use crate::png::PngDecoder;
     use anyhow::Result;
     use std::fs;
 
-    fn read_png(image_path: &str) -> Result<Png> {
-        let image_data = fs::read(image_path)?;
-        let image = Decoder::new(&image_data).decode()?;
-
-        Ok(image)
-    }
     #[test]
-    fn test_mona_lisa_gauss_1() -> Result<()> {
-        let reference = read_png("mona-lisa.png")?;
-        let test = read_png("mona-lisa-gauss-1.png")?;
-
-        let ssim = test.compute_sim(&reference)?;
-
-        assert_eq!(ssim, 0.6990);
-
-        Ok(())
-    }
-
-    #[test]
-    fn test_mona_lisa_gauss_2() -> Result<()> {
-        let reference = read_png("mona-lisa.png")?;
-        let test = read_png("mona-lisa-gauss-2.png")?;
-
-        let ssim = test.compute_sim(&reference)?;
-
-        assert_eq!(ssim, 0.5232);
-
-        Ok(())
-    }
-
-    #[test]
-    fn test_mona_lisa_gauss_3() -> Result<()> {
-        let reference = read_png("mona-lisa.png")?;
-        let test = read_png("mona-lisa-gauss-3.png")?;
-
-        let ssim = test.compute_sim(&reference)?;
-
-        assert_eq!(ssim, 0.4921);
-
-        Ok(())
-    }
-
-    #[test]
-    fn test_mona_lisa_gauss_5() -> Result<()> {
-        let reference = read_png("mona-lisa.png")?;
-        let test = read_png("mona-lisa-gauss-5.png")?;
-
-        let ssim = test.compute_sim(&reference)?;
-
-        assert_eq!(ssim, 0.4506);
-
-        Ok(())
-    }
-
-    #[test]
-    fn test_mona_lisa_gauss_10() -> Result<()> {
-        let reference = read_png("mona-lisa.png")?;
-        let test = read_png("mona-lisa-gauss-10.png")?;
-
-        let ssim = test.compute_sim(&reference)?;
+    fn test_compute_sim() -> Result<()> {
+        let reference = PngDecoder::new(&fs::read("./test_suite/basn2c08.png")?).decode()?;
+        let test = PngDecoder::new(&fs::read("./test_suite/basn0g08.png")?).decode()?;
 
-        assert_eq!(ssim, 0.4128);
+        assert!((reference.compute_sim(&reference)? - 1.0).abs() < 1e-6);
+        assert!(test.compute_sim(&reference)? < 1.0);
 
         Ok(())
     }
 }
- */
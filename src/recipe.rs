@@ -0,0 +1,512 @@
+//! Recipes: declarative edits applied to many images at once, without a window.
+//!
+//! A recipe is a versioned text file like an edit document. Its steps always run in the same
+//! order: geometry (in the order it's listed), then the viewer's effects on the CPU, then
+//! encoding:
+//!
+//! ```text
+//! norm-recipe 1
+//! crop 0 0 1024 768
+//! fit 512 512 lanczos3
+//! effect grayscale
+//! effect sharpen 1 0.5 0.01
+//! format jpeg
+//! quality 80
+//! ```
+//!
+//! Effects take the keys of [`crate::document`]s. LUTs are relative to the recipe.
+
+use crate::{
+    document::{parse_into, versioned_lines, EditParser},
+    effects::{from_working_space, to_working_space, Edits},
+    image::{
+        grammar::ImageExt, ImageBuffer, ImageReader, Resample, ResampleFilter, Rgba16, Rgba32F,
+        Rgba8,
+    },
+    jpeg::{grammar::JpegCoefficients, JpegEncoder, JpegOptions},
+    png::{grammar::Png, PngEncoder},
+};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+const HEADER: &str = "norm-recipe";
+const VERSION: u32 = 1;
+
+/// A change to an image's dimensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Geometry {
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// Resizes to exactly these dimensions.
+    Resize {
+        width: u32,
+        height: u32,
+        filter: ResampleFilter,
+    },
+    /// Shrinks to fit within these dimensions, keeping the aspect ratio. Smaller images are
+    /// left as they are.
+    Fit {
+        width: u32,
+        height: u32,
+        filter: ResampleFilter,
+    },
+}
+
+impl Geometry {
+    /// Applies the change to sRGB samples with straight alpha.
+    pub fn apply(&self, image: ImageBuffer<Rgba32F>) -> Result<ImageBuffer<Rgba32F>> {
+        match *self {
+            Self::Crop {
+                x,
+                y,
+                width,
+                height,
+            } => image.crop(x, y, width, height),
+            Self::Resize {
+                width,
+                height,
+                filter,
+            } => image.resize(width, height, Resample::new(filter)),
+            Self::Fit {
+                width,
+                height,
+                filter,
+            } => {
+                let scale = (width as f32 / image.width() as f32)
+                    .min(height as f32 / image.height() as f32);
+
+                if scale >= 1.0 {
+                    return Ok(image);
+                }
+
+                let width = ((image.width() as f32 * scale).round() as u32).max(1);
+                let height = ((image.height() as f32 * scale).round() as u32).max(1);
+
+                image.resize(width, height, Resample::new(filter))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A bit depth of `None` keeps 16 bits for sources with more than 8.
+    Png {
+        bit_depth: Option<u8>,
+    },
+    Jpeg(JpegOptions),
+}
+
+impl OutputFormat {
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Png { .. } => "png",
+            Self::Jpeg(_) => "jpg",
+        }
+    }
+
+    /// Encodes a finished sRGB image with straight alpha. `source_bit_depth` is the bit depth of
+    /// the image it was made from.
+    pub fn encode(
+        &self,
+        image: &ImageBuffer<Rgba32F>,
+        source_bit_depth: u8,
+        writer: impl Write,
+    ) -> Result<()> {
+        match *self {
+            Self::Png { bit_depth } => {
+                let png = if bit_depth.unwrap_or(source_bit_depth) > 8 {
+                    Png::from(&image.convert::<Rgba16>())
+                } else {
+                    Png::from(&image.convert::<Rgba8>())
+                };
+
+                PngEncoder::new(writer).encode(&png)
+            }
+            Self::Jpeg(options) => {
+                // JPEGs have no alpha, so translucent pixels are flattened over white.
+                let mut flattened = image.clone();
+
+                for pixel in flattened.pixels_mut() {
+                    let [r, g, b, a] = pixel.0;
+                    let [r, g, b] = [r, g, b].map(|c| c.mul_add(a, 1.0 - a));
+                    *pixel = Rgba32F([r, g, b, 1.0]);
+                }
+
+                let coefficients =
+                    JpegCoefficients::from_image(&flattened.convert::<Rgba8>(), options)?;
+
+                JpegEncoder::new(writer).encode(&coefficients)
+            }
+        }
+    }
+
+    /// Like [`Self::encode`], into a new file at `path`.
+    pub fn save(
+        &self,
+        image: &ImageBuffer<Rgba32F>,
+        source_bit_depth: u8,
+        path: &Path,
+    ) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.encode(image, source_bit_depth, &mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recipe {
+    pub geometry: Vec<Geometry>,
+    pub edits: Edits,
+    pub format: OutputFormat,
+}
+
+impl Default for Recipe {
+    fn default() -> Self {
+        Self {
+            geometry: Vec::new(),
+            edits: Edits::default(),
+            format: OutputFormat::Png { bit_depth: None },
+        }
+    }
+}
+
+impl Recipe {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        Self::parse_in(&text, path.parent().unwrap_or_else(|| Path::new("")))
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Applies the geometry and effects, returning sRGB with straight alpha.
+    pub fn apply(&self, image: &dyn ImageExt) -> Result<ImageBuffer<Rgba32F>> {
+        // Geometry resamples encoded samples, converted to sRGB first.
+        let mut image = from_working_space(&to_working_space(image)?);
+
+        for geometry in &self.geometry {
+            image = geometry.apply(image)?;
+        }
+
+        Ok(from_working_space(
+            &self.edits.apply(to_working_space(&image)?),
+        ))
+    }
+
+    /// Where [`Self::process`] writes `input`: into `output_directory` under its own name, with
+    /// the format's extension.
+    pub fn output_path(&self, input: &Path, output_directory: &Path) -> Result<PathBuf> {
+        let stem = input
+            .file_stem()
+            .ok_or_else(|| anyhow!("Failed to read the file name of {}", input.display()))?;
+
+        // Joined by hand, since `with_extension` would replace the last dot of `a.b.png`'s stem.
+        let mut name = stem.to_os_string();
+        name.push(".");
+        name.push(self.format.extension());
+
+        Ok(output_directory.join(name))
+    }
+
+    /// Reads, edits and writes one image to [`Self::output_path`], returning the path written.
+    /// Inputs are never overwritten.
+    pub fn process(&self, input: &Path, output_directory: &Path) -> Result<PathBuf> {
+        let output = self.output_path(input, output_directory)?;
+
+        ensure!(
+            !(output.exists() && fs::canonicalize(&output)? == fs::canonicalize(input)?),
+            "Refusing to overwrite the input {}.",
+            input.display()
+        );
+
+        let image = ImageReader::read_from_path(input, None)?;
+        let edited = self.apply(image.as_ref())?;
+
+        self.format.save(&edited, image.bit_depth(), &output)?;
+
+        Ok(output)
+    }
+}
+
+impl FromStr for Recipe {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Self::parse_in(text, Path::new(""))
+    }
+}
+
+impl Recipe {
+    /// Parses a recipe, loading LUTs from paths relative to `directory`.
+    fn parse_in(text: &str, directory: &Path) -> Result<Self> {
+        let mut recipe = Self::default();
+        let mut format = "png";
+        let mut bit_depth = None;
+        let mut jpeg_options = JpegOptions::default();
+        let mut edits = EditParser::in_directory(directory);
+
+        for (number, line) in versioned_lines(text, HEADER, VERSION)? {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+
+            let parsed = match key {
+                "crop" => parse_numbers(value).map(|[x, y, width, height]| {
+                    recipe.geometry.push(Geometry::Crop {
+                        x,
+                        y,
+                        width,
+                        height,
+                    });
+                }),
+                "resize" => parse_size(value).map(|(width, height, filter)| {
+                    recipe.geometry.push(Geometry::Resize {
+                        width,
+                        height,
+                        filter,
+                    });
+                }),
+                "fit" => parse_size(value).map(|(width, height, filter)| {
+                    recipe.geometry.push(Geometry::Fit {
+                        width,
+                        height,
+                        filter,
+                    });
+                }),
+                "format" => match value {
+                    "png" | "jpeg" | "jpg" => {
+                        format = value;
+                        Ok(())
+                    }
+                    _ => Err(anyhow!("Expect a format of png or jpeg.")),
+                },
+                "bit_depth" => value
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|bit_depth| matches!(bit_depth, 8 | 16))
+                    .map(|value| bit_depth = Some(value))
+                    .ok_or_else(|| anyhow!("Expect a bit depth of 8 or 16.")),
+                "quality" => parse_into(value, &mut jpeg_options.quality).and_then(|()| {
+                    ensure!(
+                        (1..=100).contains(&jpeg_options.quality),
+                        "Expect a quality between 1 and 100."
+                    );
+                    Ok(())
+                }),
+                "subsample_chroma" => parse_into(value, &mut jpeg_options.subsample_chroma),
+                _ => edits
+                    .parse(key, value)
+                    .unwrap_or_else(|| Err(anyhow!("Unknown key `{key}`."))),
+            };
+
+            parsed.with_context(|| format!("Line {number}: `{line}`"))?;
+        }
+
+        recipe.edits = edits.finish();
+        recipe.format = if format == "png" {
+            OutputFormat::Png { bit_depth }
+        } else {
+            OutputFormat::Jpeg(jpeg_options)
+        };
+
+        Ok(recipe)
+    }
+}
+
+fn parse_numbers<const N: usize>(value: &str) -> Result<[u32; N]> {
+    let numbers = value
+        .split_whitespace()
+        .map(str::parse::<u32>)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    numbers
+        .try_into()
+        .map_err(|_| anyhow!("Expect {N} whole numbers."))
+}
+
+/// Parses `<width> <height> [filter]`.
+fn parse_size(value: &str) -> Result<(u32, u32, ResampleFilter)> {
+    let (size, filter) = match value.rsplit_once(' ') {
+        Some((size, filter)) if filter.parse::<u32>().is_err() => (size, Some(filter)),
+        _ => (value, None),
+    };
+
+    let [width, height] = parse_numbers(size)?;
+    ensure!(width > 0 && height > 0, "Expect a size of at least 1x1.");
+
+    let filter = match filter {
+        None => ResampleFilter::default(),
+        Some("nearest") => ResampleFilter::Nearest,
+        Some("bilinear") => ResampleFilter::Bilinear,
+        Some("bicubic") => ResampleFilter::Bicubic,
+        Some("lanczos3") => ResampleFilter::Lanczos3,
+        Some(filter) => bail!("Unknown filter `{filter}`."),
+    };
+
+    Ok((width, height, filter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{effects::Effect, jpeg::JpegDecoder};
+
+    #[test]
+    fn test_save_jpeg() -> Result<()> {
+        let directory = std::env::temp_dir().join("norm_test_save_jpeg");
+        fs::create_dir_all(&directory)?;
+        let path = directory.join("edited.jpg");
+
+        // Transparent pixels come out white, like they'd show over a page.
+        let image = ImageBuffer::from_fn(16, 8, |x, _| match x {
+            0..8 => Rgba32F([0.0, 0.0, 0.0, 0.0]),
+            _ => Rgba32F([1.0, 1.0, 1.0, 1.0]),
+        });
+        OutputFormat::Jpeg(JpegOptions::default()).save(&image, 8, &path)?;
+
+        let jpeg = JpegDecoder::new(&fs::read(&path)?).decode()?;
+        assert_eq!(jpeg.dimensions(), (16, 8));
+        assert!(jpeg.rgba8().iter().all(|&c| c > 250));
+
+        fs::remove_dir_all(&directory)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let recipe = "norm-recipe 1\n\
+            crop 1 2 30 40\n\
+            fit 16 16\n\
+            resize 8 4 nearest\n\
+            effect grayscale\n\
+            effect blur 2.5\n\
+            format jpeg\n\
+            quality 70\n"
+            .parse::<Recipe>()?;
+
+        assert_eq!(
+            recipe.geometry,
+            [
+                Geometry::Crop {
+                    x: 1,
+                    y: 2,
+                    width: 30,
+                    height: 40
+                },
+                Geometry::Fit {
+                    width: 16,
+                    height: 16,
+                    filter: ResampleFilter::Bicubic
+                },
+                Geometry::Resize {
+                    width: 8,
+                    height: 4,
+                    filter: ResampleFilter::Nearest
+                },
+            ]
+        );
+        assert_eq!(
+            recipe.edits.effects,
+            [Effect::Grayscale, Effect::Blur { radius: 2.5 }]
+        );
+        assert_eq!(
+            recipe.format,
+            OutputFormat::Jpeg(JpegOptions::default().with_quality(70))
+        );
+
+        assert_eq!("norm-recipe 1".parse::<Recipe>()?, Recipe::default());
+
+        for invalid in [
+            "norm-document 1",
+            "norm-recipe 1\ncrop 1 2 3",
+            "norm-recipe 1\nresize 0 4",
+            "norm-recipe 1\nresize 8 4 sinc",
+            "norm-recipe 1\nformat gif",
+            "norm-recipe 1\nquality 101",
+            "norm-recipe 1\nbit_depth 12",
+            "norm-recipe 1\nsource a.png",
+        ] {
+            assert!(invalid.parse::<Recipe>().is_err(), "{invalid}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_geometry() -> Result<()> {
+        let image = ImageBuffer::from_pixel(40, 20, Rgba32F([0.5, 0.25, 1.0, 1.0]));
+
+        let fit = |width, height| Geometry::Fit {
+            width,
+            height,
+            filter: ResampleFilter::Bilinear,
+        };
+        assert_eq!(fit(10, 10).apply(image.clone())?.dimensions(), (10, 5));
+        assert_eq!(fit(100, 100).apply(image.clone())?.dimensions(), (40, 20));
+
+        let crop = Geometry::Crop {
+            x: 30,
+            y: 10,
+            width: 20,
+            height: 5,
+        };
+        assert!(crop.apply(image).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_process() -> Result<()> {
+        let directory = std::env::temp_dir().join("norm_test_recipe_process");
+        fs::create_dir_all(&directory)?;
+        let input = Path::new("./test_suite/basn6a08.png");
+
+        let recipe = "norm-recipe 1\nresize 16 8\neffect invert\n".parse::<Recipe>()?;
+        let output = recipe.process(input, &directory)?;
+        assert_eq!(output, directory.join("basn6a08.png"));
+
+        let expected = recipe.apply(ImageReader::read_from_path(input, None)?.as_ref())?;
+        let written = ImageReader::read_from_path(&output, None)?;
+        assert_eq!(written.dimensions(), (16, 8));
+        assert_eq!(
+            ImageBuffer::<Rgba8>::from_image(written.as_ref()),
+            expected.convert::<Rgba8>()
+        );
+
+        let recipe = "norm-recipe 1\nformat jpeg\n".parse::<Recipe>()?;
+        let output = recipe.process(input, &directory)?;
+        assert_eq!(output, directory.join("basn6a08.jpg"));
+        assert_eq!(
+            recipe.output_path(Path::new("in/a.b.png"), Path::new("out"))?,
+            Path::new("out/a.b.jpg")
+        );
+        let jpeg = JpegDecoder::new(&fs::read(&output)?).decode()?;
+        assert_eq!(jpeg.dimensions(), (32, 32));
+
+        // Writing beside the inputs in their own format would replace them.
+        fs::copy(input, directory.join("input.png"))?;
+        let recipe = "norm-recipe 1\neffect invert\n".parse::<Recipe>()?;
+        assert!(recipe
+            .process(&directory.join("input.png"), &directory)
+            .is_err());
+        assert_eq!(fs::read(directory.join("input.png"))?, fs::read(input)?);
+
+        fs::remove_dir_all(&directory)?;
+
+        Ok(())
+    }
+}
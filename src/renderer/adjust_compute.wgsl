@@ -0,0 +1,269 @@
+// Compute shaders for color adjustments, one entry point each. The parameters of each are the
+// components of the uniform, in the order of effects::Effect's fields.
+//
+// Exposure and white balance scale linear light. The others work on straight colors encoded
+// with the sRGB curve, clamped to [0, 1], like effects::adjust.
+
+@group(0)
+@binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var output_texture: texture_storage_2d<rgba16float, write>;
+
+@group(0)
+@binding(2)
+var<uniform> parameters: vec4<f32>;
+
+// The tables of a curve, with red, green and blue in the first three channels and luma in the
+// last, as a row of texels
+@group(0)
+@binding(4)
+var curve_table: texture_2d<f32>;
+
+const LUMA_WEIGHTS: vec3<f32> = vec3<f32>(0.29891, 0.58661, 0.11448);
+
+fn in_bounds(coords: vec2<i32>) -> bool {
+    let dimensions = vec2<i32>(textureDimensions(input_texture));
+    return coords.x < dimensions.x && coords.y < dimensions.y;
+}
+
+fn srgb_encode(linear: vec3<f32>) -> vec3<f32> {
+    let c = clamp(linear, vec3<f32>(0.0), vec3<f32>(1.0));
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c < vec3<f32>(0.0031308));
+}
+
+fn srgb_decode(encoded: vec3<f32>) -> vec3<f32> {
+    let c = clamp(encoded, vec3<f32>(0.0), vec3<f32>(1.0));
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, c < vec3<f32>(0.04045));
+}
+
+// The sRGB encoded straight color of a premultiplied pixel
+fn encoded(color: vec4<f32>) -> vec3<f32> {
+    return srgb_encode(color.rgb / color.a);
+}
+
+// Premultiplies an adjusted encoded color again
+fn store_encoded(coords: vec2<i32>, rgb: vec3<f32>, alpha: f32) {
+    textureStore(output_texture, coords, vec4<f32>(srgb_decode(rgb) * alpha, alpha));
+}
+
+fn lookup(channel: i32, x: f32) -> f32 {
+    let size = i32(textureDimensions(curve_table).x);
+    let position = clamp(x, 0.0, 1.0) * f32(size - 1);
+    let i = min(i32(position), size - 2);
+    let t = position - f32(i);
+
+    let a = textureLoad(curve_table, vec2<i32>(i, 0), 0)[channel];
+    let b = textureLoad(curve_table, vec2<i32>(i + 1, 0), 0)[channel];
+
+    return mix(a, b, t);
+}
+
+fn rgb_to_hsl(rgb: vec3<f32>) -> vec3<f32> {
+    let max_c = max(max(rgb.r, rgb.g), rgb.b);
+    let min_c = min(min(rgb.r, rgb.g), rgb.b);
+    let l = (max_c + min_c) / 2.0;
+    let chroma = max_c - min_c;
+
+    if (chroma == 0.0) {
+        return vec3<f32>(0.0, 0.0, l);
+    }
+
+    let s = chroma / (1.0 - abs(2.0 * l - 1.0));
+    var h = 0.0;
+
+    if (max_c == rgb.r) {
+        h = (rgb.g - rgb.b) / chroma;
+        h = h - 6.0 * floor(h / 6.0);
+    } else if (max_c == rgb.g) {
+        h = (rgb.b - rgb.r) / chroma + 2.0;
+    } else {
+        h = (rgb.r - rgb.g) / chroma + 4.0;
+    }
+
+    return vec3<f32>(h / 6.0, s, l);
+}
+
+fn hsl_to_rgb(hsl: vec3<f32>) -> vec3<f32> {
+    let chroma = (1.0 - abs(2.0 * hsl.z - 1.0)) * hsl.y;
+    let h = hsl.x * 6.0;
+    let x = chroma * (1.0 - abs(h - 2.0 * floor(h / 2.0) - 1.0));
+
+    var rgb = vec3<f32>(chroma, 0.0, x);
+
+    switch (u32(h)) {
+        case 0u: { rgb = vec3<f32>(chroma, x, 0.0); }
+        case 1u: { rgb = vec3<f32>(x, chroma, 0.0); }
+        case 2u: { rgb = vec3<f32>(0.0, chroma, x); }
+        case 3u: { rgb = vec3<f32>(0.0, x, chroma); }
+        case 4u: { rgb = vec3<f32>(x, 0.0, chroma); }
+        default: {}
+    }
+
+    return rgb + (hsl.z - chroma / 2.0);
+}
+
+// Scales linear light by the uniform's first three components
+@compute
+@workgroup_size(16, 16)
+fn scale(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+
+    if (!in_bounds(coords)) {
+        return;
+    }
+
+    let color = textureLoad(input_texture, coords, 0);
+    textureStore(output_texture, coords, vec4<f32>(color.rgb * parameters.xyz, color.a));
+}
+
+// brightness, contrast
+@compute
+@workgroup_size(16, 16)
+fn brightness_contrast(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+
+    if (!in_bounds(coords)) {
+        return;
+    }
+
+    let color = textureLoad(input_texture, coords, 0);
+
+    if (color.a > 0.0) {
+        let rgb = (encoded(color) - 0.5) * (1.0 + parameters.y) + 0.5 + parameters.x;
+        store_encoded(coords, rgb, color.a);
+    } else {
+        textureStore(output_texture, coords, color);
+    }
+}
+
+// black, white, gamma
+@compute
+@workgroup_size(16, 16)
+fn levels(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+
+    if (!in_bounds(coords)) {
+        return;
+    }
+
+    let color = textureLoad(input_texture, coords, 0);
+
+    if (color.a > 0.0) {
+        let stretched = clamp(
+            (encoded(color) - parameters.x) / (parameters.y - parameters.x),
+            vec3<f32>(0.0),
+            vec3<f32>(1.0),
+        );
+        store_encoded(coords, pow(stretched, vec3<f32>(1.0 / parameters.z)), color.a);
+    } else {
+        textureStore(output_texture, coords, color);
+    }
+}
+
+// Looks the channels up in the curve table, then shifts them by the change of the luma
+@compute
+@workgroup_size(16, 16)
+fn curves(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+
+    if (!in_bounds(coords)) {
+        return;
+    }
+
+    let color = textureLoad(input_texture, coords, 0);
+
+    if (color.a > 0.0) {
+        let rgb = encoded(color);
+        let mapped = vec3<f32>(lookup(0, rgb.r), lookup(1, rgb.g), lookup(2, rgb.b));
+        let y = dot(mapped, LUMA_WEIGHTS);
+        store_encoded(coords, mapped + (lookup(3, y) - y), color.a);
+    } else {
+        textureStore(output_texture, coords, color);
+    }
+}
+
+// hue in degrees, saturation, lightness
+@compute
+@workgroup_size(16, 16)
+fn hue_saturation_lightness(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+
+    if (!in_bounds(coords)) {
+        return;
+    }
+
+    let color = textureLoad(input_texture, coords, 0);
+
+    if (color.a > 0.0) {
+        var hsl = rgb_to_hsl(encoded(color));
+
+        let h = hsl.x + parameters.x / 360.0;
+        hsl.x = h - floor(h);
+        hsl.y = clamp(hsl.y * (1.0 + parameters.y), 0.0, 1.0);
+
+        if (parameters.z > 0.0) {
+            hsl.z = hsl.z + (1.0 - hsl.z) * parameters.z;
+        } else {
+            hsl.z = hsl.z * (1.0 + parameters.z);
+        }
+
+        store_encoded(coords, hsl_to_rgb(hsl), color.a);
+    } else {
+        textureStore(output_texture, coords, color);
+    }
+}
+
+// amount
+@compute
+@workgroup_size(16, 16)
+fn vibrance(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+
+    if (!in_bounds(coords)) {
+        return;
+    }
+
+    let color = textureLoad(input_texture, coords, 0);
+
+    if (color.a > 0.0) {
+        let rgb = encoded(color);
+        let y = dot(rgb, LUMA_WEIGHTS);
+        let saturation = max(max(rgb.r, rgb.g), rgb.b) - min(min(rgb.r, rgb.g), rgb.b);
+        let scale = 1.0 + parameters.x * (1.0 - saturation);
+        store_encoded(coords, y + (rgb - y) * scale, color.a);
+    } else {
+        textureStore(output_texture, coords, color);
+    }
+}
+
+// amount
+@compute
+@workgroup_size(16, 16)
+fn sepia(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+
+    if (!in_bounds(coords)) {
+        return;
+    }
+
+    let color = textureLoad(input_texture, coords, 0);
+
+    if (color.a > 0.0) {
+        let rgb = encoded(color);
+        let toned = vec3<f32>(
+            dot(rgb, vec3<f32>(0.393, 0.769, 0.189)),
+            dot(rgb, vec3<f32>(0.349, 0.686, 0.168)),
+            dot(rgb, vec3<f32>(0.272, 0.534, 0.131)),
+        );
+        store_encoded(coords, mix(rgb, toned, parameters.x), color.a);
+    } else {
+        textureStore(output_texture, coords, color);
+    }
+}
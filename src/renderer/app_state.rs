@@ -1,17 +1,35 @@
 use crate::{
+    document::EditDocument,
+    effects::{
+        from_working_space, to_working_space, CircleShape, CubeLut, Effect, LutInterpolation,
+        ADJUSTMENT_STEP, BLUR_RADIUS_STEP, COLOR_ADJUSTMENTS, DEFAULT_BLUR_RADIUS,
+        DEFAULT_CANNY_HIGH, DEFAULT_CANNY_LOW, DEFAULT_CANNY_RADIUS, DEFAULT_GAMMA,
+        DEFAULT_HISTOGRAM_CLIP, DEFAULT_SHARPEN_AMOUNT, DEFAULT_SHARPEN_RADIUS,
+        EDGE_THRESHOLD_STEP, EXPOSURE_STEP, GAMMA_STEP, MAX_BLUR_RADIUS, MAX_CIRCLES, MAX_EXPOSURE,
+        MAX_GAMMA, MAX_SHARPEN_AMOUNT, MIN_BLUR_RADIUS, MIN_GAMMA, SHARPEN_AMOUNT_STEP,
+    },
     image::grammar::Image,
+    jpeg::JpegOptions,
+    recipe::OutputFormat,
     renderer::{
         draw_uniform::DrawUniform,
         effect_pipeline::EffectPipeline,
+        export::render_offscreen,
         feature_uniform::{FeatureUniform, TransformAction},
         gpu_state::GpuResourceAllocator,
+        histogram::{GpuHistogram, HistogramUniform},
         mouse_state::MouseState,
         shader::{Shader, TextureResource},
         shape::{compute_distance, Circle, EditorState},
-        shape_uniform::{CircleData, ShapeUniform, MAX_CIRCLES},
+        shape_uniform::{CircleData, ShapeUniform},
     },
 };
 use anyhow::Result;
+use std::{
+    iter,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use winit::{
     dpi::PhysicalSize,
     event::{ElementState, Event, KeyEvent, Modifiers, MouseButton, WindowEvent},
@@ -28,6 +46,11 @@ pub struct AppState<'a> {
     pub window: &'a Window,
     pub(crate) size: PhysicalSize<u32>,
 
+    image: &'a Image,
+    source: PathBuf,
+    document_path: PathBuf,
+    export_path: PathBuf,
+
     pub feature_uniform: FeatureUniform,
     pub draw_uniform: DrawUniform,
     pub mouse_state: MouseState,
@@ -41,19 +64,32 @@ pub struct AppState<'a> {
     pub circle_storage_buffer: wgpu::Buffer,
 
     pub effect_pipeline: EffectPipeline,
-    pub gamma_effect_index: usize,
+    /// The effect of the stack that Tab selected, for reordering and adjusting.
+    pub selected_effect: Option<usize>,
+
+    /// Counts the histogram of the effect stack's output, for the overlay and auto levels.
+    pub histogram: GpuHistogram,
+    pub histogram_shader: Shader,
+    pub histogram_uniform: HistogramUniform,
+    pub show_histogram: bool,
 }
 
 impl<'a> AppState<'a> {
-    pub async fn new(window: &'a Window, image: &'a Image) -> Result<AppState<'a>> {
+    pub async fn new(
+        window: &'a Window,
+        image: &'a Image,
+        document: EditDocument,
+        document_path: PathBuf,
+        export_path: PathBuf,
+    ) -> Result<AppState<'a>> {
         let gpu_allocator = GpuResourceAllocator::new(window).await?;
 
         let size = window.inner_size();
 
         let image_texture_resource =
-            gpu_allocator.create_texture_resource("image_texture", image)?;
+            gpu_allocator.create_texture_resource("image_texture", image.as_ref())?;
 
-        let feature_uniform = { FeatureUniform::new(size.width, size.height, image.gamma()) };
+        let feature_uniform = FeatureUniform::from_edits(&document.edits, size.width, size.height);
         let feature_uniform_resource =
             gpu_allocator.create_uniform_resource("feature_uniform", feature_uniform)?;
 
@@ -72,46 +108,30 @@ impl<'a> AppState<'a> {
         let circle_storage_buffer =
             gpu_allocator.create_storage_buffer("circle_storage", &empty_circles)?;
 
-        // Build effect pipeline with copy-back after each conditional effect
-        // This ensures output is always in texture_a
-        let effect_pipeline = EffectPipeline::builder(
+        // Effects run at the image's resolution, however large the window is.
+        let (image_width, image_height) = image.dimensions();
+        let effect_pipeline = EffectPipeline::new(
             &gpu_allocator,
-            &image_texture_resource.resource.view,
-            size.width,
-            size.height,
-        )
-        .add_effect(
-            "gamma",
-            include_str!("gamma_correct_compute.wgsl"),
-            Some(feature_uniform.gamma()),
-            |_| true, // Always run (base pass)
-        )?
-        .add_effect(
-            "grayscale",
-            include_str!("grayscale_compute.wgsl"),
-            None::<u32>,
-            |f| f.grayscale(),
-        )?
-        .add_effect(
-            "copy_after_grayscale",
-            include_str!("copy_compute.wgsl"),
-            None::<u32>,
-            |f| f.grayscale(),
-        )?
-        .add_effect(
-            "invert",
-            include_str!("invert_compute.wgsl"),
-            None::<u32>,
-            |f| f.invert(),
-        )?
-        .add_effect(
-            "copy_after_invert",
-            include_str!("copy_compute.wgsl"),
-            None::<u32>,
-            |f| f.invert(),
-        )?
-        .build()?;
-        let gamma_effect_index = 0;
+            &image_texture_resource.resource.texture,
+            image_width,
+            image_height,
+            &document.edits.effects,
+        )?;
+
+        let histogram = GpuHistogram::new(
+            &gpu_allocator.device,
+            effect_pipeline.texture_a(),
+            image_width,
+            image_height,
+        );
+
+        let histogram_uniform = HistogramUniform::new(size.width, size.height);
+        let histogram_shader = gpu_allocator.create_shape_shader(
+            "histogram_shader",
+            include_str!("histogram_overlay.wgsl"),
+            gpu_allocator.create_uniform_resource("histogram_uniform", histogram_uniform)?,
+            histogram.bins(),
+        );
 
         let processed_texture_a = gpu_allocator.create_texture_resource_from_existing(
             "processed_texture_a",
@@ -138,13 +158,18 @@ impl<'a> AppState<'a> {
         );
 
         let mouse_state = MouseState::default();
-        let editor_state = EditorState::default();
+        let editor_state =
+            EditorState::with_circles(document.edits.circles.iter().map(Circle::from));
         let modifiers = Modifiers::default();
 
         Ok(Self {
             gpu_allocator,
             window,
             size,
+            image,
+            source: document.source,
+            document_path,
+            export_path,
             feature_uniform,
             draw_uniform,
             mouse_state,
@@ -156,7 +181,11 @@ impl<'a> AppState<'a> {
             shape_uniform,
             circle_storage_buffer,
             effect_pipeline,
-            gamma_effect_index,
+            selected_effect: None,
+            histogram,
+            histogram_shader,
+            histogram_uniform,
+            show_histogram: false,
         })
     }
 
@@ -172,6 +201,8 @@ impl<'a> AppState<'a> {
                 .update_window_dimensions(new_size.width, new_size.height);
             self.shape_uniform
                 .update_dimensions(new_size.width, new_size.height);
+            self.histogram_uniform
+                .update_dimensions(new_size.width, new_size.height);
         }
     }
 
@@ -326,38 +357,89 @@ impl<'a> AppState<'a> {
                     // (KeyCode::KeyC, ElementState::Pressed) => {
                     //     feature_uniform.reset_features();
                     // }
+                    (_, ElementState::Pressed) if let Some(index) = adjustment_index(*keycode) => {
+                        self.toggle_effect(COLOR_ADJUSTMENTS[index].clone());
+                    }
                     (KeyCode::KeyB, ElementState::Pressed) => {
-                        feature_uniform.toggle_blur();
+                        self.toggle_effect(Effect::Blur {
+                            radius: DEFAULT_BLUR_RADIUS,
+                        });
                     }
                     (KeyCode::ArrowUp, ElementState::Pressed) => {
-                        if feature_uniform.blur() {
-                            feature_uniform.increase_blur_radius();
-                        }
-
-                        if feature_uniform.sharpen() {
-                            feature_uniform.increase_sharpen_factor();
-                        }
+                        self.adjust_selected_effect(true);
                     }
                     (KeyCode::ArrowDown, ElementState::Pressed) => {
-                        if feature_uniform.blur() {
-                            feature_uniform.decrease_blur_radius();
-                        }
-
-                        if feature_uniform.sharpen() {
-                            feature_uniform.decrease_sharpen_factor();
-                        }
+                        self.adjust_selected_effect(false);
                     }
                     (KeyCode::KeyG, ElementState::Pressed) => {
-                        feature_uniform.toggle_grayscale();
+                        self.toggle_effect(Effect::Grayscale);
+                    }
+                    (KeyCode::KeyM, ElementState::Pressed) => {
+                        self.toggle_effect(Effect::Gamma(DEFAULT_GAMMA));
+                    }
+                    (KeyCode::Tab, ElementState::Pressed) => {
+                        let len = self.effect_pipeline.effects().len();
+
+                        self.selected_effect = match self.selected_effect {
+                            _ if len == 0 => None,
+                            Some(index) if index + 1 < len => Some(index + 1),
+                            Some(_) => None,
+                            None => Some(0),
+                        };
+                        self.log_effects();
+                    }
+                    (KeyCode::PageUp, ElementState::Pressed) => {
+                        self.move_selected_effect(false);
+                    }
+                    (KeyCode::PageDown, ElementState::Pressed) => {
+                        self.move_selected_effect(true);
                     }
                     (KeyCode::KeyS, ElementState::Pressed) => {
-                        feature_uniform.toggle_sharpen();
+                        if super_key_pressed && self.modifiers.state().shift_key() {
+                            match self.save_document() {
+                                Ok(()) => log::info!("Saved {}", self.document_path.display()),
+                                Err(error) => log::error!("Failed to save: {error}"),
+                            }
+                        } else if super_key_pressed {
+                            // Alt saves a JPEG beside the PNG.
+                            let format = if self.modifiers.state().alt_key() {
+                                OutputFormat::Jpeg(JpegOptions::default())
+                            } else {
+                                OutputFormat::Png { bit_depth: None }
+                            };
+                            let path = self.export_path.with_extension(format.extension());
+
+                            match self.export(format, &path) {
+                                Ok(()) => log::info!("Exported {}", path.display()),
+                                Err(error) => log::error!("Failed to export: {error}"),
+                            }
+                        } else {
+                            self.toggle_effect(Effect::Sharpen {
+                                radius: DEFAULT_SHARPEN_RADIUS,
+                                amount: DEFAULT_SHARPEN_AMOUNT,
+                                threshold: 0.0,
+                            });
+                        }
+                    }
+                    (KeyCode::KeyH, ElementState::Pressed) => {
+                        self.show_histogram = !self.show_histogram;
+                    }
+                    (KeyCode::KeyL, ElementState::Pressed) => {
+                        self.auto_levels(self.modifiers.state().shift_key());
                     }
                     (KeyCode::KeyI, ElementState::Pressed) => {
-                        feature_uniform.toggle_invert();
+                        self.toggle_effect(Effect::Invert);
                     }
                     (KeyCode::KeyE, ElementState::Pressed) => {
-                        feature_uniform.toggle_edge_detect();
+                        if self.modifiers.state().shift_key() {
+                            self.toggle_effect(Effect::Canny {
+                                radius: DEFAULT_CANNY_RADIUS,
+                                low: DEFAULT_CANNY_LOW,
+                                high: DEFAULT_CANNY_HIGH,
+                            });
+                        } else {
+                            self.toggle_effect(Effect::Sobel { threshold: 0.0 });
+                        }
                     }
                     (KeyCode::KeyX, ElementState::Pressed) => {
                         feature_uniform.apply_transform(TransformAction::FlipX);
@@ -407,31 +489,371 @@ impl<'a> AppState<'a> {
                     _ => return false,
                 }
             }
+            WindowEvent::DroppedFile(path) if path.extension().is_some_and(|ext| ext == "cube") => {
+                self.load_lut(path);
+            }
             _ => return false,
         }
 
         true
     }
 
-    pub(crate) fn update(&mut self) {
-        // Update gamma effect uniform in the pipeline
-        self.effect_pipeline.update_effect_uniform(
+    /// Grades with the `.cube` file at `path`, replacing the table of the stack's LUT effect when
+    /// it has one.
+    fn load_lut(&mut self, path: &Path) {
+        let lut = match CubeLut::load(path) {
+            Ok(lut) => Arc::new(lut),
+            Err(error) => {
+                log::error!("Failed to load the LUT: {error:#}");
+                return;
+            }
+        };
+
+        let effects = self.effect_pipeline.effects();
+        let existing = effects
+            .iter()
+            .enumerate()
+            .find_map(|(index, effect)| match effect {
+                Effect::Lut { interpolation, .. } => Some((index, *interpolation)),
+                _ => None,
+            });
+        let (index, interpolation) =
+            existing.unwrap_or_else(|| (effects.len(), LutInterpolation::default()));
+
+        let effect = Effect::Lut {
+            path: path.to_path_buf(),
+            lut,
+            interpolation,
+        };
+
+        match self.effect_pipeline.set(
+            &self.gpu_allocator.device,
             &self.gpu_allocator.queue,
-            self.gamma_effect_index,
-            self.feature_uniform.gamma(),
-        );
+            index,
+            effect,
+        ) {
+            Ok(()) => self.selected_effect = Some(index),
+            Err(error) => log::error!("Failed to add the LUT: {error}"),
+        }
+
+        self.log_effects();
+    }
+
+    /// Removes the first effect of the same kind as `effect` from the stack, or pushes `effect`
+    /// and selects it.
+    fn toggle_effect(&mut self, effect: Effect) {
+        let effects = self.effect_pipeline.effects();
+        let existing = effects
+            .iter()
+            .position(|e| std::mem::discriminant(e) == std::mem::discriminant(&effect));
+
+        if let Some(index) = existing {
+            self.remove_effect(index);
+        } else {
+            let name = effect.name();
+
+            match self.effect_pipeline.push(
+                &self.gpu_allocator.device,
+                &self.gpu_allocator.queue,
+                effect,
+            ) {
+                Ok(()) => self.selected_effect = Some(effects.len()),
+                Err(error) => log::error!("Failed to add the {name} effect: {error}"),
+            }
+        }
+
+        self.log_effects();
+    }
+
+    /// Removes the effect at `index`, keeping the selection on the same effect.
+    fn remove_effect(&mut self, index: usize) {
+        self.effect_pipeline.remove(index);
+
+        self.selected_effect = match self.selected_effect {
+            Some(selected) if selected == index => None,
+            Some(selected) if selected > index => Some(selected - 1),
+            selected => selected,
+        };
+    }
+
+    /// Replaces the stack's levels with levels fitted to the histogram of the other effects'
+    /// output, stretching the colors and, unless `contrast_only`, balancing the midtones.
+    fn auto_levels(&mut self, contrast_only: bool) {
+        let effects = self.effect_pipeline.effects();
+
+        if let Some(index) = effects
+            .iter()
+            .position(|effect| matches!(effect, Effect::Levels { .. }))
+        {
+            self.remove_effect(index);
+        }
+
+        let (device, queue) = (&self.gpu_allocator.device, &self.gpu_allocator.queue);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Auto Levels Encoder"),
+        });
+        self.effect_pipeline.execute(&mut encoder);
+        self.histogram.compute(&mut encoder);
+        queue.submit(iter::once(encoder.finish()));
+
+        let histogram = match self.histogram.read(device, queue) {
+            Ok(histogram) => histogram,
+            Err(error) => {
+                log::error!("Failed to read the histogram: {error}");
+                return;
+            }
+        };
+
+        let levels = if contrast_only {
+            histogram.auto_contrast(DEFAULT_HISTOGRAM_CLIP)
+        } else {
+            histogram.auto_levels(DEFAULT_HISTOGRAM_CLIP)
+        };
+
+        let Some(levels) = levels else {
+            log::warn!("The image has no opaque pixels to fit levels to.");
+            return;
+        };
+
+        let index = self.effect_pipeline.effects().len();
+
+        match self.effect_pipeline.push(device, queue, levels) {
+            Ok(()) => self.selected_effect = Some(index),
+            Err(error) => log::error!("Failed to add the levels effect: {error}"),
+        }
+
+        self.log_effects();
+    }
+
+    /// Moves the selected effect one place later in the stack, or earlier.
+    fn move_selected_effect(&mut self, later: bool) {
+        let Some(index) = self.selected_effect else {
+            return;
+        };
+
+        let to = if later {
+            index + 1
+        } else {
+            index.saturating_sub(1)
+        };
+
+        if to < self.effect_pipeline.effects().len() {
+            self.effect_pipeline.reorder(index, to);
+            self.selected_effect = Some(to);
+            self.log_effects();
+        }
+    }
 
+    /// Raises or lowers the main parameter of the selected effect: the gamma, the blur radius,
+    /// the sharpening amount, the edge thresholds, or the brightness, exposure, levels gamma,
+    /// curve midtone, saturation, vibrance, temperature or sepia amount of a color adjustment.
+    /// For a LUT, Up picks tetrahedral interpolation and Down trilinear.
+    fn adjust_selected_effect(&mut self, increase: bool) {
+        let Some(index) = self.selected_effect else {
+            return;
+        };
+
+        let Some(effect) = self.effect_pipeline.effects().get(index).cloned() else {
+            return;
+        };
+
+        let step = |value: f32, step: f32, min: f32, max: f32| {
+            if increase {
+                (value + step).min(max)
+            } else {
+                (value - step).max(min)
+            }
+        };
+
+        let name = effect.name();
+        let adjusted = match effect {
+            Effect::Gamma(gamma) => Effect::Gamma(if increase {
+                (gamma + GAMMA_STEP).min(MAX_GAMMA)
+            } else {
+                gamma.saturating_sub(GAMMA_STEP).max(MIN_GAMMA)
+            }),
+            Effect::Blur { radius } => Effect::Blur {
+                radius: step(radius, BLUR_RADIUS_STEP, MIN_BLUR_RADIUS, MAX_BLUR_RADIUS),
+            },
+            Effect::Sharpen {
+                radius,
+                amount,
+                threshold,
+            } => Effect::Sharpen {
+                radius,
+                amount: step(amount, SHARPEN_AMOUNT_STEP, 0.0, MAX_SHARPEN_AMOUNT),
+                threshold,
+            },
+            Effect::Sobel { threshold } => Effect::Sobel {
+                threshold: step(threshold, EDGE_THRESHOLD_STEP, 0.0, f32::MAX),
+            },
+            // Both thresholds move, keeping the band between them.
+            Effect::Canny { radius, low, high } => {
+                let band = high - low;
+                let high = step(high, EDGE_THRESHOLD_STEP, band, f32::MAX);
+
+                Effect::Canny {
+                    radius,
+                    low: high - band,
+                    high,
+                }
+            }
+            Effect::BrightnessContrast {
+                brightness,
+                contrast,
+            } => Effect::BrightnessContrast {
+                brightness: step(brightness, ADJUSTMENT_STEP, -1.0, 1.0),
+                contrast,
+            },
+            Effect::Exposure { stops } => Effect::Exposure {
+                stops: step(stops, EXPOSURE_STEP, -MAX_EXPOSURE, MAX_EXPOSURE),
+            },
+            Effect::Levels {
+                black,
+                white,
+                gamma,
+            } => Effect::Levels {
+                black,
+                white,
+                gamma: step(gamma, ADJUSTMENT_STEP, ADJUSTMENT_STEP, 10.0),
+            },
+            // The midtone moves, within the points either side of it.
+            Effect::Curves {
+                channel,
+                mut points,
+            } => {
+                points[2] = step(points[2], ADJUSTMENT_STEP, points[1], points[3]);
+                Effect::Curves { channel, points }
+            }
+            Effect::HueSaturationLightness {
+                hue,
+                saturation,
+                lightness,
+            } => Effect::HueSaturationLightness {
+                hue,
+                saturation: step(saturation, ADJUSTMENT_STEP, -1.0, 1.0),
+                lightness,
+            },
+            Effect::Vibrance { amount } => Effect::Vibrance {
+                amount: step(amount, ADJUSTMENT_STEP, -1.0, 1.0),
+            },
+            Effect::WhiteBalance { temperature, tint } => Effect::WhiteBalance {
+                temperature: step(temperature, ADJUSTMENT_STEP, -1.0, 1.0),
+                tint,
+            },
+            Effect::Sepia { amount } => Effect::Sepia {
+                amount: step(amount, ADJUSTMENT_STEP, 0.0, 1.0),
+            },
+            // Up picks the smoother interpolation.
+            Effect::Lut { path, lut, .. } => Effect::Lut {
+                path,
+                lut,
+                interpolation: if increase {
+                    LutInterpolation::Tetrahedral
+                } else {
+                    LutInterpolation::Trilinear
+                },
+            },
+            Effect::Grayscale | Effect::Invert => return,
+        };
+
+        if let Err(error) = self.effect_pipeline.set(
+            &self.gpu_allocator.device,
+            &self.gpu_allocator.queue,
+            index,
+            adjusted,
+        ) {
+            log::error!("Failed to adjust the {name} effect: {error}");
+        }
+
+        self.log_effects();
+    }
+
+    /// Logs the effect stack in order, with the selected effect in brackets.
+    fn log_effects(&self) {
+        let effects = self
+            .effect_pipeline
+            .effects()
+            .iter()
+            .enumerate()
+            .map(|(i, effect)| {
+                if Some(i) == self.selected_effect {
+                    format!("[{effect}]")
+                } else {
+                    effect.to_string()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        log::info!("Effects: {}", effects.join(", "));
+    }
+
+    pub(crate) fn update(&mut self) {
         // Update image shader uniforms
         let uniform_resources = &self.image_shader.uniform_resources;
         self.gpu_allocator
             .write_uniform_buffer(&uniform_resources[0].resource, self.feature_uniform);
         self.gpu_allocator
             .write_uniform_buffer(&uniform_resources[1].resource, self.draw_uniform);
+        self.gpu_allocator.write_uniform_buffer(
+            &self.histogram_shader.uniform_resources[0].resource,
+            self.histogram_uniform,
+        );
 
         // Update shape data
         self.update_shape_data();
     }
 
+    /// Saves the image at its full resolution with every edit applied, rendering on the CPU
+    /// when the GPU can't.
+    fn export(&self, format: OutputFormat, path: &Path) -> Result<()> {
+        let circles = self
+            .editor_state
+            .elements()
+            .iter()
+            .map(|e| CircleData::from(e.inner()))
+            .collect::<Vec<_>>();
+
+        let effects = self.effect_pipeline.effects();
+
+        let rendered = render_offscreen(
+            &self.gpu_allocator,
+            self.image.as_ref(),
+            self.feature_uniform,
+            &effects,
+            &circles,
+        )
+        .or_else(|error| -> Result<_> {
+            log::warn!("Failed to export on the GPU, rendering on the CPU instead: {error}");
+
+            Ok(self
+                .feature_uniform
+                .edits(effects.clone(), self.circle_shapes())
+                .apply(to_working_space(self.image.as_ref())?))
+        })?;
+
+        format.save(&from_working_space(&rendered), self.image.bit_depth(), path)
+    }
+
+    /// Saves the edits and shapes as a document, so editing can be picked up later.
+    fn save_document(&self) -> Result<()> {
+        EditDocument::new(&self.source)
+            .with_edits(
+                self.feature_uniform
+                    .edits(self.effect_pipeline.effects(), self.circle_shapes()),
+            )
+            .save(&self.document_path)
+    }
+
+    fn circle_shapes(&self) -> Vec<CircleShape> {
+        self.editor_state
+            .elements()
+            .iter()
+            .map(|e| CircleShape::from(e.inner()))
+            .collect()
+    }
+
     fn update_shape_data(&mut self) {
         let num_circles = self.editor_state.num_elements();
 
@@ -465,91 +887,44 @@ impl<'a> AppState<'a> {
     pub(crate) fn render(&self) -> Result<(), wgpu::SurfaceError> {
         let (output, view, mut encoder) = self.gpu_allocator.begin_frame()?;
 
-        let workgroup_count_x = self.size.width.div_ceil(16);
-        let workgroup_count_y = self.size.height.div_ceil(16);
+        // Run the effect stack into the texture the image shader samples
+        self.effect_pipeline.execute(&mut encoder);
 
-        // Execute effect pipeline - automatically chains all effects with CPU-level conditionals!
-        self.effect_pipeline.execute(
-            &self.feature_uniform,
-            &mut encoder,
-            workgroup_count_x,
-            workgroup_count_y,
-        );
+        if self.show_histogram {
+            self.histogram.compute(&mut encoder);
+        }
 
         // First pass: Render shapes to shape texture
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("shape render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.shape_render_texture.resource.view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
-
-            render_pass.set_pipeline(&self.shape_shader.render_pipeline);
-
-            // Set bind group for shape shader (uniform + storage buffer)
-            render_pass.set_bind_group(0, &self.shape_shader.uniform_resources[0].bind_group, &[]);
-
-            render_pass.set_vertex_buffer(0, self.gpu_allocator.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(
-                self.gpu_allocator.index_buffer.slice(..),
-                wgpu::IndexFormat::Uint16,
-            );
-
-            render_pass.draw_indexed(0..self.gpu_allocator.num_indices(), 0, 0..1);
-        }
+        self.gpu_allocator.draw_quad(
+            &mut encoder,
+            "shape render pass",
+            &self.shape_shader,
+            &self.shape_render_texture.resource.view,
+            wgpu::Color::TRANSPARENT,
+        );
 
         // Second pass: Render final image with shapes composited
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("content render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
-
-            render_pass.set_pipeline(&self.image_shader.render_pipeline);
-
-            let mut i = 0;
-
-            self.image_shader.texture_resources.iter().for_each(|r| {
-                render_pass.set_bind_group(i, &r.bind_group, &[]);
-                i += 1;
-            });
-
-            self.image_shader.uniform_resources.iter().for_each(|r| {
-                render_pass.set_bind_group(i, &r.bind_group, &[]);
-                i += 1;
-            });
+        self.gpu_allocator.draw_quad(
+            &mut encoder,
+            "content render pass",
+            &self.image_shader,
+            &view,
+            wgpu::Color {
+                r: 0.1,
+                g: 0.2,
+                b: 0.3,
+                a: 1.0,
+            },
+        );
 
-            render_pass.set_vertex_buffer(0, self.gpu_allocator.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(
-                self.gpu_allocator.index_buffer.slice(..),
-                wgpu::IndexFormat::Uint16,
+        // Last pass: Draw the histogram of the processed image over it
+        if self.show_histogram {
+            self.gpu_allocator.draw_overlay(
+                &mut encoder,
+                "histogram overlay pass",
+                &self.histogram_shader,
+                &view,
             );
-
-            render_pass.draw_indexed(0..self.gpu_allocator.num_indices(), 0, 0..1);
         }
 
         self.gpu_allocator.end_frame(encoder);
@@ -559,9 +934,29 @@ impl<'a> AppState<'a> {
     }
 }
 
+/// The index into [`COLOR_ADJUSTMENTS`] of the digit key that toggles it.
+const fn adjustment_index(keycode: KeyCode) -> Option<usize> {
+    Some(match keycode {
+        KeyCode::Digit1 => 0,
+        KeyCode::Digit2 => 1,
+        KeyCode::Digit3 => 2,
+        KeyCode::Digit4 => 3,
+        KeyCode::Digit5 => 4,
+        KeyCode::Digit6 => 5,
+        KeyCode::Digit7 => 6,
+        KeyCode::Digit8 => 7,
+        _ => return None,
+    })
+}
+
 #[allow(clippy::future_not_send)]
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
-pub async fn run(image: Image) -> anyhow::Result<()> {
+pub async fn run(
+    image: Image,
+    document: EditDocument,
+    document_path: PathBuf,
+    export_path: PathBuf,
+) -> anyhow::Result<()> {
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
             std::panic::set_hook(Box::new(console_error_panic_hook::hook));
@@ -600,7 +995,7 @@ pub async fn run(image: Image) -> anyhow::Result<()> {
     }
 
     // State::new uses async code, so we're going to wait for it to finish
-    let mut state = AppState::new(&window, &image).await?;
+    let mut state = AppState::new(&window, &image, document, document_path, export_path).await?;
     let mut surface_configured = false;
 
     event_loop.run(move |event, control_flow| {
@@ -608,52 +1003,50 @@ pub async fn run(image: Image) -> anyhow::Result<()> {
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == state.window().id() => {
-                if !state.input(event) {
-                    match event {
-                        WindowEvent::CloseRequested
-                        | WindowEvent::KeyboardInput {
-                            event:
-                                KeyEvent {
-                                    state: ElementState::Pressed,
-                                    physical_key: PhysicalKey::Code(KeyCode::Escape),
-                                    ..
-                                },
-                            ..
-                        } => control_flow.exit(),
-                        WindowEvent::Resized(physical_size) => {
-                            surface_configured = true;
-                            state.resize(*physical_size);
+            } if window_id == state.window().id() && !state.input(event) => {
+                match event {
+                    WindowEvent::CloseRequested
+                    | WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                state: ElementState::Pressed,
+                                physical_key: PhysicalKey::Code(KeyCode::Escape),
+                                ..
+                            },
+                        ..
+                    } => control_flow.exit(),
+                    WindowEvent::Resized(physical_size) => {
+                        surface_configured = true;
+                        state.resize(*physical_size);
+                    }
+                    WindowEvent::RedrawRequested => {
+                        // This tells winit that we want another frame after this one
+                        state.window().request_redraw();
+
+                        if !surface_configured {
+                            return;
                         }
-                        WindowEvent::RedrawRequested => {
-                            // This tells winit that we want another frame after this one
-                            state.window().request_redraw();
 
-                            if !surface_configured {
-                                return;
+                        state.update();
+                        match state.render() {
+                            Ok(_) => {}
+                            // Reconfigure the surface if it's lost or outdated
+                            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                                state.resize(state.size)
+                            }
+                            // The system is out of memory, we should probably quit
+                            Err(wgpu::SurfaceError::OutOfMemory) => {
+                                log::error!("OutOfMemory");
+                                control_flow.exit();
                             }
 
-                            state.update();
-                            match state.render() {
-                                Ok(_) => {}
-                                // Reconfigure the surface if it's lost or outdated
-                                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-                                    state.resize(state.size)
-                                }
-                                // The system is out of memory, we should probably quit
-                                Err(wgpu::SurfaceError::OutOfMemory) => {
-                                    log::error!("OutOfMemory");
-                                    control_flow.exit();
-                                }
-
-                                // This happens when a frame takes too long to present
-                                Err(wgpu::SurfaceError::Timeout) => {
-                                    log::warn!("Surface timeout")
-                                }
+                            // This happens when a frame takes too long to present
+                            Err(wgpu::SurfaceError::Timeout) => {
+                                log::warn!("Surface timeout")
                             }
                         }
-                        _ => {}
                     }
+                    _ => {}
                 }
             }
             _ => {}
@@ -0,0 +1,60 @@
+// Compute shader for one direction of a separable gaussian blur
+
+@group(0)
+@binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var output_texture: texture_storage_2d<rgba16float, write>;
+
+struct Blur {
+    // Pixels on either side. The sigma is half of it.
+    radius: f32,
+    // (1, 0) for the horizontal pass, (0, 1) for the vertical one
+    direction_x: f32,
+    direction_y: f32,
+}
+
+@group(0)
+@binding(2)
+var<uniform> blur: Blur;
+
+// Like effects::MAX_BLUR_RADIUS, so a stray radius can't loop for seconds
+const MAX_RADIUS: f32 = 100.0;
+
+@compute
+@workgroup_size(16, 16)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+    let dimensions = vec2<i32>(textureDimensions(input_texture));
+
+    if (coords.x >= dimensions.x || coords.y >= dimensions.y) {
+        return;
+    }
+
+    let radius = min(blur.radius, MAX_RADIUS);
+    let taps = i32(floor(radius));
+
+    if (taps < 1) {
+        textureStore(output_texture, coords, textureLoad(input_texture, coords, 0));
+        return;
+    }
+
+    let sigma = radius * 0.5;
+    let direction = vec2<i32>(i32(blur.direction_x), i32(blur.direction_y));
+
+    var color = vec4<f32>(0.0);
+    var total = 0.0;
+
+    // Pixels beyond the edges repeat the edge.
+    for (var offset = -taps; offset <= taps; offset = offset + 1) {
+        let weight = exp(-f32(offset * offset) / (2.0 * sigma * sigma));
+        let sample_coords = clamp(coords + direction * offset, vec2<i32>(0), dimensions - 1);
+
+        color = color + textureLoad(input_texture, sample_coords, 0) * weight;
+        total = total + weight;
+    }
+
+    textureStore(output_texture, coords, color / total);
+}
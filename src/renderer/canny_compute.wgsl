@@ -0,0 +1,105 @@
+// Compute shaders for the last passes of Canny edge detection, after sobel_compute.wgsl found the
+// gradients of the blurred input
+
+@group(0)
+@binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var output_texture: texture_storage_2d<rgba16float, write>;
+
+struct Thresholds {
+    low: f32,
+    high: f32,
+}
+
+@group(0)
+@binding(2)
+var<uniform> thresholds: Thresholds;
+
+const WEAK: f32 = 0.5;
+
+// tan(22.5°), the boundary between a horizontal and a diagonal direction
+const TAN_22_5: f32 = 0.41421357;
+
+fn load(coords: vec2<i32>, offset: vec2<i32>) -> vec4<f32> {
+    let dimensions = vec2<i32>(textureDimensions(input_texture));
+    return textureLoad(input_texture, clamp(coords + offset, vec2<i32>(0), dimensions - 1), 0);
+}
+
+fn in_bounds(coords: vec2<i32>) -> bool {
+    let dimensions = vec2<i32>(textureDimensions(input_texture));
+    return coords.x < dimensions.x && coords.y < dimensions.y;
+}
+
+// The neighbouring pixel along a gradient, quantized to one of the 8 directions
+fn gradient_step(gx: f32, gy: f32) -> vec2<i32> {
+    if (abs(gy) <= abs(gx) * TAN_22_5) {
+        return vec2(1, 0);
+    } else if (abs(gx) <= abs(gy) * TAN_22_5) {
+        return vec2(0, 1);
+    } else if (gx * gy > 0.0) {
+        return vec2(1, 1);
+    }
+
+    return vec2(1, -1);
+}
+
+// Thins the gradients to their local maxima and classifies them as strong (1), weak or none
+@compute
+@workgroup_size(16, 16)
+fn suppress(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+
+    if (!in_bounds(coords)) {
+        return;
+    }
+
+    let gradient = load(coords, vec2(0, 0));
+    let magnitude = gradient.r;
+    let direction = gradient_step(gradient.g, gradient.b);
+
+    let ahead = load(coords, direction).r;
+    let behind = load(coords, -direction).r;
+
+    var strength = 0.0;
+
+    if (magnitude < ahead || magnitude < behind) {
+        strength = 0.0;
+    } else if (magnitude > thresholds.high) {
+        strength = 1.0;
+    } else if (magnitude > thresholds.low) {
+        strength = WEAK;
+    }
+
+    textureStore(output_texture, coords, vec4<f32>(strength, 0.0, 0.0, 1.0));
+}
+
+// Keeps strong edges, and weak edges touching a strong one, as white on opaque black
+@compute
+@workgroup_size(16, 16)
+fn hysteresis(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+
+    if (!in_bounds(coords)) {
+        return;
+    }
+
+    let strength = load(coords, vec2(0, 0)).r;
+    var edge = 0.0;
+
+    if (strength > 0.75) {
+        edge = 1.0;
+    } else if (strength > 0.25) {
+        for (var dy = -1; dy <= 1; dy = dy + 1) {
+            for (var dx = -1; dx <= 1; dx = dx + 1) {
+                if (load(coords, vec2(dx, dy)).r > 0.75) {
+                    edge = 1.0;
+                }
+            }
+        }
+    }
+
+    textureStore(output_texture, coords, vec4<f32>(edge, edge, edge, 1.0));
+}
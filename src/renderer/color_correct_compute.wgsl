@@ -1,4 +1,5 @@
 // Compute shader for simple color corrections: gamma, grayscale, invert
+// Textures hold premultiplied alpha.
 // These are cheap per-pixel operations that don't need neighboring pixels
 
 struct FeatureUniform {
@@ -21,7 +22,7 @@ struct FeatureUniform {
 var input_texture: texture_2d<f32>;
 
 @group(0) @binding(1)
-var output_texture: texture_storage_2d<rgba8unorm, write>;
+var output_texture: texture_storage_2d<rgba16float, write>;
 
 @group(0) @binding(2)
 var<uniform> feature_uniform: FeatureUniform;
@@ -40,12 +41,11 @@ fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         let gamma_value = f32(feature_uniform.gamma) / 100000.0;
         let inv_gamma = 1.0 / gamma_value;
 
-        color = vec4<f32>(
-            pow(color.r, inv_gamma),
-            pow(color.g, inv_gamma),
-            pow(color.b, inv_gamma),
-            color.a
-        );
+        // Colors are premultiplied. The curve applies to the straight color.
+        if (color.a > 0.0) {
+            let straight = pow(color.rgb / color.a, vec3<f32>(inv_gamma));
+            color = vec4<f32>(straight * color.a, color.a);
+        }
     }
 
     if (feature_uniform.grayscale == 1u) {
@@ -54,12 +54,7 @@ fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     }
 
     if (feature_uniform.invert == 1u) {
-        color = vec4<f32>(
-            1.0 - color.r,
-            1.0 - color.g,
-            1.0 - color.b,
-            color.a
-        );
+        color = vec4<f32>(color.a - color.rgb, color.a);
     }
 
     textureStore(output_texture, coords, color);
@@ -1,12 +1,79 @@
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use wgpu::util::DeviceExt;
 
-// a compute shader effect that can be applied to textures
+/// The textures one dispatch of a compute effect reads and writes. The input is bound at 0, the
+/// output at 1 and the second input, for effects combining two textures, at 3.
+#[derive(Debug, Copy, Clone)]
+pub struct TextureRoute<'a> {
+    pub input: &'a wgpu::TextureView,
+    pub second_input: Option<&'a wgpu::TextureView>,
+    pub output: &'a wgpu::TextureView,
+}
+
+impl<'a> TextureRoute<'a> {
+    pub const fn new(input: &'a wgpu::TextureView, output: &'a wgpu::TextureView) -> Self {
+        Self {
+            input,
+            second_input: None,
+            output,
+        }
+    }
+}
+
+/// A table an effect looks values up in, bound at 4 as a texture of RGBA floats.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LookupTable {
+    /// A single row of entries.
+    Row(Vec<[f32; 4]>),
+    /// A cube of `size` entries along each axis, with x changing fastest, then y, then z.
+    Cube { size: u32, entries: Vec<[f32; 4]> },
+}
+
+impl LookupTable {
+    pub const fn size(&self) -> wgpu::Extent3d {
+        match *self {
+            Self::Row(ref entries) => wgpu::Extent3d {
+                width: entries.len() as u32,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            Self::Cube { size, .. } => wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: size,
+            },
+        }
+    }
+
+    const fn dimension(&self) -> wgpu::TextureDimension {
+        match self {
+            Self::Row(_) => wgpu::TextureDimension::D2,
+            Self::Cube { .. } => wgpu::TextureDimension::D3,
+        }
+    }
+
+    const fn view_dimension(&self) -> wgpu::TextureViewDimension {
+        match self {
+            Self::Row(_) => wgpu::TextureViewDimension::D2,
+            Self::Cube { .. } => wgpu::TextureViewDimension::D3,
+        }
+    }
+
+    fn entries(&self) -> &[[f32; 4]] {
+        match self {
+            Self::Row(entries) | Self::Cube { entries, .. } => entries,
+        }
+    }
+}
+
+// a compute shader effect that can be applied to textures, with a bind group for each route
+// between textures it runs on
 #[derive(Debug)]
 pub struct ComputeEffect {
     pub pipeline: wgpu::ComputePipeline,
-    pub bind_group: wgpu::BindGroup,
+    pub bind_groups: Vec<wgpu::BindGroup>,
     uniform_buffer: Option<wgpu::Buffer>,
+    lookup_table: Option<wgpu::Texture>,
 }
 
 impl ComputeEffect {
@@ -16,18 +83,32 @@ impl ComputeEffect {
             shader_source: None,
             entry_point: "main",
             uniform_data: None,
+            lookup_table: None,
         }
     }
 
-    pub fn update_uniform<T: bytemuck::Pod>(&self, queue: &wgpu::Queue, data: T) {
+    pub fn update_uniform(&self, queue: &wgpu::Queue, data: &[u8]) {
         if let Some(buffer) = &self.uniform_buffer {
-            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[data]));
+            queue.write_buffer(buffer, 0, data);
+        }
+    }
+
+    pub fn lookup_table_size(&self) -> Option<wgpu::Extent3d> {
+        self.lookup_table.as_ref().map(wgpu::Texture::size)
+    }
+
+    /// Rewrites the lookup table with one of the same size.
+    pub fn update_lookup_table(&self, queue: &wgpu::Queue, table: &LookupTable) {
+        if let Some(texture) = &self.lookup_table {
+            write_lookup_table(queue, texture, table);
         }
     }
 
+    /// Runs the effect between the textures of the bind group at `route`.
     pub fn dispatch(
         &self,
         encoder: &mut wgpu::CommandEncoder,
+        route: usize,
         workgroup_count_x: u32,
         workgroup_count_y: u32,
     ) {
@@ -37,7 +118,7 @@ impl ComputeEffect {
         });
 
         compute_pass.set_pipeline(&self.pipeline);
-        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.set_bind_group(0, &self.bind_groups[route], &[]);
         compute_pass.dispatch_workgroups(workgroup_count_x, workgroup_count_y, 1);
     }
 }
@@ -47,6 +128,7 @@ pub struct ComputeEffectBuilder<'a> {
     shader_source: Option<&'a str>,
     entry_point: &'a str,
     uniform_data: Option<Vec<u8>>,
+    lookup_table: Option<LookupTable>,
 }
 
 impl<'a> ComputeEffectBuilder<'a> {
@@ -55,26 +137,42 @@ impl<'a> ComputeEffectBuilder<'a> {
         self
     }
 
-    pub const fn _with_entry_point(mut self, entry_point: &'a str) -> Self {
+    pub const fn with_entry_point(mut self, entry_point: &'a str) -> Self {
         self.entry_point = entry_point;
         self
     }
 
-    pub fn with_uniform<T: bytemuck::Pod>(mut self, data: T) -> Self {
-        self.uniform_data = Some(bytemuck::cast_slice(&[data]).to_vec());
+    pub fn with_uniform(mut self, data: Vec<u8>) -> Self {
+        self.uniform_data = Some(data);
         self
     }
 
+    pub fn with_lookup_table(mut self, table: LookupTable) -> Self {
+        self.lookup_table = Some(table);
+        self
+    }
+
+    /// Compiles the effect with a bind group for each of `routes`, which either all have a
+    /// second input or all don't.
     pub fn build(
         self,
         device: &wgpu::Device,
-        input_texture: &wgpu::TextureView,
-        output_texture: &wgpu::TextureView,
+        queue: &wgpu::Queue,
+        routes: &[TextureRoute],
     ) -> Result<ComputeEffect> {
         let shader_source = self
             .shader_source
             .ok_or_else(|| anyhow::anyhow!("Shader source is required"))?;
 
+        let second_input = routes.first().is_some_and(|r| r.second_input.is_some());
+        ensure!(
+            routes
+                .iter()
+                .all(|r| r.second_input.is_some() == second_input),
+            "Expect every route of {} to have a second input, or none.",
+            self.label
+        );
+
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some(self.label),
             source: wgpu::ShaderSource::Wgsl(shader_source.into()),
@@ -88,6 +186,23 @@ impl<'a> ComputeEffectBuilder<'a> {
             })
         });
 
+        let lookup_table = self.lookup_table.as_ref().map(|table| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(&format!("{}_lookup_table", self.label)),
+                size: table.size(),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: table.dimension(),
+                format: wgpu::TextureFormat::Rgba32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            write_lookup_table(queue, &texture, table);
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            (texture, view)
+        });
+
         let mut bind_group_layout_entries = vec![
             wgpu::BindGroupLayoutEntry {
                 binding: 0,
@@ -104,7 +219,7 @@ impl<'a> ComputeEffectBuilder<'a> {
                 visibility: wgpu::ShaderStages::COMPUTE,
                 ty: wgpu::BindingType::StorageTexture {
                     access: wgpu::StorageTextureAccess::WriteOnly,
-                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    format: wgpu::TextureFormat::Rgba16Float,
                     view_dimension: wgpu::TextureViewDimension::D2,
                 },
                 count: None,
@@ -124,6 +239,32 @@ impl<'a> ComputeEffectBuilder<'a> {
             });
         }
 
+        if second_input {
+            bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            });
+        }
+
+        if let Some(table) = &self.lookup_table {
+            bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: table.view_dimension(),
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            });
+        }
+
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &bind_group_layout_entries,
             label: Some(&format!("{}_bind_group_layout", self.label)),
@@ -144,34 +285,69 @@ impl<'a> ComputeEffectBuilder<'a> {
             cache: None,
         });
 
-        let mut bind_group_entries = vec![
-            wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(input_texture),
-            },
-            wgpu::BindGroupEntry {
-                binding: 1,
-                resource: wgpu::BindingResource::TextureView(output_texture),
-            },
-        ];
+        let bind_groups = routes
+            .iter()
+            .map(|route| {
+                let mut bind_group_entries = vec![
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(route.input),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(route.output),
+                    },
+                ];
 
-        if let Some(buffer) = &uniform_buffer {
-            bind_group_entries.push(wgpu::BindGroupEntry {
-                binding: 2,
-                resource: buffer.as_entire_binding(),
-            });
-        }
+                if let Some(buffer) = &uniform_buffer {
+                    bind_group_entries.push(wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: buffer.as_entire_binding(),
+                    });
+                }
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
-            entries: &bind_group_entries,
-            label: Some(&format!("{}_bind_group", self.label)),
-        });
+                if let Some(second_input) = route.second_input {
+                    bind_group_entries.push(wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(second_input),
+                    });
+                }
+
+                if let Some((_, view)) = &lookup_table {
+                    bind_group_entries.push(wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    });
+                }
+
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &bind_group_entries,
+                    label: Some(&format!("{}_bind_group", self.label)),
+                })
+            })
+            .collect();
 
         Ok(ComputeEffect {
             pipeline,
-            bind_group,
+            bind_groups,
             uniform_buffer,
+            lookup_table: lookup_table.map(|(texture, _)| texture),
         })
     }
 }
+
+fn write_lookup_table(queue: &wgpu::Queue, texture: &wgpu::Texture, table: &LookupTable) {
+    let size = table.size();
+
+    queue.write_texture(
+        texture.as_image_copy(),
+        bytemuck::cast_slice(table.entries()),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(size.width * 16),
+            rows_per_image: Some(size.height),
+        },
+        size,
+    );
+}
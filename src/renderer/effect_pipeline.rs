@@ -1,132 +1,449 @@
-use super::{compute_effect::ComputeEffect, gpu_state::GpuResourceAllocator, Texture};
-use anyhow::Result;
+use super::{
+    compute_effect::{ComputeEffect, LookupTable, TextureRoute},
+    gpu_state::GpuResourceAllocator,
+    Texture,
+};
+use crate::effects::{curve_tables, white_balance_gains, CubeLut, Effect, LutInterpolation};
+use anyhow::{ensure, Result};
+use std::mem;
 
-use crate::renderer::feature_uniform::FeatureUniform;
+/// The textures an effect reads from and writes to, indexing each pass's bind groups.
+#[derive(Debug, Copy, Clone)]
+enum Route {
+    SourceToA,
+    SourceToB,
+    AToB,
+    BToA,
+}
+
+/// A texture a pass reads or writes: the effect's input or output, which depend on where the
+/// effect is in the stack, or one of the pipeline's scratch textures.
+#[derive(Debug, Copy, Clone)]
+enum Slot {
+    Input,
+    Output,
+    Scratch(usize),
+}
+
+const SCRATCH_TEXTURES: usize = 2;
+
+/// One compute pass of an effect.
+#[derive(Debug)]
+struct Pass {
+    shader: &'static str,
+    entry_point: &'static str,
+    uniform: Option<Vec<u8>>,
+    lookup_table: Option<LookupTable>,
+    input: Slot,
+    second_input: Option<Slot>,
+    output: Slot,
+}
+
+impl Pass {
+    const fn new(shader: &'static str, input: Slot, output: Slot) -> Self {
+        Self {
+            shader,
+            entry_point: "main",
+            uniform: None,
+            lookup_table: None,
+            input,
+            second_input: None,
+            output,
+        }
+    }
+
+    const fn with_entry_point(mut self, entry_point: &'static str) -> Self {
+        self.entry_point = entry_point;
+        self
+    }
 
-pub type DispatchConditionalFn = Box<dyn Fn(&FeatureUniform) -> bool>;
+    fn with_uniform(mut self, uniform: Vec<u8>) -> Self {
+        self.uniform = Some(uniform);
+        self
+    }
+
+    fn with_lookup_table(mut self, table: LookupTable) -> Self {
+        self.lookup_table = Some(table);
+        self
+    }
+
+    const fn with_second_input(mut self, slot: Slot) -> Self {
+        self.second_input = Some(slot);
+        self
+    }
+}
 
+/// The viewer's effect stack: compute passes that run in order, each with its own uniform,
+/// ping-ponging between two textures so the result always ends up in texture A. Textures have
+/// the image's dimensions.
 pub struct EffectPipeline {
-    effects: Vec<(ComputeEffect, DispatchConditionalFn)>,
+    effects: Vec<(Effect, Vec<ComputeEffect>)>,
+    /// Copies the source into texture A when the stack is empty.
+    copy: ComputeEffect,
 
+    source: wgpu::TextureView,
     texture_a: Texture,
     texture_b: Texture,
+    /// Intermediate results of effects with several passes.
+    scratch: [Texture; SCRATCH_TEXTURES],
+
+    width: u32,
+    height: u32,
 }
 
 impl std::fmt::Debug for EffectPipeline {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("EffectPipeline")
-            .field("effects_count", &self.effects.len())
+            .field("effects", &self.effects())
             .field("texture_a", &self.texture_a)
             .field("texture_b", &self.texture_b)
+            .field("width", &self.width)
+            .field("height", &self.height)
             .finish()
     }
 }
 
 impl EffectPipeline {
-    pub fn builder<'a, 'b>(
-        gpu_allocator: &'a GpuResourceAllocator<'b>,
-        input_texture: &'a wgpu::TextureView,
+    pub fn new(
+        gpu_allocator: &GpuResourceAllocator,
+        source: &wgpu::Texture,
         width: u32,
         height: u32,
-    ) -> EffectPipelineBuilder<'a, 'b> {
-        EffectPipelineBuilder::new(gpu_allocator, input_texture, width, height)
+        effects: &[Effect],
+    ) -> Result<Self> {
+        let (device, queue) = (&gpu_allocator.device, &gpu_allocator.queue);
+
+        let source = source.create_view(&wgpu::TextureViewDescriptor::default());
+        let texture_a = gpu_allocator.create_storage_texture("pipeline_texture_a", width, height);
+        let texture_b = gpu_allocator.create_storage_texture("pipeline_texture_b", width, height);
+        let scratch = std::array::from_fn(|i| {
+            gpu_allocator.create_storage_texture(&format!("pipeline_scratch_{i}"), width, height)
+        });
+
+        let copy = ComputeEffect::builder("copy")
+            .with_shader(include_str!("copy_compute.wgsl"))
+            .build(
+                device,
+                queue,
+                &[TextureRoute::new(&source, &texture_a.view)],
+            )?;
+
+        let mut pipeline = Self {
+            effects: Vec::with_capacity(effects.len()),
+            copy,
+            source,
+            texture_a,
+            texture_b,
+            scratch,
+            width,
+            height,
+        };
+
+        for effect in effects {
+            pipeline.push(device, queue, effect.clone())?;
+        }
+
+        Ok(pipeline)
     }
 
     pub const fn texture_a(&self) -> &Texture {
         &self.texture_a
     }
 
-    pub fn execute(
-        &self,
-        feature_uniform: &FeatureUniform,
-        encoder: &mut wgpu::CommandEncoder,
-        workgroup_count_x: u32,
-        workgroup_count_y: u32,
-    ) {
-        for (effect, condition) in &self.effects {
-            if condition(feature_uniform) {
-                effect.dispatch(encoder, workgroup_count_x, workgroup_count_y);
-            }
-        }
+    pub fn effects(&self) -> Vec<Effect> {
+        self.effects
+            .iter()
+            .map(|(effect, _)| effect.clone())
+            .collect()
     }
 
-    pub fn update_effect_uniform<T: bytemuck::Pod>(
-        &self,
+    pub fn push(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        effect: Effect,
+    ) -> Result<()> {
+        self.insert(device, queue, self.effects.len(), effect)
+    }
+
+    pub fn insert(
+        &mut self,
+        device: &wgpu::Device,
         queue: &wgpu::Queue,
         index: usize,
-        data: T,
-    ) {
-        if let Some((effect, _)) = self.effects.get(index) {
-            effect.update_uniform(queue, data);
+        effect: Effect,
+    ) -> Result<()> {
+        ensure!(
+            index <= self.effects.len(),
+            "Can't insert an effect at {index} in a stack of {}.",
+            self.effects.len()
+        );
+
+        let compiled = self.compile(device, queue, &effect)?;
+        self.effects.insert(index, (effect, compiled));
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Effect> {
+        (index < self.effects.len()).then(|| self.effects.remove(index).0)
+    }
+
+    /// Moves the effect at `from` to `to`, shifting the effects in between.
+    pub fn reorder(&mut self, from: usize, to: usize) {
+        if from < self.effects.len() && to < self.effects.len() {
+            let effect = self.effects.remove(from);
+            self.effects.insert(to, effect);
         }
     }
-}
 
-pub struct EffectPipelineBuilder<'a, 'b> {
-    gpu_allocator: &'a GpuResourceAllocator<'b>,
-    input_texture: &'a wgpu::TextureView,
-    texture_a: Texture,
-    texture_b: Texture,
-    effects: Vec<(ComputeEffect, DispatchConditionalFn)>,
-}
+    /// Replaces the effect at `index`. New parameters for the same kind of effect only rewrite
+    /// its uniforms, and its lookup tables when they keep their sizes.
+    pub fn set(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        index: usize,
+        effect: Effect,
+    ) -> Result<()> {
+        let Some((current, compiled)) = self.effects.get_mut(index) else {
+            return self.insert(device, queue, index, effect);
+        };
 
-impl<'a, 'b> EffectPipelineBuilder<'a, 'b> {
-    fn new(
-        gpu_allocator: &'a GpuResourceAllocator<'b>,
-        input_texture: &'a wgpu::TextureView,
-        width: u32,
-        height: u32,
-    ) -> Self {
-        let texture_a = gpu_allocator.create_storage_texture("pipeline_texture_a", width, height);
-        let texture_b = gpu_allocator.create_storage_texture("pipeline_texture_b", width, height);
+        let passes = passes(&effect);
+        let same_tables = compiled.iter().zip(&passes).all(|(compute_effect, pass)| {
+            compute_effect.lookup_table_size() == pass.lookup_table.as_ref().map(LookupTable::size)
+        });
 
-        Self {
-            gpu_allocator,
-            input_texture,
-            texture_a,
-            texture_b,
-            effects: Vec::new(),
+        if mem::discriminant(current) == mem::discriminant(&effect) && same_tables {
+            for (compute_effect, pass) in compiled.iter().zip(passes) {
+                if let Some(uniform) = pass.uniform {
+                    compute_effect.update_uniform(queue, &uniform);
+                }
+
+                if let Some(table) = pass.lookup_table {
+                    compute_effect.update_lookup_table(queue, &table);
+                }
+            }
+
+            *current = effect;
+
+            return Ok(());
         }
+
+        let compiled = self.compile(device, queue, &effect)?;
+        self.effects[index] = (effect, compiled);
+
+        Ok(())
     }
 
-    pub fn add_effect<U, F>(
-        mut self,
-        label: &str,
-        shader_source: &str,
-        uniform: Option<U>,
-        condition: F,
-    ) -> Result<Self>
-    where
-        U: bytemuck::Pod,
-        F: Fn(&FeatureUniform) -> bool + 'static,
-    {
-        let effect_count = self.effects.len();
-
-        let (input_view, output_view) = if effect_count == 0 {
-            (self.input_texture, &self.texture_a.view)
-        } else if effect_count % 2 == 1 {
-            (&self.texture_a.view, &self.texture_b.view)
-        } else {
-            (&self.texture_b.view, &self.texture_a.view)
-        };
+    pub fn execute(&self, encoder: &mut wgpu::CommandEncoder) {
+        let workgroup_count_x = self.width.div_ceil(16);
+        let workgroup_count_y = self.height.div_ceil(16);
 
-        let mut builder = ComputeEffect::builder(label).with_shader(shader_source);
+        if self.effects.is_empty() {
+            self.copy
+                .dispatch(encoder, 0, workgroup_count_x, workgroup_count_y);
+            return;
+        }
 
-        if let Some(data) = uniform {
-            builder = builder.with_uniform(data);
+        // Effects alternate between the textures, so the first one writes to texture A when
+        // there's an odd number of them.
+        let last = self.effects.len() - 1;
+
+        for (i, (_, compiled)) in self.effects.iter().enumerate() {
+            let output_a = (last - i).is_multiple_of(2);
+
+            let route = match (i, output_a) {
+                (0, true) => Route::SourceToA,
+                (0, false) => Route::SourceToB,
+                (_, true) => Route::BToA,
+                (_, false) => Route::AToB,
+            };
+
+            for compute_effect in compiled {
+                compute_effect.dispatch(
+                    encoder,
+                    route as usize,
+                    workgroup_count_x,
+                    workgroup_count_y,
+                );
+            }
         }
+    }
 
-        let effect = builder.build(&self.gpu_allocator.device, input_view, output_view)?;
-        self.effects.push((effect, Box::new(condition)));
+    /// Compiles an effect's passes with a bind group for every route, so it can run anywhere in
+    /// the stack.
+    fn compile(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        effect: &Effect,
+    ) -> Result<Vec<ComputeEffect>> {
+        let (a, b) = (&self.texture_a.view, &self.texture_b.view);
+
+        // In the order of `Route`.
+        let routes = [(&self.source, a), (&self.source, b), (a, b), (b, a)];
+
+        passes(effect)
+            .into_iter()
+            .map(|pass| {
+                let view = |slot, (input, output)| match slot {
+                    Slot::Input => input,
+                    Slot::Output => output,
+                    Slot::Scratch(i) => &self.scratch[i].view,
+                };
+
+                let texture_routes = routes
+                    .iter()
+                    .map(|&route| TextureRoute {
+                        input: view(pass.input, route),
+                        second_input: pass.second_input.map(|slot| view(slot, route)),
+                        output: view(pass.output, route),
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut builder = ComputeEffect::builder(effect.name())
+                    .with_shader(pass.shader)
+                    .with_entry_point(pass.entry_point);
 
-        Ok(self)
+                if let Some(uniform) = pass.uniform {
+                    builder = builder.with_uniform(uniform);
+                }
+
+                if let Some(table) = pass.lookup_table {
+                    builder = builder.with_lookup_table(table);
+                }
+
+                builder.build(device, queue, &texture_routes)
+            })
+            .collect()
     }
+}
+
+/// The compute passes of each effect, with their parameters as uniform bytes. Registering an
+/// effect means adding it here.
+fn passes(effect: &Effect) -> Vec<Pass> {
+    use Slot::{Input, Output, Scratch};
+
+    const BLUR: &str = include_str!("blur_compute.wgsl");
+    const SOBEL: &str = include_str!("sobel_compute.wgsl");
+    const CANNY: &str = include_str!("canny_compute.wgsl");
+    const ADJUST: &str = include_str!("adjust_compute.wgsl");
+
+    // A color adjustment, by its entry point in `adjust_compute.wgsl`.
+    let adjust = |entry_point, parameters| {
+        vec![Pass::new(ADJUST, Input, Output)
+            .with_entry_point(entry_point)
+            .with_uniform(uniform(parameters))]
+    };
 
-    pub fn build(self) -> Result<EffectPipeline> {
-        Ok(EffectPipeline {
-            effects: self.effects,
-            texture_a: self.texture_a,
-            texture_b: self.texture_b,
-        })
+    // A separable Gaussian, horizontally into `scratch` and vertically from it.
+    let blur = |radius: f32, input, scratch, output| {
+        vec![
+            Pass::new(BLUR, input, scratch).with_uniform(uniform([radius, 1.0, 0.0])),
+            Pass::new(BLUR, scratch, output).with_uniform(uniform([radius, 0.0, 1.0])),
+        ]
+    };
+
+    match *effect {
+        Effect::Gamma(gamma) => {
+            vec![
+                Pass::new(include_str!("gamma_correct_compute.wgsl"), Input, Output)
+                    .with_uniform(gamma.to_ne_bytes().to_vec()),
+            ]
+        }
+        Effect::Grayscale => vec![Pass::new(
+            include_str!("grayscale_compute.wgsl"),
+            Input,
+            Output,
+        )],
+        Effect::Invert => vec![Pass::new(
+            include_str!("invert_compute.wgsl"),
+            Input,
+            Output,
+        )],
+        Effect::Blur { radius } => blur(radius, Input, Scratch(0), Output),
+        Effect::Sharpen {
+            radius,
+            amount,
+            threshold,
+        } => {
+            let mut passes = blur(radius, Input, Scratch(0), Scratch(1));
+            passes.push(
+                Pass::new(include_str!("unsharp_compute.wgsl"), Input, Output)
+                    .with_second_input(Scratch(1))
+                    .with_uniform(uniform([amount, threshold])),
+            );
+            passes
+        }
+        Effect::Sobel { threshold } => {
+            vec![Pass::new(SOBEL, Input, Output).with_uniform(uniform([threshold]))]
+        }
+        Effect::Canny { radius, low, high } => {
+            let mut passes = blur(radius, Input, Scratch(0), Scratch(1));
+            passes.extend([
+                Pass::new(SOBEL, Scratch(1), Scratch(0)).with_entry_point("canny_gradient"),
+                Pass::new(CANNY, Scratch(0), Scratch(1))
+                    .with_entry_point("suppress")
+                    .with_uniform(uniform([low, high])),
+                Pass::new(CANNY, Scratch(1), Output).with_entry_point("hysteresis"),
+            ]);
+            passes
+        }
+        Effect::BrightnessContrast {
+            brightness,
+            contrast,
+        } => adjust("brightness_contrast", [brightness, contrast, 0.0]),
+        Effect::Exposure { stops } => adjust("scale", [stops.exp2(); 3]),
+        Effect::Levels {
+            black,
+            white,
+            gamma,
+        } => adjust("levels", [black, white, gamma]),
+        Effect::Curves { channel, points } => vec![Pass::new(ADJUST, Input, Output)
+            .with_entry_point("curves")
+            .with_lookup_table(LookupTable::Row(curve_tables(channel, points)))],
+        Effect::HueSaturationLightness {
+            hue,
+            saturation,
+            lightness,
+        } => adjust("hue_saturation_lightness", [hue, saturation, lightness]),
+        Effect::Vibrance { amount } => adjust("vibrance", [amount, 0.0, 0.0]),
+        Effect::WhiteBalance { temperature, tint } => {
+            adjust("scale", white_balance_gains(temperature, tint))
+        }
+        Effect::Sepia { amount } => adjust("sepia", [amount, 0.0, 0.0]),
+        Effect::Lut {
+            ref lut,
+            interpolation,
+            ..
+        } => vec![Pass::new(include_str!("lut_compute.wgsl"), Input, Output)
+            .with_uniform(lut_uniform(lut, interpolation))
+            .with_lookup_table(LookupTable::Cube {
+                size: lut.size as u32,
+                entries: lut
+                    .entries
+                    .iter()
+                    .map(|&[r, g, b]| [r, g, b, 1.0])
+                    .collect(),
+            })],
     }
 }
+
+/// The domain of a LUT and whether it's interpolated tetrahedrally, as `lut_compute.wgsl` lays
+/// them out.
+fn lut_uniform(lut: &CubeLut, interpolation: LutInterpolation) -> Vec<u8> {
+    let [min_r, min_g, min_b] = lut.domain_min;
+    let [max_r, max_g, max_b] = lut.domain_max;
+    let tetrahedral = f32::from(u8::from(interpolation == LutInterpolation::Tetrahedral));
+
+    uniform([min_r, min_g, min_b, tetrahedral, max_r, max_g, max_b])
+}
+
+/// The bytes of a uniform of floats, padded to a multiple of 16 for uniform buffers.
+fn uniform<const N: usize>(values: [f32; N]) -> Vec<u8> {
+    let mut bytes = bytemuck::cast_slice(&values).to_vec();
+    bytes.resize(bytes.len().next_multiple_of(16), 0);
+    bytes
+}
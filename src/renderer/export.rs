@@ -0,0 +1,411 @@
+use crate::{
+    effects::{from_working_space, Edits, Effect},
+    image::{grammar::ImageExt, ImageBuffer, Rgba32F},
+    renderer::{
+        draw_uniform::DrawUniform,
+        effect_pipeline::EffectPipeline,
+        feature_uniform::FeatureUniform,
+        gpu_state::GpuResourceAllocator,
+        half::f16_to_f32,
+        shape_uniform::{CircleData, ShapeUniform},
+    },
+};
+use anyhow::{anyhow, bail, ensure, Result};
+use std::{iter, sync::mpsc};
+
+/// Half floats keep the linear samples of the effects without banding.
+const EXPORT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const BYTES_PER_PIXEL: u32 = 8;
+
+/// Renders `edits` over `image` on a headless GPU, returning sRGB with straight alpha like
+/// [`Edits::render`] does on the CPU.
+pub fn render_headless(image: &dyn ImageExt, edits: &Edits) -> Result<ImageBuffer<Rgba32F>> {
+    let gpu_allocator = pollster::block_on(GpuResourceAllocator::headless())?;
+
+    let (width, height) = image.dimensions();
+    let feature_uniform = FeatureUniform::from_edits(edits, width, height);
+    let circles = edits
+        .circles
+        .iter()
+        .map(CircleData::from)
+        .collect::<Vec<_>>();
+
+    let rendered = render_offscreen(
+        &gpu_allocator,
+        image,
+        feature_uniform,
+        &edits.effects,
+        &circles,
+    )?;
+
+    Ok(from_working_space(&rendered))
+}
+
+/// Renders `image` at its full resolution through the effect stack `effects`, the viewer's
+/// filters and shapes into an offscreen texture, and reads it back.
+///
+/// Samples are linear with premultiplied alpha, like [`crate::effects::to_working_space`]
+/// returns them, so the result can be finished like a CPU render.
+pub fn render_offscreen(
+    gpu_allocator: &GpuResourceAllocator,
+    image: &dyn ImageExt,
+    feature_uniform: FeatureUniform,
+    effects: &[Effect],
+    circles: &[CircleData],
+) -> Result<ImageBuffer<Rgba32F>> {
+    let (width, height) = image.dimensions();
+    let device = &gpu_allocator.device;
+
+    ensure_fits(device, width, height)?;
+
+    let (readback, bytes_per_row) = catch_errors(device, "render the image offscreen", || {
+        submit(gpu_allocator, image, feature_uniform, effects, circles)
+    })?;
+
+    map_read(device, &readback)?;
+
+    let samples = {
+        let data = readback.slice(..).get_mapped_range();
+
+        // Rows are padded to the copy alignment.
+        data.chunks_exact(bytes_per_row as usize)
+            .flat_map(|row| row[..(width * BYTES_PER_PIXEL) as usize].chunks_exact(2))
+            .map(|half| f16_to_f32(u16::from_le_bytes([half[0], half[1]])))
+            .collect::<Vec<_>>()
+    };
+    readback.unmap();
+
+    ImageBuffer::from_raw(width, height, &samples)
+}
+
+/// Fails when the GPU can't hold a texture of `width` by `height` pixels.
+pub fn ensure_fits(device: &wgpu::Device, width: u32, height: u32) -> Result<()> {
+    let max_dimension = device.limits().max_texture_dimension_2d;
+    ensure!(
+        width <= max_dimension && height <= max_dimension,
+        "The {width}x{height} image exceeds the GPU's {max_dimension} pixel texture limit."
+    );
+
+    Ok(())
+}
+
+/// Runs `record`, returning the validation and out of memory errors it raises as an error to
+/// `action`. They would otherwise go to the device's handler, which panics.
+pub fn catch_errors<T>(
+    device: &wgpu::Device,
+    action: &str,
+    record: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+
+    let recorded = record();
+
+    let out_of_memory = pollster::block_on(device.pop_error_scope());
+    let validation = pollster::block_on(device.pop_error_scope());
+
+    if let Some(error) = out_of_memory.or(validation) {
+        bail!("Failed to {action}: {error}");
+    }
+
+    recorded
+}
+
+/// Maps `buffer` for reading, waiting for the work submitted before to finish.
+pub fn map_read(device: &wgpu::Device, buffer: &wgpu::Buffer) -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .map_err(|_| anyhow!("The readback buffer was dropped before it was mapped."))??;
+
+    Ok(())
+}
+
+/// Records and submits the render, returning the buffer it's copied into and its row pitch.
+fn submit(
+    gpu_allocator: &GpuResourceAllocator,
+    image: &dyn ImageExt,
+    mut feature_uniform: FeatureUniform,
+    effects: &[Effect],
+    circles: &[CircleData],
+) -> Result<(wgpu::Buffer, u32)> {
+    let (width, height) = image.dimensions();
+    let device = &gpu_allocator.device;
+
+    // The target has the image's dimensions rather than the window's.
+    feature_uniform.update_window_dimensions(width, height);
+
+    let image_texture = gpu_allocator.create_texture_resource("export_image_texture", image)?;
+    let effect_pipeline = EffectPipeline::new(
+        gpu_allocator,
+        &image_texture.resource.texture,
+        width,
+        height,
+        effects,
+    )?;
+
+    let shape_render_texture =
+        gpu_allocator.create_render_texture("export_shape_texture", width, height);
+
+    let mut shape_uniform = ShapeUniform::new(width, height);
+    shape_uniform.set_num_circles(circles.len() as u32);
+
+    // Storage bindings can't be empty.
+    let circle_data = if circles.is_empty() {
+        &[CircleData::default()][..]
+    } else {
+        circles
+    };
+    let circle_storage_buffer =
+        gpu_allocator.create_storage_buffer("export_circle_storage", circle_data)?;
+
+    let shape_shader = gpu_allocator.create_shape_shader(
+        "export_shape_shader",
+        include_str!("shape_shader.wgsl"),
+        gpu_allocator.create_uniform_resource("export_shape_uniform", shape_uniform)?,
+        &circle_storage_buffer,
+    );
+
+    let image_shader = gpu_allocator.create_shader_for_format(
+        "export_image_shader",
+        include_str!("image_shader.wgsl"),
+        EXPORT_FORMAT,
+        vec![
+            gpu_allocator.create_texture_resource_from_existing(
+                "export_processed_texture_a",
+                effect_pipeline.texture_a(),
+            ),
+            gpu_allocator.create_texture_resource_from_existing(
+                "export_shape_texture_ref",
+                &shape_render_texture.resource,
+            ),
+        ],
+        vec![
+            gpu_allocator.create_uniform_resource("export_feature_uniform", feature_uniform)?,
+            gpu_allocator.create_uniform_resource("export_draw_uniform", DrawUniform::new())?,
+        ],
+    );
+
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("export_target"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: EXPORT_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let bytes_per_row =
+        (width * BYTES_PER_PIXEL).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("export_readback"),
+        size: u64::from(bytes_per_row) * u64::from(height),
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Export Encoder"),
+    });
+
+    effect_pipeline.execute(&mut encoder);
+
+    gpu_allocator.draw_quad(
+        &mut encoder,
+        "export shape pass",
+        &shape_shader,
+        &shape_render_texture.resource.view,
+        wgpu::Color::TRANSPARENT,
+    );
+    gpu_allocator.draw_quad(
+        &mut encoder,
+        "export content pass",
+        &image_shader,
+        &target_view,
+        wgpu::Color::TRANSPARENT,
+    );
+
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture: &target,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &readback,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        size,
+    );
+
+    gpu_allocator.queue.submit(iter::once(encoder.finish()));
+
+    Ok((readback, bytes_per_row))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        effects::{
+            to_working_space, CircleShape, CubeLut, CurveChannel, LutInterpolation,
+            COLOR_ADJUSTMENTS,
+        },
+        image::ImageReader,
+    };
+    use std::{path::PathBuf, sync::Arc};
+
+    #[test]
+    #[cfg_attr(not(feature = "gpu-tests"), ignore = "needs the gpu-tests feature")]
+    fn test_render_offscreen_matches_cpu() -> Result<()> {
+        let gpu_allocator = GpuResourceAllocator::for_tests()?;
+
+        let image = ImageReader::read_from_path("./test_suite/basn6a08.png", None)?;
+        let (width, height) = image.dimensions();
+
+        // A look that rotates the channels and bends one, so interpolation shows.
+        let mut lut = CubeLut::identity(9);
+        for entry in &mut lut.entries {
+            let [r, g, b] = *entry;
+            *entry = [g * g, b, r];
+        }
+        let lut = Arc::new(lut);
+
+        let edits = [
+            Edits::default(),
+            Edits {
+                effects: vec![Effect::Grayscale, Effect::Invert],
+                ..Edits::default()
+            },
+            Edits {
+                effects: vec![Effect::Gamma(220_000), Effect::Invert, Effect::Grayscale],
+                ..Edits::default()
+            },
+            Edits {
+                effects: vec![Effect::Sharpen {
+                    radius: 2.0,
+                    amount: 1.5,
+                    threshold: 0.0,
+                }],
+                flip_x: true,
+                ..Edits::default()
+            },
+            Edits {
+                effects: vec![Effect::Blur { radius: 2.5 }],
+                flip_y: true,
+                ..Edits::default()
+            },
+            Edits {
+                effects: COLOR_ADJUSTMENTS.to_vec(),
+                ..Edits::default()
+            },
+            Edits {
+                effects: vec![
+                    Effect::WhiteBalance {
+                        temperature: -0.4,
+                        tint: 0.3,
+                    },
+                    Effect::Curves {
+                        channel: CurveChannel::Luma,
+                        points: [0.1, 0.2, 0.6, 0.7, 0.9],
+                    },
+                    Effect::HueSaturationLightness {
+                        hue: 120.0,
+                        saturation: -0.5,
+                        lightness: -0.2,
+                    },
+                ],
+                ..Edits::default()
+            },
+            Edits {
+                effects: vec![
+                    Effect::Lut {
+                        path: PathBuf::from("rotate.cube"),
+                        lut: lut.clone(),
+                        interpolation: LutInterpolation::Tetrahedral,
+                    },
+                    Effect::Lut {
+                        path: PathBuf::from("rotate.cube"),
+                        lut,
+                        interpolation: LutInterpolation::Trilinear,
+                    },
+                ],
+                ..Edits::default()
+            },
+            Edits {
+                effects: vec![Effect::Canny {
+                    radius: 2.0,
+                    low: 0.2,
+                    high: 0.6,
+                }],
+                ..Edits::default()
+            },
+            Edits {
+                effects: vec![Effect::Sobel { threshold: 0.0 }],
+                circles: vec![CircleShape {
+                    center: (0.25, 0.5),
+                    radius: 0.2,
+                }],
+                ..Edits::default()
+            },
+        ];
+
+        for edits in edits {
+            let circles = edits
+                .circles
+                .iter()
+                .map(CircleData::from)
+                .collect::<Vec<_>>();
+            let gpu = render_offscreen(
+                &gpu_allocator,
+                image.as_ref(),
+                FeatureUniform::from_edits(&edits, width, height),
+                &edits.effects,
+                &circles,
+            )?;
+            let cpu = edits.apply(to_working_space(image.as_ref())?);
+
+            // The GPU samples an 8-bit sRGB texture and renders to half floats. Filters amplify
+            // the rounding by the magnitudes of their weights.
+            let gain = edits
+                .effects
+                .iter()
+                .map(|effect| match *effect {
+                    Effect::Sharpen { amount, .. } => 2.0f32.mul_add(amount, 1.0),
+                    Effect::Sobel { .. } => 8.0,
+                    // The steepest slope of the look.
+                    Effect::Lut { .. } => 2.0,
+                    _ => 1.0,
+                })
+                .product::<f32>();
+            let max_error = gpu
+                .as_raw()
+                .iter()
+                .zip(cpu.as_raw())
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f32::max);
+            assert!(max_error < 0.01 * gain, "{edits:?}: {max_error}");
+        }
+
+        Ok(())
+    }
+}
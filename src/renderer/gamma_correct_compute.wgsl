@@ -6,7 +6,7 @@ var input_texture: texture_2d<f32>;
 
 @group(0) 
 @binding(1)
-var output_texture: texture_storage_2d<rgba8unorm, write>;
+var output_texture: texture_storage_2d<rgba16float, write>;
 
 @group(0)
 @binding(2)
@@ -29,12 +29,11 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         let gamma_value = f32(gamma) / 100000.0;
         let inv_gamma = 1.0 / gamma_value;
 
-        color = vec4<f32>(
-            pow(color.r, inv_gamma),
-            pow(color.g, inv_gamma),
-            pow(color.b, inv_gamma),
-            color.a
-        );
+        // Colors are premultiplied. The curve applies to the straight color.
+        if (color.a > 0.0) {
+            let straight = pow(color.rgb / color.a, vec3<f32>(inv_gamma));
+            color = vec4<f32>(straight * color.a, color.a);
+        }
     }
 
     textureStore(output_texture, coords, color);
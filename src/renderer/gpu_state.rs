@@ -1,5 +1,5 @@
 use crate::{
-    image::grammar::Image,
+    image::grammar::ImageExt,
     renderer::{
         shader::{Shader, TextureResource, UniformResource},
         Texture, Vertex,
@@ -36,7 +36,8 @@ const INDICES: &[u16] = &[
 
 #[derive(Debug)]
 pub struct GpuResourceAllocator<'a> {
-    surface: wgpu::Surface<'a>,
+    /// Headless allocators have no surface, and only render offscreen.
+    surface: Option<wgpu::Surface<'a>>,
     pub(crate) device: wgpu::Device,
     pub(crate) queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
@@ -45,6 +46,71 @@ pub struct GpuResourceAllocator<'a> {
     pub(crate) index_buffer: wgpu::Buffer,
 }
 
+impl GpuResourceAllocator<'static> {
+    /// Creates an allocator without a window, for rendering offscreen from tests and tools.
+    ///
+    /// Any backend will do, including a software rasterizer when there's no GPU. `WGPU_BACKEND`
+    /// narrows the choice. Shaders target `Rgba8UnormSrgb` in place of a surface.
+    pub async fn headless() -> Result<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all()),
+            ..Default::default()
+        });
+
+        let mut adapter = None;
+
+        for force_fallback_adapter in [false, true] {
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    compatible_surface: None,
+                    force_fallback_adapter,
+                })
+                .await;
+
+            if adapter.is_some() {
+                break;
+            }
+        }
+
+        let adapter = adapter.ok_or_else(|| anyhow!("Failed to get a headless adapter"))?;
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features: wgpu::Features::empty(),
+                    // Software adapters fall short of the defaults, so take what's there.
+                    required_limits: adapter.limits(),
+                    memory_hints: Default::default(),
+                },
+                None,
+            )
+            .await?;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: 1,
+            height: 1,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+
+        Ok(Self::from_device(None, device, queue, config))
+    }
+
+    /// The allocator of the tests that drive the GPU. They're ignored unless the `gpu-tests`
+    /// feature is on, since CI runners have neither a GPU nor a software rasterizer.
+    #[cfg(test)]
+    pub fn for_tests() -> Result<Self> {
+        pollster::block_on(Self::headless())
+            .map_err(|error| anyhow!("The GPU tests need a wgpu adapter: {error}"))
+    }
+}
+
 impl<'a> GpuResourceAllocator<'a> {
     pub async fn new(window: &'a Window) -> Result<Self> {
         let size = window.inner_size();
@@ -109,6 +175,15 @@ impl<'a> GpuResourceAllocator<'a> {
             desired_maximum_frame_latency: 2,
         };
 
+        Ok(Self::from_device(Some(surface), device, queue, config))
+    }
+
+    fn from_device(
+        surface: Option<wgpu::Surface<'a>>,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+    ) -> Self {
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
             contents: bytemuck::cast_slice(VERTICES),
@@ -120,7 +195,7 @@ impl<'a> GpuResourceAllocator<'a> {
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        Ok(Self {
+        Self {
             surface,
             device,
             queue,
@@ -128,7 +203,7 @@ impl<'a> GpuResourceAllocator<'a> {
 
             vertex_buffer,
             index_buffer,
-        })
+        }
     }
 
     pub const fn num_indices(&self) -> u32 {
@@ -142,7 +217,10 @@ impl<'a> GpuResourceAllocator<'a> {
 
     pub fn configure_surface(&mut self, size: &PhysicalSize<u32>) {
         self.update_config_size(size);
-        self.surface.configure(&self.device, &self.config);
+
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
     }
 
     pub fn create_shader(
@@ -151,6 +229,24 @@ impl<'a> GpuResourceAllocator<'a> {
         source: &str,
         texture_resources: Vec<TextureResource>,
         uniform_resources: Vec<UniformResource>,
+    ) -> Shader {
+        self.create_shader_for_format(
+            label,
+            source,
+            self.config.format,
+            texture_resources,
+            uniform_resources,
+        )
+    }
+
+    /// Like [`Self::create_shader`], but renders to targets of `format` instead of the surface.
+    pub fn create_shader_for_format(
+        &self,
+        label: &str,
+        source: &str,
+        format: wgpu::TextureFormat,
+        texture_resources: Vec<TextureResource>,
+        uniform_resources: Vec<UniformResource>,
     ) -> Shader {
         let shader = self
             .device
@@ -193,7 +289,7 @@ impl<'a> GpuResourceAllocator<'a> {
                     module: &shader,
                     entry_point: "fs_main",
                     targets: &[Some(wgpu::ColorTargetState {
-                        format: self.config.format,
+                        format,
                         blend: Some(wgpu::BlendState {
                             color: wgpu::BlendComponent::REPLACE,
                             alpha: wgpu::BlendComponent::REPLACE,
@@ -480,7 +576,11 @@ impl<'a> GpuResourceAllocator<'a> {
         }
     }
 
-    pub fn create_texture_resource(&self, label: &str, image: &Image) -> Result<TextureResource> {
+    pub fn create_texture_resource(
+        &self,
+        label: &str,
+        image: &dyn ImageExt,
+    ) -> Result<TextureResource> {
         let diffuse_texture = Texture::from_bytes(&self.device, &self.queue, image)?;
 
         let texture_bind_group_layout =
@@ -575,7 +675,9 @@ impl<'a> GpuResourceAllocator<'a> {
         ),
         wgpu::SurfaceError,
     > {
-        let output = self.surface.get_current_texture()?;
+        // There's nothing to present to without a window.
+        let surface = self.surface.as_ref().ok_or(wgpu::SurfaceError::Lost)?;
+        let output = surface.get_current_texture()?;
 
         let view = output
             .texture
@@ -590,6 +692,71 @@ impl<'a> GpuResourceAllocator<'a> {
         Ok((output, view, encoder))
     }
 
+    /// Draws the full-screen quad with `shader` into `target`, after clearing it to `clear`.
+    pub fn draw_quad(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        shader: &Shader,
+        target: &wgpu::TextureView,
+        clear: wgpu::Color,
+    ) {
+        self.draw(encoder, label, shader, target, wgpu::LoadOp::Clear(clear));
+    }
+
+    /// Draws the full-screen quad with `shader` over what `target` already holds.
+    pub fn draw_overlay(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        shader: &Shader,
+        target: &wgpu::TextureView,
+    ) {
+        self.draw(encoder, label, shader, target, wgpu::LoadOp::Load);
+    }
+
+    fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        shader: &Shader,
+        target: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&shader.render_pipeline);
+
+        // Texture bind groups come first, then uniforms.
+        let bind_groups = shader
+            .texture_resources
+            .iter()
+            .map(|r| &r.bind_group)
+            .chain(shader.uniform_resources.iter().map(|r| &r.bind_group));
+
+        for (i, bind_group) in bind_groups.enumerate() {
+            render_pass.set_bind_group(i as u32, bind_group, &[]);
+        }
+
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        render_pass.draw_indexed(0..self.num_indices(), 0, 0..1);
+    }
+
     pub fn end_frame(&self, encoder: wgpu::CommandEncoder) {
         self.queue.submit(iter::once(encoder.finish()));
     }
@@ -605,7 +772,7 @@ impl<'a> GpuResourceAllocator<'a> {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
+            format: wgpu::TextureFormat::Rgba16Float,
             usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
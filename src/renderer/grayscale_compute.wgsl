@@ -6,7 +6,7 @@ var input_texture: texture_2d<f32>;
 
 @group(0)
 @binding(1)
-var output_texture: texture_storage_2d<rgba8unorm, write>;
+var output_texture: texture_storage_2d<rgba16float, write>;
 
 @compute
 @workgroup_size(16, 16)
@@ -0,0 +1,115 @@
+/// Converts to an IEEE 754 half-precision float, rounding to the nearest even value. Values
+/// beyond the half range become infinite and NaNs stay NaN.
+pub const fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+
+    if exponent == 0xFF {
+        let nan = if mantissa == 0 { 0 } else { 0x200 };
+        return sign | 0x7C00 | nan;
+    }
+
+    // Rebias from 127 to 15.
+    let exponent = exponent - 112;
+
+    if exponent >= 0x1F {
+        return sign | 0x7C00;
+    }
+
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+
+        // Subnormal halves count in units of 2^-24, so the implicit bit is shifted in.
+        let mantissa = mantissa | 0x80_0000;
+        return sign | round_shift(mantissa, (14 - exponent) as u32) as u16;
+    }
+
+    // A mantissa that rounds up carries into the exponent, up to infinity.
+    sign | (((exponent as u32) << 10) + round_shift(mantissa, 13)) as u16
+}
+
+/// Converts from an IEEE 754 half-precision float, which is exact.
+pub const fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1F) as u32;
+    let mantissa = (half & 0x3FF) as u32;
+
+    let bits = match (exponent, mantissa) {
+        (0, 0) => sign,
+        (0, _) => {
+            // Normalize the subnormal, whose value is mantissa * 2^-24.
+            let shift = mantissa.leading_zeros() - 21;
+            let mantissa = (mantissa << shift) & 0x3FF;
+            sign | ((113 - shift) << 23) | (mantissa << 13)
+        }
+        (0x1F, _) => sign | 0x7F80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+    };
+
+    f32::from_bits(bits)
+}
+
+const fn round_shift(value: u32, shift: u32) -> u32 {
+    let halfway = 1 << (shift - 1);
+    let remainder = value & ((1 << shift) - 1);
+    let shifted = value >> shift;
+
+    if remainder > halfway || (remainder == halfway && shifted & 1 == 1) {
+        shifted + 1
+    } else {
+        shifted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_f32_to_f16() {
+        let cases = [
+            (0.0, 0x0000),
+            (-0.0, 0x8000),
+            (1.0, 0x3C00),
+            (-2.0, 0xC000),
+            (0.5, 0x3800),
+            (0.1, 0x2E66),
+            (65504.0, 0x7BFF),
+            (65520.0, 0x7C00),
+            (1e6, 0x7C00),
+            (f32::NEG_INFINITY, 0xFC00),
+            // Ties round to even.
+            (1.0 + 2f32.powi(-11), 0x3C00),
+            (1.0 + 2f32.powi(-10) + 2f32.powi(-11), 0x3C02),
+            // Subnormals.
+            (2f32.powi(-24), 0x0001),
+            (2f32.powi(-25), 0x0000),
+            (1.5 * 2f32.powi(-25), 0x0001),
+            (2f32.powi(-14) - 2f32.powi(-25), 0x0400),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(f32_to_f16(value), expected, "{value}");
+        }
+
+        let nan = f32_to_f16(f32::NAN);
+        assert!(nan & 0x7C00 == 0x7C00 && nan & 0x3FF != 0);
+    }
+
+    #[test]
+    fn test_f16_to_f32() {
+        assert!(f16_to_f32(0x7E00).is_nan());
+        assert_eq!(f16_to_f32(0xFC00), f32::NEG_INFINITY);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x03FF), 2f32.powi(-14) - 2f32.powi(-24));
+
+        // Every finite half survives the round trip.
+        for half in (0..=u16::MAX).filter(|h| h & 0x7C00 != 0x7C00) {
+            assert_eq!(f32_to_f16(f16_to_f32(half)), half, "{half:#06x}");
+        }
+    }
+}
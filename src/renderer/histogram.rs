@@ -0,0 +1,300 @@
+use crate::{
+    effects::{Effect, Histogram, HISTOGRAM_BINS},
+    image::grammar::ImageExt,
+    renderer::{
+        effect_pipeline::EffectPipeline,
+        export::{catch_errors, ensure_fits, map_read},
+        gpu_state::GpuResourceAllocator,
+        Texture,
+    },
+};
+use anyhow::Result;
+use std::iter;
+
+/// The bytes of the bins of the four channels and the peak, as `histogram_compute.wgsl` lays
+/// them out.
+const BINS_SIZE: u64 = ((4 * HISTOGRAM_BINS + 1) * 4) as u64;
+
+/// Counts the histogram of a texture with a compute shader, into a storage buffer the overlay
+/// draws from and [`Self::read`] reads back.
+#[derive(Debug)]
+pub struct GpuHistogram {
+    count: wgpu::ComputePipeline,
+    peak: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    bins: wgpu::Buffer,
+
+    width: u32,
+    height: u32,
+}
+
+impl GpuHistogram {
+    /// Counts the `width` by `height` pixels of `input`, like the effect pipeline's texture A.
+    pub fn new(device: &wgpu::Device, input: &Texture, width: u32, height: u32) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("histogram"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("histogram_compute.wgsl").into()),
+        });
+
+        let bins = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("histogram_bins"),
+            size: BINS_SIZE,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("histogram_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&input.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: bins.as_entire_binding(),
+                },
+            ],
+            label: Some("histogram_bind_group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("histogram_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("histogram"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point,
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        Self {
+            count: pipeline("main"),
+            peak: pipeline("peak"),
+            bind_group,
+            bins,
+            width,
+            height,
+        }
+    }
+
+    /// The bins and their peak, for shaders to read.
+    pub const fn bins(&self) -> &wgpu::Buffer {
+        &self.bins
+    }
+
+    /// Records counting the input from empty bins.
+    pub fn compute(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(&self.bins, 0, None);
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("histogram pass"),
+            timestamp_writes: None,
+        });
+
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+
+        compute_pass.set_pipeline(&self.count);
+        compute_pass.dispatch_workgroups(self.width.div_ceil(16), self.height.div_ceil(16), 1);
+
+        compute_pass.set_pipeline(&self.peak);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+
+    /// Reads the bins back once the work submitted before, which should include
+    /// [`Self::compute`], has finished.
+    pub fn read(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Histogram> {
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("histogram_readback"),
+            size: BINS_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Histogram Readback Encoder"),
+        });
+        encoder.copy_buffer_to_buffer(&self.bins, 0, &readback, 0, BINS_SIZE);
+        queue.submit(iter::once(encoder.finish()));
+
+        map_read(device, &readback)?;
+
+        let mut bins = [[0; HISTOGRAM_BINS]; 4];
+        {
+            let data = readback.slice(..).get_mapped_range();
+            let counts = data
+                .chunks_exact(4)
+                .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+
+            for (bin, count) in bins.iter_mut().flatten().zip(counts) {
+                *bin = count;
+            }
+        }
+        readback.unmap();
+
+        Ok(Histogram { bins })
+    }
+}
+
+/// The window's dimensions, for placing the histogram overlay.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct HistogramUniform {
+    width: u32,
+    height: u32,
+    _padding: [u32; 2],
+}
+
+impl HistogramUniform {
+    pub const fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            _padding: [0; 2],
+        }
+    }
+
+    pub const fn update_dimensions(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+}
+
+/// Counts the histogram of `image` through the effect stack `effects` on a headless GPU, like
+/// [`Histogram::of`] does on the CPU.
+pub fn histogram_headless(image: &dyn ImageExt, effects: &[Effect]) -> Result<Histogram> {
+    let gpu_allocator = pollster::block_on(GpuResourceAllocator::headless())?;
+
+    histogram_offscreen(&gpu_allocator, image, effects)
+}
+
+/// Runs `image` through `effects` at its full resolution and counts the histogram of the result.
+pub fn histogram_offscreen(
+    gpu_allocator: &GpuResourceAllocator,
+    image: &dyn ImageExt,
+    effects: &[Effect],
+) -> Result<Histogram> {
+    let (width, height) = image.dimensions();
+    let (device, queue) = (&gpu_allocator.device, &gpu_allocator.queue);
+
+    ensure_fits(device, width, height)?;
+
+    let histogram = catch_errors(device, "count the histogram offscreen", || {
+        let image_texture =
+            gpu_allocator.create_texture_resource("histogram_image_texture", image)?;
+        let effect_pipeline = EffectPipeline::new(
+            gpu_allocator,
+            &image_texture.resource.texture,
+            width,
+            height,
+            effects,
+        )?;
+        let histogram = GpuHistogram::new(device, effect_pipeline.texture_a(), width, height);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Histogram Encoder"),
+        });
+        effect_pipeline.execute(&mut encoder);
+        histogram.compute(&mut encoder);
+        queue.submit(iter::once(encoder.finish()));
+
+        Ok(histogram)
+    })?;
+
+    histogram.read(device, queue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        effects::{to_working_space, Edits, HistogramChannel},
+        image::ImageReader,
+    };
+
+    #[test]
+    #[cfg_attr(not(feature = "gpu-tests"), ignore = "needs the gpu-tests feature")]
+    fn test_histogram_offscreen_matches_cpu() -> Result<()> {
+        let gpu_allocator = GpuResourceAllocator::for_tests()?;
+
+        // Opaque, since dividing by alpha would amplify the rounding of faint pixels' colors.
+        let image = ImageReader::read_from_path("./test_suite/basn2c08.png", None)?;
+
+        let stacks = [
+            vec![],
+            vec![
+                Effect::Levels {
+                    black: 0.1,
+                    white: 0.8,
+                    gamma: 1.4,
+                },
+                Effect::Blur { radius: 2.5 },
+            ],
+        ];
+
+        for effects in stacks {
+            let gpu = histogram_offscreen(&gpu_allocator, image.as_ref(), &effects)?;
+            let cpu = Histogram::of(
+                &Edits {
+                    effects: effects.clone(),
+                    ..Edits::default()
+                }
+                .apply(to_working_space(image.as_ref())?),
+            );
+
+            assert_eq!(gpu.total(), cpu.total(), "{effects:?}");
+
+            // Half float rounding moves values across the edges of bins, but not far: the
+            // distance the counts have to move to match stays well under a bin.
+            for channel in HistogramChannel::ALL {
+                let (mut gpu_count, mut cpu_count, mut distance) = (0i64, 0i64, 0);
+
+                for (&a, &b) in gpu.channel(channel).iter().zip(cpu.channel(channel)) {
+                    gpu_count += i64::from(a);
+                    cpu_count += i64::from(b);
+                    distance += (gpu_count - cpu_count).abs();
+                }
+
+                let mean = distance as f32 / cpu.total() as f32;
+                assert!(mean < 0.5, "{effects:?} {channel:?}: {mean}");
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+// Compute shaders counting the histogram of a texture into atomic bins, like effects::Histogram:
+// the sRGB encoded straight colors of red, green, blue and luma, in 256 bins each
+
+@group(0)
+@binding(0)
+var input_texture: texture_2d<f32>;
+
+struct Bins {
+    // Red, green, blue and luma, one after the other
+    counts: array<atomic<u32>, 1024>,
+    // The largest count, which the overlay scales the bins by
+    peak: atomic<u32>,
+}
+
+@group(0)
+@binding(1)
+var<storage, read_write> bins: Bins;
+
+const BINS: u32 = 256u;
+const LUMA_WEIGHTS: vec3<f32> = vec3<f32>(0.29891, 0.58661, 0.11448);
+
+// Each workgroup counts its pixels here first, so few atomics contend for the buffer
+var<workgroup> local_counts: array<atomic<u32>, 1024>;
+
+fn srgb_encode(linear: vec3<f32>) -> vec3<f32> {
+    let c = clamp(linear, vec3<f32>(0.0), vec3<f32>(1.0));
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c < vec3<f32>(0.0031308));
+}
+
+fn bin(value: f32) -> u32 {
+    return min(u32(clamp(value, 0.0, 1.0) * f32(BINS)), BINS - 1u);
+}
+
+@compute
+@workgroup_size(16, 16)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    for (var i = local_index; i < 4u * BINS; i += 256u) {
+        atomicStore(&local_counts[i], 0u);
+    }
+
+    workgroupBarrier();
+
+    let coords = vec2<i32>(global_id.xy);
+    let dimensions = vec2<i32>(textureDimensions(input_texture));
+
+    if (coords.x < dimensions.x && coords.y < dimensions.y) {
+        let color = textureLoad(input_texture, coords, 0);
+
+        // Transparent pixels have no color to count
+        if (color.a > 0.0) {
+            let rgb = srgb_encode(color.rgb / color.a);
+
+            atomicAdd(&local_counts[bin(rgb.r)], 1u);
+            atomicAdd(&local_counts[BINS + bin(rgb.g)], 1u);
+            atomicAdd(&local_counts[2u * BINS + bin(rgb.b)], 1u);
+            atomicAdd(&local_counts[3u * BINS + bin(dot(rgb, LUMA_WEIGHTS))], 1u);
+        }
+    }
+
+    workgroupBarrier();
+
+    for (var i = local_index; i < 4u * BINS; i += 256u) {
+        let count = atomicLoad(&local_counts[i]);
+
+        if (count > 0u) {
+            atomicAdd(&bins.counts[i], count);
+        }
+    }
+}
+
+// Finds the largest count once main has run, with one invocation per bin
+@compute
+@workgroup_size(256)
+fn peak(@builtin(local_invocation_index) i: u32) {
+    for (var channel = 0u; channel < 4u; channel++) {
+        atomicMax(&bins.peak, atomicLoad(&bins.counts[channel * BINS + i]));
+    }
+}
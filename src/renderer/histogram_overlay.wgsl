@@ -0,0 +1,71 @@
+// Draws the histogram histogram_compute.wgsl counted in the bottom left corner of the window:
+// red, green and blue added together, over the luma in gray
+
+struct HistogramUniform {
+    width: u32,
+    height: u32,
+    _padding: vec2<u32>,
+}
+
+struct Bins {
+    counts: array<u32, 1024>,
+    peak: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> histogram_uniform: HistogramUniform;
+
+@group(0) @binding(1)
+var<storage, read> bins: Bins;
+
+const BINS: u32 = 256u;
+
+// In pixels, one column per bin
+const PANEL_SIZE: vec2<f32> = vec2<f32>(256.0, 128.0);
+const MARGIN: f32 = 16.0;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(model.position.x, model.position.y, model.position.z, 1.0);
+    out.tex_coords = model.tex_coords;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let window = vec2<f32>(f32(histogram_uniform.width), f32(histogram_uniform.height));
+    let origin = vec2<f32>(MARGIN, window.y - MARGIN - PANEL_SIZE.y);
+    let position = (in.tex_coords * window - origin) / PANEL_SIZE;
+
+    if (any(position < vec2<f32>(0.0)) || any(position >= vec2<f32>(1.0))) {
+        return vec4<f32>(0.0);
+    }
+
+    let bin = min(u32(position.x * f32(BINS)), BINS - 1u);
+    let peak = f32(max(bins.peak, 1u));
+    let heights = vec4<f32>(
+        f32(bins.counts[bin]),
+        f32(bins.counts[BINS + bin]),
+        f32(bins.counts[2u * BINS + bin]),
+        f32(bins.counts[3u * BINS + bin]),
+    ) / peak;
+
+    // From the bottom of the panel, with the peak at the top
+    let height = 1.0 - position.y;
+
+    let rgb = select(vec3<f32>(0.0), vec3<f32>(0.85), heights.rgb >= vec3<f32>(height));
+    let luma = select(0.0, 0.35, heights.a >= height);
+
+    return vec4<f32>(max(rgb, vec3<f32>(luma)), 0.75);
+}
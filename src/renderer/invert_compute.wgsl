@@ -6,7 +6,7 @@ var input_texture: texture_2d<f32>;
 
 @group(0)
 @binding(1)
-var output_texture: texture_storage_2d<rgba8unorm, write>;
+var output_texture: texture_storage_2d<rgba16float, write>;
 
 @compute
 @workgroup_size(16, 16)
@@ -21,13 +21,8 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 
     let color = textureLoad(input_texture, coords, 0);
 
-    // Invert RGB, keep alpha
-    let inverted = vec4<f32>(
-        1.0 - color.r,
-        1.0 - color.g,
-        1.0 - color.b,
-        color.a
-    );
+    // Invert RGB, keep alpha. Colors are premultiplied, so they're inverted against alpha.
+    let inverted = vec4<f32>(color.a - color.rgb, color.a);
 
     textureStore(output_texture, coords, inverted);
 }
@@ -0,0 +1,116 @@
+// Compute shader mapping colors through a 3D LUT, on straight colors encoded with the sRGB curve
+// like effects::color_grade
+
+@group(0)
+@binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var output_texture: texture_storage_2d<rgba16float, write>;
+
+struct Domain {
+    min: vec3<f32>,
+    // 1 to interpolate tetrahedrally, 0 to interpolate trilinearly
+    tetrahedral: f32,
+    max: vec3<f32>,
+}
+
+@group(0)
+@binding(2)
+var<uniform> domain: Domain;
+
+// The output colors, with red along x, green along y and blue along z
+@group(0)
+@binding(4)
+var lut: texture_3d<f32>;
+
+fn srgb_encode(linear: vec3<f32>) -> vec3<f32> {
+    let c = clamp(linear, vec3<f32>(0.0), vec3<f32>(1.0));
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c < vec3<f32>(0.0031308));
+}
+
+fn srgb_decode(encoded: vec3<f32>) -> vec3<f32> {
+    let c = clamp(encoded, vec3<f32>(0.0), vec3<f32>(1.0));
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, c < vec3<f32>(0.04045));
+}
+
+fn corner(base: vec3<i32>, offset: vec3<i32>) -> vec3<f32> {
+    return textureLoad(lut, base + offset, 0).rgb;
+}
+
+fn sample(rgb: vec3<f32>) -> vec3<f32> {
+    let size = i32(textureDimensions(lut).x);
+    let scale = f32(size - 1);
+
+    let position = clamp((rgb - domain.min) / (domain.max - domain.min), vec3<f32>(0.0), vec3<f32>(1.0)) * scale;
+    let base = min(vec3<i32>(position), vec3<i32>(size - 2));
+    let f = position - vec3<f32>(base);
+
+    if (domain.tetrahedral < 0.5) {
+        let c00 = mix(corner(base, vec3(0, 0, 0)), corner(base, vec3(1, 0, 0)), f.r);
+        let c10 = mix(corner(base, vec3(0, 1, 0)), corner(base, vec3(1, 1, 0)), f.r);
+        let c01 = mix(corner(base, vec3(0, 0, 1)), corner(base, vec3(1, 0, 1)), f.r);
+        let c11 = mix(corner(base, vec3(0, 1, 1)), corner(base, vec3(1, 1, 1)), f.r);
+
+        return mix(mix(c00, c10, f.g), mix(c01, c11, f.g), f.b);
+    }
+
+    // The corners from black to white along the tetrahedron, with their weights
+    var second = vec3<i32>(0, 1, 0);
+    var third = vec3<i32>(1, 1, 0);
+    var weights = vec3<f32>(f.g, f.r, f.b);
+
+    if (f.r > f.g) {
+        if (f.g > f.b) {
+            second = vec3(1, 0, 0);
+            third = vec3(1, 1, 0);
+            weights = vec3(f.r, f.g, f.b);
+        } else if (f.r > f.b) {
+            second = vec3(1, 0, 0);
+            third = vec3(1, 0, 1);
+            weights = vec3(f.r, f.b, f.g);
+        } else {
+            second = vec3(0, 0, 1);
+            third = vec3(1, 0, 1);
+            weights = vec3(f.b, f.r, f.g);
+        }
+    } else if (f.b > f.g) {
+        second = vec3(0, 0, 1);
+        third = vec3(0, 1, 1);
+        weights = vec3(f.b, f.g, f.r);
+    } else if (f.b > f.r) {
+        second = vec3(0, 1, 0);
+        third = vec3(0, 1, 1);
+        weights = vec3(f.g, f.b, f.r);
+    }
+
+    return (1.0 - weights.x) * corner(base, vec3(0, 0, 0))
+        + (weights.x - weights.y) * corner(base, second)
+        + (weights.y - weights.z) * corner(base, third)
+        + weights.z * corner(base, vec3(1, 1, 1));
+}
+
+@compute
+@workgroup_size(16, 16)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+    let dimensions = vec2<i32>(textureDimensions(input_texture));
+
+    if (coords.x >= dimensions.x || coords.y >= dimensions.y) {
+        return;
+    }
+
+    let color = textureLoad(input_texture, coords, 0);
+
+    if (color.a > 0.0) {
+        let graded = sample(srgb_encode(color.rgb / color.a));
+        textureStore(output_texture, coords, vec4<f32>(srgb_decode(graded) * color.a, color.a));
+    } else {
+        textureStore(output_texture, coords, color);
+    }
+}
@@ -1,4 +1,6 @@
 pub use app_state::run;
+pub use export::render_headless;
+pub use histogram::histogram_headless;
 pub(crate) use texture::*;
 pub(crate) use vertex::*;
 
@@ -6,8 +8,11 @@ mod app_state;
 mod compute_effect;
 mod draw_uniform;
 mod effect_pipeline;
+mod export;
 mod feature_uniform;
 mod gpu_state;
+mod half;
+mod histogram;
 mod mouse_state;
 mod shader;
 mod shape;
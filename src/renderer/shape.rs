@@ -1,3 +1,4 @@
+use crate::effects::CircleShape;
 use std::collections::BTreeMap;
 
 #[allow(dead_code)]
@@ -42,6 +43,17 @@ pub struct EditorState {
 }
 
 impl EditorState {
+    /// An editor holding `circles`, which can't be undone, like shapes loaded from a document.
+    pub fn with_circles(circles: impl IntoIterator<Item = Circle>) -> Self {
+        let mut editor_state = Self::default();
+
+        for circle in circles {
+            editor_state.create_element(circle);
+        }
+
+        editor_state
+    }
+
     pub const fn num_elements(&self) -> usize {
         self.element_stack.len()
     }
@@ -241,3 +253,21 @@ impl Circle {
         }
     }
 }
+
+impl From<&Circle> for CircleShape {
+    fn from(circle: &Circle) -> Self {
+        Self {
+            center: circle.center,
+            radius: circle.radius,
+        }
+    }
+}
+
+impl From<&CircleShape> for Circle {
+    fn from(circle: &CircleShape) -> Self {
+        Self {
+            center: circle.center,
+            radius: circle.radius,
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use crate::renderer::shape::Circle;
+use crate::{
+    effects::{CircleShape, MAX_CIRCLES},
+    renderer::shape::Circle,
+};
 
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -57,7 +60,18 @@ impl From<&Circle> for CircleData {
     }
 }
 
-pub const MAX_CIRCLES: usize = 256;
+impl From<&CircleShape> for CircleData {
+    fn from(circle: &CircleShape) -> Self {
+        let (x, y) = circle.center;
+
+        Self {
+            x,
+            y,
+            radius: circle.radius,
+            _padding: 0.0,
+        }
+    }
+}
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -0,0 +1,77 @@
+// Compute shaders for the Sobel gradient of the length of each color
+
+@group(0)
+@binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var output_texture: texture_storage_2d<rgba16float, write>;
+
+@group(0)
+@binding(2)
+var<uniform> threshold: f32;
+
+fn intensity(coords: vec2<i32>, offset: vec2<i32>) -> f32 {
+    let dimensions = vec2<i32>(textureDimensions(input_texture));
+    let color = textureLoad(input_texture, clamp(coords + offset, vec2<i32>(0), dimensions - 1), 0);
+
+    return length(color.rgb);
+}
+
+// The gradient with y growing downwards
+fn gradient(coords: vec2<i32>) -> vec2<f32> {
+    let top_left = intensity(coords, vec2(-1, -1));
+    let top = intensity(coords, vec2(0, -1));
+    let top_right = intensity(coords, vec2(1, -1));
+    let left = intensity(coords, vec2(-1, 0));
+    let right = intensity(coords, vec2(1, 0));
+    let bottom_left = intensity(coords, vec2(-1, 1));
+    let bottom = intensity(coords, vec2(0, 1));
+    let bottom_right = intensity(coords, vec2(1, 1));
+
+    let gx = (top_right + 2.0 * right + bottom_right) - (top_left + 2.0 * left + bottom_left);
+    let gy = (bottom_left + 2.0 * bottom + bottom_right) - (top_left + 2.0 * top + top_right);
+
+    return vec2<f32>(gx, gy);
+}
+
+fn in_bounds(coords: vec2<i32>) -> bool {
+    let dimensions = vec2<i32>(textureDimensions(input_texture));
+    return coords.x < dimensions.x && coords.y < dimensions.y;
+}
+
+// The gradient magnitude as opaque gray, zeroed under the threshold
+@compute
+@workgroup_size(16, 16)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+
+    if (!in_bounds(coords)) {
+        return;
+    }
+
+    let g = gradient(coords);
+    var magnitude = sqrt(g.x * g.x + g.y * g.y);
+
+    if (magnitude < threshold) {
+        magnitude = 0.0;
+    }
+
+    textureStore(output_texture, coords, vec4<f32>(magnitude, magnitude, magnitude, 1.0));
+}
+
+// The magnitude and the gradient, for canny_compute.wgsl
+@compute
+@workgroup_size(16, 16)
+fn canny_gradient(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+
+    if (!in_bounds(coords)) {
+        return;
+    }
+
+    let g = gradient(coords);
+
+    textureStore(output_texture, coords, vec4<f32>(sqrt(g.x * g.x + g.y * g.y), g.x, g.y, 1.0));
+}
@@ -1,5 +1,10 @@
-use crate::image::grammar::Image;
+use crate::{
+    color::ColorProfile,
+    image::grammar::{premultiply_rgba32f, premultiply_srgb8, ImageExt},
+    renderer::half::f32_to_f16,
+};
 use anyhow::*;
+use std::borrow::Cow;
 use wgpu::{
     AddressMode, Device, Extent3d, FilterMode, ImageCopyTexture, ImageDataLayout, Origin3d, Queue,
     Sampler, SamplerDescriptor, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat,
@@ -15,17 +20,42 @@ pub struct Texture {
 }
 
 impl Texture {
-    pub fn from_bytes(device: &Device, queue: &Queue, img: &Image) -> Result<Self> {
+    pub fn from_bytes(device: &Device, queue: &Queue, img: &dyn ImageExt) -> Result<Self> {
         Self::from_image(device, queue, img, None)
     }
 
     pub fn from_image(
         device: &Device,
         queue: &Queue,
-        img: &Image,
+        img: &dyn ImageExt,
         label: Option<&str>,
     ) -> Result<Self> {
-        let rgba = img.rgba8();
+        // Textures hold colors in sRGB, whatever profile the image was encoded in, with
+        // premultiplied alpha so filtering doesn't bleed the colors of transparent pixels into
+        // their neighbours. Colors are converted before they're premultiplied, in linear light
+        // like the GPU filters and blends them.
+        //
+        // Images with more than 8 bits per channel are kept as linear half floats, which don't
+        // band. Sampling either format yields linear light.
+        let (format, data, bytes_per_pixel) = if img.bit_depth() > 8 {
+            let mut rgba = img.rgba_f32_in(&ColorProfile::linear_srgb())?.into_owned();
+            premultiply_rgba32f(&mut rgba);
+
+            let data = rgba
+                .iter()
+                .flat_map(|&value| f32_to_f16(value).to_le_bytes())
+                .collect::<Vec<_>>();
+
+            (TextureFormat::Rgba16Float, Cow::from(data), 8)
+        } else {
+            let mut rgba = img.rgba8_in(&ColorProfile::srgb())?;
+
+            if img.color_type().has_alpha() {
+                premultiply_srgb8(rgba.to_mut());
+            }
+
+            (TextureFormat::Rgba8UnormSrgb, rgba, 4)
+        };
         let dimensions = img.dimensions();
 
         let size = Extent3d {
@@ -33,7 +63,6 @@ impl Texture {
             height: dimensions.1,
             depth_or_array_layers: 1,
         };
-        let format = TextureFormat::Rgba8UnormSrgb;
         let texture = device.create_texture(&TextureDescriptor {
             label,
             size,
@@ -52,10 +81,10 @@ impl Texture {
                 mip_level: 0,
                 origin: Origin3d::ZERO,
             },
-            &rgba,
+            &data,
             ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
+                bytes_per_row: Some(bytes_per_pixel * dimensions.0),
                 rows_per_image: Some(dimensions.1),
             },
             size,
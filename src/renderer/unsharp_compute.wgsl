@@ -0,0 +1,43 @@
+// Compute shader for the last pass of an unsharp mask, after blur_compute.wgsl blurred the input
+
+@group(0)
+@binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var output_texture: texture_storage_2d<rgba16float, write>;
+
+struct Sharpen {
+    amount: f32,
+    threshold: f32,
+}
+
+@group(0)
+@binding(2)
+var<uniform> sharpen: Sharpen;
+
+@group(0)
+@binding(3)
+var blurred_texture: texture_2d<f32>;
+
+@compute
+@workgroup_size(16, 16)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coords = vec2<i32>(global_id.xy);
+    let dimensions = textureDimensions(input_texture);
+
+    if (coords.x >= i32(dimensions.x) || coords.y >= i32(dimensions.y)) {
+        return;
+    }
+
+    let color = textureLoad(input_texture, coords, 0);
+    let blurred = textureLoad(blurred_texture, coords, 0);
+
+    // Channels move away from the blur where they differ from it by at least the threshold.
+    let difference = color.rgb - blurred.rgb;
+    let sharpened = color.rgb + sharpen.amount * difference;
+    let rgb = select(color.rgb, sharpened, abs(difference) >= vec3<f32>(sharpen.threshold));
+
+    textureStore(output_texture, coords, vec4<f32>(rgb, color.a));
+}